@@ -0,0 +1,130 @@
+//! # Minigrep: Single- vs Multi-threaded Search
+//!
+//! Generates a corpus of text files in a temporary directory, then times
+//! walking and searching it on one thread and on a thread pool.
+//!
+//! Run with: `cargo bench --bench minigrep_parallel`
+//! Pass `-- FILES LINES` to change the corpus size (default 2000 files of 400 lines).
+
+use rust_book_examples::minigrep::parallel::{search_files, FileReport, FileSearch};
+use rust_book_examples::minigrep::stream::Searcher;
+use rust_book_examples::minigrep::walk::{walk, walk_parallel, WalkOptions};
+use rust_book_examples::minigrep::Matcher;
+use rust_book_examples::thread_pool::ThreadPool;
+use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+
+const WORDS: [&str; 12] = [
+    "ownership", "borrow", "lifetime", "trait", "closure", "iterator",
+    "vector", "string", "thread", "channel", "mutex", "pattern",
+];
+
+/// Runs per configuration; the fastest is reported
+const RUNS: usize = 3;
+
+fn main() {
+    // `cargo bench` passes `--bench`; only numbers are ours
+    let sizes: Vec<usize> = env::args().skip(1).filter_map(|arg| arg.parse().ok()).collect();
+    let files = sizes.first().copied().unwrap_or(2000);
+    let lines = sizes.get(1).copied().unwrap_or(400);
+
+    let root = env::temp_dir().join(format!("minigrep_bench_{}", std::process::id()));
+    println!("📁 Generating {} files x {} lines in {}", files, lines, root.display());
+    generate_corpus(&root, files, lines);
+
+    let search = FileSearch {
+        matcher: Matcher::new(r"borrow\w* (the|a) (vector|string)", true, true).unwrap(),
+        searcher: Searcher::default(),
+        before_context: 0,
+        after_context: 0,
+        collect_lines: true,
+    };
+
+    let (single, baseline) = best_of(|| run(&root, &search, None));
+    println!("🐢 1 thread:   {:>8.1?}  ({} matches)", single, total_matches(&baseline));
+
+    let cpus = std::thread::available_parallelism().map_or(1, |n| n.get());
+    if cpus == 1 {
+        println!("⚠️  Only one CPU is available, so threads can't speed this up");
+    }
+    let mut sizes = vec![2, 4, cpus];
+    sizes.sort_unstable();
+    sizes.dedup();
+    for size in sizes.into_iter().filter(|&n| n > 1) {
+        let pool = ThreadPool::new(size);
+        let (elapsed, reports) = best_of(|| run(&root, &search, Some(&pool)));
+        assert_eq!(summaries(&reports), summaries(&baseline), "parallel output differs");
+        println!(
+            "🚀 {} threads: {:>8.1?}  ({:.2}x)",
+            size,
+            elapsed,
+            single.as_secs_f64() / elapsed.as_secs_f64()
+        );
+    }
+
+    fs::remove_dir_all(&root).unwrap();
+}
+
+fn run(root: &Path, search: &FileSearch, pool: Option<&ThreadPool>) -> Vec<FileReport> {
+    let options = WalkOptions::default();
+    let walked = match pool {
+        Some(pool) => walk_parallel(&[root], &options, pool),
+        None => walk(&[root], &options),
+    };
+    let files: Vec<PathBuf> = walked.into_iter().filter_map(Result::ok).collect();
+    search_files(search.clone(), files, pool).collect()
+}
+
+fn best_of<T>(mut f: impl FnMut() -> T) -> (Duration, T) {
+    let mut best = None;
+    for _ in 0..RUNS {
+        let start = Instant::now();
+        let result = f();
+        let elapsed = start.elapsed();
+        if best.as_ref().is_none_or(|(fastest, _)| elapsed < *fastest) {
+            best = Some((elapsed, result));
+        }
+    }
+    best.unwrap()
+}
+
+fn summaries(reports: &[FileReport]) -> Vec<(&Path, usize)> {
+    reports.iter().map(|r| (r.path.as_path(), r.summary.matches)).collect()
+}
+
+fn total_matches(reports: &[FileReport]) -> usize {
+    reports.iter().map(|r| r.summary.matches).sum()
+}
+
+/// Nested directories of pseudo-random sentences, reproducible from the file index
+fn generate_corpus(root: &Path, files: usize, lines: usize) {
+    let _ = fs::remove_dir_all(root);
+    let mut seed: u64 = 0x2545_f491_4f6c_dd1d;
+    let mut next = move || {
+        // xorshift: fast and deterministic, no dependency needed
+        seed ^= seed << 13;
+        seed ^= seed >> 7;
+        seed ^= seed << 17;
+        seed as usize
+    };
+
+    for i in 0..files {
+        let dir = root.join(format!("chapter{:02}/section{:02}", i % 20, (i / 20) % 10));
+        fs::create_dir_all(&dir).unwrap();
+
+        let mut text = String::with_capacity(lines * 48);
+        for _ in 0..lines {
+            let article = if next() % 2 == 0 { "the" } else { "a" };
+            text.push_str(&format!(
+                "{} {} {} {}\n",
+                WORDS[next() % WORDS.len()],
+                article,
+                WORDS[next() % WORDS.len()],
+                WORDS[next() % WORDS.len()]
+            ));
+        }
+        fs::write(dir.join(format!("notes{:04}.txt", i)), text).unwrap();
+    }
+}
@@ -1,13 +1,55 @@
 // Import external crate for random number generation
 use rand::Rng;
+// StdRng is a generator we can seed, so a game can be replayed exactly
+use rand::rngs::StdRng;
+use rand::SeedableRng;
+use rust_book_examples::args::{self, ArgError, Flag};
+// The game itself lives in the library, where its tests script whole games
+use rust_book_examples::guessing_game::leaderboard::{Entry, Leaderboard};
+use rust_book_examples::guessing_game::server::GameServer;
+use rust_book_examples::guessing_game::strategy::{self, Strategy};
+use rust_book_examples::guessing_game::{Bot, Difficulty, GameConfig, GameReport, Guess, GuessingGame, Player};
 use rust_book_examples::print_chapter_header;
 // Ordering is an enum with variants: Less, Greater, Equal
 use std::cmp::Ordering;
+use std::env;
 // Standard library for input/output operations
 use std::io;
+use std::process;
+use std::time::Duration;
+
+/// Where scores are kept unless `--leaderboard` says otherwise
+const LEADERBOARD_FILE: &str = "guessing_game_leaderboard.txt";
+
+/// The knobs the game engine exposes, as command line flags
+const GAME: args::Command = args::Command::new(
+    "guessing_game",
+    "Guess the secret number on the configurable engine. Without arguments, play the chapter's version.",
+)
+.flags(&[
+    Flag::option(None, "min", "N", "Smallest possible secret (default 1)"),
+    Flag::option(None, "max", "N", "Largest possible secret (default 100)"),
+    Flag::option(Some('a'), "attempts", "N", "Guesses allowed (default: unlimited)"),
+    Flag::option(Some('t'), "time", "SECONDS", "Time allowed for the game (default: unlimited)"),
+    Flag::option(Some('d'), "difficulty", "LEVEL", "Play a scored preset: easy, medium or hard"),
+    Flag::option(Some('n'), "name", "NAME", "Your name on the leaderboard").env(&["USER", "USERNAME"]),
+    Flag::option(None, "leaderboard", "FILE", "Where scores are kept (default guessing_game_leaderboard.txt)"),
+    Flag::switch(None, "scores", "Show the leaderboard and exit"),
+    Flag::option(None, "bot", "STRATEGY", "Let a bot play instead of you: binary or random"),
+    Flag::option(None, "simulate", "GAMES", "Play GAMES seeded games with every bot and compare them"),
+    Flag::option(None, "serve", "ADDR", "Host a multiplayer game over TCP, e.g. 127.0.0.1:7878"),
+    Flag::option(None, "players", "N", "With --serve, how many can play at once (default 8)"),
+    Flag::option(None, "seed", "N", "Seed the random number generator, to replay a game"),
+]);
 
 fn main() {
     print_chapter_header("Chapter 2", "Programming a Guessing Game");
+
+    // Any argument plays the library's game engine instead; `--help` lists them
+    if env::args().len() > 1 {
+        run_engine();
+        return;
+    }
     
     println!("Guess the number!");
 
@@ -25,20 +67,29 @@ fn main() {
         let mut guess = String::new();
 
         // Read user input from stdin
-        // read_line returns a Result<usize, Error>
+        // read_line returns a Result<usize, Error>: how many bytes it read
         // expect() will panic with the given message if there's an error
         // The &mut tells Rust we're borrowing 'guess' mutably
-        io::stdin()
+        let read = io::stdin()
             .read_line(&mut guess)
             .expect("Failed to read line");
 
-        // Parse the string into an unsigned 32-bit integer
+        // Nothing read means the input has ended, so there are no more guesses
+        if read == 0 {
+            break;
+        }
+
+        // Parse the string into a number between 1 and 100
         // This is "variable shadowing" - we're creating a new variable with the same name
-        // trim() removes whitespace/newlines, parse() converts string to number
-        // match handles the Result<u32, ParseIntError> returned by parse()
-        let guess: u32 = match guess.trim().parse() {
-            Ok(num) => num,        // If parsing succeeded, use the number
-            Err(_) => continue,    // If parsing failed, skip to next iteration
+        // try_new() trims whitespace/newlines, parses, and checks the range
+        // match handles the Result<Guess, GuessError> it returns
+        let guess: u32 = match Guess::try_new(&guess, &(1..=100)) {
+            Ok(guess) => guess.value(),     // If the guess is valid, use its number
+            Err(e) => {
+                // Otherwise say what was wrong and ask again
+                println!("Invalid guess: {e}.");
+                continue;
+            }
         };
 
         println!("You guessed: {guess};");
@@ -57,3 +108,179 @@ fn main() {
         }
     }
 }
+
+/// The chapter's game grown into a configurable engine: difficulty presets,
+/// a leaderboard, bots and a multiplayer server
+fn run_engine() {
+    let matches = GAME.parse(env::args()).unwrap_or_else(|err| {
+        eprintln!("{}\n\n{}", err, GAME.help("ch02_guessing_game"));
+        process::exit(2);
+    });
+    if matches.flag("help") {
+        print!("{}", GAME.help("ch02_guessing_game"));
+        return;
+    }
+    let (config, difficulty, seed) = settings(&matches).unwrap_or_else(|err| {
+        eprintln!("{}", err);
+        process::exit(2);
+    });
+
+    // A damaged leaderboard file is reported, never fatal
+    let mut leaderboard = Leaderboard::open(matches.value("leaderboard").unwrap_or(LEADERBOARD_FILE));
+    for warning in leaderboard.warnings() {
+        eprintln!("⚠️  {}: {}", leaderboard.path().display(), warning);
+    }
+    if matches.flag("scores") {
+        show_scores(&leaderboard);
+        return;
+    }
+
+    if let Err(err) = config.validate() {
+        eprintln!("Can't start the game: {}", err);
+        process::exit(2);
+    }
+
+    // Like the chapter's `rand::thread_rng()`, the secret is random by default;
+    // `--seed` swaps in a generator that picks the same secret every time
+    let rng = match seed {
+        Some(seed) => StdRng::seed_from_u64(seed),
+        None => StdRng::from_entropy(),
+    };
+    // Bots and simulations get their own seed, so they can be replayed too
+    let seed = seed.unwrap_or_else(rand::random);
+
+    let games = matches.parse::<u32>("simulate").unwrap_or_else(|err| {
+        eprintln!("{}", err);
+        process::exit(2);
+    });
+    if let Some(games) = games {
+        simulate(&config, games, seed);
+        return;
+    }
+
+    if let Some(addr) = matches.value("serve") {
+        let players = matches.parse("players").unwrap_or_else(|err| {
+            eprintln!("{}", err);
+            process::exit(2);
+        });
+        serve(addr, config, players.unwrap_or(8), seed);
+        return;
+    }
+
+    // Read guesses from stdin, or let a strategy make them, and print to stdout;
+    // tests pass byte strings and a Vec<u8> instead
+    let played = match matches.value("bot") {
+        Some(name) => match strategy::named(name, seed) {
+            Some(bot) => play(config, Bot(bot), rng),
+            None => {
+                eprintln!("Unknown strategy '{}'; try one of: {}", name, strategy::NAMES.join(", "));
+                process::exit(2);
+            }
+        },
+        None => play(config, io::stdin().lock(), rng),
+    };
+    let report = match played {
+        Ok(report) => report,
+        Err(e) => {
+            eprintln!("Failed to read line: {}", e);
+            process::exit(2);
+        }
+    };
+    println!(
+        "Game over after {} guess(es) in {:.1}s: {:?}",
+        report.attempts,
+        report.elapsed.as_secs_f64(),
+        report.outcome
+    );
+
+    // Only the presets are scored, so every entry on a board played by the same rules
+    let Some(difficulty) = difficulty else { return };
+    // Bots don't get on the leaderboard
+    if matches.is_present("bot") {
+        return;
+    }
+    let score = difficulty.score(&report);
+    println!("⭐ Score: {} / {}", score, difficulty.max_score());
+    if score > 0 {
+        let name = matches.value("name").unwrap_or("player");
+        match leaderboard.record(Entry::new(name, difficulty, &report)) {
+            Some(place) => println!("🏆 #{} on the {} leaderboard!", place, difficulty),
+            None => println!("Not quite enough for the {} leaderboard.", difficulty),
+        }
+        if let Err(e) = leaderboard.save() {
+            eprintln!("Couldn't save the leaderboard to {}: {}", leaderboard.path().display(), e);
+        }
+    }
+    show_scores(&leaderboard);
+}
+
+/// Host the game for several players until the process is stopped
+fn serve(addr: &str, config: GameConfig, players: usize, seed: u64) {
+    if players == 0 {
+        eprintln!("--players must be at least 1");
+        process::exit(2);
+    }
+    // Each connection gets a worker from the Chapter 20 thread pool
+    let server = GameServer::start(addr, config, players, seed).unwrap_or_else(|e| {
+        eprintln!("Can't listen on {}: {}", addr, e);
+        process::exit(2);
+    });
+    println!("🌐 Hosting up to {} players on {}", players, server.local_addr());
+    println!("   Join with: nc {} {}", server.local_addr().ip(), server.local_addr().port());
+    println!("   Then type a number to guess, NAME <name> to pick a name, QUIT to leave");
+    server.wait();
+}
+
+/// One game against `player`, printing to stdout
+fn play<P: Player>(config: GameConfig, player: P, rng: StdRng) -> io::Result<GameReport> {
+    // The config was validated in main
+    let mut game = GuessingGame::new(config, player, io::stdout(), rng).expect("a valid config");
+    game.play()
+}
+
+/// Pit every strategy against the same seeded secrets
+fn simulate(config: &GameConfig, games: u32, seed: u64) {
+    println!(
+        "🤖 {} games per strategy, numbers {}..={}, seed {}",
+        games,
+        config.range.start(),
+        config.range.end(),
+        seed
+    );
+    for name in strategy::NAMES {
+        let mut bot: Box<dyn Strategy> = strategy::named(name, seed).expect("every name in NAMES is known");
+        println!("  {}", strategy::simulate(&mut bot, config, games, seed));
+    }
+}
+
+fn show_scores(leaderboard: &Leaderboard) {
+    let table = leaderboard.render(5);
+    if table.is_empty() {
+        println!("No scores yet. Play with --difficulty easy, medium or hard to get on the board.");
+    } else {
+        print!("\n📋 Leaderboard\n{}", table);
+    }
+}
+
+/// Turn the flags into a game configuration, the preset it came from, if
+/// any, and an optional seed
+fn settings(matches: &args::Matches) -> Result<(GameConfig, Option<Difficulty>, Option<u64>), ArgError> {
+    let seed = matches.parse("seed")?;
+    if let Some(difficulty) = matches.parse::<Difficulty>("difficulty")? {
+        // A preset is played exactly as defined, so its scores stay comparable
+        if let Some(custom) = ["min", "max", "attempts", "time"].into_iter().find(|name| matches.is_present(name)) {
+            return Err(ArgError::Conflict("difficulty", custom));
+        }
+        return Ok((difficulty.config(), Some(difficulty), seed));
+    }
+
+    let default = GameConfig::default();
+    let min = matches.parse("min")?.unwrap_or(*default.range.start());
+    let max = matches.parse("max")?.unwrap_or(*default.range.end());
+    let config = GameConfig {
+        range: min..=max,
+        max_attempts: matches.parse("attempts")?,
+        time_limit: matches.parse("time")?.map(Duration::from_secs),
+    };
+    Ok((config, None, seed))
+}
@@ -11,15 +11,12 @@
 //! - Understanding borrowing rules with vectors
 //! - Iterating over vectors
 //! - Using enums to store different types in vectors
+//! - Growing the `SpreadsheetCell` enum into a sheet with formulas (see src/spreadsheet.rs)
 
 use rust_book_examples::print_chapter_header;
-
-#[derive(Debug)]
-enum SpreadsheetCell {
-    Int(i32),
-    Float(f64),
-    Text(String),
-}
+// The chapter's enum lives in the library, where it grew a Formula variant
+use rust_book_examples::spreadsheet::csv::{self, CsvOptions, FormulaExport};
+use rust_book_examples::spreadsheet::{Sheet, SpreadsheetCell};
 
 #[derive(Debug)]
 struct Item {
@@ -177,8 +174,62 @@ fn demonstrate_enum_storage() {
             SpreadsheetCell::Int(i) => println!("  Cell {}: Integer = {}", index, i),
             SpreadsheetCell::Float(f) => println!("  Cell {}: Float = {:.2}", index, f),
             SpreadsheetCell::Text(s) => println!("  Cell {}: Text = '{}'", index, s),
+            SpreadsheetCell::Formula(f) => println!("  Cell {}: Formula = {}", index, f),
+        }
+    }
+}
+
+fn demonstrate_spreadsheet() {
+    println!("\n=== From a Row of Cells to a Spreadsheet ===");
+    
+    // Cells are addressed like A1, and a Formula cell computes its value
+    let mut sheet = Sheet::new();
+    let entries = [
+        ("A1", "Item"), ("B1", "Price"), ("C1", "Qty"), ("D1", "Total"),
+        ("A2", "Apple"), ("B2", "0.5"), ("C2", "6"), ("D2", "=B2*C2"),
+        ("A3", "Bread"), ("B3", "2"), ("C3", "1"), ("D3", "=B3*C3"),
+        ("A4", "Milk"), ("B4", "3.5"), ("C4", "2"), ("D4", "=B4*C4"),
+        ("A5", "Sum"), ("C5", "=SUM(C2:C4)"), ("D5", "=SUM(D2:D4)"),
+        ("A6", "Big order?"), ("D6", "=IF(D5 > 20, \"yes\", \"no\")"),
+    ];
+    for (at, input) in entries {
+        if let Err(e) = sheet.enter(at, input) {
+            println!("  {}: {}", at, e);
         }
     }
+    print!("{}", sheet.render().expect("a small sheet"));
+    
+    // Only the cells that read C2, directly or not, are recalculated
+    let recalculated = sheet.enter("C2", "30").expect("a valid cell");
+    let names: Vec<String> = recalculated.iter().map(|cell| cell.to_string()).collect();
+    println!("\nAfter C2 = 30, recalculated: {}", names.join(", "));
+    print!("{}", sheet.render().expect("a small sheet"));
+    
+    // A formula that reads itself can't be calculated
+    sheet.enter("E1", "=E2+1").expect("a valid cell");
+    sheet.enter("E2", "=E1+1").expect("a valid cell");
+    println!("\nE1 = E2+1 and E2 = E1+1 give: {}", sheet.value(&"E1".parse().expect("a valid address")));
+    
+    // Typos are caught when the formula is entered
+    if let Err(e) = sheet.enter("F1", "=SUM(A1:A3") {
+        println!("=SUM(A1:A3 is rejected: {}", e);
+    }
+    
+    // Sheets load and save as CSV; formulas go out as values or as text
+    sheet.clear("E1".parse().expect("a valid address"));
+    sheet.clear("E2".parse().expect("a valid address"));
+    let values = CsvOptions { formulas: FormulaExport::Values, ..CsvOptions::default() };
+    println!("\nAs CSV with values:\n{}", csv::write(&sheet, &values).expect("a small sheet").replace("\r\n", "\n"));
+    let text = CsvOptions { delimiter: ';', ..CsvOptions::default() };
+    let exported = csv::write(&sheet, &text).expect("a small sheet");
+    println!("As CSV with formulas, ';'-separated:\n{}", exported.replace("\r\n", "\n"));
+    match csv::read(&exported, &text) {
+        Ok(copy) => println!("Read back, D5 is still {}", copy.value(&"D5".parse().expect("a valid address"))),
+        Err(e) => println!("Couldn't read it back: {}", e),
+    }
+    if let Err(e) = csv::read("Item,Note\nApple,\"crisp\" and sweet\n", &CsvOptions::default()) {
+        println!("A stray quote is reported where it is: {}", e);
+    }
 }
 
 fn demonstrate_shopping_cart() {
@@ -376,6 +427,7 @@ fn main() {
     demonstrate_borrowing_rules();
     demonstrate_iteration();
     demonstrate_enum_storage();
+    demonstrate_spreadsheet();
     demonstrate_shopping_cart();
     demonstrate_vector_methods();
     demonstrate_slices();
@@ -391,5 +443,6 @@ fn main() {
     println!("• Vectors must be mutable to modify after creation");
     println!("• Borrowing rules prevent dangling references");
     println!("• Use enums to store different types in same vector");
+    println!("• Adding a variant to an enum makes the compiler point at every match to update");
     println!("• Vectors automatically manage memory allocation");
 }
\ No newline at end of file
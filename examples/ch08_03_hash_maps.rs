@@ -11,26 +11,26 @@
 //! - Ownership rules with hash maps
 //! - Entry API for advanced operations
 //! - Iterating over hash maps
+//! - A bounded, expiring cache built on a hash map (see src/cache.rs)
+//! - Growing the student map into a records tool with statistics and a saved
+//!   file (see src/student_records.rs); run with `--records FILE` to use it
 
 use std::collections::HashMap;
+use std::env;
+use std::io;
+use std::process;
+use std::thread;
+use std::time::Duration;
+use rust_book_examples::args::{Command, Flag};
+// A HashMap alone grows forever; the library's Cache adds a size limit and expiry
+use rust_book_examples::cache::Cache;
 use rust_book_examples::print_chapter_header;
+use rust_book_examples::student_records::shell::{self, Reply, Session};
+use rust_book_examples::student_records::{Roster, Student};
 
-#[derive(Debug)]
-struct Student {
-    name: String,
-    grade: f64,
-    age: u32,
-}
-
-impl Student {
-    fn new(name: &str, grade: f64, age: u32) -> Self {
-        Student {
-            name: name.to_string(),
-            grade,
-            age,
-        }
-    }
-}
+const HASH_MAPS: Command = Command::new("ch08_03_hash_maps", "Hash map examples, or a student records tool.").flags(&[
+    Flag::option(None, "records", "FILE", "Manage the students in FILE interactively instead"),
+]);
 
 #[derive(Debug, PartialEq, Eq, Hash)]
 struct ProductId {
@@ -45,34 +45,6 @@ struct Product {
     stock: i32,
 }
 
-struct Cache {
-    data: HashMap<String, String>,
-}
-
-impl Cache {
-    fn new() -> Self {
-        Cache {
-            data: HashMap::new(),
-        }
-    }
-    
-    fn get(&mut self, key: &str) -> &str {
-        self.data.entry(key.to_string()).or_insert_with(|| {
-            // Simulate expensive computation
-            println!("  Computing value for key: {}", key);
-            format!("computed_value_for_{}", key)
-        })
-    }
-    
-    fn invalidate(&mut self, key: &str) {
-        self.data.remove(key);
-    }
-    
-    fn size(&self) -> usize {
-        self.data.len()
-    }
-}
-
 fn get_score(scores: &HashMap<String, i32>, team: &str) -> Option<i32> {
     scores.get(team).copied()
 }
@@ -263,7 +235,7 @@ fn demonstrate_entry_api() {
     println!("Final scores: {:?}", scores);
     
     // Find the winner
-    if let Some((winner, &max_score)) = scores.iter().max_by_key(|(_, &score)| score) {
+    if let Some((winner, &max_score)) = scores.iter().max_by_key(|&(_, &score)| score) {
         println!("Winner: {} with {} points!", winner, max_score);
     }
 }
@@ -273,11 +245,10 @@ fn demonstrate_student_management() {
     
     let mut students: HashMap<u32, Student> = HashMap::new();
     
-    // Add students with ID as key
-    students.insert(1001, Student::new("Alice", 85.5, 20));
-    students.insert(1002, Student::new("Bob", 92.0, 19));
-    students.insert(1003, Student::new("Charlie", 78.5, 21));
-    students.insert(1004, Student::new("Diana", 96.5, 20));
+    // Add students with ID as key; Student::new refuses a grade outside 0-100
+    for (id, name, grade, age) in [(1001, "Alice", 85.5, 20), (1002, "Bob", 92.0, 19), (1003, "Charlie", 78.5, 21), (1004, "Diana", 96.5, 20)] {
+        students.insert(id, Student::new(name, grade, age).expect("a valid student"));
+    }
     
     // Look up student by ID
     if let Some(student) = students.get(&1002) {
@@ -318,6 +289,50 @@ fn demonstrate_student_management() {
     }
 }
 
+fn demonstrate_student_records() {
+    println!("\n=== From a Student Map to a Records Tool ===");
+
+    // The same commands `--records FILE` reads from the keyboard
+    let mut session = Session::new(Roster::new(), None);
+    let script = [
+        "add Alice 85.5 20",
+        "add Bob 92 19",
+        "add Charlie 78.5 21",
+        "add Diana 96.5 20",
+        "add Eve Adams 85.5 19",
+        "add Mallory 140 22",
+        "update 1001 grade 88",
+        "grades 85 95",
+        "age 20",
+        "list name desc",
+        "stats",
+        "stats age",
+        "remove 1003",
+        "remove 1003",
+    ];
+    for line in script {
+        println!("records> {}", line);
+        match session.execute(line) {
+            Ok(Reply::Text(text)) => println!("{}", text.trim_end()),
+            Ok(Reply::Quit) => break,
+            Err(e) => println!("❌ {}", e),
+        }
+    }
+
+    // Saving writes one tab-separated line per student, and loading checks
+    // every line, naming the first bad one
+    let path = env::temp_dir().join(format!("ch08_03_students_{}.txt", process::id()));
+    match session.roster().save(&path).and_then(|_| std::fs::read_to_string(&path)) {
+        Ok(text) => println!("💾 Saved as:\n{}", text.trim_end()),
+        Err(e) => println!("❌ Couldn't save: {}", e),
+    }
+    let _ = std::fs::remove_file(&path);
+    let damaged = "student-records 1\n1001\tAlice\t88\t20\n1002\tBob\tninety\t19\n";
+    if let Err(e) = Roster::parse(damaged) {
+        println!("🚫 A damaged file is refused: {}", e);
+    }
+}
+
 fn demonstrate_different_key_types() {
     println!("\n=== Working with Different Key Types ===");
     
@@ -436,28 +451,50 @@ fn demonstrate_hash_map_methods() {
 fn demonstrate_caching_example() {
     println!("\n=== Advanced HashMap Usage: Caching ===");
     
-    let mut cache = Cache::new();
+    // The "database" the cache sits in front of
+    let users: HashMap<String, String> = [("user123", "Alice"), ("user456", "Bob"), ("user789", "Carol")]
+        .into_iter()
+        .map(|(id, name)| (id.to_string(), name.to_string()))
+        .collect();
+    
+    // Room for two users; each is fetched at most once per 100ms
+    let mut cache = Cache::new(2)
+        .with_ttl(Duration::from_millis(100))
+        .with_loader(move |id: &String| {
+            println!("  Loading {} from the database", id);
+            users.get(id).cloned()
+        });
     
-    // First access - will compute
-    println!("Getting value for 'user123':");
-    println!("Value: {}", cache.get("user123"));
+    // First access - will load
+    println!("Getting 'user123':");
+    println!("Value: {:?}", cache.get("user123"));
     
     // Second access - will use cached value
-    println!("Getting value for 'user123' again:");
-    println!("Value: {}", cache.get("user123"));
+    println!("Getting 'user123' again:");
+    println!("Value: {:?}", cache.get("user123"));
     
-    // Different key - will compute
-    println!("Getting value for 'user456':");
-    println!("Value: {}", cache.get("user456"));
+    // Unknown keys load nothing, and nothing is cached
+    println!("Getting 'nobody':");
+    println!("Value: {:?}", cache.get("nobody"));
     
-    println!("Cache size: {}", cache.size());
+    // A third user doesn't fit; user123, used longest ago, is evicted
+    cache.get("user456");
+    cache.get("user789");
+    let keys: Vec<&String> = cache.keys().collect();
+    println!("Cached, most recent first: {:?}", keys);
     
     // Invalidate and access again
-    cache.invalidate("user123");
-    println!("After invalidating 'user123', getting it again:");
-    println!("Value: {}", cache.get("user123"));
+    cache.remove("user789");
+    println!("After invalidating 'user789', getting it again:");
+    println!("Value: {:?}", cache.get("user789"));
+    
+    // Entries expire, so a changed user is picked up eventually
+    thread::sleep(Duration::from_millis(120));
+    println!("120ms later, getting 'user456':");
+    println!("Value: {:?}", cache.get("user456"));
     
-    println!("Final cache size: {}", cache.size());
+    println!("Cache size: {} of {}", cache.len(), cache.capacity());
+    println!("Stats: {}", cache.stats());
 }
 
 fn demonstrate_custom_types() {
@@ -518,6 +555,19 @@ fn demonstrate_custom_types() {
 }
 
 fn main() {
+    let matches = HASH_MAPS.parse(env::args()).unwrap_or_else(|err| {
+        eprintln!("{}\n\n{}", err, HASH_MAPS.help("ch08_03_hash_maps"));
+        process::exit(2);
+    });
+    if matches.flag("help") {
+        print!("{}", HASH_MAPS.help("ch08_03_hash_maps"));
+        return;
+    }
+    if let Some(path) = matches.value("records") {
+        run_records(path);
+        return;
+    }
+
     print_chapter_header("Chapter 8.3", "Storing Keys with Associated Values in Hash Maps");
 
     println!("HashMap<K, V> stores key-value pairs using a hash function for fast lookups.");
@@ -530,6 +580,7 @@ fn main() {
     demonstrate_updating();
     demonstrate_entry_api();
     demonstrate_student_management();
+    demonstrate_student_records();
     demonstrate_different_key_types();
     demonstrate_error_handling();
     demonstrate_hash_map_methods();
@@ -544,4 +595,19 @@ fn main() {
     println!("• Iteration order is not guaranteed to be consistent");
     println!("• Use get() method for safe access that returns Option<&V>");
     println!("• Perfect for counting, caching, and fast key-based lookups");
+}
+
+/// The interactive records tool behind `--records FILE`
+fn run_records(path: &str) {
+    // A file with a bad line is reported rather than overwritten on save
+    let roster = Roster::load(path).unwrap_or_else(|err| {
+        eprintln!("❌ {}: {}", path, err);
+        process::exit(1);
+    });
+    println!("📚 {} students in {}; type help for commands", roster.len(), path);
+    let mut session = Session::new(roster, Some(path.into()));
+    if let Err(err) = shell::run(&mut session, io::stdin().lock(), io::stdout()) {
+        eprintln!("❌ {}", err);
+        process::exit(1);
+    }
 }
\ No newline at end of file
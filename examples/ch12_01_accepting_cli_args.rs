@@ -7,11 +7,20 @@
 //! - Error handling in CLI applications
 //! - Separating concerns with modules and functions
 //! - Creating a minigrep-like application
+//! - Opting into regular-expression search with `-E`
+//! - Streaming input line by line, including stdin via `-`
+//! - grep-style exit codes: 0 for a match, 1 for none, 2 for errors
 
+use rust_book_examples::args::{self, ArgError, Flag, Matches, Positional};
+use rust_book_examples::minigrep::casefold::{CaseFolder, FoldedQuery};
+use rust_book_examples::minigrep::cli::{self, Status};
+use rust_book_examples::minigrep::stream::{open_input, search_reader};
+use rust_book_examples::minigrep::Matcher;
 use rust_book_examples::print_chapter_header;
 use std::env;
-use std::fs;
 use std::error::Error;
+use std::io::{BufRead, Cursor};
+use std::path::Path;
 use std::process;
 
 fn main() {
@@ -23,16 +32,31 @@ fn main() {
     println!("\n=== Building a Simple CLI Application ===");
     
     // Try to run our minigrep application
-    let config = Config::build(env::args()).unwrap_or_else(|err| {
+    let program = "ch12_01_accepting_cli_args";
+    let matches = MINIGREP.parse(env::args()).unwrap_or_else(|err| {
         eprintln!("Problem parsing arguments: {}", err);
-        process::exit(1);
+        eprintln!("{}", MINIGREP.help(program));
+        process::exit(Status::Error.code());
+    });
+    if matches.flag("help") {
+        print!("{}", MINIGREP.help(program));
+        return;
+    }
+    let config = Config::from_matches(&matches).unwrap_or_else(|err| {
+        eprintln!("Problem parsing arguments: {}", err);
+        eprintln!("{}", MINIGREP.help(program));
+        process::exit(Status::Error.code());
     });
     
     println!("Searching for '{}' in file '{}'", config.query, config.file_path);
     
-    if let Err(e) = run(config) {
-        eprintln!("Application error: {}", e);
-        process::exit(1);
+    // Exit like grep, so scripts can tell "no match" from "error"
+    match run(config) {
+        Ok(status) => process::exit(status.code()),
+        Err(e) => {
+            eprintln!("Application error: {}", e);
+            process::exit(Status::Error.code());
+        }
     }
 }
 
@@ -58,34 +82,54 @@ pub struct Config {
     pub query: String,
     pub file_path: String,
     pub ignore_case: bool,
+    pub use_regex: bool,
+    /// `-s`: don't report an unreadable file on stderr
+    pub no_messages: bool,
+    /// `--demo`: search the built-in poem when the file can't be opened
+    pub demo: bool,
 }
 
-impl Config {
-    pub fn build(mut args: impl Iterator<Item = String>) -> Result<Config, &'static str> {
-        args.next(); // Skip the program name
+const QUERY: Positional = Positional::required("QUERY", "Text to search for");
+const FILE: Positional = Positional::required("FILE", "File to search; - reads standard input");
 
-        let query = match args.next() {
-            Some(arg) => arg,
-            None => return Err("Didn't get a query string"),
-        };
+/// This example's command line, parsed by the library's declarative parser
+const MINIGREP: args::Command = args::Command::new("minigrep", "Search FILE for lines containing QUERY.")
+    .flags(&[
+        // IGNORE_CASE, then CASE_INSENSITIVE; see the minigrep cli module
+        Flag::switch(Some('i'), "ignore-case", "Match letters regardless of case").env(&cli::IGNORE_CASE_VARS),
+        Flag::switch(Some('E'), "regex", "Treat the query as a regular expression"),
+        Flag::switch(Some('s'), "no-messages", "Don't report an unreadable file on stderr"),
+        Flag::switch(None, "demo", "Search the built-in poem if FILE can't be opened"),
+    ])
+    .positionals(&[QUERY, FILE]);
 
-        let file_path = match args.next() {
-            Some(arg) => arg,
-            None => return Err("Didn't get a file path"),
-        };
+impl Config {
+    pub fn build(args: impl Iterator<Item = String>) -> Result<Config, ArgError> {
+        Config::from_matches(&MINIGREP.parse(args)?)
+    }
 
-        let ignore_case = env::var("IGNORE_CASE").is_ok();
+    /// Flags can appear anywhere; the parser has already sorted them out
+    pub fn from_matches(matches: &Matches) -> Result<Config, ArgError> {
+        let positional = |arg: Positional| {
+            matches
+                .positional(arg.name)
+                .map(str::to_string)
+                .ok_or(ArgError::MissingArgument(arg))
+        };
 
         Ok(Config {
-            query,
-            file_path,
-            ignore_case,
+            query: positional(QUERY)?,
+            file_path: positional(FILE)?,
+            ignore_case: matches.flag("ignore-case"),
+            use_regex: matches.flag("regex"),
+            no_messages: matches.flag("no-messages"),
+            demo: matches.flag("demo"),
         })
     }
 }
 
-pub fn run(config: Config) -> Result<(), Box<dyn Error>> {
-    // Create a sample file if it doesn't exist
+pub fn run(config: Config) -> Result<Status, Box<dyn Error>> {
+    // The demo's stand-in for a missing file
     let sample_content = r#"I'm nobody! Who are you?
 Are you nobody, too?
 Then there's a pair of us - don't tell!
@@ -96,22 +140,32 @@ How public, like a frog
 To tell your name the livelong day
 To an admiring bog!"#;
 
-    // For this example, we'll use a default content if the file doesn't exist
-    let contents = match fs::read_to_string(&config.file_path) {
-        Ok(content) => content,
-        Err(_) => {
+    // Real files (or stdin, with `-`) are streamed line by line instead of read whole.
+    // Only --demo falls back to the sample text; otherwise a missing file is an error.
+    let reader: Box<dyn BufRead> = match open_input(Path::new(&config.file_path)) {
+        Ok(reader) => reader,
+        Err(_) if config.demo => {
             println!("File '{}' not found, using sample text:", config.file_path);
-            sample_content.to_string()
+            Box::new(Cursor::new(sample_content))
         }
+        Err(e) => return Ok(file_error(&config, &e)),
     };
 
-    let results = if config.ignore_case {
-        search_case_insensitive(&config.query, &contents)
-    } else {
-        search(&config.query, &contents)
+    let matcher = Matcher::new(&config.query, config.ignore_case, config.use_regex)?;
+    let mut results = Vec::new();
+    let searched = search_reader(reader, &matcher, |_, line, is_match| {
+        if is_match {
+            results.push(line.to_string());
+        }
+    });
+    let summary = match searched {
+        Ok(summary) => summary,
+        Err(e) => return Ok(file_error(&config, &e)),
     };
 
-    if results.is_empty() {
+    if summary.binary && summary.matches > 0 {
+        println!("Binary file {} matches", config.file_path);
+    } else if results.is_empty() {
         println!("No matches found for '{}'", config.query);
     } else {
         println!("Found {} match(es):", results.len());
@@ -120,7 +174,15 @@ To an admiring bog!"#;
         }
     }
 
-    Ok(())
+    Ok(Status::from_search(summary.matches > 0, false))
+}
+
+/// Report an unreadable file on stderr unless `-s` was given
+fn file_error(config: &Config, error: &std::io::Error) -> Status {
+    if !config.no_messages {
+        eprintln!("minigrep: {}: {}", config.file_path, error);
+    }
+    Status::Error
 }
 
 pub fn search<'a>(query: &str, contents: &'a str) -> Vec<&'a str> {
@@ -130,11 +192,15 @@ pub fn search<'a>(query: &str, contents: &'a str) -> Vec<&'a str> {
         .collect()
 }
 
+/// Case-insensitive search by Unicode case folding, so "STRASSE" finds "Straße"
+///
+/// The query is folded once; lines are folded as they're compared, without
+/// allocating a lowercase copy of each one.
 pub fn search_case_insensitive<'a>(query: &str, contents: &'a str) -> Vec<&'a str> {
-    let query = query.to_lowercase();
+    let query = FoldedQuery::new(query, CaseFolder::default());
     contents
         .lines()
-        .filter(|line| line.to_lowercase().contains(&query))
+        .filter(|line| query.is_match(line))
         .collect()
 }
 
@@ -153,50 +219,10 @@ fn demonstrate_cli_patterns() {
         Ok((args[1].clone(), args[2].clone()))
     }
     
-    // Pattern 2: Using clap-like argument parsing (simplified)
-    #[derive(Debug)]
-    struct CliConfig {
-        query: String,
-        file: String,
-        verbose: bool,
-        ignore_case: bool,
-    }
-    
-    fn parse_advanced_args() -> Result<CliConfig, String> {
-        let args: Vec<String> = env::args().collect();
-        let mut config = CliConfig {
-            query: String::new(),
-            file: String::new(),
-            verbose: false,
-            ignore_case: false,
-        };
-        
-        let mut i = 1;
-        while i < args.len() {
-            match args[i].as_str() {
-                "-v" | "--verbose" => config.verbose = true,
-                "-i" | "--ignore-case" => config.ignore_case = true,
-                arg if arg.starts_with('-') => {
-                    return Err(format!("Unknown flag: {}", arg));
-                }
-                _ => {
-                    if config.query.is_empty() {
-                        config.query = args[i].clone();
-                    } else if config.file.is_empty() {
-                        config.file = args[i].clone();
-                    } else {
-                        return Err("Too many arguments".to_string());
-                    }
-                }
-            }
-            i += 1;
-        }
-        
-        if config.query.is_empty() || config.file.is_empty() {
-            return Err("Missing required arguments: query and file".to_string());
-        }
-        
-        Ok(config)
+    // Pattern 2: The full grep-style flag set (-n, -c, -l, -v, -w, ...),
+    // shared with the other minigrep examples through the library
+    fn parse_advanced_args() -> Result<cli::Config, cli::ArgError> {
+        cli::Config::build(env::args())
     }
     
     match parse_simple_args() {
@@ -206,7 +232,7 @@ fn demonstrate_cli_patterns() {
     
     match parse_advanced_args() {
         Ok(config) => println!("Advanced parsing: {:?}", config),
-        Err(e) => println!("Advanced parsing error: {}", e),
+        Err(e) => println!("Advanced parsing error: {}\n\n{}", e, cli::help("minigrep")),
     }
 }
 
@@ -284,6 +310,7 @@ mod error_handling {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use rust_book_examples::minigrep::{regex::Regex, search_regex};
 
     #[test]
     fn case_sensitive() {
@@ -312,6 +339,17 @@ Trust me.";
         );
     }
 
+    #[test]
+    fn case_insensitive_unicode() {
+        let contents = "\
+Die Straße ist lang.
+ΟΔΥΣΣΕΥΣ
+Odysseus";
+
+        assert_eq!(vec!["Die Straße ist lang."], search_case_insensitive("STRASSE", contents));
+        assert_eq!(vec!["ΟΔΥΣΣΕΥΣ"], search_case_insensitive("Οδυσσευς", contents));
+    }
+
     #[test]
     fn config_build_success() {
         let args = vec![
@@ -332,6 +370,60 @@ Trust me.";
         assert!(result.is_err());
     }
 
+    #[test]
+    fn config_build_regex_flag() {
+        let args = vec![
+            "program".to_string(),
+            "query".to_string(),
+            "--regex".to_string(),
+            "filename.txt".to_string(),
+        ];
+
+        let config = Config::build(args.into_iter()).unwrap();
+        assert!(config.use_regex);
+        assert_eq!(config.query, "query");
+        assert_eq!(config.file_path, "filename.txt");
+    }
+
+    #[test]
+    fn config_build_demo_and_silent_flags() {
+        let args = ["program", "--demo", "nobody", "-s", "poem.txt"];
+        let config = Config::build(args.iter().map(|s| s.to_string())).unwrap();
+        assert!(config.demo && config.no_messages && !config.use_regex);
+        assert_eq!(config.query, "nobody");
+        assert_eq!(config.file_path, "poem.txt");
+    }
+
+    #[test]
+    fn missing_file_is_an_error_unless_demo() {
+        let config = |demo| Config {
+            query: "nobody".to_string(),
+            file_path: "/definitely/not/here.txt".to_string(),
+            ignore_case: false,
+            use_regex: false,
+            no_messages: true,
+            demo,
+        };
+
+        assert_eq!(run(config(false)).unwrap(), Status::Error);
+        assert_eq!(run(config(true)).unwrap(), Status::Matched);
+    }
+
+    #[test]
+    fn regex_search() {
+        let regex = Regex::new("^(Are|Then) .*[?!]$").unwrap();
+        let contents = "\
+I'm nobody! Who are you?
+Are you nobody, too?
+Then there's a pair of us - don't tell!
+They'd banish us, you know.";
+
+        assert_eq!(
+            vec!["Are you nobody, too?", "Then there's a pair of us - don't tell!"],
+            search_regex(&regex, contents)
+        );
+    }
+
     #[test]
     fn no_matches() {
         let query = "xyz";
@@ -8,6 +8,7 @@
 //! 
 //! Run this example with: `cargo run --example ch12_02_reading_files -- search_term filename.txt`
 
+use rust_book_examples::args::{self, Positional};
 use rust_book_examples::print_chapter_header;
 use std::env;
 use std::fs;
@@ -19,20 +20,26 @@ fn main() {
     println!("Building a simple grep-like program that reads files...");
     println!();
 
-    // Get command line arguments
-    let args: Vec<String> = env::args().collect();
+    // Parse the command line with the library's declarative parser
+    let program = "ch12_02_reading_files";
+    let matches = GREP.parse(env::args()).unwrap_or_else(|err| {
+        eprintln!("Problem parsing arguments: {}", err);
+        eprintln!("{}", GREP.help(program));
+        process::exit(1);
+    });
+    if matches.flag("help") {
+        print!("{}", GREP.help(program));
+        return;
+    }
 
-    // Check if we have enough arguments
-    if args.len() < 3 {
-        println!("Usage: {} <search_term> <filename>", args[0]);
+    // Both arguments are needed to search a real file
+    let (Some(query), Some(file_path)) = (matches.positional(QUERY.name), matches.positional(FILE.name)) else {
+        print!("{}", GREP.help(program));
         println!();
         println!("Since no arguments provided, using demo mode with sample data:");
         demonstrate_file_reading();
         return;
-    }
-
-    let query = &args[1];
-    let file_path = &args[2];
+    };
 
     println!("Searching for: '{}'", query);
     println!("In file: {}", file_path);
@@ -48,6 +55,13 @@ fn main() {
     }
 }
 
+const QUERY: Positional = Positional::optional("QUERY", "Text to search for");
+const FILE: Positional = Positional::optional("FILE", "File to read and search");
+
+/// This example's command line; without QUERY and FILE it runs the demo
+const GREP: args::Command = args::Command::new("grep", "Read FILE and show the lines containing QUERY.")
+    .positionals(&[QUERY, FILE]);
+
 /// Reads a file and searches for a query string
 fn read_and_search(query: &str, file_path: &str) -> Result<(), Box<dyn std::error::Error>> {
     println!("📖 Reading file: {}", file_path);
@@ -201,6 +215,17 @@ mod tests {
         assert_eq!(matches.len(), 3);
     }
 
+    #[test]
+    fn test_arguments() {
+        let parse = |args: &[&str]| GREP.parse(args.iter().map(|arg| arg.to_string())).unwrap();
+        let matches = parse(&["program", "fox", "poem.txt"]);
+        assert_eq!(matches.positional(QUERY.name), Some("fox"));
+        assert_eq!(matches.positional(FILE.name), Some("poem.txt"));
+        // Too few arguments is the demo, not an error
+        assert_eq!(parse(&["program", "fox"]).positional(FILE.name), None);
+        assert!(GREP.parse(["program", "a", "b", "c"].iter().map(|arg| arg.to_string())).is_err());
+    }
+
     #[test]
     fn test_search_no_matches() {
         let content = "hello world";
@@ -7,7 +7,15 @@
 //! - Making code more testable and maintainable
 //! 
 //! Run this example with: `cargo run --example ch12_03_refactoring_modularity -- search_term filename.txt`
-
+//! Misremembered the term? Rank close matches: `-- --fuzzy --top 5 lifetmie notes/chapter-10-03.md`
+//! Search with a regular expression: `-- -E '^(Rust|Error)' filename.txt`
+//! Without arguments (or with `-- --demo`) it searches sample text instead.
+
+use rust_book_examples::args::{self, ArgError, Flag, Matches, Positional};
+use rust_book_examples::minigrep::color::{highlight, ColorChoice};
+use rust_book_examples::minigrep::fuzzy::{self, Ranked};
+use rust_book_examples::minigrep::casefold::{CaseFolder, FoldedQuery};
+use rust_book_examples::minigrep::Matcher;
 use rust_book_examples::print_chapter_header;
 use std::env;
 use std::error::Error;
@@ -20,54 +28,67 @@ struct Config {
     query: String,
     file_path: String,
     case_sensitive: bool,
+    /// Treat the query as a regular expression
+    use_regex: bool,
+    /// Rank lines by how closely they match instead of requiring the exact query
+    fuzzy: bool,
+    /// How many fuzzy results to show
+    top: usize,
 }
 
+/// Fuzzy results shown when `--top` isn't given
+const DEFAULT_TOP: usize = 10;
+
+// Optional to the parser so `--demo` can stand alone; a search checks for both
+const QUERY: Positional = Positional::optional("QUERY", "Text to search for");
+const FILE: Positional = Positional::optional("FILE", "File to search");
+
+/// Flags and arguments, declared once for parsing and `--help`
+const MINIGREP: args::Command = args::Command::new("minigrep", "Search FILE for lines containing QUERY.")
+    .flags(&[
+        Flag::switch(None, "fuzzy", "Rank lines by how closely they match QUERY"),
+        Flag::option(None, "top", "N", "Show the N best fuzzy matches (default 10)"),
+        Flag::switch(Some('i'), "ignore-case", "Match letters regardless of case").env(&["CASE_INSENSITIVE"]),
+        Flag::switch(Some('E'), "regex", "Treat QUERY as a regular expression"),
+        Flag::switch(None, "demo", "Search sample text instead, as when run without arguments"),
+    ])
+    .positionals(&[QUERY, FILE])
+    .usage(&["[OPTIONS] <QUERY> <FILE>", "--demo"]);
+
 impl Config {
     /// Build a Config from command line arguments
     /// 
     /// # Arguments
-    /// * `args` - Slice of command line arguments
+    /// * `args` - The command line, starting with the program name
     /// 
     /// # Returns
-    /// * `Result<Config, &'static str>` - Config on success, error message on failure
-    fn build(args: &[String]) -> Result<Config, &'static str> {
-        if args.len() < 3 {
-            return Err("not enough arguments");
-        }
-
-        let query = args[1].clone();
-        let file_path = args[2].clone();
-        
-        // Check for case sensitivity flag
-        let case_sensitive = env::var("CASE_INSENSITIVE").is_err();
-
-        Ok(Config {
-            query,
-            file_path,
-            case_sensitive,
-        })
+    /// * `Result<Config, ArgError>` - Config on success, or what was wrong with the arguments
+    fn build(args: impl Iterator<Item = String>) -> Result<Config, ArgError> {
+        Config::from_matches(&MINIGREP.parse(args)?)
     }
-    
-    /// Alternative constructor using iterators (more idiomatic)
-    fn build_from_iter(mut args: impl Iterator<Item = String>) -> Result<Config, &'static str> {
-        args.next(); // Skip program name
 
-        let query = match args.next() {
-            Some(arg) => arg,
-            None => return Err("Didn't get a query string"),
+    /// Read a Config out of already parsed arguments
+    fn from_matches(matches: &Matches) -> Result<Config, ArgError> {
+        let positional = |arg: Positional| {
+            matches
+                .positional(arg.name)
+                .map(str::to_string)
+                .ok_or(ArgError::MissingArgument(arg))
         };
 
-        let file_path = match args.next() {
-            Some(arg) => arg,
-            None => return Err("Didn't get a file path"),
-        };
-
-        let case_sensitive = env::var("CASE_INSENSITIVE").is_err();
+        // Fuzzy ranking compares characters, not patterns
+        if matches.flag("fuzzy") && matches.flag("regex") {
+            return Err(ArgError::Conflict("fuzzy", "regex"));
+        }
 
         Ok(Config {
-            query,
-            file_path,
-            case_sensitive,
+            query: positional(QUERY)?,
+            file_path: positional(FILE)?,
+            // -i, or CASE_INSENSITIVE set in the environment
+            case_sensitive: !matches.flag("ignore-case"),
+            use_regex: matches.flag("regex"),
+            fuzzy: matches.flag("fuzzy"),
+            top: matches.parse("top")?.unwrap_or(DEFAULT_TOP),
         })
     }
 }
@@ -78,24 +99,36 @@ fn main() {
     println!("Improved grep program with better error handling and modularity");
     println!();
 
-    let args: Vec<String> = env::args().collect();
-
-    // Use the new Config::build method
-    let config = Config::build(&args).unwrap_or_else(|err| {
+    let program = env::args().next().unwrap_or_else(|| "minigrep".to_string());
+    // Parse with the shared argument parser; bad arguments are a usage error
+    let usage_error = |err: ArgError| -> ! {
         eprintln!("Problem parsing arguments: {}", err);
-        println!();
-        println!("Usage: {} <search_term> <filename>", args[0]);
-        println!("Set CASE_INSENSITIVE=1 for case-insensitive search");
-        println!();
-        println!("Since no valid arguments provided, running demo mode:");
+        eprintln!();
+        eprint!("{}", MINIGREP.help(&program));
+        process::exit(2);
+    };
+    let matches = MINIGREP.parse(env::args()).unwrap_or_else(|err| usage_error(err));
+    if matches.flag("help") {
+        print!("{}", MINIGREP.help(&program));
+        return;
+    }
+    if env::args().len() == 1 || matches.flag("demo") {
+        println!("Running demo mode (pass QUERY FILE to search a file, or --help):");
         run_demo();
-        process::exit(0);
-    });
+        return;
+    }
+    let config = Config::from_matches(&matches).unwrap_or_else(|err| usage_error(err));
 
     println!("Configuration:");
     println!("  Query: '{}'", config.query);
     println!("  File: {}", config.file_path);
     println!("  Case sensitive: {}", config.case_sensitive);
+    if config.use_regex {
+        println!("  Regular expression: yes");
+    }
+    if config.fuzzy {
+        println!("  Fuzzy: top {} results", config.top);
+    }
     println!();
 
     // Run the main program logic
@@ -120,7 +153,18 @@ fn run(config: Config) -> Result<(), Box<dyn Error>> {
     println!("✅ File read successfully! ({} bytes)", contents.len());
     println!();
 
-    let results = if config.case_sensitive {
+    if config.fuzzy {
+        let ranked = fuzzy_search(&config.query, &contents, config.case_sensitive, config.top);
+        println!("🔍 Closest matches for '{}':", config.query);
+        println!("{}", "=".repeat(50));
+        display_ranked(&ranked, ColorChoice::Auto.enabled());
+        return Ok(());
+    }
+
+    let results = if config.use_regex {
+        let matcher = Matcher::new(&config.query, !config.case_sensitive, true)?;
+        search_matching(&matcher, &contents)
+    } else if config.case_sensitive {
         search(&config.query, &contents)
     } else {
         search_case_insensitive(&config.query, &contents)
@@ -131,6 +175,7 @@ fn run(config: Config) -> Result<(), Box<dyn Error>> {
     
     if results.is_empty() {
         println!("❌ No matches found");
+        println!("💡 Not sure of the spelling? Try again with --fuzzy");
     } else {
         let count = results.len();
         for (line_num, line) in results {
@@ -161,6 +206,8 @@ fn search(query: &str, contents: &str) -> Vec<(usize, String)> {
 
 /// Search for query in contents (case insensitive)
 /// 
+/// Compares by Unicode case folding, so "STRASSE" finds "Straße"
+/// 
 /// # Arguments
 /// * `query` - String to search for
 /// * `contents` - Text to search in
@@ -168,16 +215,79 @@ fn search(query: &str, contents: &str) -> Vec<(usize, String)> {
 /// # Returns
 /// * `Vec<(usize, String)>` - Vector of (line_number, line_content) tuples
 fn search_case_insensitive(query: &str, contents: &str) -> Vec<(usize, String)> {
-    let query = query.to_lowercase();
+    let query = FoldedQuery::new(query, CaseFolder::default());
     
     contents
         .lines()
         .enumerate()
-        .filter(|(_, line)| line.to_lowercase().contains(&query))
+        .filter(|(_, line)| query.is_match(line))
         .map(|(i, line)| (i + 1, line.to_string()))
         .collect()
 }
 
+/// Search for lines `matcher` accepts, such as a regular expression with `-E`
+///
+/// # Returns
+/// * `Vec<(usize, String)>` - Vector of (line_number, line_content) tuples
+fn search_matching(matcher: &Matcher, contents: &str) -> Vec<(usize, String)> {
+    contents
+        .lines()
+        .enumerate()
+        .filter(|(_, line)| matcher.is_match(line))
+        .map(|(i, line)| (i + 1, line.to_string()))
+        .collect()
+}
+
+/// Rank lines by how closely they match `query`, keeping the best `top`
+///
+/// Lines are numbered the same way as in `search`, and ranking works on
+/// those `(line_number, line)` pairs. Like fzf, case is ignored unless the
+/// query has a capital letter, since a half-remembered term's case is
+/// usually half-remembered too.
+fn fuzzy_search(query: &str, contents: &str, case_sensitive: bool, top: usize) -> Vec<Ranked> {
+    let ignore_case = !case_sensitive || !query.chars().any(char::is_uppercase);
+    let lines = contents
+        .lines()
+        .enumerate()
+        .map(|(i, line)| (i + 1, line.to_string()));
+    fuzzy::rank(query, lines, ignore_case, top)
+}
+
+/// Print ranked results, best first, with the matched characters marked
+fn display_ranked(ranked: &[Ranked], color: bool) {
+    if ranked.is_empty() {
+        println!("  ❌ Nothing even close");
+        println!();
+        return;
+    }
+    for (place, result) in ranked.iter().enumerate() {
+        let spans = result.fuzzy.spans(&result.line);
+        let typos = match result.fuzzy.typos {
+            0 => String::new(),
+            1 => ", 1 typo".to_string(),
+            n => format!(", {} typos", n),
+        };
+        let label = format!("  {}. Line {} (score {}{}): ", place + 1, result.line_number, result.fuzzy.score, typos);
+        if color {
+            println!("{}{}", label, highlight(&result.line, &spans));
+        } else {
+            // Without color, point at the matched characters on the next line
+            println!("{}{}", label, result.line);
+            println!("{:width$}{}", "", markers(&result.line, &spans), width = label.chars().count());
+        }
+    }
+    println!();
+}
+
+/// `^` under each matched character of `line`
+fn markers(line: &str, spans: &[(usize, usize)]) -> String {
+    let marks: String = line
+        .char_indices()
+        .map(|(i, _)| if spans.iter().any(|&(start, end)| start <= i && i < end) { '^' } else { ' ' })
+        .collect();
+    marks.trim_end().to_string()
+}
+
 /// Demonstrates the improved modularity with sample data
 fn run_demo() {
     println!();
@@ -213,6 +323,20 @@ Functional programming features work great with imperative code.";
     println!("Case-insensitive search for 'rust':");
     let results = search_case_insensitive("rust", sample_content);
     display_results(&results);
+
+    println!("Regular expression search for '^(RUST|Rust) ':");
+    let regex = Matcher::new("^(RUST|Rust) ", false, true).expect("a valid pattern");
+    display_results(&search_matching(&regex, sample_content));
+
+    // Substring search misses misspellings; fuzzy search ranks what's close
+    println!("Substring search for 'progamming':");
+    display_results(&search("progamming", sample_content));
+
+    println!("Fuzzy search for 'progamming' (top 3):");
+    display_ranked(&fuzzy_search("progamming", sample_content, false, 3), false);
+
+    println!("Fuzzy search for 'qck fx' (top 3):");
+    display_ranked(&fuzzy_search("qck fx", sample_content, false, 3), false);
     
     println!();
     println!("🏗️  Refactoring Benefits:");
@@ -238,16 +362,12 @@ fn display_results(results: &[(usize, String)]) {
 
 // === ADDITIONAL EXAMPLES ===
 
-/// Example of using the iterator-based Config constructor
+/// Example of building a Config from any iterator of arguments
 #[allow(dead_code)]
 fn demonstrate_iterator_config() {
-    let args = vec![
-        "program".to_string(),
-        "query".to_string(),
-        "file.txt".to_string(),
-    ];
-    
-    match Config::build_from_iter(args.into_iter()) {
+    let args = ["program", "query", "file.txt"].iter().map(|s| s.to_string());
+
+    match Config::build(args) {
         Ok(config) => println!("Iterator config: {:?}", config),
         Err(e) => println!("Error: {}", e),
     }
@@ -257,19 +377,19 @@ fn demonstrate_iterator_config() {
 #[allow(dead_code)]
 fn error_handling_examples() {
     // Strategy 1: expect() - panic on error (use for unrecoverable errors)
-    // let config = Config::build(&args).expect("Failed to parse arguments");
+    // let config = Config::build(args).expect("Failed to parse arguments");
     
     // Strategy 2: unwrap_or_else() - provide fallback behavior
-    // let config = Config::build(&args).unwrap_or_else(|_| Config::default());
+    // let config = Config::build(args).unwrap_or_else(|_| Config::default());
     
     // Strategy 3: match - handle each case explicitly
-    // match Config::build(&args) {
+    // match Config::build(args) {
     //     Ok(config) => { /* use config */ },
     //     Err(e) => { /* handle error */ },
     // }
     
     // Strategy 4: ? operator - propagate error up the call stack
-    // fn parse_config(args: &[String]) -> Result<Config, Box<dyn Error>> {
+    // fn parse_config(args: impl Iterator<Item = String>) -> Result<Config, Box<dyn Error>> {
     //     let config = Config::build(args)?;
     //     Ok(config)
     // }
@@ -287,7 +407,7 @@ mod tests {
             "file.txt".to_string(),
         ];
         
-        let config = Config::build(&args).unwrap();
+        let config = Config::build(args.into_iter()).unwrap();
         assert_eq!(config.query, "query");
         assert_eq!(config.file_path, "file.txt");
     }
@@ -296,7 +416,7 @@ mod tests {
     fn test_config_build_failure() {
         let args = vec!["program".to_string()];
         
-        let result = Config::build(&args);
+        let result = Config::build(args.into_iter());
         assert!(result.is_err());
     }
 
@@ -316,6 +436,10 @@ mod tests {
         let results = search_case_insensitive("rust", contents);
         
         assert_eq!(results.len(), 3);
+        assert_eq!(search_case_insensitive("STRASSE", "Die Straße\nDie Strasse"), vec![
+            (1, "Die Straße".to_string()),
+            (2, "Die Strasse".to_string()),
+        ]);
     }
 
     #[test]
@@ -327,15 +451,57 @@ mod tests {
     }
 
     #[test]
-    fn test_config_from_iterator() {
-        let args = vec![
-            "program".to_string(),
-            "search".to_string(),
-            "file.txt".to_string(),
-        ];
-        
-        let config = Config::build_from_iter(args.into_iter()).unwrap();
-        assert_eq!(config.query, "search");
-        assert_eq!(config.file_path, "file.txt");
+    fn test_search_regex() {
+        let args = ["program", "-E", "fun$|^RUST", "file.txt"].iter().map(|s| s.to_string());
+        let config = Config::build(args).unwrap();
+        assert!(config.use_regex);
+
+        let contents = "Rust is great\nrust is fun\nRUST rocks";
+        let matcher = Matcher::new(&config.query, !config.case_sensitive, true).unwrap();
+        let lines: Vec<usize> = search_matching(&matcher, contents).iter().map(|(n, _)| *n).collect();
+        assert_eq!(lines, [2, 3]);
+
+        let args = ["program", "-E", "--fuzzy", "q", "f"].iter().map(|s| s.to_string());
+        assert!(matches!(Config::build(args), Err(ArgError::Conflict("fuzzy", "regex"))));
+    }
+
+    #[test]
+    fn test_config_build_fuzzy() {
+        let args: Vec<String> = ["program", "--fuzzy", "lifetmie", "--top", "3", "notes.md"]
+            .iter()
+            .map(|s| s.to_string())
+            .collect();
+        let config = Config::build(args.into_iter()).unwrap();
+        assert!(config.fuzzy);
+        assert_eq!(config.top, 3);
+        assert_eq!((config.query.as_str(), config.file_path.as_str()), ("lifetmie", "notes.md"));
+
+        let args: Vec<String> = ["program", "q", "f", "--top"].iter().map(|s| s.to_string()).collect();
+        assert!(matches!(Config::build(args.into_iter()), Err(ArgError::MissingValue(_))));
+    }
+
+    #[test]
+    fn test_fuzzy_search() {
+        let contents = "Validating references with lifetimes\nSmart pointers\nlife time";
+        let ranked = fuzzy_search("lifetmie", contents, false, 10);
+        assert_eq!(ranked[0].line_number, 1);
+        assert!(ranked.iter().all(|r| r.line_number != 2));
+
+        // Same line numbers as the substring search
+        let exact = fuzzy_search("pointers", contents, true, 1);
+        assert_eq!((exact[0].line_number, exact[0].line.clone()), search("pointers", contents)[0]);
+        assert_eq!(markers("a pointer", &[(2, 4)]), "  ^^");
+    }
+
+    #[test]
+    fn test_config_flags_anywhere() {
+        let args = ["program", "search", "-i", "file.txt", "--top=2"].iter().map(|s| s.to_string());
+        let config = Config::build(args).unwrap();
+        assert_eq!((config.query.as_str(), config.file_path.as_str()), ("search", "file.txt"));
+        assert!(!config.case_sensitive && !config.fuzzy);
+        assert_eq!(config.top, 2);
+
+        let args = ["program", "--top", "many", "q", "f"].iter().map(|s| s.to_string());
+        assert!(matches!(Config::build(args), Err(ArgError::InvalidValue { .. })));
     }
 }
\ No newline at end of file
@@ -7,9 +7,52 @@
 //! - Building functionality incrementally
 //! 
 //! Run this example with: `cargo run --example ch12_04_tdd_development`
+//! Try the tested functions on a file: `cargo run --example ch12_04_tdd_development -- -i rust poem.txt`
 //! Run tests with: `cargo test --example ch12_04_tdd_development`
 
+use rust_book_examples::args::{self, Flag, Matches, Positional};
+use rust_book_examples::minigrep::casefold::{CaseFolder, FoldedQuery};
+use rust_book_examples::minigrep::Matcher;
 use rust_book_examples::print_chapter_header;
+use std::env;
+use std::error::Error;
+use std::fs;
+use std::process;
+
+const QUERY: Positional = Positional::optional("QUERY", "Text to search for");
+const FILE: Positional = Positional::optional("FILE", "File to search");
+
+/// This example's command line; without QUERY and FILE it runs the demo
+const MINIGREP: args::Command = args::Command::new("minigrep", "Search FILE with the functions built test-first.")
+    .flags(&[
+        Flag::switch(Some('i'), "ignore-case", "Use search_case_insensitive"),
+        Flag::switch(Some('E'), "regex", "Use search_matching with QUERY as a regular expression"),
+    ])
+    .positionals(&[QUERY, FILE]);
+
+/// A search of a real file, once both arguments are given
+#[derive(Debug, PartialEq)]
+struct Config {
+    query: String,
+    file_path: String,
+    ignore_case: bool,
+    use_regex: bool,
+}
+
+impl Config {
+    /// `None` when QUERY or FILE is missing, which means "run the demo"
+    fn from_matches(matches: &Matches) -> Option<Config> {
+        let (Some(query), Some(file_path)) = (matches.positional(QUERY.name), matches.positional(FILE.name)) else {
+            return None;
+        };
+        Some(Config {
+            query: query.to_string(),
+            file_path: file_path.to_string(),
+            ignore_case: matches.flag("ignore-case"),
+            use_regex: matches.flag("regex"),
+        })
+    }
+}
 
 fn main() {
     print_chapter_header("Chapter 12.4", "Developing the Library's Functionality with Test-Driven Development");
@@ -24,8 +67,51 @@ fn main() {
     println!("🔄 REFACTOR: Improve code while keeping tests passing");
     println!();
 
-    demonstrate_search_functionality();
-    demonstrate_tdd_process();
+    let program = "ch12_04_tdd_development";
+    let matches = MINIGREP.parse(env::args()).unwrap_or_else(|err| {
+        eprintln!("Problem parsing arguments: {}", err);
+        eprintln!("{}", MINIGREP.help(program));
+        process::exit(1);
+    });
+    if matches.flag("help") {
+        print!("{}", MINIGREP.help(program));
+        return;
+    }
+
+    match Config::from_matches(&matches) {
+        Some(config) => {
+            if let Err(e) = run(&config) {
+                eprintln!("Application error: {}", e);
+                process::exit(1);
+            }
+        }
+        None => {
+            demonstrate_search_functionality();
+            demonstrate_tdd_process();
+        }
+    }
+}
+
+/// Search a file with whichever tested function the flags pick
+fn run(config: &Config) -> Result<(), Box<dyn Error>> {
+    let contents = fs::read_to_string(&config.file_path)?;
+
+    let results = if config.use_regex {
+        search_matching(&Matcher::new(&config.query, config.ignore_case, true)?, &contents)
+    } else if config.ignore_case {
+        search_case_insensitive(&config.query, &contents)
+    } else {
+        search(&config.query, &contents)
+    };
+
+    println!("Searching for '{}' in {}:", config.query, config.file_path);
+    for line in &results {
+        println!("  Found: {}", line);
+    }
+    if results.is_empty() {
+        println!("  No matches found!");
+    }
+    Ok(())
 }
 
 /// Demonstrates the search functionality we'll build with TDD
@@ -62,6 +148,15 @@ Duct tape.";
         println!("  Found: {}", result);
     }
     println!();
+
+    // The same tests drove a regular-expression mode (`-E` in the other examples)
+    let pattern = "^(Rust|Duct)[: ]";
+    let matcher = Matcher::new(pattern, false, true).expect("a valid pattern");
+    println!("Searching for regex '{}':", pattern);
+    for result in search_matching(&matcher, contents) {
+        println!("  Found: {}", result);
+    }
+    println!();
 }
 
 /// Demonstrates the TDD process step by step
@@ -128,13 +223,14 @@ pub fn search<'a>(query: &str, contents: &'a str) -> Vec<&'a str> {
 
 /// Search for a query string in contents (case-insensitive)
 /// 
-/// This function was also developed with TDD after the case-sensitive version
+/// This function was also developed with TDD after the case-sensitive version.
+/// It compares by Unicode case folding, so "STRASSE" finds "Straße".
 pub fn search_case_insensitive<'a>(query: &str, contents: &'a str) -> Vec<&'a str> {
-    let query = query.to_lowercase();
+    let query = FoldedQuery::new(query, CaseFolder::default());
     let mut results = Vec::new();
 
     for line in contents.lines() {
-        if line.to_lowercase().contains(&query) {
+        if query.is_match(line) {
             results.push(line);
         }
     }
@@ -142,6 +238,18 @@ pub fn search_case_insensitive<'a>(query: &str, contents: &'a str) -> Vec<&'a st
     results
 }
 
+/// Search for lines that `matcher` accepts, such as a regular expression
+///
+/// Written test-first like `search`, once the tests for `-E` were in place.
+/// The matcher does the matching, so this keeps `search`'s signature and
+/// still returns lines borrowed from `contents`.
+pub fn search_matching<'a>(matcher: &Matcher, contents: &'a str) -> Vec<&'a str> {
+    contents
+        .lines()
+        .filter(|line| matcher.is_match(line))
+        .collect()
+}
+
 // === TDD EVOLUTION EXAMPLES ===
 
 /// Example showing how the search function might have evolved during TDD
@@ -191,6 +299,21 @@ mod tdd_evolution {
 mod tests {
     use super::*;
 
+    #[test]
+    fn arguments() {
+        let parse = |args: &[&str]| Config::from_matches(&MINIGREP.parse(args.iter().map(|arg| arg.to_string())).unwrap());
+        assert_eq!(parse(&["program"]), None);
+        assert_eq!(
+            parse(&["program", "-iE", "^ru", "poem.txt"]),
+            Some(Config {
+                query: "^ru".to_string(),
+                file_path: "poem.txt".to_string(),
+                ignore_case: true,
+                use_regex: true,
+            })
+        );
+    }
+
     #[test]
     fn case_sensitive() {
         // TDD Step 1: Write this test first (it will fail)
@@ -220,6 +343,30 @@ Trust me.";
         );
     }
 
+    #[test]
+    fn case_insensitive_unicode() {
+        // Lowercasing can't match ß with SS; case folding can
+        let contents = "\
+Die Straße ist lang.
+Σίσυφος ΚΑΙ ΟΔΥΣΣΕΥΣ";
+
+        assert_eq!(vec!["Die Straße ist lang."], search_case_insensitive("STRASSE", contents));
+        assert_eq!(vec!["Σίσυφος ΚΑΙ ΟΔΥΣΣΕΥΣ"], search_case_insensitive("οδυσσευς", contents));
+    }
+
+    #[test]
+    fn regex() {
+        // TDD: A failing test for `-E` came before `search_matching`
+        let contents = "\
+Rust:
+safe, fast, productive.
+Pick three.
+Duct tape.";
+        let matcher = Matcher::new(r"^\w+:$|(three|tape)\.$", false, true).unwrap();
+
+        assert_eq!(vec!["Rust:", "Pick three.", "Duct tape."], search_matching(&matcher, contents));
+    }
+
     #[test]
     fn no_results() {
         // TDD: Test edge case - no matches
@@ -8,7 +8,12 @@
 //! 
 //! Run this example with: `cargo run --example ch12_05_environment_variables -- search filename.txt`
 //! For case-insensitive search: `CASE_INSENSITIVE=1 cargo run --example ch12_05_environment_variables -- search filename.txt`
+//! For a regular expression: `cargo run --example ch12_05_environment_variables -- -E '^(Rust|Go) ' filename.txt`
+//! Without arguments (or with `-- --demo`) it searches sample data instead.
 
+use rust_book_examples::args::{self, ArgError, Flag, Positional};
+use rust_book_examples::minigrep::casefold::{CaseFolder, FoldedQuery};
+use rust_book_examples::minigrep::Matcher;
 use rust_book_examples::print_chapter_header;
 use std::env;
 use std::error::Error;
@@ -22,30 +27,56 @@ struct Config {
     file_path: String,
     ignore_case: bool,
     verbose: bool,
+    use_regex: bool,
 }
 
+// Optional to the parser so `--demo` can stand alone; a search checks for both
+const QUERY: Positional = Positional::optional("QUERY", "Text to search for");
+const FILE: Positional = Positional::optional("FILE", "File to search");
+
+/// Flags and arguments, declared once for parsing and `--help`
+///
+/// `--verbose` takes its default from `VERBOSE` through the parser, where
+/// `0`, `false`, `no` and `off` count as unset. `CASE_INSENSITIVE` is read
+/// the book's way instead, in [`Config::build_with_env`].
+const GREP: args::Command = args::Command::new("grep", "Search FILE for lines containing QUERY.")
+    .flags(&[
+        Flag::switch(Some('i'), "ignore-case", "Match letters regardless of case (or set CASE_INSENSITIVE)"),
+        Flag::switch(Some('v'), "verbose", "Show the file size and a preview before searching").env(&["VERBOSE"]),
+        Flag::switch(Some('E'), "regex", "Treat QUERY as a regular expression"),
+        Flag::switch(None, "demo", "Search sample data instead, as when run without arguments"),
+    ])
+    .positionals(&[QUERY, FILE])
+    .usage(&["[OPTIONS] <QUERY> <FILE>", "--demo"]);
+
 impl Config {
     /// Build configuration from command line args and environment variables
-    fn build(args: &[String]) -> Result<Config, &'static str> {
-        if args.len() < 3 {
-            return Err("Usage: program <query> <file_path>");
-        }
+    fn build(args: impl Iterator<Item = String>) -> Result<Config, ArgError> {
+        Config::build_with_env(args, |name| env::var(name).ok())
+    }
 
-        let query = args[1].clone();
-        let file_path = args[2].clone();
-
-        // Read environment variables for configuration
-        // CASE_INSENSITIVE=1 enables case-insensitive search
-        let ignore_case = env::var("CASE_INSENSITIVE").is_ok();
-        
-        // VERBOSE=1 enables verbose output
-        let verbose = env::var("VERBOSE").is_ok();
+    /// Like [`Config::build`], but looks environment variables up with `env`
+    fn build_with_env<F>(args: impl Iterator<Item = String>, env: F) -> Result<Config, ArgError>
+    where
+        F: Fn(&str) -> Option<String>,
+    {
+        let matches = GREP.parse_with_env(args, &env)?;
+        let positional = |arg: Positional| {
+            matches
+                .positional(arg.name)
+                .map(str::to_string)
+                .ok_or(ArgError::MissingArgument(arg))
+        };
 
         Ok(Config {
-            query,
-            file_path,
-            ignore_case,
-            verbose,
+            query: positional(QUERY)?,
+            file_path: positional(FILE)?,
+            // Like the book's `env::var("CASE_INSENSITIVE").is_ok()`, the variable
+            // turns case-insensitive search on just by being set, to any value
+            ignore_case: matches.flag("ignore-case") || env("CASE_INSENSITIVE").is_some(),
+            // VERBOSE=1 or -v enables verbose output; VERBOSE=0/false/no/off doesn't
+            verbose: matches.flag("verbose"),
+            use_regex: matches.flag("regex"),
         })
     }
 }
@@ -56,23 +87,28 @@ fn main() {
     println!("Grep program with environment variable configuration");
     println!();
 
-    let args: Vec<String> = env::args().collect();
-
-    let config = Config::build(&args).unwrap_or_else(|err| {
+    // Bad arguments are a usage error; the demo runs only when asked for
+    let usage_error = |err: ArgError| -> ! {
         eprintln!("Problem parsing arguments: {}", err);
-        println!();
-        println!("Environment Variables:");
-        println!("  CASE_INSENSITIVE=1  - Enable case-insensitive search");
-        println!("  VERBOSE=1          - Enable verbose output");
-        println!();
-        println!("Examples:");
-        println!("  cargo run --example ch12_05_environment_variables -- rust file.txt");
-        println!("  CASE_INSENSITIVE=1 cargo run --example ch12_05_environment_variables -- rust file.txt");
-        println!();
-        println!("Running demo mode with sample data:");
+        eprintln!();
+        eprint!("{}", GREP.help("ch12_05_environment_variables"));
+        eprintln!();
+        eprintln!("Examples:");
+        eprintln!("  cargo run --example ch12_05_environment_variables -- rust file.txt");
+        eprintln!("  CASE_INSENSITIVE=1 cargo run --example ch12_05_environment_variables -- rust file.txt");
+        process::exit(2);
+    };
+    let matches = GREP.parse(env::args()).unwrap_or_else(|err| usage_error(err));
+    if matches.flag("help") {
+        print!("{}", GREP.help("ch12_05_environment_variables"));
+        return;
+    }
+    if env::args().len() == 1 || matches.flag("demo") {
+        println!("Running demo mode with sample data (pass QUERY FILE to search a file, or --help):");
         run_demo();
-        process::exit(0);
-    });
+        return;
+    }
+    let config = Config::build(env::args()).unwrap_or_else(|err| usage_error(err));
 
     display_config(&config);
 
@@ -88,6 +124,7 @@ fn display_config(config: &Config) {
     println!("  File: {}", config.file_path);
     println!("  Case insensitive: {}", config.ignore_case);
     println!("  Verbose mode: {}", config.verbose);
+    println!("  Regular expression: {}", config.use_regex);
     println!();
 }
 
@@ -113,13 +150,21 @@ fn run(config: Config) -> Result<(), Box<dyn Error>> {
         println!();
     }
 
-    let results = if config.ignore_case {
+    let results = if config.use_regex {
+        let matcher = Matcher::new(&config.query, config.ignore_case, true)?;
+        search_matching(&matcher, &contents)
+    } else if config.ignore_case {
         search_case_insensitive(&config.query, &contents)
     } else {
         search(&config.query, &contents)
     };
 
-    let search_type = if config.ignore_case { "case-insensitive" } else { "case-sensitive" };
+    let search_type = match (config.use_regex, config.ignore_case) {
+        (true, true) => "case-insensitive regex",
+        (true, false) => "regex",
+        (false, true) => "case-insensitive",
+        (false, false) => "case-sensitive",
+    };
     println!("🔍 {} search results for '{}':", search_type, config.query);
     println!("{}", "=".repeat(50));
 
@@ -149,11 +194,21 @@ fn search<'a>(query: &str, contents: &'a str) -> Vec<&'a str> {
 }
 
 /// Case-insensitive search function
+///
+/// Compares by Unicode case folding, so "STRASSE" finds "Straße"
 fn search_case_insensitive<'a>(query: &str, contents: &'a str) -> Vec<&'a str> {
-    let query = query.to_lowercase();
+    let query = FoldedQuery::new(query, CaseFolder::default());
     contents
         .lines()
-        .filter(|line| line.to_lowercase().contains(&query))
+        .filter(|line| query.is_match(line))
+        .collect()
+}
+
+/// Lines `matcher` accepts, such as a regular expression with `-E`
+fn search_matching<'a>(matcher: &Matcher, contents: &'a str) -> Vec<&'a str> {
+    contents
+        .lines()
+        .filter(|line| matcher.is_match(line))
         .collect()
 }
 
@@ -246,6 +301,12 @@ JAVASCRIPT and rust serve different purposes.";
     let results = search_case_insensitive("rust", sample_content);
     display_demo_results(&results);
 
+    // -E combines with CASE_INSENSITIVE too
+    println!("5. Case-insensitive regex search for '^(go|java) ' (-E, CASE_INSENSITIVE=1):");
+    let regex = Matcher::new("^(go|java) ", true, true).expect("a valid pattern");
+    let results = search_matching(&regex, sample_content);
+    display_demo_results(&results);
+
     println!();
     println!("💡 Environment Variable Benefits:");
     println!("• Configure program behavior without recompilation");
@@ -321,6 +382,7 @@ mod tests {
         assert!(results.contains(&"rust"));
         assert!(results.contains(&"RUST"));
         assert!(!results.contains(&"Java"));
+        assert_eq!(vec!["Die Straße"], search_case_insensitive("STRASSE", "Die Straße\nJava"));
     }
 
     #[test]
@@ -331,7 +393,7 @@ mod tests {
             "file.txt".to_string(),
         ];
         
-        let config = Config::build(&args).unwrap();
+        let config = Config::build(args.into_iter()).unwrap();
         assert_eq!(config.query, "query");
         assert_eq!(config.file_path, "file.txt");
         // ignore_case and verbose depend on environment variables
@@ -340,11 +402,38 @@ mod tests {
     #[test]
     fn test_config_build_insufficient_args() {
         let args = vec!["program".to_string()];
-        let result = Config::build(&args);
+        let result = Config::build(args.into_iter());
         
         assert!(result.is_err());
     }
 
+    #[test]
+    fn test_config_build_with_env() {
+        let args = || ["program", "query", "file.txt"].iter().map(|s| s.to_string());
+        let config = Config::build_with_env(args(), |name| (name == "VERBOSE").then(|| "1".to_string())).unwrap();
+        assert!(config.verbose && !config.ignore_case);
+
+        // Any value of CASE_INSENSITIVE turns it on, as in the book; VERBOSE=off doesn't
+        let config = Config::build_with_env(args(), |_| Some("off".to_string())).unwrap();
+        assert!(!config.verbose && config.ignore_case);
+        let config = Config::build_with_env(args(), |name| (name == "CASE_INSENSITIVE").then(String::new)).unwrap();
+        assert!(config.ignore_case);
+
+        let flags = ["program", "-iv", "query", "file.txt"].iter().map(|s| s.to_string());
+        let config = Config::build_with_env(flags, |_| None).unwrap();
+        assert!(config.verbose && config.ignore_case);
+    }
+
+    #[test]
+    fn test_search_regex() {
+        let flags = ["program", "-E", "^ru|T$", "file.txt"].iter().map(|s| s.to_string());
+        let config = Config::build_with_env(flags, |_| None).unwrap();
+        assert!(config.use_regex);
+
+        let matcher = Matcher::new(&config.query, config.ignore_case, true).unwrap();
+        assert_eq!(vec!["rust", "RUST"], search_matching(&matcher, "Rust\nrust\nRUST\nJava"));
+    }
+
     #[test]
     fn test_get_line_number() {
         let contents = "line 1\nline 2\nline 3";
@@ -7,117 +7,538 @@
 //! - Performance benefits of iterator chains
 //! 
 //! Run this example with: `cargo run --example ch13_03_improving_io_project -- search_term filename.txt`
+//! Add `-E` to search with a regular expression: `-- -E '^Rust|rust$' filename.txt`
+//! Pass directories to search recursively: `-- --include '*.md' ownership notes/`
+//! Show context around matches: `-- -C 2 --color=always borrow notes/`
+//! Search for many strings at once: `-- -e ownership -e borrowing -f more_patterns.txt notes/`
+//! Rewrite matches with `-- -E -r '$2, $1' '(\w+) (\w+)' poem.txt`; add `--dry-run` for a diff or `--in-place` to save
+//! Feed other tools with `-- --json rust src/` (JSON Lines) or `-- -lZ rust src/ | xargs -0 wc -l`
+//! Read standard input with `-`: `cat poem.txt | cargo run --example ch13_03_improving_io_project -- nobody -`
+//! Index a directory you search often with `-- index notes/`; later searches of `notes/` skip files that can't match
+//! Large trees are searched on a thread pool; `-j1` stays single-threaded
+//! See every grep-style flag (`-n`, `-c`, `-l`, `-v`, `-w`, `-o`, ...) with `-- --help`
 
+use rust_book_examples::minigrep::aho_corasick::AhoCorasick;
+use rust_book_examples::minigrep::casefold::{CaseFolder, FoldedQuery};
+use rust_book_examples::minigrep::cli::{self, Command, Config, SortBy, Status};
+use rust_book_examples::minigrep::color::highlight;
+use rust_book_examples::minigrep::context::{ContextItem, ContextWindow};
+use rust_book_examples::minigrep::glob::{Glob, GlobError};
+use rust_book_examples::minigrep::index::{Index, IndexQuery};
+use rust_book_examples::minigrep::json;
+use rust_book_examples::minigrep::parallel::{search_files, FileReport, FileSearch, ReportLine};
+use rust_book_examples::minigrep::replace::{self, Template};
+use rust_book_examples::minigrep::stream::{display_name, is_stdin, Searcher};
+use rust_book_examples::minigrep::walk::{walk, walk_parallel, WalkOptions};
+use rust_book_examples::minigrep::Matcher;
 use rust_book_examples::print_chapter_header;
+use rust_book_examples::thread_pool::ThreadPool;
 use std::env;
 use std::error::Error;
-use std::fs;
+use std::fmt;
+use std::path::Path;
 use std::process;
+use std::time::Instant;
 
-/// Configuration using iterator-based argument parsing
-#[derive(Debug)]
-struct Config {
-    query: String,
-    file_path: String,
-    ignore_case: bool,
+fn main() {
+    // `Config::build` lives in the shared minigrep library, on the declarative args parser
+    let config = Config::build(env::args()).unwrap_or_else(|err| {
+        eprintln!("Problem parsing arguments: {}", err);
+        eprintln!("Try --help for usage, or --demo to run the demo with sample data");
+        process::exit(Status::Error.code());
+    });
+
+    // Output meant for other programs leaves out the decoration
+    if config.decorate() || config.demo {
+        print_chapter_header("Chapter 13.3", "Improving Our I/O Project");
+
+        println!("Refactored grep using iterators and functional programming style");
+        println!();
+    }
+
+    if config.help {
+        print!("{}", cli::help("minigrep"));
+        return;
+    }
+    if config.demo {
+        println!("Running demo with sample data:");
+        run_demo();
+        return;
+    }
+    if config.command == Command::Index {
+        process::exit(run_index(&config).code());
+    }
+
+    if config.decorate() {
+        println!("📋 Configuration:");
+        if config.patterns.is_empty() && config.pattern_files.is_empty() {
+            println!("  Query: '{}'", config.query);
+        } else {
+            println!("  Patterns: {:?}", config.patterns);
+            if !config.pattern_files.is_empty() {
+                println!("  Pattern files: {}", config.pattern_files.join(", "));
+            }
+        }
+        println!("  Paths: {}", config.paths.join(", "));
+        println!("  Case insensitive: {}", config.ignore_case);
+        println!("  Regex: {}", config.use_regex);
+        println!("  Context: {} before, {} after", config.before_context, config.after_context);
+        println!();
+    }
+
+    // Exit like grep: 0 for a match, 1 for none, 2 for errors
+    match run(config) {
+        Ok(status) => process::exit(status.code()),
+        Err(e) => {
+            eprintln!("Application error: {}", e);
+            process::exit(Status::Error.code());
+        }
+    }
 }
 
-impl Config {
-    /// Build configuration using iterator instead of indexing
-    /// This is more idiomatic and handles edge cases better
-    fn build(mut args: impl Iterator<Item = String>) -> Result<Config, &'static str> {
-        args.next(); // Skip program name
+fn run(config: Config) -> Result<Status, Box<dyn Error>> {
+    let started = Instant::now();
+    let decorate = config.decorate();
+    // Any number of -e/-f patterns are matched together in one pass over each line
+    let patterns = config.load_patterns()?;
+    let matcher = Matcher::with_patterns(&patterns, &config.match_options())?;
+    // With several patterns, say which ones each line matched
+    let tag_patterns = patterns.len() > 1 && !config.invert && !config.only_matching;
+    let template = config.replace.as_deref().map(Template::parse).transpose()?;
+    let editing = template.is_some() && (config.in_place || config.dry_run);
+    // Replaced text is printed plain, since the matches it would highlight are gone
+    let color = config.color.enabled() && !config.invert && !config.json && template.is_none();
 
-        let query = match args.next() {
-            Some(arg) => arg,
-            None => return Err("Didn't get a query string"),
-        };
+    // -c, -l and -L print one line per file instead of the lines themselves
+    let list_files = config.files_with_matches || config.files_without_match;
+    let per_file_only = config.count || list_files || config.quiet;
+    let show_context = !per_file_only && (!config.only_matching || config.json);
+    let has_context = show_context && (config.before_context > 0 || config.after_context > 0);
 
-        let file_path = match args.next() {
-            Some(arg) => arg,
-            None => return Err("Didn't get a file path"),
-        };
+    let search = FileSearch {
+        matcher: matcher.clone(),
+        searcher: Searcher {
+            invert: config.invert,
+            // Listing a file, or -q, only needs its first selected line
+            max_count: if list_files || config.quiet {
+                Some(config.max_count.unwrap_or(1).min(1))
+            } else {
+                config.max_count
+            },
+            after_context: config.after_context,
+        },
+        before_context: if show_context { config.before_context } else { 0 },
+        after_context: if show_context { config.after_context } else { 0 },
+        // Editing rereads each file with matches, so the lines aren't needed either
+        collect_lines: !per_file_only && !editing,
+    };
 
-        let ignore_case = env::var("CASE_INSENSITIVE").is_ok();
+    let options = WalkOptions {
+        include: compile_globs(&config.include)?,
+        exclude: compile_globs(&config.exclude)?,
+        hidden: config.hidden,
+        respect_gitignore: !config.no_ignore,
+        follow_links: config.follow,
+    };
 
-        Ok(Config {
-            query,
-            file_path,
-            ignore_case,
-        })
+    let mut printer = Printer {
+        config: &config,
+        matcher: &matcher,
+        patterns: &patterns,
+        template: template.as_ref(),
+        decorate,
+        color,
+        tag_patterns,
+        has_context,
+        editing,
+        list_files,
+        per_file_only,
+        // Like grep, prefix lines with the file name once there's more than one file
+        show_file_names: config.paths.len() > 1 || config.paths.iter().any(|path| Path::new(path).is_dir()),
+        match_count: 0,
+        had_errors: false,
+        printed_lines: false,
+        file: None,
+        stats: json::Stats::default(),
+    };
+
+    // Walking and searching both run on the pool; -j1 keeps everything on this thread
+    let pool = match config.threads {
+        Some(1) => None,
+        Some(threads) => Some(ThreadPool::new(threads)),
+        None => Some(ThreadPool::with_available_parallelism()),
+    };
+    let walked = match &pool {
+        Some(pool) => walk_parallel(&config.paths, &options, pool),
+        None => walk(&config.paths, &options),
+    };
+
+    let mut files = Vec::new();
+    for entry in walked {
+        match entry {
+            Ok(path) => files.push(path),
+            Err(e) => printer.error(&e),
+        }
+    }
+    if config.sort == SortBy::Path {
+        files.sort();
     }
 
-    /// Alternative: Old indexing-based approach (for comparison)
-    #[allow(dead_code)]
-    fn build_old_way(args: &[String]) -> Result<Config, &'static str> {
-        if args.len() < 3 {
-            return Err("not enough arguments");
+    // A directory's trigram index rules out files that can't contain the query.
+    // Inverted matches, -c and -L need every file, matching or not.
+    let needs_every_file = config.invert || config.count || config.files_without_match;
+    if !config.no_index
+        && !needs_every_file
+        && let Some(query) = IndexQuery::new(&patterns, &config.match_options())
+    {
+        let indexes = load_indexes(&config);
+        let total = files.len();
+        files.retain(|file| indexes.iter().all(|index| index.may_match(file, &query)));
+        if decorate && !indexes.is_empty() {
+            println!("📇 Index ruled out {} of {} files", total - files.len(), total);
         }
+    }
 
-        let query = args[1].clone();
-        let file_path = args[2].clone();
-        let ignore_case = env::var("CASE_INSENSITIVE").is_ok();
+    if decorate {
+        println!("🔍 Search Results:");
+        println!("{}", "=".repeat(50));
+    }
+
+    // Reports arrive in file order, so output is the same however many threads run.
+    // With -j1 each line is printed as soon as it's found, and nothing is kept.
+    match &pool {
+        Some(pool) => {
+            for report in search_files(search, files, Some(pool)) {
+                for line in &report.lines {
+                    printer.line(&report.path, line);
+                }
+                if !printer.finish_file(report) {
+                    break;
+                }
+            }
+        }
+        None => {
+            for path in files {
+                let report = search.search_path_with(&path, |line| printer.line(&path, &line));
+                if !printer.finish_file(report) {
+                    break;
+                }
+            }
+        }
+    }
+    let Printer { match_count, had_errors, stats, .. } = printer;
 
-        Ok(Config {
-            query,
-            file_path,
-            ignore_case,
-        })
+    if config.json {
+        println!("{}", json::summary(started.elapsed(), &stats));
+    } else if decorate {
+        if match_count == 0 && patterns.len() == 1 {
+            println!("❌ No matches found for '{}'", patterns[0]);
+        } else if match_count == 0 {
+            println!("❌ No matches found for any of {} patterns", patterns.len());
+        } else {
+            println!("\n✅ Found {} matches", match_count);
+        }
     }
+
+    Ok(Status::from_search(match_count > 0, had_errors))
 }
 
-fn main() {
-    print_chapter_header("Chapter 13.3", "Improving Our I/O Project");
+/// The indexes of the directories being searched; a broken index is skipped
+fn load_indexes(config: &Config) -> Vec<Index> {
+    let mut indexes = Vec::new();
+    for path in config.paths.iter().filter(|path| Path::new(path).is_dir()) {
+        match Index::load(path) {
+            Ok(Some(index)) => indexes.push(index),
+            Ok(None) => {}
+            Err(e) if !config.no_messages => {
+                eprintln!("minigrep: {}: {} (searching without it)", path, e);
+            }
+            Err(_) => {}
+        }
+    }
+    indexes
+}
 
-    println!("Refactored grep using iterators and functional programming style");
-    println!();
+/// `minigrep index`: build or refresh the trigram index of each directory
+fn run_index(config: &Config) -> Status {
+    let options = match (compile_globs(&config.include), compile_globs(&config.exclude)) {
+        (Ok(include), Ok(exclude)) => WalkOptions {
+            include,
+            exclude,
+            hidden: config.hidden,
+            respect_gitignore: !config.no_ignore,
+            follow_links: config.follow,
+        },
+        (Err(e), _) | (_, Err(e)) => {
+            eprintln!("Application error: {}", e);
+            return Status::Error;
+        }
+    };
 
-    // Use iterator-based config building
-    let config = Config::build(env::args()).unwrap_or_else(|err| {
-        eprintln!("Problem parsing arguments: {}", err);
-        println!();
-        println!("Usage: program <query> <file_path>");
-        println!("Set CASE_INSENSITIVE=1 for case-insensitive search");
-        println!();
-        println!("Running demo with sample data:");
-        run_demo();
-        process::exit(0);
-    });
+    let mut had_errors = false;
+    for path in &config.paths {
+        if !Path::new(path).is_dir() {
+            eprintln!("minigrep: {}: only directories can be indexed", path);
+            had_errors = true;
+            continue;
+        }
+        // A damaged index is rebuilt from scratch
+        let mut index = Index::open(path).unwrap_or_else(|e| {
+            eprintln!("minigrep: {}: {}; rebuilding", path, e);
+            Index::new(path)
+        });
+        let stats = index.update(&options);
+        if let Err(e) = index.save() {
+            eprintln!("minigrep: {}: {}", path, e);
+            had_errors = true;
+            continue;
+        }
+        println!(
+            "📇 Indexed {}: {} files ({} added, {} updated, {} removed, {} unchanged)",
+            path,
+            index.len(),
+            stats.added,
+            stats.updated,
+            stats.removed,
+            stats.unchanged
+        );
+    }
 
-    println!("📋 Configuration:");
-    println!("  Query: '{}'", config.query);
-    println!("  File: {}", config.file_path);
-    println!("  Case insensitive: {}", config.ignore_case);
-    println!();
+    if had_errors { Status::Error } else { Status::Matched }
+}
 
-    if let Err(e) = run(config) {
-        eprintln!("Application error: {}", e);
-        process::exit(1);
+/// Apply `--replace` to a whole file: print its diff for `--dry-run`, or
+/// rewrite it for `--in-place`
+fn edit_file(path: &Path, matcher: &Matcher, template: &Template, config: &Config) -> Result<(), Box<dyn Error>> {
+    if is_stdin(path) {
+        return Err("standard input can't be edited".into());
+    }
+    let edit = replace::edit_file(path, matcher, template)?;
+    if config.dry_run {
+        print!("{}", edit.diff());
+    } else {
+        edit.apply(config.backup_suffix())?;
+        if !config.null {
+            println!("✏️  Rewrote {} ({} lines changed)", path.display(), edit.changes.len());
+        }
     }
+    Ok(())
 }
 
-fn run(config: Config) -> Result<(), Box<dyn Error>> {
-    let contents = fs::read_to_string(config.file_path)?;
+/// Prints search results one line at a time, in the grep style or as JSON
+/// Lines, then each file's summary once its search is done
+struct Printer<'a> {
+    config: &'a Config,
+    matcher: &'a Matcher,
+    patterns: &'a [String],
+    template: Option<&'a Template>,
+    decorate: bool,
+    color: bool,
+    tag_patterns: bool,
+    has_context: bool,
+    editing: bool,
+    list_files: bool,
+    per_file_only: bool,
+    show_file_names: bool,
+    /// Selected lines in the files finished so far
+    match_count: usize,
+    had_errors: bool,
+    printed_lines: bool,
+    /// The file whose lines are being printed
+    file: Option<FileOutput>,
+    stats: json::Stats,
+}
 
-    let results = if config.ignore_case {
-        search_case_insensitive(&config.query, &contents)
-    } else {
-        search(&config.query, &contents)
-    };
+/// What's been printed for the current file
+struct FileOutput {
+    name: String,
+    /// Selected lines printed
+    matches: usize,
+    /// Submatches in the JSON records
+    spans: usize,
+}
 
-    println!("🔍 Search Results:");
-    println!("{}", "=".repeat(50));
+impl Printer<'_> {
+    /// Report an unreadable file or directory, unless -s asks for quiet
+    fn error(&mut self, error: &dyn fmt::Display) {
+        self.had_errors = true;
+        if !self.config.no_messages {
+            eprintln!("minigrep: {}", error);
+        }
+    }
 
-    if results.is_empty() {
-        println!("❌ No matches found for '{}'", config.query);
-    } else {
-        for (line_num, line) in results.iter().enumerate() {
-            println!("{}. {}", line_num + 1, line);
+    /// Print one line of `path`'s output
+    fn line(&mut self, path: &Path, line: &ReportLine) {
+        if self.editing {
+            return;
+        }
+        let (config, matcher, decorate, match_count) = (self.config, self.matcher, self.decorate, self.match_count);
+        if self.file.is_none() {
+            let name = display_name(path).into_owned();
+            if config.json {
+                println!("{}", json::begin(&name));
+            } else if self.has_context && self.printed_lines {
+                // Context groups from different files are separated too
+                println!("--");
+            }
+            self.printed_lines = true;
+            self.file = Some(FileOutput { name, matches: 0, spans: 0 });
+        }
+        let file = self.file.as_mut().expect("the file was started above");
+
+        if config.json {
+            match line {
+                ReportLine::Line { number, offset, text, selected: true } => {
+                    // Inverted lines are selected for not matching, so they have no submatches
+                    let spans: Vec<(usize, usize)> = if config.invert {
+                        Vec::new()
+                    } else {
+                        matcher.find_spans(text).into_iter().filter(|(start, end)| start < end).collect()
+                    };
+                    file.spans += spans.len();
+                    println!("{}", json::matched(&file.name, *number, *offset, text, &spans));
+                }
+                ReportLine::Line { number, offset, text, .. } => {
+                    println!("{}", json::context(&file.name, *number, *offset, text))
+                }
+                ReportLine::Separator => {}
+            }
+            return;
+        }
+
+        // grep marks selected lines with `:` and context lines with `-`; -Z puts NUL after names
+        let name_end = |separator: char| if config.null { '\0' } else { separator };
+        let prefix = |number: usize, separator: char| {
+            let mut prefix = String::new();
+            if self.show_file_names {
+                prefix.push_str(&format!("{}{}", file.name, name_end(separator)));
+            }
+            if config.line_number {
+                prefix.push_str(&format!("{}{}", number, separator));
+            }
+            prefix
+        };
+
+        match line {
+            ReportLine::Separator => println!("--"),
+            ReportLine::Line { number, text, selected: true, .. } => {
+                file.matches += 1;
+                let label = if decorate { format!("{}. ", match_count + file.matches) } else { String::new() };
+                let prefix = prefix(*number, ':');
+                if config.only_matching
+                    && let Some(template) = self.template
+                {
+                    // -o prints just the replacement for each match
+                    for ((start, end), replaced) in matcher.replacements(text, template) {
+                        if start < end {
+                            println!("{}{}{}", label, prefix, replaced);
+                        }
+                    }
+                    return;
+                }
+                if config.only_matching {
+                    // Inverted lines have no matches to show
+                    for (start, end) in matcher.find_spans(text).into_iter().filter(|(s, e)| s < e) {
+                        let part = &text[start..end];
+                        let part = if self.color { highlight(part, &[(0, part.len())]) } else { part.to_string() };
+                        println!("{}{}{}", label, prefix, part);
+                    }
+                    return;
+                }
+                // The tag goes after the `file:line:` prefix, so tools splitting on `:` still work
+                let tags = if self.tag_patterns {
+                    let matched: Vec<&str> = matcher
+                        .matching_patterns(text)
+                        .into_iter()
+                        .map(|id| self.patterns[id].as_str())
+                        .collect();
+                    format!("[{}] ", matched.join(", "))
+                } else {
+                    String::new()
+                };
+                let text = if self.color {
+                    highlight(text, &matcher.find_spans(text))
+                } else if let Some(template) = self.template {
+                    matcher.replace_all(text, template)
+                } else {
+                    text.to_string()
+                };
+                println!("{}{}{}{}", label, prefix, tags, text);
+            }
+            ReportLine::Line { number, text, .. } => {
+                // Indent to line up with the "N. " numbering of matches
+                let width = if decorate { format!("{}. ", (match_count + file.matches).max(1)).len() } else { 0 };
+                println!("{:width$}{}{}", "", prefix(*number, '-'), text);
+            }
         }
-        println!("\n✅ Found {} matches", results.len());
     }
 
-    Ok(())
+    /// Finish a file whose lines have all been printed; false once -q has its answer
+    fn finish_file(&mut self, report: FileReport) -> bool {
+        let config = self.config;
+        let file = self.file.take();
+        let name = display_name(&report.path);
+        if let Some(e) = &report.error {
+            self.error(&format!("{}: {}", name, e));
+            return true;
+        }
+
+        let summary = report.summary;
+        self.match_count += summary.matches;
+
+        if config.json {
+            let mut stats = json::Stats {
+                searches: 1,
+                ..json::Stats::default()
+            };
+            // Like ripgrep, files without matches produce no records
+            if summary.matches > 0 {
+                if file.is_none() {
+                    println!("{}", json::begin(&name));
+                }
+                stats.searches_with_match = 1;
+                stats.matched_lines = summary.matches;
+                stats.matches = file.map_or(0, |file| file.spans);
+                println!("{}", json::end(&name, summary.binary, &stats));
+            }
+            self.stats.add(&stats);
+            return true;
+        }
+
+        if let Some(template) = self.template.filter(|_| self.editing) {
+            if summary.matches > 0
+                && let Err(e) = edit_file(&report.path, self.matcher, template, config)
+            {
+                self.error(&format!("{}: {}", name, e));
+            }
+            return true;
+        }
+
+        // -q has its answer; files still being searched are dropped
+        if config.quiet && self.match_count > 0 {
+            return false;
+        }
+
+        // -Z puts NUL after names
+        let name_end = |separator: char| if config.null { '\0' } else { separator };
+        if config.files_with_matches && summary.matches > 0 || config.files_without_match && summary.matches == 0 {
+            print!("{}{}", name, name_end('\n'));
+        } else if config.count && !self.list_files {
+            if self.show_file_names {
+                println!("{}{}{}", name, name_end(':'), summary.matches);
+            } else {
+                println!("{}", summary.matches);
+            }
+        } else if summary.binary && summary.matches > 0 && !self.per_file_only {
+            let label = if self.decorate { format!("{}. ", self.match_count) } else { String::new() };
+            println!("{}Binary file {} matches", label, name);
+        }
+        true
+    }
+}
+
+fn compile_globs(patterns: &[String]) -> Result<Vec<Glob>, GlobError> {
+    patterns.iter().map(|pattern| Glob::new(pattern)).collect()
 }
 
 /// Case-sensitive search using iterators (improved version)
@@ -128,13 +549,16 @@ fn search<'a>(query: &str, contents: &'a str) -> Vec<&'a str> {
         .collect()
 }
 
-/// Case-insensitive search using iterators (improved version)  
+/// Case-insensitive search using iterators (improved version)
+///
+/// Compares by Unicode case folding instead of lowercasing every line, so it
+/// allocates nothing per line and "STRASSE" finds "Straße".
 fn search_case_insensitive<'a>(query: &str, contents: &'a str) -> Vec<&'a str> {
-    let query = query.to_lowercase();
-    
+    let query = FoldedQuery::new(query, CaseFolder::default());
+
     contents
         .lines()
-        .filter(|line| line.to_lowercase().contains(&query))
+        .filter(|line| query.is_match(line))
         .collect()
 }
 
@@ -240,6 +664,7 @@ Programming in rust is enjoyable";
     for (i, result) in new_results.iter().enumerate() {
         println!("  {}. {}", i + 1, result);
     }
+    println!("(case-sensitive search finds {})", search(query, sample_content).len());
     
     // Old loop-based approach (same results)
     let old_results = old_implementations::search_case_insensitive_old(query, sample_content);
@@ -342,36 +767,28 @@ Some lines have the word 'sample' in them.
 Others talk about Rust programming.
 The SAMPLE word appears in different cases.";
 
-    // Multi-word search
+    // Multi-word search: one pass per line finds every query, instead of one pass per query
     fn search_multiple_words<'a>(queries: &[&str], contents: &'a str) -> Vec<&'a str> {
+        let automaton = AhoCorasick::case_insensitive(queries);
         contents
             .lines()
-            .filter(|line| {
-                queries.iter().all(|query| 
-                    line.to_lowercase().contains(&query.to_lowercase())
-                )
-            })
+            .filter(|line| automaton.matching_patterns(line).len() == queries.len())
             .collect()
     }
 
-    // Search with context (lines before/after)
-    fn search_with_context<'a>(query: &str, contents: &'a str, context: usize) -> Vec<(usize, &'a str)> {
-        let lines: Vec<&str> = contents.lines().collect();
+    // Search with context (lines before/after), using the same window as the CLI
+    fn search_with_context(query: &str, contents: &str, context: usize) -> Vec<(usize, String)> {
+        let mut window = ContextWindow::new(context, context);
         let mut results = Vec::new();
-        
-        for (i, line) in lines.iter().enumerate() {
-            if line.contains(query) {
-                let start = i.saturating_sub(context);
-                let end = std::cmp::min(i + context + 1, lines.len());
-                
-                for j in start..end {
-                    results.push((j + 1, lines[j]));
+
+        for (i, line) in contents.lines().enumerate() {
+            window.push(i + 1, line, line.contains(query), |item| {
+                if let ContextItem::Line { number, text, .. } = item {
+                    results.push((number, text.to_string()));
                 }
-            }
+            });
         }
-        
-        results.sort_by_key(|&(line_num, _)| line_num);
-        results.dedup();
+
         results
     }
 
@@ -403,6 +820,8 @@ The SAMPLE word appears in different cases.";
 #[cfg(test)]
 mod tests {
     use super::*;
+    use rust_book_examples::minigrep::color::ColorChoice;
+    use rust_book_examples::minigrep::{regex::Regex, search_regex};
 
     #[test]
     fn test_config_build_success() {
@@ -414,7 +833,7 @@ mod tests {
         
         let config = Config::build(args.into_iter()).unwrap();
         assert_eq!(config.query, "query");
-        assert_eq!(config.file_path, "file.txt");
+        assert_eq!(config.paths, vec!["file.txt"]);
     }
 
     #[test]
@@ -454,6 +873,137 @@ Duct tape.";
         );
     }
 
+    #[test]
+    fn test_search_case_insensitive_unicode() {
+        let contents = "Die Straße ist lang.
+Σίσυφος ΚΑΙ ΟΔΥΣΣΕΥΣ
+Odysseus";
+
+        assert_eq!(vec!["Die Straße ist lang."], search_case_insensitive("STRASSE", contents));
+        assert_eq!(vec!["Σίσυφος ΚΑΙ ΟΔΥΣΣΕΥΣ"], search_case_insensitive("οδυσσευς", contents));
+        // Lowercasing can't match ß with SS
+        assert!(old_implementations::search_case_insensitive_old("STRASSE", contents).is_empty());
+    }
+
+    #[test]
+    fn test_config_build_regex_flag() {
+        let args = vec![
+            "program".to_string(),
+            "-E".to_string(),
+            "^ru?st".to_string(),
+            "file.txt".to_string(),
+        ];
+
+        let config = Config::build(args.into_iter()).unwrap();
+        assert!(config.use_regex);
+        assert_eq!(config.query, "^ru?st");
+        assert_eq!(config.paths, vec!["file.txt"]);
+    }
+
+    #[test]
+    fn test_config_build_multiple_paths_and_globs() {
+        let args = vec![
+            "program".to_string(),
+            "--include".to_string(),
+            "*.md".to_string(),
+            "ownership".to_string(),
+            "--exclude=drafts".to_string(),
+            "notes".to_string(),
+            "README.md".to_string(),
+            "--hidden".to_string(),
+        ];
+
+        let config = Config::build(args.into_iter()).unwrap();
+        assert_eq!(config.query, "ownership");
+        assert_eq!(config.paths, vec!["notes", "README.md"]);
+        assert_eq!(config.include, vec!["*.md"]);
+        assert_eq!(config.exclude, vec!["drafts"]);
+        assert!(config.hidden);
+        assert!(!config.no_ignore);
+    }
+
+    #[test]
+    fn test_config_build_flag_missing_value() {
+        let args = vec!["program".to_string(), "query".to_string(), "--include".to_string()];
+        let error = Config::build(args.into_iter()).unwrap_err();
+        assert_eq!(error.to_string(), "missing GLOB for --include");
+    }
+
+    #[test]
+    fn test_config_build_context_and_color() {
+        let args = ["program", "-C", "2", "-A1", "--color=never", "query", "poem.txt"];
+        let config = Config::build(args.iter().map(|s| s.to_string())).unwrap();
+        assert_eq!(config.before_context, 2);
+        assert_eq!(config.after_context, 1);
+        assert_eq!(config.color, ColorChoice::Never);
+        assert_eq!(config.paths, vec!["poem.txt"]);
+
+        let args = ["program", "--before-context=3", "--color", "always", "query", "poem.txt"];
+        let config = Config::build(args.iter().map(|s| s.to_string())).unwrap();
+        assert_eq!((config.before_context, config.after_context), (3, 0));
+        assert_eq!(config.color, ColorChoice::Always);
+
+        let args = ["program", "-A", "many", "query", "poem.txt"];
+        assert!(Config::build(args.iter().map(|s| s.to_string())).is_err());
+        let args = ["program", "--color=sometimes", "query", "poem.txt"];
+        assert!(Config::build(args.iter().map(|s| s.to_string())).is_err());
+    }
+
+    #[test]
+    fn test_config_build_multiple_patterns() {
+        let args = ["program", "-e", "sample", "-e", "Rust", "notes", "README.md"];
+        let config = Config::build(args.iter().map(|s| s.to_string())).unwrap();
+        assert_eq!(config.load_patterns().unwrap(), vec!["sample", "Rust"]);
+        assert_eq!(config.paths, vec!["notes", "README.md"]);
+
+        let matcher = Matcher::with_patterns(&config.load_patterns().unwrap(), &config.match_options()).unwrap();
+        assert_eq!(matcher.matching_patterns("Rust sample code"), vec![0, 1]);
+        assert!(!matcher.is_match("SAMPLE"));
+    }
+
+    #[test]
+    fn test_config_build_machine_output() {
+        let args = ["program", "--json", "-C1", "query", "src"];
+        let config = Config::build(args.iter().map(|s| s.to_string())).unwrap();
+        assert!(config.json && !config.null);
+        assert_eq!(config.before_context, 1);
+
+        let args = ["program", "-lZ", "query", "src"];
+        let config = Config::build(args.iter().map(|s| s.to_string())).unwrap();
+        assert!(config.null && config.files_with_matches);
+
+        let args = ["program", "--json", "--count", "query", "src"];
+        assert!(Config::build(args.iter().map(|s| s.to_string())).is_err());
+    }
+
+    #[test]
+    fn test_config_build_replace() {
+        let args = ["program", "-E", "--replace", "$2, $1", "--dry-run", r"(\w+) (\w+)", "poem.txt"];
+        let config = Config::build(args.iter().map(|s| s.to_string())).unwrap();
+        assert!(config.dry_run && !config.in_place);
+
+        let template = Template::parse(config.replace.as_deref().unwrap()).unwrap();
+        let matcher = Matcher::new(&config.query, config.ignore_case, config.use_regex).unwrap();
+        assert_eq!(matcher.replace_all("Pick three.", &template), "three, Pick.");
+
+        let args = ["program", "--in-place", "query", "poem.txt"];
+        assert!(Config::build(args.iter().map(|s| s.to_string())).is_err());
+    }
+
+    #[test]
+    fn test_search_regex() {
+        let contents = "Rust:
+safe, fast, productive.
+Pick three.
+Duct tape.";
+
+        let regex = Regex::new(r"^\w+ t").unwrap();
+        assert_eq!(vec!["Pick three.", "Duct tape."], search_regex(&regex, contents));
+
+        let regex = Regex::case_insensitive("^(rust|duct)").unwrap();
+        assert_eq!(vec!["Rust:", "Duct tape."], search_regex(&regex, contents));
+    }
+
     #[test]
     fn test_old_vs_new_implementations() {
         let contents = "Hello World
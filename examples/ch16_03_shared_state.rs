@@ -1,3 +1,4 @@
+use rust_book_examples::cache::concurrent::ConcurrentCache;
 use rust_book_examples::print_chapter_header;
 use std::sync::{Arc, Mutex, RwLock};
 use std::thread;
@@ -297,47 +298,36 @@ fn demonstrate_practical_examples() {
 }
 
 fn thread_safe_cache_example() {
-    use std::collections::HashMap;
-    
-    type Cache = Arc<RwLock<HashMap<String, String>>>;
-    
-    let cache: Cache = Arc::new(RwLock::new(HashMap::new()));
+    // A RwLock<HashMap> lets every thread that misses a key compute it.
+    // The library's ConcurrentCache computes each key once, and threads that
+    // miss it meanwhile wait for that result (single flight). Keys are spread
+    // over separately locked shards, so different keys rarely contend.
+    let cache: Arc<ConcurrentCache<String, String, String>> = Arc::new(ConcurrentCache::new(100));
     
     // Function to get from cache or compute
-    fn get_or_compute(cache: &Cache, key: &str) -> String {
-        // Try to read from cache first
-        {
-            let reader = cache.read().unwrap();
-            if let Some(value) = reader.get(key) {
-                println!("  💾 Cache hit for key: {}", key);
-                return value.clone();
+    fn get_or_compute(cache: &ConcurrentCache<String, String, String>, key: &str) -> Result<String, String> {
+        cache.get_or_try_compute(key.to_string(), |key| {
+            println!("  🔄 Cache miss for key: {}, computing...", key);
+            thread::sleep(Duration::from_millis(100)); // Simulate expensive computation
+            if key.ends_with('3') {
+                // Every thread waiting on this key gets the same error
+                return Err(format!("no value for {}", key));
             }
-        }
-        
-        // Not in cache, compute the value
-        println!("  🔄 Cache miss for key: {}, computing...", key);
-        thread::sleep(Duration::from_millis(100)); // Simulate expensive computation
-        let computed_value = format!("computed_value_for_{}", key);
-        
-        // Store in cache
-        {
-            let mut writer = cache.write().unwrap();
-            writer.insert(key.to_string(), computed_value.clone());
-            println!("  📝 Stored in cache: {} -> {}", key, computed_value);
-        }
-        
-        computed_value
+            Ok(format!("computed_value_for_{}", key))
+        })
     }
     
     let mut handles = vec![];
     
     // Multiple threads accessing the cache
-    for i in 0..5 {
+    for i in 0..8 {
         let cache = Arc::clone(&cache);
         let handle = thread::spawn(move || {
-            let key = format!("key_{}", i % 3); // Some overlap in keys
-            let value = get_or_compute(&cache, &key);
-            println!("  🧵 Thread {}: {} = {}", i, key, value);
+            let key = format!("key_{}", i % 4); // Some overlap in keys
+            match get_or_compute(&cache, &key) {
+                Ok(value) => println!("  🧵 Thread {}: {} = {}", i, key, value),
+                Err(e) => println!("  ❌ Thread {}: {}", i, e),
+            }
         });
         handles.push(handle);
     }
@@ -346,8 +336,11 @@ fn thread_safe_cache_example() {
         handle.join().unwrap();
     }
     
-    let final_cache = cache.read().unwrap();
-    println!("  📊 Final cache size: {}", final_cache.len());
+    // A second round finds everything but the failed key cached
+    println!("  💾 key_0 again: {:?}", get_or_compute(&cache, "key_0"));
+    
+    println!("  📊 Final cache size: {}", cache.len());
+    println!("  📊 {}", cache.stats());
 }
 
 fn producer_consumer_example() {
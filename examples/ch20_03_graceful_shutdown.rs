@@ -5,8 +5,14 @@
 //! - Message-based worker termination
 //! - Proper thread joining and resource deallocation
 //! - Graceful handling of server shutdown
+//! - Serving pages from an in-memory cache that hot-reloads edited files
+//!
+//! Run with `-- --dev` (or set `DEV_MODE=1` or `DEV_MODE=true`) to inject a live-reload script
+//! into HTML pages so the browser refreshes when a file in `web_assets/` changes.
 
+use rust_book_examples::asset_cache::{self, AssetCache, Watcher, LIVE_RELOAD_ROUTE};
 use rust_book_examples::print_chapter_header;
+use std::env;
 use std::fs;
 use std::io::prelude::*;
 use std::net::{TcpListener, TcpStream};
@@ -25,6 +31,23 @@ fn main() {
     // Create static HTML files
     create_html_files();
     
+    // Load the assets into memory and watch them for changes
+    let dev_mode = env::args().any(|arg| arg == "--dev")
+        || env::var("DEV_MODE").is_ok_and(|value| value == "1" || value.eq_ignore_ascii_case("true"));
+    let cache = Arc::new(AssetCache::new("web_assets", dev_mode));
+    match cache.preload() {
+        Ok(count) => println!("🗂️  Cached {} asset(s) from {}", count, cache.root().display()),
+        Err(e) => eprintln!("⚠️  Failed to preload assets: {}", e),
+    }
+    let _watcher = Watcher::spawn(Arc::clone(&cache), Duration::from_millis(500), |changed| {
+        for path in changed {
+            println!("🔁 Reloaded {}", path.display());
+        }
+    });
+    if dev_mode {
+        println!("🔥 Dev mode: pages reload automatically when assets change");
+    }
+    
     // Bind to localhost on port 7880 (different from other versions)
     let listener = TcpListener::bind("127.0.0.1:7880").unwrap();
     println!("🚀 Server with graceful shutdown listening on http://127.0.0.1:7880");
@@ -33,20 +56,24 @@ fn main() {
     let pool = ThreadPool::new(4);
     println!("📋 Thread pool created with 4 workers\n");
     
-    // Limit to 5 requests for demonstration, then shut down gracefully
+    // Limit to 5 requests for demonstration, then shut down gracefully.
+    // Dev mode keeps serving, since the live-reload script polls every second.
     for (i, stream) in listener.incoming().enumerate() {
-        if i >= 5 {
+        if !dev_mode && i >= 5 {
             println!("\n🛑 Reached request limit (5). Initiating graceful shutdown...");
             break;
         }
         
         let stream = stream.unwrap();
         
-        println!("📝 Queuing request {} of 5", i + 1);
+        if !dev_mode {
+            println!("📝 Queuing request {} of 5", i + 1);
+        }
         
         // Submit work to the thread pool
-        pool.execute(|| {
-            handle_connection(stream);
+        let cache = Arc::clone(&cache);
+        pool.execute(move || {
+            handle_connection(stream, &cache);
         });
     }
     
@@ -217,7 +244,7 @@ impl Worker {
 }
 
 /// Handles an individual HTTP connection
-fn handle_connection(mut stream: TcpStream) {
+fn handle_connection(mut stream: TcpStream, cache: &AssetCache) {
     let thread_id = thread::current().id();
     println!("\n--- New Connection (Thread: {:?}) ---", thread_id);
     
@@ -244,23 +271,31 @@ fn handle_connection(mut stream: TcpStream) {
     
     println!("📨 Request: {} (Thread: {:?})", request_line, thread_id);
     
-    // Route the request
-    let (status_line, filename) = route_request(request_line);
-    
-    // Read file content
-    let contents = match fs::read_to_string(filename) {
-        Ok(content) => content,
-        Err(_) => {
-            println!("⚠️  File '{}' not found, using fallback", filename);
-            create_fallback_content(filename)
-        }
-    };
+    // The live-reload script polls this route for the current asset generation
+    let (status_line, content_type, contents) =
+        if request_line == format!("GET {} HTTP/1.1", LIVE_RELOAD_ROUTE) {
+            ("HTTP/1.1 200 OK", "text/plain; charset=utf-8", cache.generation().to_string())
+        } else {
+            // Route the request
+            let (status_line, filename) = route_request(request_line);
+            
+            // Read file content from the cache
+            let contents = match cache.get_for_response(filename) {
+                Ok(content) => content,
+                Err(_) => {
+                    println!("⚠️  File '{}' not found, using fallback", filename);
+                    create_fallback_content(filename)
+                }
+            };
+            (status_line, asset_cache::content_type(filename), contents)
+        };
     
     // Build and send response
     let response = format!(
-        "{}\r\nContent-Length: {}\r\nContent-Type: text/html\r\nX-Served-By: Worker-{:?}\r\n\r\n{}",
+        "{}\r\nContent-Length: {}\r\nContent-Type: {}\r\nX-Served-By: Worker-{:?}\r\n\r\n{}",
         status_line,
         contents.len(),
+        content_type,
         thread_id,
         contents
     );
@@ -0,0 +1,616 @@
+//! # Argument Parsing
+//!
+//! A small declarative command line parser shared by the Chapter 12 and 13
+//! examples, so none of them has to walk `env::args()` by index.
+//!
+//! A [`Command`] is a `const` table of [`Flag`]s, [`Positional`] arguments
+//! and subcommands. Parsing it produces [`Matches`] to read values from,
+//! and the same table generates the `--help` text, so the two can't drift
+//! apart.
+//!
+//! - Short flags can be bundled (`-inw`) and take values attached or as the
+//!   next argument (`-A3`, `-A 3`)
+//! - Long flags take `--name=value` or `--name value`
+//! - `--` ends flag parsing; `-` on its own is a positional (often stdin)
+//! - A flag can name environment variables that supply its default
+//! - `-h`/`--help` is always understood and skips required arguments
+//! - A subcommand must come first, and accepts its parent's flags too
+//!
+//! This module demonstrates:
+//! - `const fn` builders for tables known at compile time
+//! - Typed errors that carry the flag they're about
+//! - Generic parsing with `FromStr`
+
+use std::env;
+use std::fmt;
+use std::str::FromStr;
+
+/// One command line flag: a switch, or an option that takes a value
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Flag {
+    pub short: Option<char>,
+    pub long: &'static str,
+    /// Name of the value the flag takes, shown in help; `None` for switches
+    pub value: Option<&'static str>,
+    pub help: &'static str,
+    /// Environment variables giving the default, highest priority first
+    pub env: &'static [&'static str],
+}
+
+impl Flag {
+    /// A flag that takes no value
+    pub const fn switch(short: Option<char>, long: &'static str, help: &'static str) -> Flag {
+        Flag {
+            short,
+            long,
+            value: None,
+            help,
+            env: &[],
+        }
+    }
+
+    /// A flag that takes a value named `value` in help
+    pub const fn option(short: Option<char>, long: &'static str, value: &'static str, help: &'static str) -> Flag {
+        Flag {
+            short,
+            long,
+            value: Some(value),
+            help,
+            env: &[],
+        }
+    }
+
+    /// Take the default from the first of `vars` that is set
+    ///
+    /// A switch counts as on unless the variable is `0`, `false`, `no` or `off`.
+    pub const fn env(self, vars: &'static [&'static str]) -> Flag {
+        Flag { env: vars, ..self }
+    }
+}
+
+/// The `-h`/`--help` flag every command understands
+pub const HELP: Flag = Flag::switch(Some('h'), "help", "Print this help and exit");
+
+/// A positional argument
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Positional {
+    pub name: &'static str,
+    pub help: &'static str,
+    pub required: bool,
+    /// Takes every remaining argument
+    pub multiple: bool,
+}
+
+impl Positional {
+    pub const fn required(name: &'static str, help: &'static str) -> Positional {
+        Positional {
+            name,
+            help,
+            required: true,
+            multiple: false,
+        }
+    }
+
+    pub const fn optional(name: &'static str, help: &'static str) -> Positional {
+        Positional {
+            name,
+            help,
+            required: false,
+            multiple: false,
+        }
+    }
+
+    /// Take every remaining argument, rather than one
+    pub const fn multiple(self) -> Positional {
+        Positional { multiple: true, ..self }
+    }
+
+    fn usage(&self) -> String {
+        let dots = if self.multiple { "..." } else { "" };
+        if self.required {
+            format!("<{}>{}", self.name, dots)
+        } else {
+            format!("[{}]{}", self.name, dots)
+        }
+    }
+}
+
+/// A program or subcommand: what it accepts and how to describe it
+///
+/// # Example
+/// ```
+/// use rust_book_examples::args::{Command, Flag, Positional};
+///
+/// const GREP: Command = Command::new("minigrep", "Search FILE for lines containing QUERY.")
+///     .flags(&[
+///         Flag::switch(Some('i'), "ignore-case", "Match letters regardless of case").env(&["IGNORE_CASE"]),
+///         Flag::option(Some('m'), "max-count", "NUM", "Stop after NUM matching lines"),
+///     ])
+///     .positionals(&[Positional::required("QUERY", "Text to find"), Positional::required("FILE", "File to search")]);
+///
+/// let args = ["minigrep", "-m3", "nobody", "poem.txt"].map(String::from);
+/// let matches = GREP.parse_with_env(args.into_iter(), |_| Some("1".to_string())).unwrap();
+/// assert!(matches.flag("ignore-case"));
+/// assert_eq!(matches.parse::<usize>("max-count"), Ok(Some(3)));
+/// assert_eq!(matches.positional("FILE"), Some("poem.txt"));
+/// assert!(GREP.help("minigrep").contains("Usage: minigrep [OPTIONS] <QUERY> <FILE>"));
+/// ```
+#[derive(Debug, Clone, Copy)]
+pub struct Command {
+    pub name: &'static str,
+    pub about: &'static str,
+    pub flags: &'static [Flag],
+    pub positionals: &'static [Positional],
+    pub subcommands: &'static [Command],
+    /// Usage lines after the program name; generated when empty
+    pub usage: &'static [&'static str],
+    /// Text appended to the help, such as notes on exit status
+    pub after_help: &'static str,
+}
+
+impl Command {
+    pub const fn new(name: &'static str, about: &'static str) -> Command {
+        Command {
+            name,
+            about,
+            flags: &[],
+            positionals: &[],
+            subcommands: &[],
+            usage: &[],
+            after_help: "",
+        }
+    }
+
+    pub const fn flags(self, flags: &'static [Flag]) -> Command {
+        Command { flags, ..self }
+    }
+
+    pub const fn positionals(self, positionals: &'static [Positional]) -> Command {
+        Command { positionals, ..self }
+    }
+
+    pub const fn subcommands(self, subcommands: &'static [Command]) -> Command {
+        Command { subcommands, ..self }
+    }
+
+    pub const fn usage(self, usage: &'static [&'static str]) -> Command {
+        Command { usage, ..self }
+    }
+
+    pub const fn after_help(self, after_help: &'static str) -> Command {
+        Command { after_help, ..self }
+    }
+
+    /// Parse `args` (including the program name), reading defaults from the environment
+    pub fn parse(&self, args: impl Iterator<Item = String>) -> Result<Matches, ArgError> {
+        self.parse_with_env(args, |name| env::var(name).ok())
+    }
+
+    /// Like [`Command::parse`], but looks environment variables up with `env`
+    pub fn parse_with_env<F>(&self, mut args: impl Iterator<Item = String>, env: F) -> Result<Matches, ArgError>
+    where
+        F: Fn(&str) -> Option<String>,
+    {
+        args.next(); // Skip the program name
+        self.parse_args(args.collect(), &env, &[])
+    }
+
+    /// The flag called `--long`, or `None` if this command has no such flag
+    pub fn find_long(&self, long: &str) -> Option<Flag> {
+        self.all_flags(&[]).find(|flag| flag.long == long)
+    }
+
+    fn all_flags<'a>(&'a self, inherited: &'a [Flag]) -> impl Iterator<Item = Flag> + 'a {
+        self.flags.iter().chain(inherited).chain([&HELP]).copied()
+    }
+
+    fn parse_args<F>(&self, args: Vec<String>, env: &F, inherited: &[Flag]) -> Result<Matches, ArgError>
+    where
+        F: Fn(&str) -> Option<String>,
+    {
+        let mut args = args.into_iter().peekable();
+        if let Some(sub) = args.peek().and_then(|first| self.subcommands.iter().find(|sub| sub.name == first)) {
+            args.next();
+            let inherited: Vec<Flag> = self.flags.iter().chain(inherited).copied().collect();
+            let matches = sub.parse_args(args.collect(), env, &inherited)?;
+            return Ok(Matches {
+                subcommand: Some((sub.name, Box::new(matches))),
+                ..Matches::default()
+            });
+        }
+
+        let find_long = |name: &str| self.all_flags(inherited).find(|flag| flag.long == name);
+        let find_short = |c: char| self.all_flags(inherited).find(|flag| flag.short == Some(c));
+
+        let mut matches = Matches::default();
+        let mut positional = Vec::new();
+        while let Some(arg) = args.next() {
+            if arg == "--" {
+                positional.extend(args.by_ref());
+                break;
+            }
+
+            if let Some(long) = arg.strip_prefix("--") {
+                let (name, inline_value) = match long.split_once('=') {
+                    Some((name, value)) => (name, Some(value.to_string())),
+                    None => (long, None),
+                };
+                let flag = find_long(name).ok_or_else(|| ArgError::UnknownFlag(arg.clone()))?;
+                let value = match (flag.value, inline_value) {
+                    (None, None) => None,
+                    (None, Some(_)) => return Err(ArgError::UnexpectedValue(flag)),
+                    (Some(_), Some(value)) => Some(value),
+                    (Some(_), None) => Some(args.next().ok_or(ArgError::MissingValue(flag))?),
+                };
+                matches.given.push((flag, value));
+            } else if arg.len() > 1 && arg.starts_with('-') {
+                // A bundle of short flags; one that takes a value ends the bundle
+                for (index, c) in arg.char_indices().skip(1) {
+                    let flag = find_short(c).ok_or_else(|| ArgError::UnknownFlag(format!("-{}", c)))?;
+                    if flag.value.is_none() {
+                        matches.given.push((flag, None));
+                        continue;
+                    }
+
+                    let attached = &arg[index + c.len_utf8()..];
+                    let value = if attached.is_empty() {
+                        args.next().ok_or(ArgError::MissingValue(flag))?
+                    } else {
+                        attached.to_string()
+                    };
+                    matches.given.push((flag, Some(value)));
+                    break;
+                }
+            } else {
+                positional.push(arg);
+            }
+        }
+
+        // Defaults from the environment, for flags the command line left out
+        for flag in self.all_flags(inherited) {
+            if matches.is_present(flag.long) {
+                continue;
+            }
+            if let Some(value) = flag.env.iter().find_map(|var| env(var)) {
+                matches.env.push((flag, value));
+            }
+        }
+
+        let mut positional = positional.into_iter();
+        let help = matches.is_present(HELP.long);
+        for spec in self.positionals {
+            let values: Vec<String> = if spec.multiple {
+                positional.by_ref().collect()
+            } else {
+                positional.next().into_iter().collect()
+            };
+            if values.is_empty() && spec.required && !help {
+                return Err(ArgError::MissingArgument(*spec));
+            }
+            matches.positionals.push((spec.name, values));
+        }
+        if let Some(extra) = positional.next() {
+            return Err(ArgError::UnexpectedArgument(extra));
+        }
+
+        Ok(matches)
+    }
+
+    /// Usage and flag descriptions generated from the tables
+    pub fn help(&self, program: &str) -> String {
+        let mut text = String::new();
+        let usage = self.usage_lines(program);
+        for (i, line) in usage.iter().enumerate() {
+            let lead = if i == 0 { "Usage:" } else { "      " };
+            text.push_str(&format!("{} {}\n", lead, line));
+        }
+        if !self.about.is_empty() {
+            text.push_str(&format!("\n{}\n", self.about));
+        }
+
+        if !self.positionals.is_empty() {
+            text.push_str("\nArguments:\n");
+            let width = self.positionals.iter().map(|p| p.usage().len()).max().unwrap_or(0);
+            for positional in self.positionals {
+                text.push_str(&format!("  {:width$}  {}\n", positional.usage(), positional.help));
+            }
+        }
+
+        let flags: Vec<Flag> = self.all_flags(&[]).collect();
+        let labels: Vec<String> = flags
+            .iter()
+            .map(|flag| {
+                let short = flag.short.map(|c| format!("-{}, ", c)).unwrap_or_else(|| "    ".to_string());
+                let value = flag.value.map(|v| format!(" {}", v)).unwrap_or_default();
+                format!("{}--{}{}", short, flag.long, value)
+            })
+            .collect();
+        let width = labels.iter().map(String::len).max().unwrap_or(0);
+        text.push_str("\nOptions:\n");
+        for (label, flag) in labels.iter().zip(&flags) {
+            text.push_str(&format!("  {:width$}  {}\n", label, flag.help));
+        }
+
+        if !self.subcommands.is_empty() {
+            text.push_str("\nCommands:\n");
+            let width = self.subcommands.iter().map(|sub| sub.name.len()).max().unwrap_or(0);
+            for sub in self.subcommands {
+                text.push_str(&format!("  {:width$}  {}\n", sub.name, sub.about));
+            }
+        }
+
+        let with_env: Vec<&Flag> = flags.iter().filter(|flag| !flag.env.is_empty()).collect();
+        if !with_env.is_empty() {
+            text.push_str("\nEnvironment:\n");
+            let vars: Vec<String> = with_env.iter().map(|flag| flag.env.join(", ")).collect();
+            let width = vars.iter().map(String::len).max().unwrap_or(0);
+            for (vars, flag) in vars.iter().zip(with_env) {
+                text.push_str(&format!("  {:width$}  Default for --{}\n", vars, flag.long));
+            }
+        }
+
+        if !self.after_help.is_empty() {
+            text.push_str(&format!("\n{}\n", self.after_help.trim_end()));
+        }
+        text
+    }
+
+    fn usage_lines(&self, program: &str) -> Vec<String> {
+        let mut lines: Vec<String> = if self.usage.is_empty() {
+            let mut line = format!("{} [OPTIONS]", program);
+            for positional in self.positionals {
+                line.push(' ');
+                line.push_str(&positional.usage());
+            }
+            vec![line]
+        } else {
+            self.usage.iter().map(|usage| format!("{} {}", program, usage)).collect()
+        };
+        for sub in self.subcommands {
+            lines.extend(sub.usage_lines(&format!("{} {}", program, sub.name)));
+        }
+        lines
+    }
+}
+
+/// What the command line said, read by flag and positional name
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct Matches {
+    /// Flags in the order they were given
+    given: Vec<(Flag, Option<String>)>,
+    /// Environment defaults for flags that weren't given
+    env: Vec<(Flag, String)>,
+    positionals: Vec<(&'static str, Vec<String>)>,
+    subcommand: Option<(&'static str, Box<Matches>)>,
+}
+
+impl Matches {
+    /// Every flag given on the command line, in order, with its value
+    ///
+    /// For flags where the last one wins, like `-i` against `--no-ignore-case`.
+    pub fn given(&self) -> impl Iterator<Item = (Flag, Option<&str>)> {
+        self.given.iter().map(|(flag, value)| (*flag, value.as_deref()))
+    }
+
+    /// Whether `--long` was given on the command line
+    pub fn is_present(&self, long: &str) -> bool {
+        self.given.iter().any(|(flag, _)| flag.long == long)
+    }
+
+    /// Whether the switch `--long` is on, from the command line or the environment
+    pub fn flag(&self, long: &str) -> bool {
+        self.is_present(long) || self.env_value(long).is_some_and(env_is_on)
+    }
+
+    /// The last value given for `--long`, or its environment default
+    pub fn value(&self, long: &str) -> Option<&str> {
+        self.given
+            .iter()
+            .rev()
+            .find(|(flag, _)| flag.long == long)
+            .and_then(|(_, value)| value.as_deref())
+            .or_else(|| self.env_value(long))
+    }
+
+    /// Every value given for a repeatable `--long`
+    pub fn values(&self, long: &str) -> Vec<&str> {
+        self.given
+            .iter()
+            .filter(|(flag, _)| flag.long == long)
+            .filter_map(|(_, value)| value.as_deref())
+            .collect()
+    }
+
+    /// The value of `--long` parsed as a `T`
+    ///
+    /// # Errors
+    ///
+    /// [`ArgError::InvalidValue`] if the value doesn't parse.
+    pub fn parse<T: FromStr>(&self, long: &str) -> Result<Option<T>, ArgError> {
+        let Some(value) = self.value(long) else {
+            return Ok(None);
+        };
+        let flag = self
+            .given
+            .iter()
+            .map(|(flag, _)| *flag)
+            .chain(self.env.iter().map(|(flag, _)| *flag))
+            .find(|flag| flag.long == long)
+            .expect("a flag with a value was given or set in the environment");
+        value.parse().map(Some).map_err(|_| ArgError::InvalidValue {
+            flag,
+            value: value.to_string(),
+        })
+    }
+
+    /// The first value of the positional `name`
+    pub fn positional(&self, name: &str) -> Option<&str> {
+        self.positionals(name).first().map(String::as_str)
+    }
+
+    /// Every value of the positional `name`
+    pub fn positionals(&self, name: &str) -> &[String] {
+        self.positionals
+            .iter()
+            .find(|(positional, _)| *positional == name)
+            .map_or(&[], |(_, values)| values.as_slice())
+    }
+
+    /// The subcommand that was given, with its own matches
+    pub fn subcommand(&self) -> Option<(&str, &Matches)> {
+        self.subcommand.as_ref().map(|(name, matches)| (*name, matches.as_ref()))
+    }
+
+    fn env_value(&self, long: &str) -> Option<&str> {
+        self.env.iter().find(|(flag, _)| flag.long == long).map(|(_, value)| value.as_str())
+    }
+}
+
+/// Whether an environment variable turns a switch on: anything but `0`,
+/// `false`, `no` or `off`, in any case
+pub fn env_is_on(value: &str) -> bool {
+    !["0", "false", "no", "off"].iter().any(|off| value.eq_ignore_ascii_case(off))
+}
+
+/// Errors from parsing a command line
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ArgError {
+    /// A flag the command doesn't have
+    UnknownFlag(String),
+    /// A flag that needs a value was last on the command line
+    MissingValue(Flag),
+    /// A switch was given a value with `--name=value`
+    UnexpectedValue(Flag),
+    /// A value that couldn't be parsed
+    InvalidValue { flag: Flag, value: String },
+    /// Two flags, by long name, that can't be used together
+    Conflict(&'static str, &'static str),
+    /// The first flag, by long name, only makes sense with the second
+    Requires(&'static str, &'static str),
+    /// A required positional argument is missing
+    MissingArgument(Positional),
+    /// More positional arguments than the command takes
+    UnexpectedArgument(String),
+}
+
+impl fmt::Display for ArgError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ArgError::UnknownFlag(flag) => write!(f, "unknown flag '{}'", flag),
+            ArgError::MissingValue(flag) => {
+                write!(f, "missing {} for --{}", flag.value.unwrap_or("value"), flag.long)
+            }
+            ArgError::UnexpectedValue(flag) => write!(f, "--{} doesn't take a value", flag.long),
+            ArgError::InvalidValue { flag, value } => write!(
+                f,
+                "invalid {} '{}' for --{}",
+                flag.value.unwrap_or("value"),
+                value,
+                flag.long
+            ),
+            ArgError::Conflict(first, second) => {
+                write!(f, "--{} can't be used with --{}", first, second)
+            }
+            ArgError::Requires(flag, needed) => write!(f, "--{} requires --{}", flag, needed),
+            ArgError::MissingArgument(positional) => write!(f, "missing {}", positional.usage()),
+            ArgError::UnexpectedArgument(arg) => write!(f, "unexpected argument '{}'", arg),
+        }
+    }
+}
+
+impl std::error::Error for ArgError {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const NOTES: Command = Command::new("notes", "Keep notes.")
+        .flags(&[
+            Flag::switch(Some('v'), "verbose", "Say more").env(&["NOTES_VERBOSE"]),
+            Flag::option(Some('t'), "tag", "TAG", "Tag the note (repeatable)"),
+            Flag::option(None, "width", "COLUMNS", "Wrap at COLUMNS").env(&["NOTES_WIDTH", "COLUMNS"]),
+        ])
+        .positionals(&[Positional::required("TITLE", "The note's title"), Positional::optional("BODY", "Its text").multiple()])
+        .subcommands(&[Command::new("list", "List notes").positionals(&[Positional::optional("TAG", "Only this tag")])]);
+
+    fn parse(args: &[&str], vars: &[(&str, &str)]) -> Result<Matches, ArgError> {
+        let args = std::iter::once("notes").chain(args.iter().copied()).map(String::from);
+        NOTES.parse_with_env(args, |name| {
+            vars.iter().find(|(var, _)| *var == name).map(|(_, value)| value.to_string())
+        })
+    }
+
+    #[test]
+    fn flags_values_and_positionals() {
+        let matches = parse(&["-vt", "rust", "Ownership", "--tag=ch04", "moves", "and", "-", "borrows"], &[]).unwrap();
+        assert!(matches.flag("verbose"));
+        assert_eq!(matches.values("tag"), vec!["rust", "ch04"]);
+        assert_eq!(matches.value("tag"), Some("ch04"));
+        assert_eq!(matches.positional("TITLE"), Some("Ownership"));
+        assert_eq!(matches.positionals("BODY"), ["moves", "and", "-", "borrows"]);
+
+        let order: Vec<&str> = matches.given().map(|(flag, _)| flag.long).collect();
+        assert_eq!(order, vec!["verbose", "tag", "tag"]);
+
+        let matches = parse(&["--", "-v"], &[]).unwrap();
+        assert!(!matches.flag("verbose"));
+        assert_eq!(matches.positional("TITLE"), Some("-v"));
+    }
+
+    #[test]
+    fn environment_defaults() {
+        let matches = parse(&["t"], &[("NOTES_VERBOSE", "off"), ("COLUMNS", "80")]).unwrap();
+        assert!(!matches.flag("verbose"));
+        assert_eq!(matches.parse::<usize>("width"), Ok(Some(80)));
+
+        // The command line beats the environment, and earlier variables beat later ones
+        let vars = [("NOTES_VERBOSE", "yes"), ("NOTES_WIDTH", "100"), ("COLUMNS", "80")];
+        let matches = parse(&["--width", "60", "t"], &vars).unwrap();
+        assert!(matches.flag("verbose") && !matches.is_present("verbose"));
+        assert_eq!(matches.value("width"), Some("60"));
+        assert_eq!(parse(&["t"], &vars).unwrap().value("width"), Some("100"));
+    }
+
+    #[test]
+    fn subcommands() {
+        let matches = parse(&["list", "-v", "rust"], &[]).unwrap();
+        let (name, list) = matches.subcommand().unwrap();
+        assert_eq!(name, "list");
+        // Subcommands accept their parent's flags
+        assert!(list.flag("verbose"));
+        assert_eq!(list.positional("TAG"), Some("rust"));
+
+        // Only as the first argument
+        let matches = parse(&["-v", "list"], &[]).unwrap();
+        assert!(matches.subcommand().is_none());
+        assert_eq!(matches.positional("TITLE"), Some("list"));
+    }
+
+    #[test]
+    fn errors() {
+        let width = NOTES.find_long("width").unwrap();
+        assert_eq!(parse(&["-q", "t"], &[]), Err(ArgError::UnknownFlag("-q".to_string())));
+        assert_eq!(parse(&["t", "--width"], &[]), Err(ArgError::MissingValue(width)));
+        assert!(matches!(parse(&["--verbose=yes", "t"], &[]), Err(ArgError::UnexpectedValue(_))));
+        assert_eq!(parse(&[], &[]).unwrap_err().to_string(), "missing <TITLE>");
+        assert_eq!(parse(&["list", "a", "b"], &[]), Err(ArgError::UnexpectedArgument("b".to_string())));
+
+        let matches = parse(&["--width=wide", "t"], &[]).unwrap();
+        let error = matches.parse::<usize>("width").unwrap_err();
+        assert_eq!(error.to_string(), "invalid COLUMNS 'wide' for --width");
+
+        // --help skips required arguments
+        assert!(parse(&["-h"], &[]).unwrap().flag("help"));
+    }
+
+    #[test]
+    fn help_text() {
+        let text = NOTES.help("notes");
+        assert!(text.starts_with("Usage: notes [OPTIONS] <TITLE> [BODY]...\n       notes list [OPTIONS] [TAG]\n"));
+        assert!(text.contains("  -t, --tag TAG        Tag the note (repeatable)\n"));
+        assert!(text.contains("  -h, --help           Print this help and exit\n"));
+        assert!(text.contains("Commands:\n  list  List notes\n"));
+        assert!(text.contains("Arguments:\n  <TITLE>    The note's title\n  [BODY]...  Its text\n"));
+        assert!(text.contains("NOTES_WIDTH, COLUMNS  Default for --width"));
+    }
+}
@@ -0,0 +1,497 @@
+//! # In-Memory Asset Cache with Hot Reload
+//!
+//! Used by the Chapter 20 web server to serve files from `web_assets/`
+//! without hitting the disk on every request.
+//!
+//! This module demonstrates:
+//! - Sharing a cache between worker threads with `Arc<RwLock<...>>`
+//! - Polling file modification times and sizes to detect changes
+//! - A background watcher thread that stops cleanly through `Drop`
+//! - Injecting a live-reload script into HTML responses in dev mode
+
+use std::collections::HashMap;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::{Arc, RwLock};
+use std::thread;
+use std::time::{Duration, SystemTime};
+
+/// Route polled by the live-reload script to learn the current generation
+pub const LIVE_RELOAD_ROUTE: &str = "/__livereload";
+
+/// A single cached file together with the mtime and length it was read at
+#[derive(Debug, Clone)]
+struct CachedAsset {
+    contents: Arc<String>,
+    modified: Option<SystemTime>,
+    len: u64,
+}
+
+/// Thread-safe cache of text assets keyed by path
+///
+/// Files are read from disk on first access and kept in memory afterwards.
+/// Call [`AssetCache::poll_changes`] (or start a [`Watcher`]) to pick up
+/// edits made while the server is running.
+///
+/// # Example
+/// ```
+/// use rust_book_examples::asset_cache::AssetCache;
+///
+/// let cache = AssetCache::new("web_assets", false);
+/// assert_eq!(cache.generation(), 0);
+/// assert!(cache.get("web_assets/does_not_exist.html").is_err());
+/// ```
+#[derive(Debug)]
+pub struct AssetCache {
+    root: PathBuf,
+    dev_mode: bool,
+    entries: RwLock<HashMap<PathBuf, CachedAsset>>,
+    generation: AtomicU64,
+}
+
+impl AssetCache {
+    /// Create an empty cache for files under `root`
+    ///
+    /// # Arguments
+    /// * `root` - Directory scanned by [`AssetCache::preload`]
+    /// * `dev_mode` - Whether HTML responses get the live-reload script
+    pub fn new(root: impl Into<PathBuf>, dev_mode: bool) -> AssetCache {
+        AssetCache {
+            root: root.into(),
+            dev_mode,
+            entries: RwLock::new(HashMap::new()),
+            generation: AtomicU64::new(0),
+        }
+    }
+
+    /// Directory this cache was created for
+    pub fn root(&self) -> &Path {
+        &self.root
+    }
+
+    /// Whether live reload is enabled
+    pub fn dev_mode(&self) -> bool {
+        self.dev_mode
+    }
+
+    /// Number of times a change has been detected since startup
+    pub fn generation(&self) -> u64 {
+        self.generation.load(Ordering::SeqCst)
+    }
+
+    /// Number of files currently held in memory
+    pub fn len(&self) -> usize {
+        self.entries.read().unwrap().len()
+    }
+
+    /// Returns true if nothing has been cached yet
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Read every file below the root directory into the cache
+    ///
+    /// Returns the number of files loaded.
+    pub fn preload(&self) -> io::Result<usize> {
+        let mut files = Vec::new();
+        collect_files(&self.root, &mut files)?;
+
+        let mut loaded = 0;
+        for path in files {
+            if self.load(&path).is_ok() {
+                loaded += 1;
+            }
+        }
+        Ok(loaded)
+    }
+
+    /// Get the contents of `path`, reading it from disk on a cache miss
+    pub fn get(&self, path: impl AsRef<Path>) -> io::Result<Arc<String>> {
+        let path = path.as_ref();
+
+        if let Some(asset) = self.entries.read().unwrap().get(path) {
+            return Ok(Arc::clone(&asset.contents));
+        }
+
+        self.load(path)
+    }
+
+    /// Get the contents of `path` ready to be sent to a browser
+    ///
+    /// In dev mode, HTML files get the live-reload script injected.
+    pub fn get_for_response(&self, path: impl AsRef<Path>) -> io::Result<String> {
+        let path = path.as_ref();
+        let contents = self.get(path)?;
+
+        let is_html = path
+            .extension()
+            .is_some_and(|ext| ext.eq_ignore_ascii_case("html"));
+
+        if self.dev_mode && is_html {
+            Ok(inject_live_reload(&contents, self.generation()))
+        } else {
+            Ok(contents.to_string())
+        }
+    }
+
+    /// Reload cached files whose mtime or length changed, and load new
+    /// files that appeared below the root directory
+    ///
+    /// An edit within the filesystem's mtime granularity is still caught
+    /// when it changes the file's length. Deleted files are dropped from
+    /// the cache. Returns the paths that changed, sorted; the generation
+    /// counter is bumped if the list is non-empty.
+    pub fn poll_changes(&self) -> Vec<PathBuf> {
+        let snapshot: Vec<(PathBuf, Option<SystemTime>, u64)> = self
+            .entries
+            .read()
+            .unwrap()
+            .iter()
+            .map(|(path, asset)| (path.clone(), asset.modified, asset.len))
+            .collect();
+
+        let mut changed = Vec::new();
+
+        for (path, cached_mtime, cached_len) in &snapshot {
+            match fs::metadata(path) {
+                Ok(metadata) => {
+                    let edited = metadata.modified().ok() != *cached_mtime || metadata.len() != *cached_len;
+                    if edited && self.load(path).is_ok() {
+                        changed.push(path.clone());
+                    }
+                }
+                Err(_) => {
+                    self.entries.write().unwrap().remove(path);
+                    changed.push(path.clone());
+                }
+            }
+        }
+
+        // A missing root just means there's nothing new to pick up
+        let mut files = Vec::new();
+        if collect_files(&self.root, &mut files).is_ok() {
+            for path in files {
+                let known = snapshot.iter().any(|(cached, _, _)| *cached == path);
+                if !known && self.load(&path).is_ok() {
+                    changed.push(path);
+                }
+            }
+        }
+
+        if !changed.is_empty() {
+            self.generation.fetch_add(1, Ordering::SeqCst);
+        }
+
+        changed.sort();
+        changed
+    }
+
+    /// Read `path` from disk and store it in the cache
+    fn load(&self, path: &Path) -> io::Result<Arc<String>> {
+        let metadata = fs::metadata(path)?;
+        let contents = Arc::new(fs::read_to_string(path)?);
+
+        self.entries.write().unwrap().insert(
+            path.to_path_buf(),
+            CachedAsset {
+                contents: Arc::clone(&contents),
+                modified: metadata.modified().ok(),
+                len: metadata.len(),
+            },
+        );
+
+        Ok(contents)
+    }
+}
+
+/// Background thread that polls an [`AssetCache`] for changes
+///
+/// The thread is stopped and joined when the watcher is dropped, using the
+/// same RAII shutdown idea as the Chapter 20 `ThreadPool`.
+pub struct Watcher {
+    running: Arc<AtomicBool>,
+    thread: Option<thread::JoinHandle<()>>,
+}
+
+impl Watcher {
+    /// Start polling `cache` every `interval`
+    ///
+    /// `on_change` runs on the watcher thread with the paths each poll
+    /// found changed, whenever there are any.
+    pub fn spawn<F>(cache: Arc<AssetCache>, interval: Duration, mut on_change: F) -> Watcher
+    where
+        F: FnMut(&[PathBuf]) + Send + 'static,
+    {
+        let running = Arc::new(AtomicBool::new(true));
+        let flag = Arc::clone(&running);
+
+        let thread = thread::spawn(move || {
+            while flag.load(Ordering::SeqCst) {
+                let changed = cache.poll_changes();
+                if !changed.is_empty() {
+                    on_change(&changed);
+                }
+                thread::sleep(interval);
+            }
+        });
+
+        Watcher {
+            running,
+            thread: Some(thread),
+        }
+    }
+}
+
+impl Drop for Watcher {
+    fn drop(&mut self) {
+        self.running.store(false, Ordering::SeqCst);
+
+        if let Some(thread) = self.thread.take()
+            && thread.join().is_err()
+        {
+            eprintln!("❌ Asset watcher panicked during shutdown");
+        }
+    }
+}
+
+/// Insert the live-reload script right before `</body>`
+///
+/// The script polls [`LIVE_RELOAD_ROUTE`] and reloads the page once the
+/// server reports a generation different from the one it was served with.
+/// If the document has no `</body>` tag, the script is appended.
+pub fn inject_live_reload(html: &str, generation: u64) -> String {
+    let script = format!(
+        r#"<script>
+(function () {{
+    const served = "{generation}";
+    setInterval(function () {{
+        fetch("{route}")
+            .then(function (r) {{ return r.text(); }})
+            .then(function (current) {{ if (current.trim() !== served) location.reload(); }})
+            .catch(function () {{}});
+    }}, 1000);
+}})();
+</script>
+"#,
+        generation = generation,
+        route = LIVE_RELOAD_ROUTE,
+    );
+
+    match html.to_ascii_lowercase().rfind("</body>") {
+        Some(index) => {
+            let mut result = String::with_capacity(html.len() + script.len());
+            result.push_str(&html[..index]);
+            result.push_str(&script);
+            result.push_str(&html[index..]);
+            result
+        }
+        None => format!("{}{}", html, script),
+    }
+}
+
+/// The `Content-Type` to serve `path` with, judged by its extension
+///
+/// # Example
+/// ```
+/// use rust_book_examples::asset_cache::content_type;
+///
+/// assert_eq!(content_type("web_assets/index.HTML"), "text/html; charset=utf-8");
+/// assert_eq!(content_type("web_assets/site.css"), "text/css; charset=utf-8");
+/// assert_eq!(content_type("web_assets/README"), "text/plain; charset=utf-8");
+/// ```
+pub fn content_type(path: impl AsRef<Path>) -> &'static str {
+    let extension = path
+        .as_ref()
+        .extension()
+        .and_then(|ext| ext.to_str())
+        .map(|ext| ext.to_ascii_lowercase());
+
+    match extension.as_deref() {
+        Some("html" | "htm") => "text/html; charset=utf-8",
+        Some("css") => "text/css; charset=utf-8",
+        Some("js" | "mjs") => "text/javascript; charset=utf-8",
+        Some("json") => "application/json",
+        Some("svg") => "image/svg+xml",
+        Some("xml") => "application/xml",
+        _ => "text/plain; charset=utf-8",
+    }
+}
+
+/// Recursively collect regular files below `dir`
+fn collect_files(dir: &Path, files: &mut Vec<PathBuf>) -> io::Result<()> {
+    for entry in fs::read_dir(dir)? {
+        let path = entry?.path();
+        if path.is_dir() {
+            collect_files(&path, files)?;
+        } else {
+            files.push(path);
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fs_util::TempPath;
+
+    fn temp_dir(name: &str) -> TempPath {
+        TempPath::dir(&format!("asset_cache_{}", name))
+    }
+
+    /// Bump the mtime so changes are visible even on coarse filesystems
+    fn touch_later(path: &Path) {
+        let file = fs::File::options().write(true).open(path).unwrap();
+        let later = SystemTime::now() + Duration::from_secs(5);
+        file.set_modified(later).unwrap();
+    }
+
+    #[test]
+    fn caches_after_first_read() {
+        let dir = temp_dir("caches");
+        let page = dir.join("index.html");
+        fs::write(&page, "<p>hello</p>").unwrap();
+
+        let cache = AssetCache::new(&*dir, false);
+        assert_eq!(*cache.get(&page).unwrap(), "<p>hello</p>");
+        assert_eq!(cache.len(), 1);
+
+        // Without polling, the cached copy keeps being served
+        fs::write(&page, "<p>changed</p>").unwrap();
+        assert_eq!(*cache.get(&page).unwrap(), "<p>hello</p>");
+    }
+
+    #[test]
+    fn poll_reloads_modified_files() {
+        let dir = temp_dir("poll");
+        let page = dir.join("index.html");
+        fs::write(&page, "v1").unwrap();
+
+        let cache = AssetCache::new(&*dir, false);
+        cache.get(&page).unwrap();
+        assert!(cache.poll_changes().is_empty());
+        assert_eq!(cache.generation(), 0);
+
+        fs::write(&page, "v2").unwrap();
+        touch_later(&page);
+
+        assert_eq!(cache.poll_changes(), vec![page.clone()]);
+        assert_eq!(cache.generation(), 1);
+        assert_eq!(*cache.get(&page).unwrap(), "v2");
+    }
+
+    #[test]
+    fn poll_drops_deleted_files() {
+        let dir = temp_dir("deleted");
+        let page = dir.join("gone.html");
+        fs::write(&page, "bye").unwrap();
+
+        let cache = AssetCache::new(&*dir, false);
+        cache.get(&page).unwrap();
+        fs::remove_file(&page).unwrap();
+
+        assert_eq!(cache.poll_changes(), vec![page.clone()]);
+        assert!(cache.is_empty());
+        assert!(cache.get(&page).is_err());
+    }
+
+    #[test]
+    fn poll_catches_same_mtime_edits_by_length() {
+        let dir = temp_dir("length");
+        let page = dir.join("index.html");
+        fs::write(&page, "v1").unwrap();
+        let mtime = fs::metadata(&page).unwrap().modified().unwrap();
+
+        let cache = AssetCache::new(&*dir, false);
+        cache.preload().unwrap();
+
+        // An edit landing within the same mtime tick
+        fs::write(&page, "version 2").unwrap();
+        fs::File::options().write(true).open(&page).unwrap().set_modified(mtime).unwrap();
+
+        assert_eq!(cache.poll_changes(), vec![page.clone()]);
+        assert_eq!(*cache.get(&page).unwrap(), "version 2");
+    }
+
+    #[test]
+    fn poll_picks_up_new_files() {
+        let dir = temp_dir("new");
+        fs::write(dir.join("a.html"), "a").unwrap();
+
+        let cache = AssetCache::new(&*dir, false);
+        cache.preload().unwrap();
+        assert!(cache.poll_changes().is_empty());
+
+        fs::create_dir_all(dir.join("nested")).unwrap();
+        let added = dir.join("nested/b.css");
+        fs::write(&added, "b {}").unwrap();
+
+        assert_eq!(cache.poll_changes(), vec![added.clone()]);
+        assert_eq!(cache.generation(), 1);
+        assert_eq!(cache.len(), 2);
+        assert!(cache.poll_changes().is_empty());
+    }
+
+    #[test]
+    fn watcher_reports_changes_to_its_callback() {
+        let dir = temp_dir("watcher");
+        let cache = Arc::new(AssetCache::new(&*dir, false));
+        let (sender, receiver) = std::sync::mpsc::channel();
+        let watcher = Watcher::spawn(Arc::clone(&cache), Duration::from_millis(10), move |changed| {
+            sender.send(changed.to_vec()).unwrap();
+        });
+
+        let page = dir.join("late.html");
+        fs::write(&page, "late").unwrap();
+        assert_eq!(receiver.recv_timeout(Duration::from_secs(5)).unwrap(), vec![page]);
+        drop(watcher);
+        assert_eq!(cache.generation(), 1);
+    }
+
+    #[test]
+    fn content_type_follows_the_extension() {
+        assert_eq!(content_type("a/b.html"), "text/html; charset=utf-8");
+        assert_eq!(content_type("app.JS"), "text/javascript; charset=utf-8");
+        assert_eq!(content_type("logo.svg"), "image/svg+xml");
+        assert_eq!(content_type("no_extension"), "text/plain; charset=utf-8");
+    }
+
+    #[test]
+    fn preload_walks_subdirectories() {
+        let dir = temp_dir("preload");
+        fs::create_dir_all(dir.join("nested")).unwrap();
+        fs::write(dir.join("a.html"), "a").unwrap();
+        fs::write(dir.join("nested/b.html"), "b").unwrap();
+
+        let cache = AssetCache::new(&*dir, false);
+        assert_eq!(cache.preload().unwrap(), 2);
+        assert_eq!(cache.len(), 2);
+    }
+
+    #[test]
+    fn dev_mode_injects_script_into_html_only() {
+        let dir = temp_dir("dev");
+        let page = dir.join("page.html");
+        let style = dir.join("style.css");
+        fs::write(&page, "<html><body><p>hi</p></body></html>").unwrap();
+        fs::write(&style, "body {}").unwrap();
+
+        let dev = AssetCache::new(&*dir, true);
+        let html = dev.get_for_response(&page).unwrap();
+        assert!(html.contains(LIVE_RELOAD_ROUTE));
+        assert!(html.find("<script>").unwrap() < html.find("</body>").unwrap());
+        assert_eq!(dev.get_for_response(&style).unwrap(), "body {}");
+
+        let prod = AssetCache::new(&*dir, false);
+        assert!(!prod.get_for_response(&page).unwrap().contains("<script>"));
+    }
+
+    #[test]
+    fn inject_appends_without_body_tag() {
+        let html = inject_live_reload("<h1>fragment</h1>", 3);
+        assert!(html.starts_with("<h1>fragment</h1><script>"));
+        assert!(html.contains("const served = \"3\""));
+    }
+}
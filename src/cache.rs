@@ -0,0 +1,517 @@
+//! # LRU Cache
+//!
+//! Chapter 8.3 ends with a `HashMap` used as a cache. A map alone grows
+//! without bound and never forgets a stale value; [`Cache`] adds the rest:
+//!
+//! - A capacity: once full, inserting evicts the least recently used entry
+//! - An optional time to live, for the whole cache or per entry
+//! - A loader closure, called on a miss to fetch the value
+//! - Hit, miss, eviction and expiration counts, in [`CacheStats`]
+//!
+//! Every operation is O(1). Entries live in a `Vec` used as a slab, linked
+//! into a doubly linked list by index from most to least recently used, and
+//! a `HashMap` finds a key's slot. Indices stand in for the pointers a
+//! linked list would use in other languages, so there is no `unsafe` and no
+//! `Rc<RefCell<...>>`.
+//!
+//! This module demonstrates:
+//! - A generic struct with `Hash + Eq` bounds on its keys
+//! - `Borrow`, so a `Cache<String, V>` can be queried with a `&str`
+//! - Boxed closures as pluggable behavior: the loader and the clock
+//! - Injecting the clock, so expiry can be tested without sleeping
+//!
+//! The **concurrent** submodule shares these caches between threads.
+
+pub mod concurrent;
+
+use std::borrow::Borrow;
+use std::collections::HashMap;
+use std::fmt;
+use std::hash::Hash;
+use std::mem;
+use std::time::{Duration, Instant};
+
+/// Marks the end of the list
+const NIL: usize = usize::MAX;
+
+/// Lookups and removals since the cache was created
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct CacheStats {
+    pub hits: u64,
+    pub misses: u64,
+    /// Entries dropped to make room
+    pub evictions: u64,
+    /// Entries found past their time to live
+    pub expirations: u64,
+}
+
+impl CacheStats {
+    /// Hits as a fraction of lookups; 0 before the first lookup
+    pub fn hit_rate(&self) -> f64 {
+        let lookups = self.hits + self.misses;
+        if lookups == 0 { 0.0 } else { self.hits as f64 / lookups as f64 }
+    }
+}
+
+impl fmt::Display for CacheStats {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{} hits, {} misses ({:.0}% hit rate), {} evictions, {} expired",
+            self.hits,
+            self.misses,
+            self.hit_rate() * 100.0,
+            self.evictions,
+            self.expirations
+        )
+    }
+}
+
+struct Node<K, V> {
+    key: K,
+    value: V,
+    expires: Option<Instant>,
+    /// Toward the most recently used end
+    prev: usize,
+    next: usize,
+}
+
+type Loader<K, V> = Box<dyn FnMut(&K) -> Option<V> + Send>;
+
+/// A bounded map that forgets the least recently used entry first
+///
+/// # Example
+/// ```
+/// use rust_book_examples::cache::Cache;
+///
+/// let mut cache = Cache::new(2).with_loader(|key: &String| Some(key.len()));
+/// assert_eq!(cache.get("one"), Some(&3));
+/// assert_eq!(cache.get("three"), Some(&5));
+/// assert_eq!(cache.get("one"), Some(&3));
+/// // Full, so "three", used longest ago, makes way
+/// cache.insert("four".to_string(), 4);
+/// assert_eq!(cache.keys().collect::<Vec<_>>(), ["four", "one"]);
+/// assert_eq!((cache.stats().hits, cache.stats().misses, cache.stats().evictions), (1, 2, 1));
+/// ```
+pub struct Cache<K, V> {
+    map: HashMap<K, usize>,
+    /// Slots for entries; `None` slots are listed in `free`
+    nodes: Vec<Option<Node<K, V>>>,
+    free: Vec<usize>,
+    /// Most recently used
+    head: usize,
+    /// Least recently used, evicted first
+    tail: usize,
+    capacity: usize,
+    ttl: Option<Duration>,
+    loader: Option<Loader<K, V>>,
+    clock: Box<dyn FnMut() -> Instant + Send>,
+    stats: CacheStats,
+}
+
+impl<K: Hash + Eq + Clone, V> Cache<K, V> {
+    /// An empty cache holding at most `capacity` entries
+    ///
+    /// # Panics
+    ///
+    /// Panics if `capacity` is zero.
+    pub fn new(capacity: usize) -> Cache<K, V> {
+        assert!(capacity > 0, "a cache needs room for at least one entry");
+        Cache {
+            map: HashMap::with_capacity(capacity),
+            nodes: Vec::with_capacity(capacity),
+            free: Vec::new(),
+            head: NIL,
+            tail: NIL,
+            capacity,
+            ttl: None,
+            loader: None,
+            clock: Box::new(Instant::now),
+            stats: CacheStats::default(),
+        }
+    }
+
+    /// Expire entries `ttl` after they're inserted, unless inserted with
+    /// [`Cache::insert_with_ttl`]
+    ///
+    /// A `ttl` too long to count to, like `Duration::MAX`, never expires.
+    pub fn with_ttl(mut self, ttl: Duration) -> Cache<K, V> {
+        self.ttl = Some(ttl);
+        self
+    }
+
+    /// On a miss, call `loader` and cache what it returns; `None` means the
+    /// key has no value, and nothing is cached
+    pub fn with_loader(mut self, loader: impl FnMut(&K) -> Option<V> + Send + 'static) -> Cache<K, V> {
+        self.loader = Some(Box::new(loader));
+        self
+    }
+
+    /// Read the time from `clock` instead of `Instant::now`
+    pub fn with_clock(mut self, clock: impl FnMut() -> Instant + Send + 'static) -> Cache<K, V> {
+        self.clock = Box::new(clock);
+        self
+    }
+
+    /// The value for `key`, loading it on a miss if there's a loader
+    ///
+    /// A hit makes the entry the most recently used.
+    pub fn get<Q>(&mut self, key: &Q) -> Option<&V>
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ToOwned<Owned = K> + ?Sized,
+    {
+        let now = (self.clock)();
+        if let Some(index) = self.live_index(key, now) {
+            self.stats.hits += 1;
+            self.touch(index);
+            return Some(&self.node(index).value);
+        }
+
+        self.stats.misses += 1;
+        let key = key.to_owned();
+        let value = (self.loader.as_mut()?)(&key)?;
+        let index = self.push(key, value, expiry(now, self.ttl));
+        Some(&self.node(index).value)
+    }
+
+    /// Whether `key` has a live entry, without counting a lookup or
+    /// changing the order of use
+    pub fn contains_key<Q>(&mut self, key: &Q) -> bool
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        let now = (self.clock)();
+        self.live_index(key, now).is_some()
+    }
+
+    /// Insert or replace `key`'s value with the cache's time to live,
+    /// returning the value it replaces
+    pub fn insert(&mut self, key: K, value: V) -> Option<V> {
+        let ttl = self.ttl;
+        self.insert_expiring(key, value, ttl)
+    }
+
+    /// Insert or replace `key`'s value, expiring after `ttl` whatever the
+    /// cache's own time to live; as with [`Cache::with_ttl`], a `ttl` too
+    /// long to count to never expires
+    pub fn insert_with_ttl(&mut self, key: K, value: V, ttl: Duration) -> Option<V> {
+        self.insert_expiring(key, value, Some(ttl))
+    }
+
+    pub fn remove<Q>(&mut self, key: &Q) -> Option<V>
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        let now = (self.clock)();
+        let index = self.live_index(key, now)?;
+        Some(self.unlink(index).value)
+    }
+
+    /// Drop every expired entry now rather than when it's next looked up,
+    /// returning how many there were
+    ///
+    /// Unlike the other operations this is O(n).
+    pub fn purge_expired(&mut self) -> usize {
+        let now = (self.clock)();
+        let expired: Vec<usize> = self
+            .map
+            .values()
+            .copied()
+            .filter(|&index| self.node(index).expires.is_some_and(|expires| expires <= now))
+            .collect();
+        for &index in &expired {
+            self.unlink(index);
+        }
+        self.stats.expirations += expired.len() as u64;
+        expired.len()
+    }
+
+    pub fn clear(&mut self) {
+        self.map.clear();
+        self.nodes.clear();
+        self.free.clear();
+        self.head = NIL;
+        self.tail = NIL;
+    }
+
+    /// Entries held, counting expired ones not yet noticed
+    pub fn len(&self) -> usize {
+        self.map.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.map.is_empty()
+    }
+
+    pub fn capacity(&self) -> usize {
+        self.capacity
+    }
+
+    pub fn stats(&self) -> CacheStats {
+        self.stats
+    }
+
+    /// Keys from most to least recently used
+    pub fn keys(&self) -> impl Iterator<Item = &K> {
+        self.iter().map(|(key, _)| key)
+    }
+
+    /// Entries from most to least recently used
+    pub fn iter(&self) -> impl Iterator<Item = (&K, &V)> {
+        let mut index = self.head;
+        std::iter::from_fn(move || {
+            let node = self.nodes.get(index)?.as_ref()?;
+            index = node.next;
+            Some((&node.key, &node.value))
+        })
+    }
+
+    fn node(&self, index: usize) -> &Node<K, V> {
+        self.nodes[index].as_ref().expect("linked slots are occupied")
+    }
+
+    fn node_mut(&mut self, index: usize) -> &mut Node<K, V> {
+        self.nodes[index].as_mut().expect("linked slots are occupied")
+    }
+
+    /// The slot of `key`'s entry, dropping it if it has expired
+    fn live_index<Q>(&mut self, key: &Q, now: Instant) -> Option<usize>
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        let index = *self.map.get(key)?;
+        if self.node(index).expires.is_some_and(|expires| expires <= now) {
+            self.unlink(index);
+            self.stats.expirations += 1;
+            return None;
+        }
+        Some(index)
+    }
+
+    fn insert_expiring(&mut self, key: K, value: V, ttl: Option<Duration>) -> Option<V> {
+        let now = (self.clock)();
+        let expires = expiry(now, ttl);
+        if let Some(index) = self.live_index(&key, now) {
+            self.touch(index);
+            let node = self.node_mut(index);
+            node.expires = expires;
+            return Some(mem::replace(&mut node.value, value));
+        }
+        self.push(key, value, expires);
+        None
+    }
+
+    /// Add a new entry as the most recently used, evicting the least
+    /// recently used if the cache is full
+    fn push(&mut self, key: K, value: V, expires: Option<Instant>) -> usize {
+        if self.map.len() == self.capacity {
+            self.unlink(self.tail);
+            self.stats.evictions += 1;
+        }
+        let node = Node { key: key.clone(), value, expires, prev: NIL, next: NIL };
+        let index = match self.free.pop() {
+            Some(index) => {
+                self.nodes[index] = Some(node);
+                index
+            }
+            None => {
+                self.nodes.push(Some(node));
+                self.nodes.len() - 1
+            }
+        };
+        self.map.insert(key, index);
+        self.link_front(index);
+        index
+    }
+
+    /// Move an entry to the most recently used end
+    fn touch(&mut self, index: usize) {
+        if index != self.head {
+            self.detach(index);
+            self.link_front(index);
+        }
+    }
+
+    fn link_front(&mut self, index: usize) {
+        let head = self.head;
+        let node = self.node_mut(index);
+        node.prev = NIL;
+        node.next = head;
+        if head == NIL {
+            self.tail = index;
+        } else {
+            self.node_mut(head).prev = index;
+        }
+        self.head = index;
+    }
+
+    /// Take an entry out of the list, leaving it in its slot
+    fn detach(&mut self, index: usize) {
+        let (prev, next) = {
+            let node = self.node(index);
+            (node.prev, node.next)
+        };
+        if prev == NIL {
+            self.head = next;
+        } else {
+            self.node_mut(prev).next = next;
+        }
+        if next == NIL {
+            self.tail = prev;
+        } else {
+            self.node_mut(next).prev = prev;
+        }
+    }
+
+    /// Remove an entry entirely, freeing its slot
+    fn unlink(&mut self, index: usize) -> Node<K, V> {
+        self.detach(index);
+        let node = self.nodes[index].take().expect("linked slots are occupied");
+        self.map.remove(&node.key);
+        self.free.push(index);
+        node
+    }
+}
+
+impl<K, V> fmt::Debug for Cache<K, V> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("Cache")
+            .field("len", &self.map.len())
+            .field("capacity", &self.capacity)
+            .field("ttl", &self.ttl)
+            .field("stats", &self.stats)
+            .finish_non_exhaustive()
+    }
+}
+
+/// When something living `ttl` from `now` expires; `None` for never
+fn expiry(now: Instant, ttl: Option<Duration>) -> Option<Instant> {
+    // Past what an `Instant` can hold is as good as never
+    ttl.and_then(|ttl| now.checked_add(ttl))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::{Arc, Mutex};
+
+    /// A clock that only moves when told to
+    fn manual_clock() -> (Arc<Mutex<Instant>>, impl FnMut() -> Instant + Send + 'static) {
+        let now = Arc::new(Mutex::new(Instant::now()));
+        let clock = {
+            let now = Arc::clone(&now);
+            move || *now.lock().unwrap()
+        };
+        (now, clock)
+    }
+
+    #[test]
+    fn evicts_least_recently_used() {
+        let mut cache = Cache::new(3);
+        for (key, value) in [("a", 1), ("b", 2), ("c", 3)] {
+            assert_eq!(cache.insert(key, value), None);
+        }
+        assert_eq!(cache.get(&"a"), Some(&1));
+        assert_eq!(cache.insert("b", 20), Some(2));
+        cache.insert("d", 4);
+        // "c" went unused longest
+        assert_eq!(cache.keys().copied().collect::<Vec<_>>(), ["d", "b", "a"]);
+        assert_eq!(cache.get(&"c"), None);
+        assert_eq!(cache.remove("a"), Some(1));
+        cache.insert("e", 5);
+        cache.insert("f", 6);
+        assert_eq!(cache.iter().map(|(k, v)| (*k, *v)).collect::<Vec<_>>(), [("f", 6), ("e", 5), ("d", 4)]);
+        assert_eq!(cache.len(), 3);
+        assert_eq!(cache.stats(), CacheStats { hits: 1, misses: 1, evictions: 2, expirations: 0 });
+        // Freed slots are reused rather than growing the slab
+        assert_eq!(cache.nodes.len(), 3);
+    }
+
+    #[test]
+    fn loader_fills_misses() {
+        let calls = Arc::new(Mutex::new(Vec::new()));
+        let mut cache = Cache::new(2).with_loader({
+            let calls = Arc::clone(&calls);
+            move |key: &String| {
+                calls.lock().unwrap().push(key.clone());
+                key.parse::<u32>().ok()
+            }
+        });
+        assert_eq!(cache.get("7"), Some(&7));
+        assert_eq!(cache.get("7"), Some(&7));
+        // Nothing to load, and nothing cached
+        assert_eq!(cache.get("seven"), None);
+        assert_eq!(cache.get("seven"), None);
+        assert_eq!(*calls.lock().unwrap(), ["7", "seven", "seven"]);
+        assert_eq!(cache.len(), 1);
+        assert!((cache.stats().hit_rate() - 0.25).abs() < 1e-9);
+        assert_eq!(cache.stats().to_string(), "1 hits, 3 misses (25% hit rate), 0 evictions, 0 expired");
+    }
+
+    #[test]
+    fn entries_expire() {
+        let (now, clock) = manual_clock();
+        let advance = |secs| *now.lock().unwrap() += Duration::from_secs(secs);
+        let mut cache = Cache::new(10).with_ttl(Duration::from_secs(60)).with_clock(clock);
+        cache.insert("session", "abc");
+        cache.insert_with_ttl("token", "xyz", Duration::from_secs(5));
+        cache.insert_with_ttl("report", "pdf", Duration::from_secs(600));
+
+        advance(5);
+        assert_eq!(cache.get(&"token"), None);
+        assert_eq!(cache.get(&"session"), Some(&"abc"));
+        advance(55);
+        assert!(!cache.contains_key("session"));
+        // Replacing an entry restarts its time to live
+        cache.insert("report", "docx");
+        advance(30);
+        assert_eq!(cache.get(&"report"), Some(&"docx"));
+
+        cache.insert_with_ttl("a", "1", Duration::from_secs(1));
+        cache.insert_with_ttl("b", "2", Duration::from_secs(1));
+        advance(1);
+        assert_eq!(cache.len(), 3);
+        assert_eq!(cache.purge_expired(), 2);
+        assert_eq!(cache.keys().copied().collect::<Vec<_>>(), ["report"]);
+        assert_eq!(cache.stats().expirations, 4);
+    }
+
+    #[test]
+    fn endless_ttls_never_expire() {
+        let (now, clock) = manual_clock();
+        let mut cache = Cache::new(10)
+            .with_ttl(Duration::MAX)
+            .with_loader(|key: &u32| Some(key * 2))
+            .with_clock(clock);
+        cache.insert(1, 10);
+        cache.insert_with_ttl(2, 20, Duration::MAX);
+        assert_eq!(cache.get(&3), Some(&6));
+
+        *now.lock().unwrap() += Duration::from_secs(100 * 365 * 24 * 60 * 60);
+        assert_eq!(cache.purge_expired(), 0);
+        assert_eq!(cache.keys().copied().collect::<Vec<_>>(), [3, 2, 1]);
+    }
+
+    #[test]
+    fn a_cache_of_one() {
+        let mut cache = Cache::new(1);
+        cache.insert(1, "one");
+        cache.insert(2, "two");
+        assert_eq!(cache.get(&1), None);
+        assert_eq!(cache.get(&2), Some(&"two"));
+        cache.clear();
+        assert!(cache.is_empty());
+        cache.insert(3, "three");
+        assert_eq!(cache.keys().copied().collect::<Vec<_>>(), [3]);
+    }
+
+    #[test]
+    #[should_panic(expected = "at least one entry")]
+    fn capacity_must_be_positive() {
+        Cache::<u32, u32>::new(0);
+    }
+}
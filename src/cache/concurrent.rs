@@ -0,0 +1,450 @@
+//! # Concurrent Cache
+//!
+//! A [`Cache`] shared between threads, for values that are expensive to
+//! compute.
+//!
+//! A plain `RwLock<HashMap>` has two problems when many threads ask for the
+//! same missing key: every one of them computes it, and every lookup
+//! anywhere waits on the same lock. [`ConcurrentCache`] fixes both:
+//!
+//! - **Single flight**: the first thread to miss a key computes it; threads
+//!   that miss the same key meanwhile wait for that result instead of
+//!   computing it again. An error is handed to every waiter too, and isn't
+//!   cached, so the next request tries again. If the computing thread
+//!   panics, one of the waiters takes over.
+//! - **Sharding**: keys are spread by hash over several independently locked
+//!   LRU caches, so threads working on different keys rarely contend. No
+//!   lock is held while a value is computed.
+//!
+//! This module demonstrates:
+//! - `Mutex` and `Condvar` together: waiting until another thread is done
+//! - A drop guard that cleans up even when a closure panics
+//! - `BuildHasher::hash_one` to pick a shard for a key
+
+use super::{Cache, CacheStats};
+use std::borrow::Borrow;
+use std::collections::HashMap;
+use std::collections::hash_map::RandomState;
+use std::fmt;
+use std::hash::{BuildHasher, Hash};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Condvar, Mutex, MutexGuard};
+use std::thread::{self, ThreadId};
+use std::time::Duration;
+
+/// Shards used by [`ConcurrentCache::new`]
+pub const DEFAULT_SHARDS: usize = 16;
+
+/// [`CacheStats`] summed over the shards, plus what happened on misses
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct ConcurrentStats {
+    pub cache: CacheStats,
+    /// Values computed, successfully or not
+    pub computed: u64,
+    /// Misses that waited for another thread's computation
+    pub waited: u64,
+    /// Computations that returned an error
+    pub failed: u64,
+}
+
+impl fmt::Display for ConcurrentStats {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{}; {} computed ({} failed), {} waited for another thread",
+            self.cache, self.computed, self.failed, self.waited
+        )
+    }
+}
+
+enum State<V, E> {
+    Running,
+    Done(Result<V, E>),
+    /// The computing thread panicked
+    Abandoned,
+}
+
+/// One computation in progress, which other threads can wait on
+struct Flight<V, E> {
+    state: Mutex<State<V, E>>,
+    changed: Condvar,
+    /// The thread computing it, which would wait forever on itself
+    leader: ThreadId,
+}
+
+impl<V: Clone, E: Clone> Flight<V, E> {
+    /// The result, or `None` if the computation was abandoned
+    fn wait(&self) -> Option<Result<V, E>> {
+        let mut state = lock(&self.state);
+        while matches!(*state, State::Running) {
+            state = self.changed.wait(state).unwrap_or_else(|poisoned| poisoned.into_inner());
+        }
+        match &*state {
+            State::Done(result) => Some(result.clone()),
+            _ => None,
+        }
+    }
+}
+
+struct Shard<K, V, E> {
+    cache: Cache<K, V>,
+    in_flight: HashMap<K, Arc<Flight<V, E>>>,
+}
+
+/// A sharded, thread-safe LRU cache that computes each missing key once
+///
+/// # Example
+/// ```
+/// use rust_book_examples::cache::concurrent::ConcurrentCache;
+/// use std::sync::Arc;
+/// use std::thread;
+///
+/// let cache: Arc<ConcurrentCache<u64, u64, String>> = Arc::new(ConcurrentCache::new(100));
+/// let handles: Vec<_> = (0..4)
+///     .map(|_| {
+///         let cache = Arc::clone(&cache);
+///         thread::spawn(move || cache.get_or_try_compute(10, |n| Ok((1..=*n).product())))
+///     })
+///     .collect();
+/// for handle in handles {
+///     assert_eq!(handle.join().unwrap(), Ok(3_628_800));
+/// }
+/// // However the threads interleaved, the factorial was computed once
+/// assert_eq!(cache.stats().computed, 1);
+///
+/// let err = cache.get_or_try_compute(0, |_| Err("no such thing".to_string()));
+/// assert_eq!(err, Err("no such thing".to_string()));
+/// assert_eq!(cache.get(&0), None);
+/// ```
+pub struct ConcurrentCache<K, V, E> {
+    shards: Vec<Mutex<Shard<K, V, E>>>,
+    hasher: RandomState,
+    computed: AtomicU64,
+    waited: AtomicU64,
+    failed: AtomicU64,
+}
+
+impl<K, V, E> ConcurrentCache<K, V, E>
+where
+    K: Hash + Eq + Clone,
+    V: Clone,
+    E: Clone,
+{
+    /// A cache for about `capacity` entries over [`DEFAULT_SHARDS`] shards
+    ///
+    /// # Panics
+    ///
+    /// Panics if `capacity` is zero.
+    pub fn new(capacity: usize) -> ConcurrentCache<K, V, E> {
+        ConcurrentCache::with_shards(capacity, DEFAULT_SHARDS)
+    }
+
+    /// A cache for about `capacity` entries over `shards` shards
+    ///
+    /// Each shard evicts on its own, so the cache holds up to `capacity`
+    /// rounded up to a multiple of the number of shards. There are never
+    /// more shards than `capacity`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `capacity` or `shards` is zero.
+    pub fn with_shards(capacity: usize, shards: usize) -> ConcurrentCache<K, V, E> {
+        assert!(shards > 0, "a cache needs at least one shard");
+        assert!(capacity > 0, "a cache needs room for at least one entry");
+        let shards = shards.min(capacity);
+        let per_shard = capacity.div_ceil(shards);
+        ConcurrentCache {
+            shards: (0..shards)
+                .map(|_| Mutex::new(Shard { cache: Cache::new(per_shard), in_flight: HashMap::new() }))
+                .collect(),
+            hasher: RandomState::new(),
+            computed: AtomicU64::new(0),
+            waited: AtomicU64::new(0),
+            failed: AtomicU64::new(0),
+        }
+    }
+
+    /// Expire entries `ttl` after they're computed or inserted
+    pub fn with_ttl(mut self, ttl: Duration) -> ConcurrentCache<K, V, E> {
+        self.shards = self
+            .shards
+            .into_iter()
+            .map(|shard| {
+                let shard = shard.into_inner().unwrap_or_else(|poisoned| poisoned.into_inner());
+                Mutex::new(Shard { cache: shard.cache.with_ttl(ttl), ..shard })
+            })
+            .collect();
+        self
+    }
+
+    /// The cached value for `key`, or the result of `compute`
+    ///
+    /// If another thread is already computing `key`, this waits for its
+    /// result rather than calling `compute`. Successful results are cached;
+    /// errors are returned to every thread that was waiting, then forgotten.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `compute` asks for the key it's computing, which would
+    /// otherwise wait on itself forever. Asking for other keys is fine, as
+    /// long as no two computations end up waiting on each other.
+    pub fn get_or_try_compute(&self, key: K, compute: impl FnOnce(&K) -> Result<V, E>) -> Result<V, E> {
+        loop {
+            let flight = {
+                let mut shard = self.shard(&key);
+                if let Some(value) = shard.cache.get(&key) {
+                    return Ok(value.clone());
+                }
+                match shard.in_flight.get(&key) {
+                    Some(flight) => {
+                        assert!(
+                            flight.leader != thread::current().id(),
+                            "get_or_try_compute called for a key while computing it"
+                        );
+                        Arc::clone(flight)
+                    }
+                    None => {
+                        let flight = Arc::new(Flight {
+                            state: Mutex::new(State::Running),
+                            changed: Condvar::new(),
+                            leader: thread::current().id(),
+                        });
+                        shard.in_flight.insert(key.clone(), Arc::clone(&flight));
+                        drop(shard);
+                        return self.lead(key, flight, compute);
+                    }
+                }
+            };
+
+            self.waited.fetch_add(1, Ordering::Relaxed);
+            if let Some(result) = flight.wait() {
+                return result;
+            }
+            // The computing thread panicked; start over, perhaps computing it ourselves
+        }
+    }
+
+    /// The cached value for `key`, without computing or waiting
+    pub fn get<Q>(&self, key: &Q) -> Option<V>
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ToOwned<Owned = K> + ?Sized,
+    {
+        self.shard(key).cache.get(key).cloned()
+    }
+
+    pub fn insert(&self, key: K, value: V) -> Option<V> {
+        self.shard(&key).cache.insert(key, value)
+    }
+
+    /// Forget `key`'s value; a computation already running still caches its
+    /// result when it finishes
+    pub fn remove<Q>(&self, key: &Q) -> Option<V>
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        self.shard(key).cache.remove(key)
+    }
+
+    /// Entries held across all shards
+    pub fn len(&self) -> usize {
+        self.shards.iter().map(|shard| lock(shard).cache.len()).sum()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    pub fn stats(&self) -> ConcurrentStats {
+        let mut cache = CacheStats::default();
+        for shard in &self.shards {
+            let stats = lock(shard).cache.stats();
+            cache.hits += stats.hits;
+            cache.misses += stats.misses;
+            cache.evictions += stats.evictions;
+            cache.expirations += stats.expirations;
+        }
+        ConcurrentStats {
+            cache,
+            computed: self.computed.load(Ordering::Relaxed),
+            waited: self.waited.load(Ordering::Relaxed),
+            failed: self.failed.load(Ordering::Relaxed),
+        }
+    }
+
+    fn shard<Q: Hash + ?Sized>(&self, key: &Q) -> MutexGuard<'_, Shard<K, V, E>> {
+        let index = self.hasher.hash_one(key) % self.shards.len() as u64;
+        lock(&self.shards[index as usize])
+    }
+
+    /// Compute `key` for everyone waiting on `flight`
+    fn lead(&self, key: K, flight: Arc<Flight<V, E>>, compute: impl FnOnce(&K) -> Result<V, E>) -> Result<V, E> {
+        self.computed.fetch_add(1, Ordering::Relaxed);
+        // If `compute` panics, the guard's drop lets the waiters start over
+        let mut leader = Leader { cache: self, key, flight, settled: false };
+        let result = compute(&leader.key);
+        if result.is_err() {
+            self.failed.fetch_add(1, Ordering::Relaxed);
+        }
+        leader.settle(State::Done(result.clone()));
+        result
+    }
+}
+
+/// The thread computing a key, which must tell its waiters how it went
+struct Leader<'a, K: Hash + Eq + Clone, V: Clone, E: Clone> {
+    cache: &'a ConcurrentCache<K, V, E>,
+    key: K,
+    flight: Arc<Flight<V, E>>,
+    settled: bool,
+}
+
+impl<K: Hash + Eq + Clone, V: Clone, E: Clone> Leader<'_, K, V, E> {
+    fn settle(&mut self, state: State<V, E>) {
+        {
+            let mut shard = self.cache.shard(&self.key);
+            if let State::Done(Ok(value)) = &state {
+                shard.cache.insert(self.key.clone(), value.clone());
+            }
+            shard.in_flight.remove(&self.key);
+        }
+        *lock(&self.flight.state) = state;
+        self.flight.changed.notify_all();
+        self.settled = true;
+    }
+}
+
+impl<K: Hash + Eq + Clone, V: Clone, E: Clone> Drop for Leader<'_, K, V, E> {
+    fn drop(&mut self) {
+        if !self.settled {
+            self.settle(State::Abandoned);
+        }
+    }
+}
+
+/// Lock, ignoring poisoning: no lock is held while user code runs, so a
+/// panic can't leave a shard half-updated
+fn lock<T>(mutex: &Mutex<T>) -> MutexGuard<'_, T> {
+    mutex.lock().unwrap_or_else(|poisoned| poisoned.into_inner())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::AtomicUsize;
+    use std::sync::{mpsc, Barrier};
+
+    /// Run `compute` for `key` on `threads` threads started together
+    fn race<F>(cache: &Arc<ConcurrentCache<&'static str, u32, String>>, threads: usize, compute: F) -> Vec<Result<u32, String>>
+    where
+        F: Fn(&&'static str) -> Result<u32, String> + Send + Sync + 'static,
+    {
+        let barrier = Arc::new(Barrier::new(threads));
+        let compute = Arc::new(compute);
+        let handles: Vec<_> = (0..threads)
+            .map(|_| {
+                let (cache, barrier, compute) = (Arc::clone(cache), Arc::clone(&barrier), Arc::clone(&compute));
+                thread::spawn(move || {
+                    barrier.wait();
+                    cache.get_or_try_compute("key", |key| compute(key))
+                })
+            })
+            .collect();
+        handles.into_iter().map(|handle| handle.join().unwrap()).collect()
+    }
+
+    #[test]
+    fn computes_each_key_once() {
+        let cache = Arc::new(ConcurrentCache::new(10));
+        let calls = Arc::new(AtomicUsize::new(0));
+        let results = race(&cache, 8, {
+            let calls = Arc::clone(&calls);
+            move |_| {
+                calls.fetch_add(1, Ordering::SeqCst);
+                thread::sleep(Duration::from_millis(50));
+                Ok(42)
+            }
+        });
+        assert_eq!(results, vec![Ok(42); 8]);
+        assert_eq!(calls.load(Ordering::SeqCst), 1);
+        let stats = cache.stats();
+        assert_eq!(stats.computed, 1);
+        // Everyone else either waited or, arriving late, hit
+        assert_eq!(stats.waited + stats.cache.hits, 7);
+        assert_eq!(cache.get(&"key"), Some(42));
+    }
+
+    #[test]
+    fn errors_reach_every_waiter_and_are_not_cached() {
+        let cache = Arc::new(ConcurrentCache::new(10));
+        let results = race(&cache, 4, |_| {
+            thread::sleep(Duration::from_millis(50));
+            Err("database down".to_string())
+        });
+        assert_eq!(results, vec![Err("database down".to_string()); 4]);
+        assert_eq!(cache.stats().failed, cache.stats().computed);
+        assert!(cache.is_empty());
+
+        assert_eq!(cache.get_or_try_compute("key", |_| Ok(7)), Ok(7));
+        assert_eq!(cache.get_or_try_compute("key", |_| Err("not called".to_string())), Ok(7));
+    }
+
+    #[test]
+    fn a_waiter_takes_over_from_a_panicking_leader() {
+        let cache: Arc<ConcurrentCache<&str, u32, String>> = Arc::new(ConcurrentCache::new(10));
+        let (started, is_started) = mpsc::channel();
+        let leader = {
+            let cache = Arc::clone(&cache);
+            thread::spawn(move || {
+                cache.get_or_try_compute("key", |_| {
+                    started.send(()).unwrap();
+                    // Hold on until the main thread is waiting for us
+                    while cache.stats().waited == 0 {
+                        thread::yield_now();
+                    }
+                    panic!("computation crashed");
+                })
+            })
+        };
+        is_started.recv().unwrap();
+        // This waits on the leader, then computes the value itself
+        assert_eq!(cache.get_or_try_compute("key", |_| Ok(2)), Ok(2));
+        assert!(leader.join().is_err());
+        assert_eq!(cache.stats().waited, 1);
+        assert_eq!(cache.get(&"key"), Some(2));
+    }
+
+    #[test]
+    #[should_panic(expected = "while computing it")]
+    fn asking_for_the_key_being_computed_panics() {
+        let cache: ConcurrentCache<&str, u32, String> = ConcurrentCache::new(10);
+        let _ = cache.get_or_try_compute("key", |key| cache.get_or_try_compute(key, |_| Ok(1)));
+    }
+
+    #[test]
+    fn computations_can_ask_for_other_keys() {
+        let cache: ConcurrentCache<u32, u32, String> = ConcurrentCache::with_shards(2, 1);
+        let outer = cache.get_or_try_compute(2, |n| Ok(cache.get_or_try_compute(n - 1, |n| Ok(n * 10))? + 1));
+        assert_eq!(outer, Ok(11));
+        assert_eq!((cache.get(&1), cache.get(&2)), (Some(10), Some(11)));
+    }
+
+    #[test]
+    fn shards_share_the_capacity() {
+        let cache: ConcurrentCache<u32, u32, ()> = ConcurrentCache::with_shards(8, 4);
+        for n in 0..100 {
+            cache.insert(n, n * n);
+        }
+        // Each of the 4 shards holds 2
+        assert_eq!(cache.len(), 8);
+        assert_eq!(cache.stats().cache.evictions, 92);
+
+        // No more shards than entries
+        let small: ConcurrentCache<u32, u32, ()> = ConcurrentCache::with_shards(2, 16);
+        assert_eq!(small.shards.len(), 2);
+        small.insert(1, 1);
+        assert_eq!(small.remove(&1), Some(1));
+        assert_eq!(small.get(&1), None);
+    }
+}
@@ -0,0 +1,144 @@
+//! # File System Helpers
+//!
+//! Small pieces of file handling shared by the examples that save data:
+//! the minigrep `--in-place` edits and index, the guessing game leaderboard,
+//! the spreadsheet's CSV files and the student records.
+
+use std::fs::{self, File};
+use std::io::{self, Write};
+use std::path::Path;
+#[cfg(test)]
+use std::path::PathBuf;
+
+/// Replace `path` with `contents` so that it never exists half-written
+///
+/// The contents are written and synced to a temporary file beside `path`,
+/// which is then renamed over it. An existing file's permissions are kept;
+/// a missing one is created. A symbolic link is followed, so the file it
+/// points to is replaced and the link stays a link.
+pub fn write_atomically(path: &Path, contents: &[u8], backup_suffix: Option<&str>) -> io::Result<()> {
+    // Renaming over a link would replace the link itself with a regular file
+    let target;
+    let path = if fs::symlink_metadata(path).is_ok_and(|metadata| metadata.file_type().is_symlink()) {
+        target = fs::canonicalize(path)?;
+        target.as_path()
+    } else {
+        path
+    };
+    let permissions = match fs::metadata(path) {
+        Ok(metadata) => Some(metadata.permissions()),
+        Err(e) if e.kind() == io::ErrorKind::NotFound => None,
+        Err(e) => return Err(e),
+    };
+    let name = path
+        .file_name()
+        .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, "not a file path"))?;
+    let temp = path.with_file_name(format!(".{}.{}.tmp", name.to_string_lossy(), std::process::id()));
+
+    let written = (|| {
+        let mut file = File::create(&temp)?;
+        file.write_all(contents)?;
+        file.sync_all()?;
+        if let Some(permissions) = &permissions {
+            fs::set_permissions(&temp, permissions.clone())?;
+        }
+
+        if let Some(suffix) = backup_suffix
+            && permissions.is_some()
+        {
+            let mut backup = path.as_os_str().to_owned();
+            backup.push(suffix);
+            fs::copy(path, backup)?;
+        }
+        fs::rename(&temp, path)
+    })();
+
+    if written.is_err() {
+        let _ = fs::remove_file(&temp);
+    }
+    written
+}
+
+/// A path under the system temp directory for one test, whose file or
+/// directory is deleted when it's dropped, even if an assertion failed
+#[cfg(test)]
+pub(crate) struct TempPath(PathBuf);
+
+#[cfg(test)]
+impl TempPath {
+    /// A path ending in `name`, unique to this process; nothing is created
+    pub(crate) fn new(name: &str) -> TempPath {
+        let path = TempPath(std::env::temp_dir().join(format!("rust_book_examples_{}_{}", std::process::id(), name)));
+        // Left over from an earlier run that was killed
+        path.remove();
+        path
+    }
+
+    /// Like [`TempPath::new`], but creates it as an empty directory
+    pub(crate) fn dir(name: &str) -> TempPath {
+        let dir = TempPath::new(name);
+        fs::create_dir_all(&dir.0).unwrap();
+        dir
+    }
+
+    fn remove(&self) {
+        let _ = if self.0.is_dir() { fs::remove_dir_all(&self.0) } else { fs::remove_file(&self.0) };
+    }
+}
+
+#[cfg(test)]
+impl std::ops::Deref for TempPath {
+    type Target = Path;
+
+    fn deref(&self) -> &Path {
+        &self.0
+    }
+}
+
+#[cfg(test)]
+impl AsRef<Path> for TempPath {
+    fn as_ref(&self) -> &Path {
+        &self.0
+    }
+}
+
+#[cfg(test)]
+impl Drop for TempPath {
+    fn drop(&mut self) {
+        self.remove();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[cfg(unix)]
+    #[test]
+    fn writing_through_a_symlink_keeps_the_link() {
+        use std::os::unix::fs::symlink;
+
+        let dir = TempPath::dir("fs_util_link");
+        let target = dir.join("poem.txt");
+        let link = dir.join("link.txt");
+        fs::write(&target, "frog\n").unwrap();
+        symlink(&target, &link).unwrap();
+
+        write_atomically(&link, b"toad\n", Some(".bak")).unwrap();
+        assert!(fs::symlink_metadata(&link).unwrap().file_type().is_symlink());
+        assert_eq!(fs::read_to_string(&target).unwrap(), "toad\n");
+        assert_eq!(fs::read_to_string(dir.join("poem.txt.bak")).unwrap(), "frog\n");
+    }
+
+    #[test]
+    fn temp_paths_are_removed_on_drop() {
+        let file = TempPath::new("fs_util_drop.txt");
+        fs::write(&file, "gone soon").unwrap();
+        let dir = TempPath::dir("fs_util_drop");
+        fs::write(dir.join("inside.txt"), "gone too").unwrap();
+        let (file_path, dir_path) = (file.to_path_buf(), dir.to_path_buf());
+
+        drop((file, dir));
+        assert!(!file_path.exists() && !dir_path.exists());
+    }
+}
@@ -0,0 +1,705 @@
+//! # Guessing Game
+//!
+//! The Chapter 2 guessing game as a reusable engine. Where the chapter's
+//! `main` reaches straight for `rand::thread_rng()`, `io::stdin()` and
+//! `println!`, [`GuessingGame`] takes all three as parameters, so a test can
+//! script a whole game with a seeded RNG, a byte string of guesses and a
+//! `Vec<u8>` to read the transcript back from.
+//!
+//! Guesses come from a [`Player`]: any `BufRead`, such as locked stdin, or a
+//! [`Bot`] running one of the **strategy** submodule's solvers.
+//!
+//! [`Difficulty`] presets add a time limit and a score, and the
+//! **leaderboard** submodule keeps the best scores in a local file. The
+//! **server** submodule hosts one shared game for many players over TCP.
+//!
+//! This module demonstrates:
+//! - Generic structs bounded by traits: [`Player`], `Write` and `Rng`
+//! - A blanket implementation, making every `BufRead` a [`Player`]
+//! - Separating game rules ([`Round`]) from input and output ([`GuessingGame`])
+//! - Seedable randomness with `StdRng::seed_from_u64` for reproducible tests
+//! - Injecting the clock, so time limits can be tested without waiting
+//! - A validated [`Guess`] newtype whose [`GuessError`] says why input was rejected
+
+pub mod leaderboard;
+pub mod server;
+pub mod strategy;
+
+use rand::Rng;
+use std::cmp::Ordering;
+use std::fmt;
+use std::io::{self, BufRead, Write};
+use std::num::IntErrorKind;
+use std::ops::RangeInclusive;
+use std::str::FromStr;
+use std::time::{Duration, Instant};
+
+/// How a game is set up
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct GameConfig {
+    /// Where the secret number is picked from, inclusive
+    pub range: RangeInclusive<u32>,
+    /// Guesses allowed before the game is lost; `None` for unlimited
+    pub max_attempts: Option<u32>,
+    /// Time allowed for the whole game; `None` for unlimited
+    pub time_limit: Option<Duration>,
+}
+
+impl Default for GameConfig {
+    /// The chapter's game: 1 to 100, as many guesses as it takes
+    fn default() -> GameConfig {
+        GameConfig {
+            range: 1..=100,
+            max_attempts: None,
+            time_limit: None,
+        }
+    }
+}
+
+impl GameConfig {
+    /// Check the settings make a playable game
+    pub fn validate(&self) -> Result<(), ConfigError> {
+        if self.range.is_empty() {
+            return Err(ConfigError::EmptyRange(self.range.clone()));
+        }
+        if self.max_attempts == Some(0) {
+            return Err(ConfigError::NoAttempts);
+        }
+        if self.time_limit == Some(Duration::ZERO) {
+            return Err(ConfigError::NoTime);
+        }
+        Ok(())
+    }
+}
+
+/// Settings that can't make a game
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ConfigError {
+    /// There's no number to pick
+    EmptyRange(RangeInclusive<u32>),
+    /// A limit of zero guesses
+    NoAttempts,
+    /// A time limit of zero
+    NoTime,
+}
+
+impl fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ConfigError::EmptyRange(range) => {
+                write!(f, "the range {}..={} is empty", range.start(), range.end())
+            }
+            ConfigError::NoAttempts => write!(f, "the attempt limit must be at least 1"),
+            ConfigError::NoTime => write!(f, "the time limit must be more than zero"),
+        }
+    }
+}
+
+impl std::error::Error for ConfigError {}
+
+/// Preset games, each harder and worth more than the last
+///
+/// # Example
+/// ```
+/// use rust_book_examples::guessing_game::Difficulty;
+///
+/// let hard: Difficulty = "hard".parse().unwrap();
+/// assert_eq!(hard.config().range, 1..=1000);
+/// assert!(hard.max_score() > Difficulty::Easy.max_score());
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Difficulty {
+    Easy,
+    Medium,
+    Hard,
+}
+
+impl Difficulty {
+    pub const ALL: [Difficulty; 3] = [Difficulty::Easy, Difficulty::Medium, Difficulty::Hard];
+
+    /// The range, attempt limit and time limit of the preset
+    ///
+    /// Every preset leaves enough guesses for a binary search to always win.
+    pub fn config(self) -> GameConfig {
+        let (high, attempts, seconds) = match self {
+            Difficulty::Easy => (50, 10, 120),
+            Difficulty::Medium => (100, 8, 60),
+            Difficulty::Hard => (1000, 10, 45),
+        };
+        GameConfig {
+            range: 1..=high,
+            max_attempts: Some(attempts),
+            time_limit: Some(Duration::from_secs(seconds)),
+        }
+    }
+
+    /// Points for winning with the first guess, instantly
+    pub fn max_score(self) -> u32 {
+        1000 * (self as u32 + 1)
+    }
+
+    /// Points for a finished game: nothing unless it was won, then the
+    /// maximum less 50 for each guess after the first and 5 per whole second,
+    /// but never below a tenth of the maximum
+    ///
+    /// # Example
+    /// ```
+    /// use rust_book_examples::guessing_game::{Difficulty, GameReport, Outcome};
+    /// use std::time::Duration;
+    ///
+    /// let report = GameReport { outcome: Outcome::Won, secret: 7, attempts: 3, elapsed: Duration::from_secs(10) };
+    /// assert_eq!(Difficulty::Easy.score(&report), 1000 - 2 * 50 - 10 * 5);
+    /// ```
+    pub fn score(self, report: &GameReport) -> u32 {
+        if report.outcome != Outcome::Won {
+            return 0;
+        }
+        let max = self.max_score();
+        let penalty = 50 * report.attempts.saturating_sub(1) + 5 * report.elapsed.as_secs() as u32;
+        max.saturating_sub(penalty).max(max / 10)
+    }
+}
+
+impl fmt::Display for Difficulty {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let name = match self {
+            Difficulty::Easy => "easy",
+            Difficulty::Medium => "medium",
+            Difficulty::Hard => "hard",
+        };
+        f.pad(name)
+    }
+}
+
+impl FromStr for Difficulty {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Difficulty, String> {
+        Difficulty::ALL
+            .into_iter()
+            .find(|difficulty| difficulty.to_string().eq_ignore_ascii_case(s))
+            .ok_or_else(|| format!("unknown difficulty '{}'", s))
+    }
+}
+
+/// The rules of one game: a secret number and a count of guesses
+///
+/// # Example
+/// ```
+/// use rust_book_examples::guessing_game::{GameConfig, Round};
+/// use std::cmp::Ordering;
+///
+/// let config = GameConfig { max_attempts: Some(2), ..GameConfig::default() };
+/// let mut round = Round::with_secret(&config, 42);
+/// assert_eq!(round.guess(50), Ordering::Greater);
+/// assert_eq!(round.attempts_left(), Some(1));
+/// assert_eq!(round.guess(42), Ordering::Equal);
+/// assert!(round.is_won());
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Round {
+    secret: u32,
+    attempts: u32,
+    max_attempts: Option<u32>,
+    won: bool,
+}
+
+impl Round {
+    /// Start a round with a secret picked from the configured range
+    pub fn new<G: Rng + ?Sized>(config: &GameConfig, rng: &mut G) -> Round {
+        Round::with_secret(config, rng.gen_range(config.range.clone()))
+    }
+
+    /// Start a round with a known secret
+    pub fn with_secret(config: &GameConfig, secret: u32) -> Round {
+        Round {
+            secret,
+            attempts: 0,
+            max_attempts: config.max_attempts,
+            won: false,
+        }
+    }
+
+    /// Count a guess and compare it with the secret, as `guess.cmp(&secret)`
+    pub fn guess(&mut self, guess: u32) -> Ordering {
+        self.attempts += 1;
+        let ordering = guess.cmp(&self.secret);
+        self.won |= ordering == Ordering::Equal;
+        ordering
+    }
+
+    pub fn secret(&self) -> u32 {
+        self.secret
+    }
+
+    /// Guesses made so far
+    pub fn attempts(&self) -> u32 {
+        self.attempts
+    }
+
+    /// Guesses still allowed; `None` when there's no limit
+    pub fn attempts_left(&self) -> Option<u32> {
+        self.max_attempts.map(|max| max.saturating_sub(self.attempts))
+    }
+
+    pub fn is_won(&self) -> bool {
+        self.won
+    }
+
+    /// Won, or out of guesses
+    pub fn is_over(&self) -> bool {
+        self.won || self.attempts_left() == Some(0)
+    }
+}
+
+/// A guess known to be inside the game's range
+///
+/// Chapter 9's `Guess::new` panics on a bad value; [`Guess::try_new`] says
+/// what was wrong with the input instead, so the game can tell the player.
+///
+/// # Example
+/// ```
+/// use rust_book_examples::guessing_game::{Guess, GuessError};
+///
+/// let range = 1..=100;
+/// assert_eq!(Guess::try_new(" 42\n", &range).unwrap().value(), 42);
+/// assert_eq!(Guess::try_new("", &range), Err(GuessError::Empty));
+/// assert!(matches!(Guess::try_new("forty", &range), Err(GuessError::NotANumber(_))));
+/// assert!(matches!(Guess::try_new("-3", &range), Err(GuessError::OutOfRange { .. })));
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Guess {
+    value: u32,
+}
+
+impl Guess {
+    /// Parse a guess as typed, surrounding whitespace and all
+    pub fn try_new(input: &str, range: &RangeInclusive<u32>) -> Result<Guess, GuessError> {
+        let input = input.trim();
+        if input.is_empty() {
+            return Err(GuessError::Empty);
+        }
+        let out_of_range = || GuessError::OutOfRange {
+            value: input.to_string(),
+            low: *range.start(),
+            high: *range.end(),
+        };
+        match input.parse::<i64>() {
+            Ok(value) => u32::try_from(value)
+                .ok()
+                .filter(|value| range.contains(value))
+                .map(|value| Guess { value })
+                .ok_or_else(out_of_range),
+            // Still a number, just an enormous one
+            Err(e) if matches!(e.kind(), IntErrorKind::PosOverflow | IntErrorKind::NegOverflow) => Err(out_of_range()),
+            Err(_) => Err(GuessError::NotANumber(input.to_string())),
+        }
+    }
+
+    /// A guess from a value the caller has already checked
+    ///
+    /// # Panics
+    ///
+    /// Panics if `value` is outside `range`, like the chapter's `Guess::new`.
+    pub fn new(value: u32, range: &RangeInclusive<u32>) -> Guess {
+        if !range.contains(&value) {
+            panic!(
+                "Guess value must be between {} and {}, got {}.",
+                range.start(),
+                range.end(),
+                value
+            );
+        }
+        Guess { value }
+    }
+
+    pub fn value(&self) -> u32 {
+        self.value
+    }
+}
+
+/// Why input wasn't a usable guess
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum GuessError {
+    /// Nothing but whitespace
+    Empty,
+    NotANumber(String),
+    /// A number, as typed, outside `low..=high`
+    OutOfRange { value: String, low: u32, high: u32 },
+}
+
+impl fmt::Display for GuessError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            GuessError::Empty => write!(f, "please type a number"),
+            GuessError::NotANumber(input) => write!(f, "'{}' is not a number", input),
+            GuessError::OutOfRange { value, low, high } => {
+                write!(f, "{} is out of range; guess between {} and {}", value, low, high)
+            }
+        }
+    }
+}
+
+impl std::error::Error for GuessError {}
+
+/// How a game ended
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Outcome {
+    Won,
+    /// The attempt limit was reached
+    OutOfAttempts,
+    /// The time limit passed; noticed when the next guess arrives
+    OutOfTime,
+    /// The input ran out before the game was over
+    Quit,
+}
+
+/// The result of [`GuessingGame::play`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct GameReport {
+    pub outcome: Outcome,
+    pub secret: u32,
+    pub attempts: u32,
+    /// From the first prompt to the end of the game
+    pub elapsed: Duration,
+}
+
+/// Where a game's guesses come from
+///
+/// Every `BufRead` is a player that types one guess per line; [`Bot`] turns
+/// a [`Strategy`](strategy::Strategy) into one.
+pub trait Player {
+    /// Called when a game starts, with its settings
+    fn start(&mut self, _config: &GameConfig) {}
+
+    /// The next guess as entered, or `None` once the player has stopped
+    fn next_guess(&mut self) -> io::Result<Option<String>>;
+
+    /// How a guess compared with the secret, as `guess.cmp(&secret)`
+    fn feedback(&mut self, _guess: u32, _ordering: Ordering) {}
+}
+
+impl<R: BufRead> Player for R {
+    fn next_guess(&mut self) -> io::Result<Option<String>> {
+        let mut guess = String::new();
+        match self.read_line(&mut guess)? {
+            0 => Ok(None),
+            _ => Ok(Some(guess)),
+        }
+    }
+}
+
+/// A [`Strategy`](strategy::Strategy) playing through the [`Player`] interface
+pub struct Bot<S>(pub S);
+
+impl<S: strategy::Strategy> Player for Bot<S> {
+    fn start(&mut self, config: &GameConfig) {
+        self.0.start(config.range.clone());
+    }
+
+    fn next_guess(&mut self) -> io::Result<Option<String>> {
+        Ok(Some(self.0.guess().to_string()))
+    }
+
+    fn feedback(&mut self, guess: u32, ordering: Ordering) {
+        self.0.feedback(guess, ordering);
+    }
+}
+
+/// The interactive game, generic over where guesses come from, where
+/// messages go and how the secret is picked
+///
+/// # Example
+/// ```
+/// use rand::rngs::StdRng;
+/// use rand::SeedableRng;
+/// use rust_book_examples::guessing_game::{GameConfig, GuessingGame, Outcome};
+///
+/// // With only one number in range, the first valid guess has to be right;
+/// // 3 is rejected and doesn't count as an attempt
+/// let config = GameConfig { range: 7..=7, ..GameConfig::default() };
+/// let mut output = Vec::new();
+/// let mut game = GuessingGame::new(config, "3\n7\n".as_bytes(), &mut output, StdRng::seed_from_u64(1)).unwrap();
+/// let report = game.play().unwrap();
+/// assert_eq!((report.outcome, report.attempts), (Outcome::Won, 1));
+/// let transcript = String::from_utf8(output).unwrap();
+/// assert!(transcript.contains("Invalid guess: 3 is out of range; guess between 7 and 7.\n"));
+/// assert!(transcript.ends_with("You win!\n"));
+/// ```
+pub struct GuessingGame<P, W, G> {
+    config: GameConfig,
+    player: P,
+    output: W,
+    rng: G,
+    clock: Box<dyn FnMut() -> Instant>,
+}
+
+impl<P: Player, W: Write, G: Rng> GuessingGame<P, W, G> {
+    pub fn new(config: GameConfig, player: P, output: W, rng: G) -> Result<GuessingGame<P, W, G>, ConfigError> {
+        config.validate()?;
+        Ok(GuessingGame {
+            config,
+            player,
+            output,
+            rng,
+            clock: Box::new(Instant::now),
+        })
+    }
+
+    /// Read the time from `clock` instead of `Instant::now`
+    pub fn with_clock(mut self, clock: impl FnMut() -> Instant + 'static) -> GuessingGame<P, W, G> {
+        self.clock = Box::new(clock);
+        self
+    }
+
+    pub fn config(&self) -> &GameConfig {
+        &self.config
+    }
+
+    /// Play one game to the end, with a fresh secret
+    pub fn play(&mut self) -> io::Result<GameReport> {
+        let mut round = Round::new(&self.config, &mut self.rng);
+        let (low, high) = (*self.config.range.start(), *self.config.range.end());
+        writeln!(self.output, "Guess the number between {} and {}!", low, high)?;
+        if let Some(attempts) = self.config.max_attempts {
+            writeln!(self.output, "You have {} attempt(s).", attempts)?;
+        }
+        if let Some(limit) = self.config.time_limit {
+            writeln!(self.output, "You have {} second(s).", limit.as_secs_f64())?;
+        }
+        self.player.start(&self.config);
+        let start = (self.clock)();
+
+        let outcome = loop {
+            if round.is_over() {
+                break if round.is_won() { Outcome::Won } else { Outcome::OutOfAttempts };
+            }
+            writeln!(self.output, "Please input your guess.")?;
+
+            let Some(guess) = self.player.next_guess()? else {
+                break Outcome::Quit;
+            };
+            // A blocked read can't be interrupted, so a late guess simply doesn't count
+            if self.config.time_limit.is_some_and(|limit| (self.clock)() - start > limit) {
+                break Outcome::OutOfTime;
+            }
+            // Rejected input doesn't count as an attempt, but the player hears why
+            let guess = match Guess::try_new(&guess, &self.config.range) {
+                Ok(guess) => guess.value(),
+                Err(e) => {
+                    writeln!(self.output, "Invalid guess: {}.", e)?;
+                    continue;
+                }
+            };
+
+            writeln!(self.output, "You guessed: {}", guess)?;
+            let ordering = round.guess(guess);
+            self.player.feedback(guess, ordering);
+            match ordering {
+                Ordering::Less => writeln!(self.output, "Too small!")?,
+                Ordering::Greater => writeln!(self.output, "Too big!")?,
+                Ordering::Equal => {
+                    writeln!(self.output, "You win!")?;
+                    continue;
+                }
+            }
+            if let Some(left) = round.attempts_left().filter(|&left| left > 0) {
+                writeln!(self.output, "{} attempt(s) left.", left)?;
+            }
+        };
+
+        match outcome {
+            Outcome::OutOfAttempts => {
+                writeln!(self.output, "Out of attempts! The secret number was {}.", round.secret())?
+            }
+            Outcome::OutOfTime => writeln!(self.output, "Time's up! The secret number was {}.", round.secret())?,
+            Outcome::Quit => writeln!(self.output, "Giving up? The secret number was {}.", round.secret())?,
+            Outcome::Won => {}
+        }
+        self.output.flush()?;
+
+        Ok(GameReport {
+            outcome,
+            secret: round.secret(),
+            attempts: round.attempts(),
+            elapsed: (self.clock)() - start,
+        })
+    }
+
+    /// Take back the output, to read what a scripted game printed
+    pub fn into_output(self) -> W {
+        self.output
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::rngs::StdRng;
+    use rand::SeedableRng;
+
+    /// The secret a game seeded with `seed` will pick
+    fn secret_for(seed: u64, config: &GameConfig) -> u32 {
+        Round::new(config, &mut StdRng::seed_from_u64(seed)).secret()
+    }
+
+    fn play(config: GameConfig, seed: u64, input: &str) -> (GameReport, String) {
+        let mut game = GuessingGame::new(config, input.as_bytes(), Vec::new(), StdRng::seed_from_u64(seed)).unwrap();
+        let report = game.play().unwrap();
+        (report, String::from_utf8(game.into_output()).unwrap())
+    }
+
+    #[test]
+    fn scripted_win() {
+        let config = GameConfig {
+            range: 2..=99,
+            ..GameConfig::default()
+        };
+        let secret = secret_for(42, &config);
+        let input = format!("{}\nabc\n\n100\n{}\n{}\n", secret + 1, secret - 1, secret);
+
+        let (report, transcript) = play(config, 42, &input);
+        assert_eq!(report.outcome, Outcome::Won);
+        assert_eq!(report.secret, secret);
+        // Rejected input doesn't count
+        assert_eq!(report.attempts, 3);
+        assert!(transcript.starts_with("Guess the number between 2 and 99!\n"));
+        assert!(transcript.contains("Too big!\nPlease input your guess.\nInvalid guess: 'abc' is not a number.\n"));
+        assert!(transcript.contains("Invalid guess: please type a number.\n"));
+        assert!(transcript.contains("Invalid guess: 100 is out of range; guess between 2 and 99.\n"));
+        assert!(transcript.contains("Too small!\n"));
+        assert!(transcript.ends_with(&format!("You guessed: {}\nYou win!\n", secret)));
+    }
+
+    #[test]
+    fn guesses_are_validated_against_the_range() {
+        let range = 10..=20;
+        assert_eq!(Guess::try_new("\t15 ", &range).map(|g| g.value()), Ok(15));
+        assert_eq!(Guess::try_new(" \n", &range), Err(GuessError::Empty));
+        assert_eq!(Guess::try_new("1.5", &range), Err(GuessError::NotANumber("1.5".to_string())));
+        for input in ["9", "21", "-15", "99999999999999999999"] {
+            let error = Guess::try_new(input, &range).unwrap_err();
+            assert_eq!(error.to_string(), format!("{} is out of range; guess between 10 and 20", input));
+        }
+        assert_eq!(Guess::new(20, &range).value(), 20);
+    }
+
+    #[test]
+    #[should_panic(expected = "Guess value must be between 10 and 20, got 9.")]
+    fn new_panics_outside_the_range() {
+        Guess::new(9, &(10..=20));
+    }
+
+    #[test]
+    fn same_seed_same_game() {
+        let config = GameConfig {
+            range: 1..=1_000_000,
+            ..GameConfig::default()
+        };
+        assert_eq!(secret_for(7, &config), secret_for(7, &config));
+        assert_ne!(secret_for(7, &config), secret_for(8, &config));
+        assert!((1..=1_000_000).contains(&secret_for(9, &config)));
+    }
+
+    #[test]
+    fn attempt_limit_ends_the_game() {
+        let config = GameConfig {
+            range: 10..=20,
+            max_attempts: Some(2),
+            ..GameConfig::default()
+        };
+        let secret = secret_for(3, &config);
+        let wrong = if secret == 10 { 11 } else { 10 };
+        let input = format!("{}\n{}\n{}\n", wrong, wrong, secret);
+
+        let (report, transcript) = play(config, 3, &input);
+        assert_eq!((report.outcome, report.attempts), (Outcome::OutOfAttempts, 2));
+        assert!(transcript.contains("1 attempt(s) left.\n"));
+        assert!(transcript.ends_with(&format!("Out of attempts! The secret number was {}.\n", secret)));
+    }
+
+    #[test]
+    fn time_limit_ends_the_game() {
+        use std::cell::Cell;
+        use std::rc::Rc;
+
+        // Each reading of the clock is 20 seconds after the last
+        let now = Rc::new(Cell::new(Instant::now()));
+        let clock = {
+            let now = Rc::clone(&now);
+            move || {
+                now.set(now.get() + Duration::from_secs(20));
+                now.get()
+            }
+        };
+        let config = Difficulty::Hard.config();
+        let wrong = if secret_for(5, &config) == 1 { 2 } else { 1 };
+        let input = format!("{0}\n{0}\n{0}\n", wrong);
+        let game = GuessingGame::new(config, input.as_bytes(), Vec::new(), StdRng::seed_from_u64(5)).unwrap();
+        let mut game = game.with_clock(clock);
+
+        let report = game.play().unwrap();
+        // The first two guesses arrive 20s and 40s in; the third, 60s in, is past the 45s limit
+        assert_eq!((report.outcome, report.attempts), (Outcome::OutOfTime, 2));
+        assert_eq!(report.elapsed, Duration::from_secs(80));
+        assert_eq!(Difficulty::Hard.score(&report), 0);
+        let transcript = String::from_utf8(game.into_output()).unwrap();
+        assert!(transcript.contains("You have 10 attempt(s).\nYou have 45 second(s).\n"));
+        assert!(transcript.ends_with(&format!("Time's up! The secret number was {}.\n", report.secret)));
+    }
+
+    #[test]
+    fn scores_and_presets() {
+        let won = |attempts, secs| GameReport {
+            outcome: Outcome::Won,
+            secret: 1,
+            attempts,
+            elapsed: Duration::from_secs(secs),
+        };
+        assert_eq!(Difficulty::Medium.score(&won(1, 0)), 2000);
+        assert!(Difficulty::Medium.score(&won(2, 0)) > Difficulty::Medium.score(&won(3, 0)));
+        assert!(Difficulty::Medium.score(&won(2, 1)) > Difficulty::Medium.score(&won(2, 9)));
+        assert_eq!(Difficulty::Easy.score(&won(100, 1000)), 100);
+        let lost = GameReport { outcome: Outcome::OutOfAttempts, ..won(1, 0) };
+        assert_eq!(Difficulty::Hard.score(&lost), 0);
+
+        for difficulty in Difficulty::ALL {
+            let config = difficulty.config();
+            assert_eq!(config.validate(), Ok(()));
+            // Enough guesses for a binary search to always win
+            let size = config.range.end() - config.range.start() + 1;
+            assert!(2u32.pow(config.max_attempts.unwrap()) > size);
+            assert_eq!(difficulty.to_string().parse(), Ok(difficulty));
+        }
+        assert!("impossible".parse::<Difficulty>().is_err());
+    }
+
+    #[test]
+    fn running_out_of_input_quits() {
+        let secret = secret_for(1, &GameConfig::default());
+        let (wrong, hint) = if secret == 100 { (1, "Too small!") } else { (100, "Too big!") };
+        let (report, transcript) = play(GameConfig::default(), 1, &format!("{}\n", wrong));
+        assert_eq!((report.outcome, report.attempts), (Outcome::Quit, 1));
+        assert!(transcript.contains(&format!("You guessed: {}\n{}\n", wrong, hint)));
+        assert!(transcript.ends_with(&format!("Giving up? The secret number was {}.\n", report.secret)));
+
+        let (report, _) = play(GameConfig::default(), 1, "");
+        assert_eq!((report.outcome, report.attempts), (Outcome::Quit, 0));
+    }
+
+    #[test]
+    fn bad_configs_are_rejected() {
+        let new = |config| GuessingGame::new(config, io::empty(), io::sink(), StdRng::seed_from_u64(0)).err();
+        let (low, high) = (5, 4);
+        let empty = GameConfig {
+            range: low..=high,
+            ..GameConfig::default()
+        };
+        assert_eq!(new(empty), Some(ConfigError::EmptyRange(low..=high)));
+        let none = GameConfig {
+            max_attempts: Some(0),
+            ..GameConfig::default()
+        };
+        assert_eq!(new(none), Some(ConfigError::NoAttempts));
+    }
+}
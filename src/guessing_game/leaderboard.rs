@@ -0,0 +1,357 @@
+//! # Leaderboard
+//!
+//! The best scores for each [`Difficulty`], kept in a local text file.
+//!
+//! A damaged file never stops a game: lines that can't be read are skipped
+//! and reported through [`Leaderboard::warnings`], and the next save keeps a
+//! copy of the damaged file next to it (with a `.corrupt` suffix) before
+//! writing the good entries back.
+//!
+//! ## File format
+//!
+//! A `guessing-game-leaderboard 1` header, then one tab-separated line per
+//! entry: `DIFFICULTY SCORE ATTEMPTS MILLISECONDS NAME`.
+
+use crate::fs_util;
+use super::{Difficulty, GameReport};
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+const HEADER: &str = "guessing-game-leaderboard 1";
+
+/// Entries kept per difficulty
+pub const KEEP: usize = 10;
+
+/// Suffix of the copy kept of a damaged leaderboard file
+pub const CORRUPT_SUFFIX: &str = ".corrupt";
+
+/// A problem found while loading a leaderboard
+#[derive(Debug)]
+pub enum LeaderboardError {
+    Io(io::Error),
+    /// A line, or with line 1 the whole file, couldn't be read
+    Corrupt { line: usize, message: String },
+}
+
+impl fmt::Display for LeaderboardError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            LeaderboardError::Io(e) => write!(f, "{}", e),
+            LeaderboardError::Corrupt { line, message } => {
+                write!(f, "corrupt leaderboard at line {}: {}", line, message)
+            }
+        }
+    }
+}
+
+impl std::error::Error for LeaderboardError {}
+
+impl From<io::Error> for LeaderboardError {
+    fn from(e: io::Error) -> LeaderboardError {
+        LeaderboardError::Io(e)
+    }
+}
+
+/// One finished, won game
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Entry {
+    pub name: String,
+    pub difficulty: Difficulty,
+    pub score: u32,
+    pub attempts: u32,
+    pub elapsed: Duration,
+}
+
+impl Entry {
+    /// The entry for `name`'s game, scored by `difficulty`
+    pub fn new(name: &str, difficulty: Difficulty, report: &GameReport) -> Entry {
+        Entry {
+            // Tabs and newlines would break the file format
+            name: name.replace(['\t', '\n', '\r'], " ").trim().to_string(),
+            difficulty,
+            score: difficulty.score(report),
+            attempts: report.attempts,
+            elapsed: report.elapsed,
+        }
+    }
+
+    /// Higher scores first, then fewer attempts, then faster
+    fn ranks_above(&self, other: &Entry) -> bool {
+        (other.score, self.attempts, self.elapsed) < (self.score, other.attempts, other.elapsed)
+    }
+}
+
+/// The top scores per difficulty, backed by a file
+///
+/// # Example
+/// ```
+/// use rust_book_examples::guessing_game::leaderboard::{Entry, Leaderboard};
+/// use rust_book_examples::guessing_game::{Difficulty, GameReport, Outcome};
+/// use std::time::Duration;
+///
+/// let path = std::env::temp_dir().join(format!("leaderboard_doc_{}.txt", std::process::id()));
+/// let mut board = Leaderboard::open(&path);
+/// let report = GameReport { outcome: Outcome::Won, secret: 42, attempts: 4, elapsed: Duration::from_secs(9) };
+/// assert_eq!(board.record(Entry::new("ferris", Difficulty::Medium, &report)), Some(1));
+/// board.save().unwrap();
+///
+/// let board = Leaderboard::open(&path);
+/// assert_eq!(board.top(Difficulty::Medium)[0].name, "ferris");
+/// std::fs::remove_file(&path).unwrap();
+/// ```
+#[derive(Debug)]
+pub struct Leaderboard {
+    path: PathBuf,
+    /// Sorted best first within each difficulty
+    entries: Vec<Entry>,
+    warnings: Vec<LeaderboardError>,
+}
+
+impl Leaderboard {
+    /// Load the leaderboard in `path`, starting empty if there isn't one
+    ///
+    /// Never fails: whatever couldn't be read is left out and reported by
+    /// [`Leaderboard::warnings`].
+    pub fn open(path: impl AsRef<Path>) -> Leaderboard {
+        let mut board = Leaderboard {
+            path: path.as_ref().to_path_buf(),
+            entries: Vec::new(),
+            warnings: Vec::new(),
+        };
+        let text = match fs::read(&board.path) {
+            Ok(bytes) => bytes,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return board,
+            Err(e) => {
+                board.warnings.push(e.into());
+                return board;
+            }
+        };
+        let text = String::from_utf8_lossy(&text);
+
+        let mut lines = text.lines().enumerate().map(|(i, line)| (i + 1, line));
+        if lines.next().map(|(_, header)| header) != Some(HEADER) {
+            board.warnings.push(LeaderboardError::Corrupt {
+                line: 1,
+                message: format!("expected '{}'", HEADER),
+            });
+            return board;
+        }
+        for (number, line) in lines.filter(|(_, line)| !line.is_empty()) {
+            match parse_entry(line) {
+                Ok(entry) => board.entries.push(entry),
+                Err(message) => board.warnings.push(LeaderboardError::Corrupt { line: number, message }),
+            }
+        }
+        board.sort();
+        board
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// What went wrong loading the file, if anything
+    pub fn warnings(&self) -> &[LeaderboardError] {
+        &self.warnings
+    }
+
+    /// Add an entry, returning its place among its difficulty's top
+    /// [`KEEP`], or `None` if it didn't make the cut
+    pub fn record(&mut self, entry: Entry) -> Option<usize> {
+        let difficulty = entry.difficulty;
+        let place = self.top(difficulty).iter().take_while(|other| !entry.ranks_above(other)).count();
+        if place >= KEEP {
+            return None;
+        }
+        self.entries.push(entry);
+        self.sort();
+        Some(place + 1)
+    }
+
+    /// The best entries for `difficulty`, best first
+    pub fn top(&self, difficulty: Difficulty) -> Vec<&Entry> {
+        self.entries.iter().filter(|entry| entry.difficulty == difficulty).collect()
+    }
+
+    /// Write the leaderboard back to its file
+    ///
+    /// If loading found damage, the old file is first copied aside with
+    /// [`CORRUPT_SUFFIX`], so nothing is lost without a trace.
+    pub fn save(&mut self) -> io::Result<()> {
+        if !self.warnings.is_empty() && self.path.exists() {
+            let mut backup = self.path.clone().into_os_string();
+            backup.push(CORRUPT_SUFFIX);
+            fs::copy(&self.path, backup)?;
+        }
+
+        let mut text = format!("{}\n", HEADER);
+        for entry in &self.entries {
+            text.push_str(&format!(
+                "{}\t{}\t{}\t{}\t{}\n",
+                entry.difficulty,
+                entry.score,
+                entry.attempts,
+                entry.elapsed.as_millis(),
+                entry.name
+            ));
+        }
+        fs_util::write_atomically(&self.path, text.as_bytes(), None)?;
+        // The damaged file is backed up and replaced; don't back up the good one next time
+        self.warnings.clear();
+        Ok(())
+    }
+
+    /// A table of the top `n` entries for each difficulty that has any
+    pub fn render(&self, n: usize) -> String {
+        let mut text = String::new();
+        for difficulty in Difficulty::ALL {
+            let top = self.top(difficulty);
+            if top.is_empty() {
+                continue;
+            }
+            let width = top.iter().take(n).map(|entry| entry.name.chars().count()).max().unwrap_or(0).max(4);
+            text.push_str(&format!("{}\n", difficulty));
+            text.push_str(&format!("  {:>2}  {:width$}  {:>5}  {:>7}  {:>7}\n", "#", "Name", "Score", "Guesses", "Time"));
+            for (place, entry) in top.iter().take(n).enumerate() {
+                text.push_str(&format!(
+                    "  {:>2}  {:width$}  {:>5}  {:>7}  {:>6.1}s\n",
+                    place + 1,
+                    entry.name,
+                    entry.score,
+                    entry.attempts,
+                    entry.elapsed.as_secs_f64()
+                ));
+            }
+        }
+        text
+    }
+
+    /// Best first within each difficulty, keeping only the top [`KEEP`]
+    fn sort(&mut self) {
+        // A stable sort keeps earlier entries ahead of later ties
+        self.entries.sort_by(|a, b| {
+            a.difficulty
+                .cmp(&b.difficulty)
+                .then(b.score.cmp(&a.score))
+                .then(a.attempts.cmp(&b.attempts))
+                .then(a.elapsed.cmp(&b.elapsed))
+        });
+        let mut kept = [0; Difficulty::ALL.len()];
+        self.entries.retain(|entry| {
+            kept[entry.difficulty as usize] += 1;
+            kept[entry.difficulty as usize] <= KEEP
+        });
+    }
+}
+
+fn parse_entry(line: &str) -> Result<Entry, String> {
+    let fields: Vec<&str> = line.splitn(5, '\t').collect();
+    let [difficulty, score, attempts, millis, name] = fields[..] else {
+        return Err(format!("expected 5 tab-separated fields, found {}", fields.len()));
+    };
+    let number = |field: &str, what: &str| field.parse::<u64>().map_err(|_| format!("invalid {} '{}'", what, field));
+
+    let difficulty: Difficulty = difficulty.parse()?;
+    let score = number(score, "score")?;
+    if score > u64::from(difficulty.max_score()) {
+        return Err(format!("score {} is more than {} allows", score, difficulty));
+    }
+    Ok(Entry {
+        name: name.to_string(),
+        difficulty,
+        score: score as u32,
+        attempts: u32::try_from(number(attempts, "attempts")?).map_err(|e| e.to_string())?,
+        elapsed: Duration::from_millis(number(millis, "time")?),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fs_util::TempPath;
+    use crate::guessing_game::Outcome;
+
+    fn temp_path(name: &str) -> TempPath {
+        TempPath::new(&format!("leaderboard_{}.txt", name))
+    }
+
+    fn won(attempts: u32, secs: u64) -> GameReport {
+        GameReport {
+            outcome: Outcome::Won,
+            secret: 1,
+            attempts,
+            elapsed: Duration::from_secs(secs),
+        }
+    }
+
+    #[test]
+    fn ranks_per_difficulty_and_keeps_the_top() {
+        let mut board = Leaderboard::open(temp_path("unsaved"));
+        assert_eq!(board.record(Entry::new("slow", Difficulty::Easy, &won(5, 30))), Some(1));
+        assert_eq!(board.record(Entry::new("fast", Difficulty::Easy, &won(2, 3))), Some(1));
+        assert_eq!(board.record(Entry::new("hard", Difficulty::Hard, &won(9, 40))), Some(1));
+        // A tie goes to whoever got there first
+        assert_eq!(board.record(Entry::new("again", Difficulty::Easy, &won(2, 3))), Some(2));
+
+        let names: Vec<&str> = board.top(Difficulty::Easy).iter().map(|e| e.name.as_str()).collect();
+        assert_eq!(names, vec!["fast", "again", "slow"]);
+
+        for _ in 0..KEEP {
+            board.record(Entry::new("pro", Difficulty::Easy, &won(1, 0)));
+        }
+        assert_eq!(board.top(Difficulty::Easy).len(), KEEP);
+        assert_eq!(board.record(Entry::new("late", Difficulty::Easy, &won(1, 0))), None);
+        assert_eq!(board.top(Difficulty::Hard).len(), 1);
+
+        let table = board.render(3);
+        assert!(table.starts_with("easy\n"));
+        assert!(table.contains("hard\n") && !table.contains("medium"));
+        assert!(table.contains("   1  pro    1000        1     0.0s\n"));
+    }
+
+    #[test]
+    fn saves_and_loads() {
+        let path = temp_path("round_trip");
+        let mut board = Leaderboard::open(&path);
+        board.record(Entry::new("tab\tby", Difficulty::Medium, &won(3, 12)));
+        board.save().unwrap();
+
+        let loaded = Leaderboard::open(&path);
+        assert!(loaded.warnings().is_empty());
+        assert_eq!(loaded.top(Difficulty::Medium), board.top(Difficulty::Medium));
+        assert_eq!(loaded.top(Difficulty::Medium)[0].name, "tab by");
+    }
+
+    #[test]
+    fn damaged_files_are_survived() {
+        let path = temp_path("damaged");
+        let backup = TempPath::new(&format!("leaderboard_damaged.txt{}", CORRUPT_SUFFIX));
+        fs::write(
+            &path,
+            format!("{}\neasy\t900\t3\t1000\tok\nhard\tlots\t1\t0\tcheat\nimpossible\nmedium\t1\t1\t1\n", HEADER),
+        )
+        .unwrap();
+
+        let mut board = Leaderboard::open(&path);
+        let lines: Vec<String> = board.warnings().iter().map(|w| w.to_string()).collect();
+        assert_eq!(lines.len(), 3);
+        assert!(lines[0].starts_with("corrupt leaderboard at line 3: invalid score 'lots'"));
+        assert_eq!(board.top(Difficulty::Easy)[0].name, "ok");
+
+        // Saving keeps a copy of the damage and writes back what was good
+        board.save().unwrap();
+        assert!(fs::read_to_string(&backup).unwrap().contains("cheat"));
+        let board = Leaderboard::open(&path);
+        assert!(board.warnings().is_empty());
+        assert_eq!(board.top(Difficulty::Easy).len(), 1);
+
+        // Garbage, even binary, just means starting over
+        fs::write(&path, [0xff, 0x00, b'\n']).unwrap();
+        let board = Leaderboard::open(&path);
+        assert!(matches!(board.warnings(), [LeaderboardError::Corrupt { line: 1, .. }]));
+        assert!(board.top(Difficulty::Easy).is_empty());
+    }
+}
@@ -0,0 +1,538 @@
+//! # Multiplayer Server
+//!
+//! The guessing game hosted over TCP for several players at once, in the
+//! style of the Chapter 20 web server: a listener hands each connection to
+//! the shared [`ThreadPool`], and every connection keeps two workers busy
+//! while its player is connected, one reading their lines and one writing
+//! them everyone else's. A slow player only holds up their own messages.
+//!
+//! All players race to guess one shared secret. Every guess is broadcast to
+//! everyone, and when someone wins a new round starts with a fresh secret.
+//! There's no attempt or time limit; the range comes from the [`GameConfig`].
+//!
+//! ## Protocol
+//!
+//! One message per line, so `nc 127.0.0.1 7878` is a client. Players send:
+//!
+//! - a number, to guess it
+//! - `NAME <name>`, to change their name
+//! - `QUIT`, to leave
+//!
+//! and receive:
+//!
+//! - `WELCOME <name> <round> <low> <high>` on connecting
+//! - `JOIN <name>`, `LEAVE <name>` and `NAME <old> <new>` as players come and go
+//! - `GUESS <name> <number> SMALL|BIG` for every wrong guess
+//! - `WIN <name> <secret> <attempts>`, then `ROUND <round> <low> <high>`
+//! - `ERROR <message>` for input the server couldn't use; only the sender sees it
+//!
+//! A line longer than [`MAX_LINE`] bytes is answered with an `ERROR` and
+//! otherwise ignored, so a client can't make the server buffer without end.
+
+use super::{GameConfig, Guess, Round};
+use crate::thread_pool::ThreadPool;
+use rand::rngs::StdRng;
+use rand::SeedableRng;
+use std::cmp::Ordering;
+use std::collections::BTreeMap;
+use std::fmt;
+use std::io::{self, BufRead, BufReader, Read, Write};
+use std::net::{Shutdown, SocketAddr, TcpListener, TcpStream, ToSocketAddrs};
+use std::sync::atomic::{AtomicBool, Ordering as AtomicOrdering};
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::thread;
+use std::time::Duration;
+
+/// How long a write to a client may stall before they're disconnected
+const WRITE_TIMEOUT: Duration = Duration::from_secs(2);
+
+/// How often a quiet connection checks whether the server is stopping
+const READ_TIMEOUT: Duration = Duration::from_millis(250);
+
+/// The longest line a player may send, counting its newline
+pub const MAX_LINE: usize = 1024;
+
+/// A line sent to clients
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Message {
+    Welcome { name: String, round: u32, low: u32, high: u32 },
+    Join(String),
+    Leave(String),
+    Rename { old: String, new: String },
+    /// A wrong guess: `Less` if it was too small, `Greater` if too big
+    Guess { name: String, guess: u32, ordering: Ordering },
+    Win { name: String, secret: u32, attempts: u32 },
+    Round { round: u32, low: u32, high: u32 },
+    Error(String),
+}
+
+impl fmt::Display for Message {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Message::Welcome { name, round, low, high } => write!(f, "WELCOME {} {} {} {}", name, round, low, high),
+            Message::Join(name) => write!(f, "JOIN {}", name),
+            Message::Leave(name) => write!(f, "LEAVE {}", name),
+            Message::Rename { old, new } => write!(f, "NAME {} {}", old, new),
+            Message::Guess { name, guess, ordering } => {
+                let hint = if *ordering == Ordering::Less { "SMALL" } else { "BIG" };
+                write!(f, "GUESS {} {} {}", name, guess, hint)
+            }
+            Message::Win { name, secret, attempts } => write!(f, "WIN {} {} {}", name, secret, attempts),
+            Message::Round { round, low, high } => write!(f, "ROUND {} {} {}", round, low, high),
+            Message::Error(message) => write!(f, "ERROR {}", message),
+        }
+    }
+}
+
+/// A connected player
+struct Client {
+    name: String,
+    /// A clone of the connection, for shutting it down
+    stream: TcpStream,
+    /// Lines waiting for the connection's writer
+    outbox: Sender<String>,
+    /// Guesses made this round
+    attempts: u32,
+}
+
+/// Everything the connections share
+struct Lobby {
+    config: GameConfig,
+    rng: StdRng,
+    round: Round,
+    round_number: u32,
+    clients: BTreeMap<u64, Client>,
+    next_id: u64,
+}
+
+impl Lobby {
+    fn bounds(&self) -> (u32, u32) {
+        (*self.config.range.start(), *self.config.range.end())
+    }
+
+    /// Queue `message` for one client
+    ///
+    /// This never blocks on the network, so the lobby isn't held up by a
+    /// slow client; their writer sends the lines in order.
+    fn send(&mut self, id: u64, message: &Message) {
+        let Some(client) = self.clients.get(&id) else { return };
+        // A writer that's gone has already shut the connection down
+        let _ = client.outbox.send(format!("{}\n", message));
+    }
+
+    fn broadcast(&mut self, message: &Message) {
+        let ids: Vec<u64> = self.clients.keys().copied().collect();
+        for id in ids {
+            self.send(id, message);
+        }
+    }
+
+    /// Add a player, returning their ID and the lines to write to them
+    fn join(&mut self, stream: TcpStream) -> (u64, Receiver<String>) {
+        let id = self.next_id;
+        self.next_id += 1;
+        let name = format!("player{}", id);
+        self.broadcast(&Message::Join(name.clone()));
+
+        let (outbox, lines) = mpsc::channel();
+        self.clients.insert(
+            id,
+            Client {
+                name: name.clone(),
+                stream,
+                outbox,
+                attempts: 0,
+            },
+        );
+        let (low, high) = self.bounds();
+        let round = self.round_number;
+        self.send(id, &Message::Welcome { name, round, low, high });
+        (id, lines)
+    }
+
+    fn leave(&mut self, id: u64) {
+        if let Some(client) = self.clients.remove(&id) {
+            self.broadcast(&Message::Leave(client.name));
+        }
+    }
+
+    fn rename(&mut self, id: u64, new: &str) {
+        let valid = !new.is_empty() && !new.contains(char::is_whitespace);
+        if !valid {
+            return self.send(id, &Message::Error("a name is one word".to_string()));
+        }
+        if self.clients.iter().any(|(&other, client)| other != id && client.name == new) {
+            return self.send(id, &Message::Error(format!("{} is taken", new)));
+        }
+        let client = self.clients.get_mut(&id).expect("renaming a connected client");
+        let old = std::mem::replace(&mut client.name, new.to_string());
+        self.broadcast(&Message::Rename { old, new: new.to_string() });
+    }
+
+    fn guess(&mut self, id: u64, input: &str) {
+        let guess = match Guess::try_new(input, &self.config.range) {
+            Ok(guess) => guess.value(),
+            Err(e) => return self.send(id, &Message::Error(e.to_string())),
+        };
+        let client = self.clients.get_mut(&id).expect("guessing from a connected client");
+        client.attempts += 1;
+        let (name, attempts) = (client.name.clone(), client.attempts);
+
+        match self.round.guess(guess) {
+            Ordering::Equal => {
+                let secret = self.round.secret();
+                self.broadcast(&Message::Win { name, secret, attempts });
+                self.new_round();
+            }
+            ordering => self.broadcast(&Message::Guess { name, guess, ordering }),
+        }
+    }
+
+    fn new_round(&mut self) {
+        self.round = Round::new(&self.config, &mut self.rng);
+        self.round_number += 1;
+        for client in self.clients.values_mut() {
+            client.attempts = 0;
+        }
+        let (low, high) = self.bounds();
+        let round = self.round_number;
+        self.broadcast(&Message::Round { round, low, high });
+    }
+}
+
+/// A running game server; dropping it shuts the server down
+///
+/// # Example
+/// ```no_run
+/// use rust_book_examples::guessing_game::server::GameServer;
+/// use rust_book_examples::guessing_game::GameConfig;
+///
+/// let server = GameServer::start("127.0.0.1:7878", GameConfig::default(), 8, 42).unwrap();
+/// println!("Connect with: nc {}", server.local_addr());
+/// server.wait();
+/// ```
+pub struct GameServer {
+    addr: SocketAddr,
+    lobby: Arc<Mutex<Lobby>>,
+    stopping: Arc<AtomicBool>,
+    acceptor: Option<thread::JoinHandle<()>>,
+}
+
+impl GameServer {
+    /// Listen on `addr` for up to `max_players` players at once, picking
+    /// secrets with an RNG seeded with `seed`
+    ///
+    /// Bind to port 0 to let the system choose a free port, then ask
+    /// [`GameServer::local_addr`] which one it was. Players beyond
+    /// `max_players` are turned away with an `ERROR`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `max_players` is zero, like [`ThreadPool::new`].
+    pub fn start(addr: impl ToSocketAddrs, config: GameConfig, max_players: usize, seed: u64) -> io::Result<GameServer> {
+        config
+            .validate()
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidInput, e))?;
+        let listener = TcpListener::bind(addr)?;
+        let addr = listener.local_addr()?;
+
+        let mut rng = StdRng::seed_from_u64(seed);
+        let round = Round::new(&config, &mut rng);
+        let lobby = Arc::new(Mutex::new(Lobby {
+            config,
+            rng,
+            round,
+            round_number: 1,
+            clients: BTreeMap::new(),
+            next_id: 1,
+        }));
+        let stopping = Arc::new(AtomicBool::new(false));
+
+        // A reader and a writer for each player
+        let pool = ThreadPool::new(max_players * 2);
+        let acceptor = {
+            let lobby = Arc::clone(&lobby);
+            let stopping = Arc::clone(&stopping);
+            thread::spawn(move || accept(listener, pool, max_players, lobby, stopping))
+        };
+
+        Ok(GameServer {
+            addr,
+            lobby,
+            stopping,
+            acceptor: Some(acceptor),
+        })
+    }
+
+    pub fn local_addr(&self) -> SocketAddr {
+        self.addr
+    }
+
+    /// Players connected right now
+    pub fn players(&self) -> usize {
+        lock(&self.lobby).clients.len()
+    }
+
+    /// Block until the server stops; it only stops when shut down
+    pub fn wait(mut self) {
+        if let Some(acceptor) = self.acceptor.take() {
+            let _ = acceptor.join();
+        }
+    }
+
+    /// Disconnect everyone and stop listening
+    pub fn shutdown(mut self) {
+        self.stop();
+    }
+
+    fn stop(&mut self) {
+        let Some(acceptor) = self.acceptor.take() else { return };
+        // The acceptor checks this under the lobby lock, so any connection
+        // it registers is in the lobby by the time we look
+        self.stopping.store(true, AtomicOrdering::SeqCst);
+        // Closing the connections ends their workers' reads...
+        for client in lock(&self.lobby).clients.values() {
+            let _ = client.stream.shutdown(Shutdown::Both);
+        }
+        // ...and a connection of our own wakes the acceptor to notice it should stop
+        let _ = TcpStream::connect(self.addr);
+        let _ = acceptor.join();
+    }
+}
+
+impl Drop for GameServer {
+    fn drop(&mut self) {
+        self.stop();
+    }
+}
+
+/// A poisoned lobby is still consistent enough to keep playing
+fn lock(lobby: &Mutex<Lobby>) -> MutexGuard<'_, Lobby> {
+    lobby.lock().unwrap_or_else(|poisoned| poisoned.into_inner())
+}
+
+/// Hand each connection to the pool, until told to stop
+///
+/// Dropping the pool at the end waits for every connection to finish.
+fn accept(
+    listener: TcpListener,
+    pool: ThreadPool,
+    max_players: usize,
+    lobby: Arc<Mutex<Lobby>>,
+    stopping: Arc<AtomicBool>,
+) {
+    for stream in listener.incoming() {
+        let Ok(mut stream) = stream else { continue };
+        // Lines are small and interactive; don't hold them back to batch them
+        let _ = stream.set_nodelay(true);
+        let _ = stream.set_read_timeout(Some(READ_TIMEOUT));
+        let _ = stream.set_write_timeout(Some(WRITE_TIMEOUT));
+        let (Ok(writer), Ok(closer)) = (stream.try_clone(), stream.try_clone()) else { continue };
+
+        let (id, lines) = {
+            let mut lobby = lock(&lobby);
+            // Checked under the lock, so `stop` either sees this player to
+            // disconnect them or they're never let in
+            if stopping.load(AtomicOrdering::SeqCst) {
+                break;
+            }
+            if lobby.clients.len() >= max_players {
+                let _ = writeln!(stream, "{}", Message::Error("the game is full".to_string()));
+                continue;
+            }
+            lobby.join(closer)
+        };
+        pool.execute(move || deliver(writer, lines));
+        let lobby = Arc::clone(&lobby);
+        let stopping = Arc::clone(&stopping);
+        pool.execute(move || serve(stream, id, &lobby, &stopping));
+    }
+}
+
+/// Write one player's lines until they leave or stop keeping up
+fn deliver(mut stream: TcpStream, lines: Receiver<String>) {
+    for line in lines {
+        // One write per line, so a message never goes out in pieces
+        if stream.write_all(line.as_bytes()).is_err() {
+            // Its reader sees the shutdown and leaves properly
+            let _ = stream.shutdown(Shutdown::Both);
+            break;
+        }
+    }
+}
+
+/// Read one player's lines until they quit or disconnect
+fn serve(stream: TcpStream, id: u64, lobby: &Mutex<Lobby>, stopping: &AtomicBool) {
+    let mut reader = BufReader::new(stream);
+    let mut line = Vec::new();
+    // Whether the rest of an over-long line is still to be thrown away
+    let mut skipping = false;
+    loop {
+        let room = (MAX_LINE - line.len()) as u64;
+        match (&mut reader).take(room).read_until(b'\n', &mut line) {
+            Ok(0) => break,
+            Ok(_) => {}
+            // Nothing yet; whatever came of the line so far stays in `line`
+            Err(e) if matches!(e.kind(), io::ErrorKind::WouldBlock | io::ErrorKind::TimedOut) => {
+                if stopping.load(AtomicOrdering::SeqCst) {
+                    break;
+                }
+                continue;
+            }
+            Err(_) => break,
+        }
+        let complete = line.ends_with(b"\n");
+        if !complete && line.len() >= MAX_LINE {
+            if !skipping {
+                lock(lobby).send(id, &Message::Error(format!("a line is at most {} bytes", MAX_LINE)));
+            }
+            skipping = true;
+            line.clear();
+            continue;
+        }
+        if skipping {
+            skipping = !complete;
+            line.clear();
+            continue;
+        }
+        let text = String::from_utf8_lossy(&line);
+        let text = text.trim();
+        let (command, rest) = text.split_once(' ').unwrap_or((text, ""));
+
+        let mut lobby = lock(lobby);
+        match command {
+            "" => {}
+            "QUIT" => break,
+            "NAME" => lobby.rename(id, rest.trim()),
+            _ => lobby.guess(id, text),
+        }
+        drop(lobby);
+        line.clear();
+    }
+    lock(lobby).leave(id);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A test client reading the server's lines
+    struct Player {
+        reader: BufReader<TcpStream>,
+        writer: TcpStream,
+    }
+
+    impl Player {
+        fn connect(server: &GameServer) -> Player {
+            let writer = TcpStream::connect(server.local_addr()).unwrap();
+            writer.set_read_timeout(Some(Duration::from_secs(5))).unwrap();
+            writer.set_nodelay(true).unwrap();
+            Player {
+                reader: BufReader::new(writer.try_clone().unwrap()),
+                writer,
+            }
+        }
+
+        fn send(&mut self, line: &str) {
+            writeln!(self.writer, "{}", line).unwrap();
+        }
+
+        fn expect(&mut self, expected: &str) {
+            let mut line = String::new();
+            self.reader.read_line(&mut line).unwrap();
+            assert_eq!(line.trim_end(), expected);
+        }
+    }
+
+    fn config() -> GameConfig {
+        GameConfig {
+            range: 1..=10,
+            ..GameConfig::default()
+        }
+    }
+
+    /// The secrets a server seeded with `seed` picks, round by round
+    fn secrets(seed: u64) -> impl Iterator<Item = u32> {
+        let mut rng = StdRng::seed_from_u64(seed);
+        std::iter::repeat_with(move || Round::new(&config(), &mut rng).secret())
+    }
+
+    fn wrong_guess(secret: u32) -> (u32, &'static str) {
+        if secret == 1 { (2, "BIG") } else { (1, "SMALL") }
+    }
+
+    #[test]
+    fn players_race_for_a_shared_secret() {
+        let server = GameServer::start("127.0.0.1:0", config(), 4, 7).unwrap();
+        let mut secrets = secrets(7);
+        let (first, second) = (secrets.next().unwrap(), secrets.next().unwrap());
+
+        let mut alice = Player::connect(&server);
+        alice.expect("WELCOME player1 1 1 10");
+        alice.send("NAME alice");
+        alice.expect("NAME player1 alice");
+        let mut bob = Player::connect(&server);
+        alice.expect("JOIN player2");
+        bob.expect("WELCOME player2 1 1 10");
+
+        // Every guess is broadcast to everyone
+        let (wrong, hint) = wrong_guess(first);
+        bob.send(&wrong.to_string());
+        let turn = format!("GUESS player2 {} {}", wrong, hint);
+        alice.expect(&turn);
+        bob.expect(&turn);
+
+        // A win restarts the round with the next secret
+        alice.send(&first.to_string());
+        for player in [&mut alice, &mut bob] {
+            player.expect(&format!("WIN alice {} 1", first));
+            player.expect("ROUND 2 1 10");
+        }
+        bob.send(&second.to_string());
+        alice.expect(&format!("WIN player2 {} 1", second));
+
+        drop(bob);
+        alice.expect("ROUND 3 1 10");
+        alice.expect("LEAVE player2");
+        server.shutdown();
+    }
+
+    #[test]
+    fn bad_input_only_bothers_the_sender() {
+        let server = GameServer::start("127.0.0.1:0", config(), 2, 1).unwrap();
+        let mut alice = Player::connect(&server);
+        alice.expect("WELCOME player1 1 1 10");
+        let mut bob = Player::connect(&server);
+        bob.expect("WELCOME player2 1 1 10");
+        alice.expect("JOIN player2");
+
+        bob.send("eleven");
+        bob.expect("ERROR 'eleven' is not a number");
+        bob.send("11");
+        bob.expect("ERROR 11 is out of range; guess between 1 and 10");
+        bob.send(&"9".repeat(MAX_LINE * 5));
+        bob.expect(&format!("ERROR a line is at most {} bytes", MAX_LINE));
+        bob.send("11");
+        bob.expect("ERROR 11 is out of range; guess between 1 and 10");
+        bob.send("NAME player1");
+        bob.expect("ERROR player1 is taken");
+        // Your own name isn't taken, by you
+        bob.send("NAME player2");
+        bob.expect("NAME player2 player2");
+        alice.expect("NAME player2 player2");
+
+        // The game takes two players, so a third is turned away, though
+        // the pool has workers to spare: two for each player allowed
+        let mut carol = Player::connect(&server);
+        carol.expect("ERROR the game is full");
+
+        bob.send("QUIT");
+        alice.expect("LEAVE player2");
+        assert_eq!(server.players(), 1);
+        // Shutting down disconnects the players still there
+        server.shutdown();
+        let mut line = String::new();
+        assert_eq!(alice.reader.read_line(&mut line).unwrap(), 0);
+    }
+}
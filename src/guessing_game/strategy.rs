@@ -0,0 +1,400 @@
+//! # Solver Strategies
+//!
+//! Bots that play the guessing game from nothing but the chapter's
+//! `Ordering` feedback, and a simulator that pits them against thousands of
+//! seeded secrets.
+//!
+//! - [`BinarySearch`] halves the numbers still possible; it never needs more
+//!   than `⌈log₂(n + 1)⌉` guesses for `n` numbers
+//! - [`RandomGuess`] picks any number still possible
+//! - [`Adversary`] is the other side of the table: a secret keeper that never
+//!   commits to a number, answering each guess so that as many numbers as
+//!   possible stay possible. Against it every strategy meets its worst case.
+
+use super::{GameConfig, Round};
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use std::cmp::Ordering;
+use std::fmt;
+use std::ops::RangeInclusive;
+
+/// A way of choosing guesses
+pub trait Strategy {
+    fn name(&self) -> &str;
+
+    /// Forget the last game; the secret is somewhere in `range`
+    fn start(&mut self, range: RangeInclusive<u32>);
+
+    fn guess(&mut self) -> u32;
+
+    /// How `guess` compared with the secret, as `guess.cmp(&secret)`
+    fn feedback(&mut self, guess: u32, ordering: Ordering);
+}
+
+impl<S: Strategy + ?Sized> Strategy for Box<S> {
+    fn name(&self) -> &str {
+        (**self).name()
+    }
+
+    fn start(&mut self, range: RangeInclusive<u32>) {
+        (**self).start(range);
+    }
+
+    fn guess(&mut self) -> u32 {
+        (**self).guess()
+    }
+
+    fn feedback(&mut self, guess: u32, ordering: Ordering) {
+        (**self).feedback(guess, ordering);
+    }
+}
+
+/// The numbers still possible, narrowed by feedback
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct Bounds {
+    low: u32,
+    high: u32,
+}
+
+impl Bounds {
+    fn new(range: RangeInclusive<u32>) -> Bounds {
+        Bounds {
+            low: *range.start(),
+            high: *range.end(),
+        }
+    }
+
+    fn narrow(&mut self, guess: u32, ordering: Ordering) {
+        match ordering {
+            // Guessed too small, so the secret is above the guess
+            Ordering::Less => self.low = self.low.max(guess.saturating_add(1)),
+            Ordering::Greater => self.high = self.high.min(guess.saturating_sub(1)),
+            Ordering::Equal => (self.low, self.high) = (guess, guess),
+        }
+    }
+}
+
+/// Always guess the middle of what's left
+///
+/// # Example
+/// ```
+/// use rust_book_examples::guessing_game::strategy::{BinarySearch, Strategy};
+/// use std::cmp::Ordering;
+///
+/// let mut bot = BinarySearch::default();
+/// bot.start(1..=100);
+/// assert_eq!(bot.guess(), 50);
+/// bot.feedback(50, Ordering::Less);
+/// assert_eq!(bot.guess(), 75);
+/// ```
+#[derive(Debug, Clone)]
+pub struct BinarySearch {
+    bounds: Bounds,
+}
+
+impl Default for BinarySearch {
+    fn default() -> BinarySearch {
+        BinarySearch {
+            bounds: Bounds::new(1..=100),
+        }
+    }
+}
+
+impl Strategy for BinarySearch {
+    fn name(&self) -> &str {
+        "binary"
+    }
+
+    fn start(&mut self, range: RangeInclusive<u32>) {
+        self.bounds = Bounds::new(range);
+    }
+
+    fn guess(&mut self) -> u32 {
+        let Bounds { low, high } = self.bounds;
+        low + (high.saturating_sub(low)) / 2
+    }
+
+    fn feedback(&mut self, guess: u32, ordering: Ordering) {
+        self.bounds.narrow(guess, ordering);
+    }
+}
+
+/// Guess any number that could still be the secret, at random
+#[derive(Debug, Clone)]
+pub struct RandomGuess {
+    bounds: Bounds,
+    rng: StdRng,
+}
+
+impl RandomGuess {
+    pub fn new(seed: u64) -> RandomGuess {
+        RandomGuess {
+            bounds: Bounds::new(1..=100),
+            rng: StdRng::seed_from_u64(seed),
+        }
+    }
+}
+
+impl Strategy for RandomGuess {
+    fn name(&self) -> &str {
+        "random"
+    }
+
+    fn start(&mut self, range: RangeInclusive<u32>) {
+        self.bounds = Bounds::new(range);
+    }
+
+    fn guess(&mut self) -> u32 {
+        let Bounds { low, high } = self.bounds;
+        // Feedback that contradicts itself can leave nothing possible
+        if low > high { low } else { self.rng.gen_range(low..=high) }
+    }
+
+    fn feedback(&mut self, guess: u32, ordering: Ordering) {
+        self.bounds.narrow(guess, ordering);
+    }
+}
+
+/// The strategies [`named`] knows
+pub const NAMES: [&str; 2] = ["binary", "random"];
+
+/// A strategy by name, seeded if it's random
+pub fn named(name: &str, seed: u64) -> Option<Box<dyn Strategy>> {
+    match name {
+        "binary" => Some(Box::new(BinarySearch::default())),
+        "random" => Some(Box::new(RandomGuess::new(seed))),
+        _ => None,
+    }
+}
+
+/// A secret keeper that answers every guess as unhelpfully as it can
+///
+/// It only says "correct" when one number is left, so whatever a strategy
+/// does, no fixed secret could have made it take longer.
+///
+/// # Example
+/// ```
+/// use rust_book_examples::guessing_game::strategy::Adversary;
+/// use std::cmp::Ordering;
+///
+/// let mut adversary = Adversary::new(1..=3);
+/// assert_eq!(adversary.answer(1), Ordering::Less);
+/// assert_eq!(adversary.answer(3), Ordering::Greater);
+/// assert_eq!(adversary.answer(2), Ordering::Equal);
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Adversary {
+    bounds: Bounds,
+}
+
+impl Adversary {
+    pub fn new(range: RangeInclusive<u32>) -> Adversary {
+        Adversary {
+            bounds: Bounds::new(range),
+        }
+    }
+
+    /// Compare `guess` with a secret chosen only now: `guess.cmp(&secret)`
+    pub fn answer(&mut self, guess: u32) -> Ordering {
+        let Bounds { low, high } = self.bounds;
+        let ordering = if guess < low {
+            Ordering::Less
+        } else if guess > high {
+            Ordering::Greater
+        } else if low == high {
+            Ordering::Equal
+        } else if guess - low >= high - guess {
+            // More numbers below the guess than above: the secret is below
+            Ordering::Greater
+        } else {
+            Ordering::Less
+        };
+        self.bounds.narrow(guess, ordering);
+        ordering
+    }
+}
+
+/// How a strategy did over many games
+#[derive(Debug, Clone, PartialEq)]
+pub struct SimulationReport {
+    pub strategy: String,
+    pub games: u32,
+    pub won: u32,
+    /// Attempts over all games, won or lost
+    pub total_attempts: u64,
+    pub max_attempts: u32,
+    /// Attempts needed against the [`Adversary`]
+    pub worst_case: u32,
+}
+
+impl SimulationReport {
+    pub fn average_attempts(&self) -> f64 {
+        if self.games == 0 {
+            0.0
+        } else {
+            self.total_attempts as f64 / f64::from(self.games)
+        }
+    }
+}
+
+impl fmt::Display for SimulationReport {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{:<8} won {:>6}/{:<6} average {:>6.2}  max {:>4}  worst case {:>4}",
+            self.strategy,
+            self.won,
+            self.games,
+            self.average_attempts(),
+            self.max_attempts,
+            self.worst_case
+        )
+    }
+}
+
+/// Play `games` games with secrets drawn from an RNG seeded with `seed`
+///
+/// Games without an attempt limit are cut off after as many guesses as
+/// there are numbers in the range, and count as lost, so a strategy that
+/// repeats itself can't run forever.
+///
+/// # Example
+/// ```
+/// use rust_book_examples::guessing_game::strategy::{simulate, BinarySearch};
+/// use rust_book_examples::guessing_game::GameConfig;
+///
+/// let report = simulate(&mut BinarySearch::default(), &GameConfig::default(), 1000, 7);
+/// assert_eq!(report.won, 1000);
+/// assert_eq!(report.max_attempts, 7);
+/// assert_eq!(report.worst_case, 7);
+/// ```
+pub fn simulate<S: Strategy + ?Sized>(strategy: &mut S, config: &GameConfig, games: u32, seed: u64) -> SimulationReport {
+    let mut rng = StdRng::seed_from_u64(seed);
+    let mut report = SimulationReport {
+        strategy: strategy.name().to_string(),
+        games,
+        won: 0,
+        total_attempts: 0,
+        max_attempts: 0,
+        worst_case: worst_case(strategy, config),
+    };
+
+    for _ in 0..games {
+        let mut round = Round::new(config, &mut rng);
+        let limit = attempt_cap(config);
+        strategy.start(config.range.clone());
+        while !round.is_over() && round.attempts() < limit {
+            let guess = strategy.guess();
+            let ordering = round.guess(guess);
+            strategy.feedback(guess, ordering);
+        }
+        report.won += u32::from(round.is_won());
+        report.total_attempts += u64::from(round.attempts());
+        report.max_attempts = report.max_attempts.max(round.attempts());
+    }
+    report
+}
+
+/// Guesses `strategy` needs against the [`Adversary`], up to the attempt cap
+pub fn worst_case<S: Strategy + ?Sized>(strategy: &mut S, config: &GameConfig) -> u32 {
+    let mut adversary = Adversary::new(config.range.clone());
+    strategy.start(config.range.clone());
+    let limit = attempt_cap(config);
+    for attempt in 1..=limit {
+        let guess = strategy.guess();
+        let ordering = adversary.answer(guess);
+        if ordering == Ordering::Equal {
+            return attempt;
+        }
+        strategy.feedback(guess, ordering);
+    }
+    limit
+}
+
+fn attempt_cap(config: &GameConfig) -> u32 {
+    let size = config.range.end().saturating_sub(*config.range.start()).saturating_add(1);
+    config.max_attempts.unwrap_or(size)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::guessing_game::{Bot, GuessingGame, Outcome};
+
+    #[test]
+    fn binary_search_is_optimal() {
+        for high in [1, 2, 3, 7, 8, 100, 1000] {
+            let config = GameConfig {
+                range: 1..=high,
+                ..GameConfig::default()
+            };
+            let report = simulate(&mut BinarySearch::default(), &config, 500, 1);
+            // ⌈log₂(n + 1)⌉ guesses for n numbers
+            let optimal = 32 - high.leading_zeros();
+            assert_eq!(report.won, 500);
+            assert!(report.max_attempts <= optimal, "1..={}: {}", high, report.max_attempts);
+            assert_eq!(report.worst_case, optimal);
+        }
+    }
+
+    #[test]
+    fn random_is_worse_but_reproducible() {
+        let config = GameConfig::default();
+        let binary = simulate(&mut BinarySearch::default(), &config, 2000, 9);
+        let random = simulate(&mut RandomGuess::new(3), &config, 2000, 9);
+        assert_eq!(random.won, 2000);
+        assert!(random.average_attempts() > binary.average_attempts());
+        assert!(random.max_attempts > binary.max_attempts);
+        assert_eq!(random, simulate(&mut RandomGuess::new(3), &config, 2000, 9));
+        assert!(random.to_string().starts_with("random   won   2000/2000"));
+    }
+
+    #[test]
+    fn attempt_limits_and_caps() {
+        // Binary search can't always find one of 100 numbers in 5 guesses
+        let config = GameConfig {
+            max_attempts: Some(5),
+            ..GameConfig::default()
+        };
+        let report = simulate(&mut BinarySearch::default(), &config, 1000, 2);
+        assert!(report.won < 1000 && report.won > 0);
+        assert_eq!((report.max_attempts, report.worst_case), (5, 5));
+
+        // A strategy stuck on one number is cut off rather than looping forever
+        struct Stubborn;
+        impl Strategy for Stubborn {
+            fn name(&self) -> &str {
+                "stubborn"
+            }
+            fn start(&mut self, _: RangeInclusive<u32>) {}
+            fn guess(&mut self) -> u32 {
+                0
+            }
+            fn feedback(&mut self, _: u32, _: Ordering) {}
+        }
+        let report = simulate(&mut Stubborn, &GameConfig::default(), 3, 0);
+        assert_eq!((report.won, report.max_attempts, report.worst_case), (0, 100, 100));
+        assert!(named("stubborn", 0).is_none());
+    }
+
+    #[test]
+    fn bots_play_the_real_game() {
+        let config = GameConfig {
+            range: 1..=1000,
+            max_attempts: Some(10),
+            ..GameConfig::default()
+        };
+        for name in NAMES {
+            let bot = Bot(named(name, 4).unwrap());
+            let mut game = GuessingGame::new(config.clone(), bot, Vec::new(), StdRng::seed_from_u64(11)).unwrap();
+            let report = game.play().unwrap();
+            let transcript = String::from_utf8(game.into_output()).unwrap();
+            if name == "binary" {
+                assert_eq!(report.outcome, Outcome::Won);
+                assert!(transcript.starts_with("Guess the number between 1 and 1000!\n"));
+                assert!(transcript.contains("You guessed: 500\n"));
+            }
+            assert_eq!(transcript.matches("You guessed:").count() as u32, report.attempts);
+        }
+    }
+}
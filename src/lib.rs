@@ -7,6 +7,15 @@
 //!
 //! - **examples/**: Individual chapter examples with comprehensive explanations
 //! - **src/lib.rs**: Shared utility functions used across multiple examples
+//! - **src/args.rs**: Declarative command line parsing with generated help, for the Chapter 12/13 examples
+//! - **src/asset_cache.rs**: Hot-reloading asset cache for the Chapter 20 web server
+//! - **src/cache.rs**: A bounded LRU cache with expiry, a loader and statistics, plus a sharded single-flight version for threads (Chapters 8 and 16)
+//! - **src/fs_util.rs**: Atomic file writes, shared by every example that saves data
+//! - **src/guessing_game.rs**: The Chapter 2 guessing game as a testable engine, with scored presets and a leaderboard
+//! - **src/minigrep.rs**: Search engine pieces shared by the Chapter 12/13 minigrep examples
+//! - **src/spreadsheet.rs**: The Chapter 8 `SpreadsheetCell` grown into a sheet with formulas and incremental recalculation
+//! - **src/student_records.rs**: The Chapter 8 student `HashMap` as a records tool with queries, statistics and a saved file
+//! - **src/thread_pool.rs**: The Chapter 20 thread pool, grown for the parallel search and the game server
 //!
 //! ## Key Concepts Covered
 //!
@@ -134,6 +143,18 @@
 //! cargo run --example ch20_03_graceful_shutdown        # Graceful Shutdown and Cleanup
 //! ```
 
+// === SHARED MODULES ===
+
+pub mod args;
+pub mod asset_cache;
+pub mod cache;
+pub mod fs_util;
+pub mod guessing_game;
+pub mod minigrep;
+pub mod spreadsheet;
+pub mod student_records;
+pub mod thread_pool;
+
 // === UTILITY FUNCTIONS ===
 
 /// Prints a formatted separator for organizing output
@@ -0,0 +1,569 @@
+//! # Minigrep Search Engine
+//!
+//! Shared search machinery for the Chapter 12 and 13 minigrep examples.
+//! The examples keep their own simple `search` functions for teaching;
+//! the heavier features they opt into live here.
+//!
+//! - **regex**: The regular-expression engine behind the `-E` flag
+//! - **aho_corasick**: One-pass search for many fixed strings (`-e`, `-f`)
+//! - **casefold**: Unicode case folding for `-i`, with offsets back into the original line
+//! - **fuzzy**: `--fuzzy` scoring by subsequence or edit distance, with ranking
+//! - **glob**: Shell-style wildcards for path filters
+//! - **walk**: Recursive directory walking with glob and `.gitignore` filtering
+//! - **index**: An on-disk trigram index that skips files a query can't match
+//! - **context**: `-A`/`-B`/`-C` context lines around matches
+//! - **color**: `--color` handling and match highlighting
+//! - **cli**: The shared command line flags, their parser and generated `--help`
+//! - **stream**: Line-by-line search over any reader, including stdin and binary files
+//! - **json**: `--json` output as JSON Lines, in ripgrep's message format
+//! - **parallel**: Searching many files on a thread pool with deterministic output
+//! - **replace**: `--replace` templates, atomic `--in-place` edits and `--dry-run` diffs
+
+pub mod aho_corasick;
+pub mod casefold;
+pub mod cli;
+pub mod color;
+pub mod context;
+pub mod fuzzy;
+pub mod glob;
+pub mod index;
+pub mod json;
+pub mod parallel;
+pub mod regex;
+pub mod replace;
+pub mod stream;
+pub mod walk;
+
+use aho_corasick::{AhoCorasick, PatternMatch};
+use casefold::{CaseFolder, FoldedQuery};
+use regex::{Regex, RegexError};
+
+/// How a [`Matcher`] interprets the query
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct MatchOptions {
+    /// Match letters regardless of case (`-i`)
+    pub ignore_case: bool,
+    /// Treat the query as a regular expression (`-E`)
+    pub regex: bool,
+    /// Only match whole words (`-w`)
+    pub word: bool,
+    /// Only match whole lines (`-x`)
+    pub whole_line: bool,
+    /// With `ignore_case`, pair `I` with `ı` and `İ` with `i` (`--turkic`)
+    pub turkic: bool,
+}
+
+impl MatchOptions {
+    /// The case folding `ignore_case` compares fixed strings with
+    pub fn folder(&self) -> CaseFolder {
+        if self.turkic {
+            CaseFolder::default().turkic()
+        } else {
+            CaseFolder::default()
+        }
+    }
+}
+
+/// Decides whether a line matches and where
+///
+/// Unlike the examples' `search` functions, a matcher also reports the byte
+/// spans of each match, which highlighting needs.
+///
+/// # Example
+/// ```
+/// use rust_book_examples::minigrep::Matcher;
+///
+/// let matcher = Matcher::new("rust", true, false).unwrap();
+/// assert!(matcher.is_match("Trust me."));
+/// assert_eq!(matcher.find_spans("Rust and rust"), vec![(0, 4), (9, 13)]);
+/// ```
+#[derive(Debug, Clone)]
+pub struct Matcher {
+    pattern: Pattern,
+    word: bool,
+}
+
+#[derive(Debug, Clone)]
+enum Pattern {
+    /// A plain substring, compared exactly
+    Literal(String),
+    /// A plain substring, compared by Unicode case folding
+    Folded { query: FoldedQuery, whole_line: bool },
+    /// A regular expression; whole-line literals are escaped into one
+    Regex(Regex),
+    /// Several fixed strings, found in one pass
+    Literals(AhoCorasick),
+    /// Several patterns joined into one regex, with each kept to tell which matched
+    Alternatives { combined: Regex, each: Vec<Regex> },
+}
+
+impl Matcher {
+    /// Build a matcher for `query`
+    ///
+    /// # Arguments
+    /// * `ignore_case` - Match letters regardless of case
+    /// * `use_regex` - Treat `query` as a regular expression instead of a literal
+    pub fn new(query: &str, ignore_case: bool, use_regex: bool) -> Result<Matcher, RegexError> {
+        Matcher::with_options(
+            query,
+            &MatchOptions {
+                ignore_case,
+                regex: use_regex,
+                ..MatchOptions::default()
+            },
+        )
+    }
+
+    /// Build a matcher for `query` with every option spelled out
+    ///
+    /// # Example
+    /// ```
+    /// use rust_book_examples::minigrep::{MatchOptions, Matcher};
+    ///
+    /// let options = MatchOptions { word: true, ..MatchOptions::default() };
+    /// let matcher = Matcher::with_options("us", &options).unwrap();
+    /// assert!(matcher.is_match("a pair of us"));
+    /// assert!(!matcher.is_match("They'd banish users"));
+    /// ```
+    pub fn with_options(query: &str, options: &MatchOptions) -> Result<Matcher, RegexError> {
+        if options.ignore_case && !options.regex {
+            return Ok(Matcher {
+                pattern: Pattern::Folded {
+                    query: FoldedQuery::new(query, options.folder()),
+                    whole_line: options.whole_line,
+                },
+                word: options.word,
+            });
+        }
+
+        let regex = match pattern_source(query, options) {
+            Some(pattern) => compile(&pattern, options)?,
+            None => {
+                return Ok(Matcher {
+                    pattern: Pattern::Literal(query.to_string()),
+                    word: options.word,
+                });
+            }
+        };
+        Ok(Matcher {
+            pattern: Pattern::Regex(regex),
+            word: options.word,
+        })
+    }
+
+    /// Build a matcher selecting lines that match any of `patterns`
+    ///
+    /// Fixed strings are all found in a single pass with an Aho–Corasick
+    /// automaton, so thousands of them cost little more than one. With no
+    /// patterns at all, nothing matches.
+    ///
+    /// # Example
+    /// ```
+    /// use rust_book_examples::minigrep::{MatchOptions, Matcher};
+    ///
+    /// let matcher = Matcher::with_patterns(&["fast", "three"], &MatchOptions::default()).unwrap();
+    /// assert!(matcher.is_match("Pick three."));
+    /// assert_eq!(matcher.matching_patterns("safe, fast, productive."), vec![0]);
+    /// ```
+    pub fn with_patterns<S: AsRef<str>>(patterns: &[S], options: &MatchOptions) -> Result<Matcher, RegexError> {
+        if let [query] = patterns {
+            return Matcher::with_options(query.as_ref(), options);
+        }
+
+        let pattern = if patterns.is_empty() {
+            Pattern::Literals(AhoCorasick::new(patterns))
+        } else if options.regex || options.whole_line {
+            let sources: Vec<String> = patterns
+                .iter()
+                .map(|p| pattern_source(p.as_ref(), options).unwrap_or_else(|| regex::escape(p.as_ref())))
+                .collect();
+            let joined = sources.iter().map(|s| format!("(?:{})", s)).collect::<Vec<_>>().join("|");
+            Pattern::Alternatives {
+                combined: compile(&joined, options)?,
+                each: sources.iter().map(|s| compile(s, options)).collect::<Result<_, _>>()?,
+            }
+        } else if options.ignore_case {
+            Pattern::Literals(AhoCorasick::with_folder(patterns, options.folder()))
+        } else {
+            Pattern::Literals(AhoCorasick::new(patterns))
+        };
+
+        Ok(Matcher {
+            pattern,
+            word: options.word,
+        })
+    }
+
+    /// Returns true if `line` contains a match
+    pub fn is_match(&self, line: &str) -> bool {
+        match &self.pattern {
+            _ if self.word => self.next_match(line, 0).is_some(),
+            Pattern::Literal(query) => line.contains(query.as_str()),
+            Pattern::Folded { query, whole_line: false } => query.is_match(line),
+            Pattern::Folded { query, whole_line: true } => query.is_full_match(line),
+            Pattern::Regex(regex) => regex.is_match(line),
+            Pattern::Literals(automaton) => automaton.is_match(line),
+            Pattern::Alternatives { combined, .. } => combined.is_match(line),
+        }
+    }
+
+    /// Indexes of the patterns that match somewhere in `line`, sorted
+    ///
+    /// A matcher built from a single query reports `[0]` when it matches.
+    pub fn matching_patterns(&self, line: &str) -> Vec<usize> {
+        match &self.pattern {
+            Pattern::Literals(automaton) if self.word => {
+                let mut ids: Vec<usize> = automaton
+                    .find_overlapping(line)
+                    .into_iter()
+                    .filter(|m| is_whole_word(line, m.start, m.end))
+                    .map(|m| m.pattern)
+                    .collect();
+                ids.sort_unstable();
+                ids.dedup();
+                ids
+            }
+            Pattern::Literals(automaton) => automaton.matching_patterns(line),
+            Pattern::Alternatives { each, .. } => (0..each.len())
+                .filter(|&id| {
+                    self.retry_words(line, 0, |position| {
+                        each[id].find_at(line, position).map(|m| (m.start(), m.end()))
+                    })
+                    .is_some()
+                })
+                .collect(),
+            _ if self.is_match(line) => vec![0],
+            _ => Vec::new(),
+        }
+    }
+
+    /// Byte spans of the non-overlapping matches in `line`
+    pub fn find_spans(&self, line: &str) -> Vec<(usize, usize)> {
+        let empty_query = match &self.pattern {
+            Pattern::Literal(query) => query.is_empty(),
+            Pattern::Folded { query, whole_line } => query.is_empty() && !whole_line,
+            _ => false,
+        };
+        if empty_query {
+            return Vec::new();
+        }
+
+        let mut spans = Vec::new();
+        let mut position = 0;
+        while let Some((start, end)) = self.next_match(line, position) {
+            spans.push((start, end));
+            position = if end > start { end } else { next_char_boundary(line, end) };
+            if position > line.len() {
+                break;
+            }
+        }
+        spans
+    }
+
+    /// `line` with every match replaced by `template`
+    ///
+    /// Capture groups are available when matching a regular expression;
+    /// otherwise only `$0`, the whole match, is.
+    ///
+    /// # Example
+    /// ```
+    /// use rust_book_examples::minigrep::{replace::Template, Matcher};
+    ///
+    /// let matcher = Matcher::new(r"(\w+) (\w+)", false, true).unwrap();
+    /// let template = Template::parse("$2 $1").unwrap();
+    /// assert_eq!(matcher.replace_all("safe fast, productive", &template), "fast safe, productive");
+    /// ```
+    pub fn replace_all(&self, line: &str, template: &replace::Template) -> String {
+        let mut replaced = String::with_capacity(line.len());
+        let mut copied = 0;
+        for ((start, end), replacement) in self.replacements(line, template) {
+            replaced.push_str(&line[copied..start]);
+            replaced.push_str(&replacement);
+            copied = end;
+        }
+        replaced.push_str(&line[copied..]);
+        replaced
+    }
+
+    /// Each match span in `line` with the text `template` replaces it by
+    pub fn replacements(&self, line: &str, template: &replace::Template) -> Vec<((usize, usize), String)> {
+        self.find_spans(line)
+            .into_iter()
+            .map(|(start, end)| {
+                let mut replacement = String::new();
+                match self.captures(line, start, end) {
+                    Some(captures) => template.expand(|i| captures.get(i).map(|m| m.as_str()), &mut replacement),
+                    None => template.expand(|i| (i == 0).then_some(&line[start..end]), &mut replacement),
+                }
+                ((start, end), replacement)
+            })
+            .collect()
+    }
+
+    /// The capture groups of the regex match spanning `start..end`
+    ///
+    /// The groups are found by a second search from `start`. Should it settle
+    /// on a different span, it's run again where the match can't go past
+    /// `end`, and then on the span's text alone, so `$1`... aren't lost.
+    fn captures<'t>(&self, line: &'t str, start: usize, end: usize) -> Option<regex::Captures<'t>> {
+        let regexes = match &self.pattern {
+            Pattern::Regex(regex) => std::slice::from_ref(regex),
+            // Group numbers belong to whichever alternative matched
+            Pattern::Alternatives { each, .. } => each.as_slice(),
+            _ => return None,
+        };
+        let searches = [(line, start, start, end), (&line[..end], start, start, end), (&line[start..end], 0, 0, end - start)];
+        searches.into_iter().find_map(|(text, from, start, end)| {
+            regexes.iter().find_map(|regex| {
+                regex
+                    .captures_at(text, from)
+                    .filter(|c| c.get(0).is_some_and(|m| m.start() == start && m.end() == end))
+            })
+        })
+    }
+
+    /// The first match starting at or after `position`
+    fn next_match(&self, line: &str, position: usize) -> Option<(usize, usize)> {
+        match &self.pattern {
+            Pattern::Literal(query) => self.retry_words(line, position, |position| {
+                line[position..]
+                    .find(query.as_str())
+                    .map(|offset| (position + offset, position + offset + query.len()))
+            }),
+            Pattern::Folded { query, whole_line: false } => {
+                self.retry_words(line, position, |position| query.find_at(line, position))
+            }
+            Pattern::Folded { query, whole_line: true } => {
+                (position == 0 && query.is_full_match(line)).then_some((0, line.len()))
+            }
+            // With -w, a pattern turned down where it starts mustn't hide
+            // another that matches there as a whole word
+            Pattern::Alternatives { each, .. } if self.word => each
+                .iter()
+                .filter_map(|regex| {
+                    self.retry_words(line, position, |position| {
+                        regex.find_at(line, position).map(|m| (m.start(), m.end()))
+                    })
+                })
+                .min_by_key(|&(start, _)| start),
+            Pattern::Regex(regex) | Pattern::Alternatives { combined: regex, .. } => {
+                self.retry_words(line, position, |position| {
+                    regex.find_at(line, position).map(|m| (m.start(), m.end()))
+                })
+            }
+            Pattern::Literals(automaton) => self.retry_words(line, position, |position| {
+                // An empty match is left for retry_words to check and step past
+                let accept = |m: &PatternMatch| !self.word || m.start == m.end || is_whole_word(line, m.start, m.end);
+                automaton.find_at_where(line, position, accept).map(|m| (m.start, m.end))
+            }),
+        }
+    }
+
+    /// Run `find` from `position`, skipping matches that `-w` rejects
+    fn retry_words<F>(&self, line: &str, mut position: usize, find: F) -> Option<(usize, usize)>
+    where
+        F: Fn(usize) -> Option<(usize, usize)>,
+    {
+        loop {
+            let (start, end) = find(position)?;
+            if !self.word || is_whole_word(line, start, end) {
+                return Some((start, end));
+            }
+            // Like grep -w, retry from just after where the rejected match began
+            position = next_char_boundary(line, start);
+            if position > line.len() {
+                return None;
+            }
+        }
+    }
+}
+
+/// The regex source for `query`, or `None` when it can be matched as a plain substring
+fn pattern_source(query: &str, options: &MatchOptions) -> Option<String> {
+    let pattern = if options.regex {
+        query.to_string()
+    } else if options.ignore_case || options.whole_line {
+        regex::escape(query)
+    } else {
+        return None;
+    };
+
+    if options.whole_line {
+        Some(format!("^(?:{})$", pattern))
+    } else {
+        Some(pattern)
+    }
+}
+
+fn compile(pattern: &str, options: &MatchOptions) -> Result<Regex, RegexError> {
+    if options.ignore_case {
+        Regex::case_insensitive(pattern)
+    } else {
+        Regex::new(pattern)
+    }
+}
+
+/// Word characters for `-w`, as grep defines them
+fn is_word_char(c: char) -> bool {
+    c.is_alphanumeric() || c == '_'
+}
+
+fn is_whole_word(line: &str, start: usize, end: usize) -> bool {
+    let before = line[..start].chars().next_back();
+    let after = line[end..].chars().next();
+    !before.is_some_and(is_word_char) && !after.is_some_and(is_word_char)
+}
+
+/// The byte index of the character after `index`, or one past the end
+fn next_char_boundary(line: &str, index: usize) -> usize {
+    line[index..].chars().next().map_or(index + 1, |c| index + c.len_utf8())
+}
+
+/// Search for lines matching a regular expression
+///
+/// Works like the examples' `search`, returning lines borrowed from `contents`.
+///
+/// # Example
+/// ```
+/// use rust_book_examples::minigrep::{regex::Regex, search_regex};
+///
+/// let re = Regex::new(r"^\w+:$").unwrap();
+/// let contents = "Rust:\nsafe, fast, productive.\nPick three.";
+/// assert_eq!(search_regex(&re, contents), vec!["Rust:"]);
+/// ```
+pub fn search_regex<'a>(regex: &Regex, contents: &'a str) -> Vec<&'a str> {
+    contents
+        .lines()
+        .filter(|line| regex.is_match(line))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn matcher(query: &str, options: MatchOptions) -> Matcher {
+        Matcher::with_options(query, &options).unwrap()
+    }
+
+    fn matcher_for(patterns: &[&str], options: MatchOptions) -> Matcher {
+        Matcher::with_patterns(patterns, &options).unwrap()
+    }
+
+    #[test]
+    fn whole_words() {
+        let word = MatchOptions {
+            word: true,
+            ..MatchOptions::default()
+        };
+        let m = matcher("foo", word);
+        assert_eq!(m.find_spans("foobar foo foo_x (foo)"), vec![(7, 10), (18, 21)]);
+        assert!(!m.is_match("foobar"));
+
+        // Punctuation in the query still needs non-word neighbours
+        assert!(matcher("c++", word).is_match("I like c++ a lot"));
+
+        let regex_word = MatchOptions {
+            regex: true,
+            ..word
+        };
+        assert_eq!(matcher("b+", regex_word).find_spans("abb bb"), vec![(4, 6)]);
+    }
+
+    #[test]
+    fn whole_lines() {
+        let line = MatchOptions {
+            whole_line: true,
+            ..MatchOptions::default()
+        };
+        assert!(matcher("Pick three.", line).is_match("Pick three."));
+        assert!(!matcher("Pick", line).is_match("Pick three."));
+
+        let regex_line = MatchOptions {
+            regex: true,
+            ignore_case: true,
+            ..line
+        };
+        assert!(matcher("rust|duct tape.", regex_line).is_match("Duct tape."));
+        assert!(!matcher("rust|duct", regex_line).is_match("Duct tape."));
+    }
+
+    #[test]
+    fn ignore_case_uses_full_case_folding() {
+        let ignore_case = MatchOptions {
+            ignore_case: true,
+            ..MatchOptions::default()
+        };
+        let m = matcher("STRASSE", ignore_case);
+        assert_eq!(m.find_spans("Die Straße, die strasse"), vec![(4, 11), (17, 24)]);
+        assert!(matcher("Οδυσσευς", MatchOptions { whole_line: true, ..ignore_case }).is_match("ΟΔΥΣΣΕΥΣ"));
+        assert!(matcher("maße", MatchOptions { word: true, ..ignore_case }).is_match("gleiche MASSE"));
+
+        let turkic = MatchOptions {
+            turkic: true,
+            ..ignore_case
+        };
+        assert!(!matcher("ırmak", ignore_case).is_match("IRMAK"));
+        assert!(matcher("ırmak", turkic).is_match("IRMAK"));
+        assert!(matcher_for(&["istanbul", "izmir"], turkic).is_match("İSTANBUL"));
+    }
+
+    #[test]
+    fn multiple_patterns() {
+        let m = matcher_for(&["fast", "Pick", "tape"], MatchOptions::default());
+        assert_eq!(m.find_spans("fast tape"), vec![(0, 4), (5, 9)]);
+        assert_eq!(m.matching_patterns("Duct tape. Pick it"), vec![1, 2]);
+        assert!(!m.is_match("Rust:"));
+
+        let word = MatchOptions {
+            word: true,
+            ignore_case: true,
+            ..MatchOptions::default()
+        };
+        let m = matcher_for(&["us", "TRUST"], word);
+        assert_eq!(m.matching_patterns("users trust us"), vec![0, 1]);
+        assert!(!m.is_match("users"));
+
+        let regex_line = MatchOptions {
+            regex: true,
+            whole_line: true,
+            ..MatchOptions::default()
+        };
+        let m = matcher_for(&["R\\w+:", "Pick.*"], regex_line);
+        assert_eq!(m.matching_patterns("Pick three."), vec![1]);
+        assert!(!m.is_match("Rust: yes"));
+
+        // A longer pattern failing -w doesn't hide a shorter one at the same place
+        let word = MatchOptions {
+            word: true,
+            ..MatchOptions::default()
+        };
+        let m = matcher_for(&["a b", "a"], word);
+        assert!(m.is_match("a bc"));
+        assert_eq!(m.find_spans("a bc"), vec![(0, 1)]);
+        assert_eq!(m.matching_patterns("a bc"), vec![1]);
+        let m = matcher_for(&["a b", "a+"], MatchOptions { regex: true, ..word });
+        assert_eq!(m.find_spans("a bc aa"), vec![(0, 1), (5, 7)]);
+        assert_eq!(m.matching_patterns("a bc"), vec![1]);
+
+        let none: [&str; 0] = [];
+        assert!(!matcher_for(&none, MatchOptions::default()).is_match("anything"));
+    }
+
+    #[test]
+    fn empty_matches_advance() {
+        let m = matcher("x*", MatchOptions { regex: true, ..MatchOptions::default() });
+        assert_eq!(m.find_spans("axé"), vec![(0, 0), (1, 2), (2, 2), (4, 4)]);
+    }
+
+    #[test]
+    fn captures_are_found_for_the_exact_span() {
+        let m = matcher("(a)(b)?", MatchOptions { regex: true, ..MatchOptions::default() });
+        let groups = |c: regex::Captures| (0..3).map(|i| c.get(i).map(|m| m.as_str().to_string())).collect::<Vec<_>>();
+        let ab = groups(m.captures("ab", 0, 2).unwrap());
+        assert_eq!(ab, [Some("ab".into()), Some("a".into()), Some("b".into())]);
+        // A shorter span than the search would pick still gets its groups
+        let a = groups(m.captures("ab", 0, 1).unwrap());
+        assert_eq!(a, [Some("a".into()), Some("a".into()), None]);
+    }
+}
@@ -0,0 +1,435 @@
+//! # Aho–Corasick Multi-Pattern Search
+//!
+//! Finds any of many fixed strings in one pass over the text, however many
+//! patterns there are. This is what makes `-e PAT -e PAT ...` and
+//! `-f patterns.txt` with thousands of entries cheap.
+//!
+//! The patterns are stored in a trie over characters. Each trie node also
+//! gets a *failure link* to the longest proper suffix of its text that is
+//! itself a prefix in the trie, so scanning never backs up, and a
+//! *dictionary link* to the nearest node along those links that ends a
+//! pattern, so every pattern ending at a position is found.
+//!
+//! Case-insensitive automata fold both the patterns and the text with full
+//! Unicode case folding, so `STRASSE` finds `Straße`.
+
+use super::casefold::CaseFolder;
+use std::collections::{HashMap, VecDeque};
+
+/// A pattern found in the text
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PatternMatch {
+    /// Index of the pattern, in the order the patterns were given
+    pub pattern: usize,
+    /// Byte offset of the start of the match
+    pub start: usize,
+    /// Byte offset just past the end of the match
+    pub end: usize,
+}
+
+impl PatternMatch {
+    fn len(&self) -> usize {
+        self.end - self.start
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+struct State {
+    next: HashMap<char, usize>,
+    fail: usize,
+    /// Length in characters of the text this node spells
+    depth: usize,
+    /// The pattern spelled exactly by this node
+    output: Option<usize>,
+    /// The nearest node along the failure links that has an output
+    dict: Option<usize>,
+}
+
+/// An automaton matching a fixed set of strings
+///
+/// # Example
+/// ```
+/// use rust_book_examples::minigrep::aho_corasick::AhoCorasick;
+///
+/// let ac = AhoCorasick::new(["borrow", "borrowing", "own"]);
+/// let text = "ownership and borrowing";
+///
+/// let found: Vec<&str> = ac.find_iter(text).iter().map(|m| &text[m.start..m.end]).collect();
+/// assert_eq!(found, vec!["own", "borrowing"]);
+/// assert_eq!(ac.matching_patterns(text), vec![0, 1, 2]);
+/// ```
+#[derive(Debug, Clone)]
+pub struct AhoCorasick {
+    patterns: Vec<String>,
+    states: Vec<State>,
+    folder: Option<CaseFolder>,
+    /// An empty pattern matches everywhere, so it's kept out of the trie
+    empty: Option<usize>,
+}
+
+const ROOT: usize = 0;
+
+impl AhoCorasick {
+    /// Build an automaton matching `patterns` exactly
+    pub fn new<I, P>(patterns: I) -> AhoCorasick
+    where
+        I: IntoIterator<Item = P>,
+        P: AsRef<str>,
+    {
+        AhoCorasick::build(patterns, None)
+    }
+
+    /// Build an automaton that ignores letter case
+    pub fn case_insensitive<I, P>(patterns: I) -> AhoCorasick
+    where
+        I: IntoIterator<Item = P>,
+        P: AsRef<str>,
+    {
+        AhoCorasick::build(patterns, Some(CaseFolder::default()))
+    }
+
+    /// Build an automaton that compares text folded by `folder`
+    pub fn with_folder<I, P>(patterns: I, folder: CaseFolder) -> AhoCorasick
+    where
+        I: IntoIterator<Item = P>,
+        P: AsRef<str>,
+    {
+        AhoCorasick::build(patterns, Some(folder))
+    }
+
+    fn build<I, P>(patterns: I, folder: Option<CaseFolder>) -> AhoCorasick
+    where
+        I: IntoIterator<Item = P>,
+        P: AsRef<str>,
+    {
+        let mut automaton = AhoCorasick {
+            patterns: patterns.into_iter().map(|p| p.as_ref().to_string()).collect(),
+            states: vec![State::default()],
+            folder,
+            empty: None,
+        };
+
+        for id in 0..automaton.patterns.len() {
+            automaton.insert(id);
+        }
+        automaton.link();
+        automaton
+    }
+
+    fn insert(&mut self, id: usize) {
+        let pattern = std::mem::take(&mut self.patterns[id]);
+        if pattern.is_empty() {
+            self.empty.get_or_insert(id);
+        }
+
+        let mut state = ROOT;
+        let folded: Vec<char> = pattern.chars().flat_map(|c| self.fold(c)).collect();
+        for c in folded {
+            state = match self.states[state].next.get(&c) {
+                Some(&next) => next,
+                None => {
+                    let next = self.states.len();
+                    let depth = self.states[state].depth + 1;
+                    self.states.push(State {
+                        depth,
+                        ..State::default()
+                    });
+                    self.states[state].next.insert(c, next);
+                    next
+                }
+            };
+        }
+        if state != ROOT {
+            // Duplicates report the first pattern given
+            self.states[state].output.get_or_insert(id);
+        }
+
+        self.patterns[id] = pattern;
+    }
+
+    /// Fill in failure and dictionary links, breadth first so that every
+    /// node's links point at shallower, finished nodes
+    fn link(&mut self) {
+        let mut queue: VecDeque<usize> = self.states[ROOT].next.values().copied().collect();
+
+        while let Some(state) = queue.pop_front() {
+            let children: Vec<(char, usize)> = self.states[state].next.iter().map(|(&c, &s)| (c, s)).collect();
+
+            for (c, child) in children {
+                let mut fail = self.states[state].fail;
+                let target = loop {
+                    if let Some(&next) = self.states[fail].next.get(&c) {
+                        break next;
+                    }
+                    if fail == ROOT {
+                        break ROOT;
+                    }
+                    fail = self.states[fail].fail;
+                };
+
+                let fail = if target == child { ROOT } else { target };
+                let dict = match self.states[fail].output {
+                    Some(_) => Some(fail),
+                    None => self.states[fail].dict,
+                };
+                self.states[child].fail = fail;
+                self.states[child].dict = dict;
+                queue.push_back(child);
+            }
+        }
+    }
+
+    /// The characters the automaton sees for `c`
+    fn fold(&self, c: char) -> impl Iterator<Item = char> {
+        let (folded, plain) = match self.folder {
+            Some(folder) => (Some(folder.fold_char(c)), None),
+            None => (None, Some(c)),
+        };
+        folded.into_iter().flatten().chain(plain)
+    }
+
+    fn step(&self, mut state: usize, c: char) -> usize {
+        loop {
+            if let Some(&next) = self.states[state].next.get(&c) {
+                return next;
+            }
+            if state == ROOT {
+                return ROOT;
+            }
+            state = self.states[state].fail;
+        }
+    }
+
+    /// Visit every pattern ending at `state`, longest first
+    fn outputs(&self, state: usize) -> impl Iterator<Item = usize> + '_ {
+        let first = match self.states[state].output {
+            Some(_) => Some(state),
+            None => self.states[state].dict,
+        };
+        std::iter::successors(first, move |&s| self.states[s].dict)
+    }
+
+    /// The patterns, in the order they were given
+    pub fn patterns(&self) -> &[String] {
+        &self.patterns
+    }
+
+    /// Feed `text[start..]` through the automaton a character at a time
+    ///
+    /// After each character, `visit` gets the matches ending there and the
+    /// earliest offset any later match could start at. Scanning stops when
+    /// `visit` returns false.
+    fn scan<F>(&self, text: &str, start: usize, mut visit: F)
+    where
+        F: FnMut(&[PatternMatch], usize) -> bool,
+    {
+        let mut state = ROOT;
+        // For each folded character read so far, the offset of the character
+        // it came from and whether it was the first of that character's folding.
+        // Matches have to start and end on whole characters of the text.
+        let mut sources: Vec<(usize, bool)> = Vec::new();
+        let mut found = Vec::new();
+
+        for (index, c) in text[start..].char_indices() {
+            let offset = start + index;
+            let end = offset + c.len_utf8();
+            let mut folded = self.fold(c).peekable();
+            let mut first = true;
+
+            while let Some(f) = folded.next() {
+                sources.push((offset, first));
+                first = false;
+                state = self.step(state, f);
+                if folded.peek().is_some() {
+                    continue;
+                }
+
+                found.clear();
+                for node in self.outputs(state) {
+                    let (start, whole) = sources[sources.len() - self.states[node].depth];
+                    if whole {
+                        found.push(PatternMatch {
+                            pattern: self.states[node].output.expect("dictionary nodes have outputs"),
+                            start,
+                            end,
+                        });
+                    }
+                }
+            }
+
+            let depth = self.states[state].depth;
+            let earliest = if depth == 0 { end } else { sources[sources.len() - depth].0 };
+            if !visit(&found, earliest) {
+                return;
+            }
+        }
+    }
+
+    /// Returns true if any pattern occurs in `text`
+    pub fn is_match(&self, text: &str) -> bool {
+        let mut matched = self.empty.is_some();
+        if !matched {
+            self.scan(text, 0, |found, _| {
+                matched = !found.is_empty();
+                !matched
+            });
+        }
+        matched
+    }
+
+    /// The leftmost match starting at or after byte `start`, preferring
+    /// the longest pattern when several start at the same place
+    pub fn find_at(&self, text: &str, start: usize) -> Option<PatternMatch> {
+        self.find_at_where(text, start, |_| true)
+    }
+
+    /// Like [`find_at`](Self::find_at), but only among the matches `accept`
+    /// allows, so a shorter pattern can still match where a longer one
+    /// starting at the same place is turned down
+    ///
+    /// An empty pattern is offered only at `start`.
+    pub fn find_at_where<F>(&self, text: &str, start: usize, accept: F) -> Option<PatternMatch>
+    where
+        F: Fn(&PatternMatch) -> bool,
+    {
+        let mut best: Option<PatternMatch> = None;
+
+        self.scan(text, start, |found, earliest| {
+            for &m in found.iter().filter(|m| accept(m)) {
+                if best.is_none_or(|b| m.start < b.start || (m.start == b.start && m.len() > b.len())) {
+                    best = Some(m);
+                }
+            }
+            // Keep going while a later match could still start at or before the best one
+            best.is_none_or(|b| earliest <= b.start)
+        });
+
+        let empty = self.empty.map(|pattern| PatternMatch { pattern, start, end: start });
+        match (empty, best) {
+            (Some(empty), best) if best.is_none_or(|b| b.start > start) && accept(&empty) => Some(empty),
+            (_, best) => best,
+        }
+    }
+
+    /// Successive non-overlapping leftmost-longest matches
+    pub fn find_iter(&self, text: &str) -> Vec<PatternMatch> {
+        let mut matches = Vec::new();
+        let mut position = 0;
+
+        while position <= text.len() {
+            let Some(found) = self.find_at(text, position) else {
+                break;
+            };
+            matches.push(found);
+            position = if found.end > found.start {
+                found.end
+            } else {
+                // Step over one character after an empty match
+                found.end + text[found.end..].chars().next().map_or(1, char::len_utf8)
+            };
+        }
+        matches
+    }
+
+    /// Every occurrence of every pattern, including overlapping ones,
+    /// ordered by where they end
+    pub fn find_overlapping(&self, text: &str) -> Vec<PatternMatch> {
+        let mut matches = Vec::new();
+        self.scan(text, 0, |found, _| {
+            matches.extend_from_slice(found);
+            true
+        });
+        matches
+    }
+
+    /// Indexes of the patterns that occur in `text`, sorted
+    pub fn matching_patterns(&self, text: &str) -> Vec<usize> {
+        let mut ids: Vec<usize> = self.find_overlapping(text).into_iter().map(|m| m.pattern).collect();
+        ids.extend(self.empty);
+        ids.sort_unstable();
+        ids.dedup();
+        ids
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn spans<'a>(ac: &AhoCorasick, text: &'a str) -> Vec<(usize, &'a str)> {
+        ac.find_iter(text).iter().map(|m| (m.start, &text[m.start..m.end])).collect()
+    }
+
+    #[test]
+    fn classic_he_she_his_hers() {
+        let ac = AhoCorasick::new(["he", "she", "his", "hers"]);
+        let overlapping: Vec<(usize, usize)> = ac
+            .find_overlapping("ushers")
+            .iter()
+            .map(|m| (m.pattern, m.start))
+            .collect();
+        assert_eq!(overlapping, vec![(1, 1), (0, 2), (3, 2)]);
+        assert_eq!(spans(&ac, "ushers"), vec![(1, "she")]);
+    }
+
+    #[test]
+    fn leftmost_longest() {
+        let ac = AhoCorasick::new(["abcd", "b", "bc", "abc"]);
+        assert_eq!(spans(&ac, "xabcdbc"), vec![(1, "abcd"), (5, "bc")]);
+
+        // A shorter match that starts earlier wins over a longer later one
+        let ac = AhoCorasick::new(["ab", "bcdef"]);
+        assert_eq!(spans(&ac, "abcdef"), vec![(0, "ab")]);
+    }
+
+    #[test]
+    fn reports_which_patterns_matched() {
+        let ac = AhoCorasick::new(["rust", "safe", "fast", "slow"]);
+        assert_eq!(ac.matching_patterns("safe, fast, productive. rust!"), vec![0, 1, 2]);
+        assert!(ac.matching_patterns("Pick three.").is_empty());
+        assert!(!ac.is_match("Pick three."));
+    }
+
+    #[test]
+    fn case_insensitive_keeps_original_offsets() {
+        let ac = AhoCorasick::case_insensitive(["Ärger", "RUST"]);
+        let text = "ÄRGER über rust";
+        assert_eq!(spans(&ac, text), vec![(0, "ÄRGER"), (13, "rust")]);
+    }
+
+    #[test]
+    fn full_case_folding() {
+        let ac = AhoCorasick::case_insensitive(["STRASSE", "σ", "s"]);
+        let text = "Die Straße, ΟΔΥΣΣΕΥΣ";
+        let found: Vec<(usize, usize, usize)> = ac.find_iter(text).iter().map(|m| (m.pattern, m.start, m.end)).collect();
+        // "s" never matches half of the ß
+        assert_eq!(found, vec![(0, 4, 11), (1, 19, 21), (1, 21, 23), (1, 27, 29)]);
+        assert!(!AhoCorasick::case_insensitive(["s"]).is_match("ß"));
+    }
+
+    #[test]
+    fn empty_pattern_matches_everywhere() {
+        let ac = AhoCorasick::new(["", "ab"]);
+        assert!(ac.is_match("zzz"));
+        assert_eq!(spans(&ac, "ab"), vec![(0, "ab"), (2, "")]);
+        assert_eq!(ac.matching_patterns("zzz"), vec![0]);
+    }
+
+    #[test]
+    fn find_at_where_falls_back_to_shorter_patterns() {
+        let ac = AhoCorasick::new(["a b", "a", "bc"]);
+        let short = |m: &PatternMatch| m.len() < 3;
+        assert_eq!(ac.find_at("a bc", 0), Some(PatternMatch { pattern: 0, start: 0, end: 3 }));
+        assert_eq!(ac.find_at_where("a bc", 0, short), Some(PatternMatch { pattern: 1, start: 0, end: 1 }));
+        assert_eq!(ac.find_at_where("a bc", 1, short), Some(PatternMatch { pattern: 2, start: 2, end: 4 }));
+        assert_eq!(ac.find_at_where("a bc", 0, |m| m.len() > 3), None);
+    }
+
+    #[test]
+    fn thousands_of_patterns() {
+        let patterns: Vec<String> = (0..5000).map(|n| format!("word{}x", n)).collect();
+        let ac = AhoCorasick::new(&patterns);
+        let text = "first word42x then word4999x and word5000x";
+        assert_eq!(ac.matching_patterns(text), vec![42, 4999]);
+    }
+}
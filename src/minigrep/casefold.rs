@@ -0,0 +1,485 @@
+//! # Unicode Case Folding
+//!
+//! Case-insensitive comparison done properly. Lowercasing both sides with
+//! `to_lowercase` gets many languages wrong: `"STRASSE"` never equals
+//! `"straße"`, a word-final `ς` never equals `σ`, and every comparison
+//! allocates a new `String`.
+//!
+//! Case folding maps each character to a canonical form that exists only
+//! for comparison:
+//! - **Simple folding** maps one character to one character, so offsets in
+//!   the folded text line up with the original
+//! - **Full folding** may expand a character, so `ß` folds to `ss` and `ﬁ`
+//!   to `fi`
+//! - **Turkic folding** pairs `I` with dotless `ı` and `İ` with `i`, as
+//!   Turkish and Azerbaijani expect
+//!
+//! The tables hold the mappings from Unicode's `CaseFolding.txt` that differ
+//! from `char::to_lowercase`; every other character folds to its lowercase.
+
+/// Characters whose simple folding isn't their lowercase
+const SIMPLE: &[(char, char)] = &[
+    ('\u{B5}', 'μ'),        // MICRO SIGN
+    ('ſ', 's'),             // LATIN SMALL LETTER LONG S
+    ('\u{345}', 'ι'),       // COMBINING GREEK YPOGEGRAMMENI
+    ('ς', 'σ'),             // GREEK SMALL LETTER FINAL SIGMA
+    ('ϐ', 'β'),
+    ('ϑ', 'θ'),
+    ('ϕ', 'φ'),
+    ('ϖ', 'π'),
+    ('ϰ', 'κ'),
+    ('ϱ', 'ρ'),
+    ('ϵ', 'ε'),
+    ('ẛ', 'ṡ'),
+    ('\u{1FBE}', 'ι'),      // GREEK PROSGEGRAMMENI
+];
+
+/// Characters that fold to more than one character under full folding
+const FULL: &[(char, &str)] = &[
+    ('ß', "ss"),
+    ('İ', "i\u{307}"),
+    ('ŉ', "\u{2BC}n"),
+    ('ǰ', "j\u{30C}"),
+    ('ΐ', "ι\u{308}\u{301}"),
+    ('ΰ', "υ\u{308}\u{301}"),
+    ('և', "եւ"),
+    ('ẖ', "h\u{331}"),
+    ('ẗ', "t\u{308}"),
+    ('ẘ', "w\u{30A}"),
+    ('ẙ', "y\u{30A}"),
+    ('ẚ', "a\u{2BE}"),
+    ('ẞ', "ss"),
+    ('ὐ', "υ\u{313}"),
+    ('ὒ', "υ\u{313}\u{300}"),
+    ('ὔ', "υ\u{313}\u{301}"),
+    ('ὖ', "υ\u{313}\u{342}"),
+    ('ᾲ', "ὰι"),
+    ('ᾳ', "αι"),
+    ('ᾴ', "άι"),
+    ('ᾶ', "α\u{342}"),
+    ('ᾷ', "α\u{342}ι"),
+    ('ᾼ', "αι"),
+    ('ῂ', "ὴι"),
+    ('ῃ', "ηι"),
+    ('ῄ', "ήι"),
+    ('ῆ', "η\u{342}"),
+    ('ῇ', "η\u{342}ι"),
+    ('ῌ', "ηι"),
+    ('ῒ', "ι\u{308}\u{300}"),
+    ('\u{1FD3}', "ι\u{308}\u{301}"),
+    ('ῖ', "ι\u{342}"),
+    ('ῗ', "ι\u{308}\u{342}"),
+    ('ῢ', "υ\u{308}\u{300}"),
+    ('\u{1FE3}', "υ\u{308}\u{301}"),
+    ('ῤ', "ρ\u{313}"),
+    ('ῦ', "υ\u{342}"),
+    ('ῧ', "υ\u{308}\u{342}"),
+    ('ῲ', "ὼι"),
+    ('ῳ', "ωι"),
+    ('ῴ', "ώι"),
+    ('ῶ', "ω\u{342}"),
+    ('ῷ', "ω\u{342}ι"),
+    ('ῼ', "ωι"),
+    ('ﬀ', "ff"),
+    ('ﬁ', "fi"),
+    ('ﬂ', "fl"),
+    ('ﬃ', "ffi"),
+    ('ﬄ', "ffl"),
+    ('ﬅ', "st"),
+    ('ﬆ', "st"),
+    ('ﬓ', "մն"),
+    ('ﬔ', "մե"),
+    ('ﬕ', "մի"),
+    ('ﬖ', "վն"),
+    ('ﬗ', "մխ"),
+];
+
+/// The simple case folding of `c`
+///
+/// # Example
+/// ```
+/// use rust_book_examples::minigrep::casefold::simple_fold;
+///
+/// assert_eq!(simple_fold('Σ'), simple_fold('ς'));
+/// assert_eq!(simple_fold('ſ'), 's');
+/// ```
+pub fn simple_fold(c: char) -> char {
+    if c.is_ascii() {
+        return c.to_ascii_lowercase();
+    }
+    if let Ok(index) = SIMPLE.binary_search_by_key(&c, |&(from, _)| from) {
+        return SIMPLE[index].1;
+    }
+
+    match c as u32 {
+        // Cherokee folds to its uppercase letters, which came first in Unicode
+        0x13A0..=0x13F5 => c,
+        0x13F8..=0x13FD => char::from_u32(c as u32 - 8).unwrap_or(c),
+        0xAB70..=0xABBF => char::from_u32(c as u32 - 0xAB70 + 0x13A0).unwrap_or(c),
+        _ => {
+            let mut lower = c.to_lowercase();
+            match (lower.next(), lower.next()) {
+                (Some(folded), None) => folded,
+                _ => c,
+            }
+        }
+    }
+}
+
+/// The characters one character folds to
+///
+/// An iterator over at most three characters, so folding never allocates.
+#[derive(Debug, Clone)]
+pub struct Folded {
+    chars: [char; 3],
+    len: usize,
+    next: usize,
+}
+
+impl Folded {
+    fn one(c: char) -> Folded {
+        Folded {
+            chars: [c, '\0', '\0'],
+            len: 1,
+            next: 0,
+        }
+    }
+
+    fn many(chars: impl Iterator<Item = char>) -> Folded {
+        let mut folded = Folded {
+            chars: ['\0'; 3],
+            len: 0,
+            next: 0,
+        };
+        for c in chars.take(3) {
+            folded.chars[folded.len] = c;
+            folded.len += 1;
+        }
+        folded
+    }
+}
+
+impl Iterator for Folded {
+    type Item = char;
+
+    fn next(&mut self) -> Option<char> {
+        let c = self.chars[..self.len].get(self.next).copied();
+        self.next += 1;
+        c
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let remaining = self.len.saturating_sub(self.next);
+        (remaining, Some(remaining))
+    }
+}
+
+impl ExactSizeIterator for Folded {}
+
+/// Which folding rules to apply
+///
+/// # Example
+/// ```
+/// use rust_book_examples::minigrep::casefold::CaseFolder;
+///
+/// let folder = CaseFolder::default();
+/// assert!(folder.eq("STRASSE", "Straße"));
+/// assert!(folder.eq("ΣΊΣΥΦΟΣ", "σίσυφος"));
+/// assert!(!CaseFolder::simple().eq("STRASSE", "Straße"));
+/// assert!(CaseFolder::default().turkic().eq("DİYARBAKIR", "diyarbakır"));
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CaseFolder {
+    /// Let one character fold to several, like `ß` to `ss`
+    pub full: bool,
+    /// Fold `I` to `ı` and `İ` to `i`
+    pub turkic: bool,
+}
+
+impl Default for CaseFolder {
+    /// Full folding without the Turkic rules, which is what Unicode recommends
+    /// when the language isn't known
+    fn default() -> CaseFolder {
+        CaseFolder {
+            full: true,
+            turkic: false,
+        }
+    }
+}
+
+impl CaseFolder {
+    /// One character to one character only
+    pub fn simple() -> CaseFolder {
+        CaseFolder {
+            full: false,
+            turkic: false,
+        }
+    }
+
+    /// The same rules, plus the Turkic dotted and dotless i
+    pub fn turkic(self) -> CaseFolder {
+        CaseFolder { turkic: true, ..self }
+    }
+
+    /// Fold one character
+    pub fn fold_char(&self, c: char) -> Folded {
+        if self.turkic {
+            match c {
+                'I' => return Folded::one('ı'),
+                'İ' => return Folded::one('i'),
+                _ => {}
+            }
+        }
+        if self.full
+            && !c.is_ascii()
+            && let Some(&(_, to)) = FULL.iter().find(|&&(from, _)| from == c)
+        {
+            return Folded::many(to.chars());
+        }
+
+        match c as u32 {
+            // Greek letters with ypogegrammeni fold to the letter followed by iota
+            0x1F80..=0x1FAF if self.full => {
+                let base = match c as u32 & !0xF {
+                    0x1F80 => 0x1F00,
+                    0x1F90 => 0x1F20,
+                    _ => 0x1F60,
+                };
+                let letter = char::from_u32(base + (c as u32 & 0x7)).unwrap_or(c);
+                Folded::many([letter, 'ι'].into_iter())
+            }
+            _ => Folded::one(simple_fold(c)),
+        }
+    }
+
+    /// Fold a whole string
+    pub fn fold(&self, text: &str) -> String {
+        text.chars().flat_map(|c| self.fold_char(c)).collect()
+    }
+
+    /// Fold `text`, remembering where each folded byte came from
+    pub fn fold_with_offsets(&self, text: &str) -> FoldedText {
+        let mut folded = FoldedText {
+            text: String::with_capacity(text.len()),
+            sources: Vec::with_capacity(text.len()),
+            original_len: text.len(),
+        };
+        for (start, c) in text.char_indices() {
+            let end = start + c.len_utf8();
+            for f in self.fold_char(c) {
+                folded.text.push(f);
+                folded.sources.extend(std::iter::repeat_n((start, end), f.len_utf8()));
+            }
+        }
+        folded
+    }
+
+    /// Returns true if `a` and `b` are equal once folded
+    pub fn eq(&self, a: &str, b: &str) -> bool {
+        a.chars()
+            .flat_map(|c| self.fold_char(c))
+            .eq(b.chars().flat_map(|c| self.fold_char(c)))
+    }
+}
+
+/// Folded text that can map its offsets back to the original
+///
+/// # Example
+/// ```
+/// use rust_book_examples::minigrep::casefold::CaseFolder;
+///
+/// let folded = CaseFolder::default().fold_with_offsets("Die Straße");
+/// assert_eq!(folded.as_str(), "die strasse");
+///
+/// let start = folded.as_str().find("strasse").unwrap();
+/// assert_eq!(folded.original_span(start, start + "strasse".len()), (4, 11));
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FoldedText {
+    text: String,
+    /// For each folded byte, the original character it came from
+    sources: Vec<(usize, usize)>,
+    original_len: usize,
+}
+
+impl FoldedText {
+    pub fn as_str(&self) -> &str {
+        &self.text
+    }
+
+    /// The original span covering folded bytes `start..end`
+    ///
+    /// A span that starts or ends partway through an expansion, like one
+    /// `s` of the `ss` from `ß`, grows to cover the whole original character.
+    pub fn original_span(&self, start: usize, end: usize) -> (usize, usize) {
+        let at = |index: usize| self.sources.get(index).map_or(self.original_len, |&(start, _)| start);
+        if end <= start {
+            let offset = at(start);
+            return (offset, offset);
+        }
+        (at(start), self.sources[end - 1].1)
+    }
+}
+
+/// A query folded once, ready to find in many lines
+///
+/// Lines are folded on the fly as they are compared, so searching doesn't
+/// allocate. A match always covers whole characters of the line: `ss` finds
+/// `ß`, but `s` alone doesn't.
+///
+/// # Example
+/// ```
+/// use rust_book_examples::minigrep::casefold::{CaseFolder, FoldedQuery};
+///
+/// let query = FoldedQuery::new("STRASSE", CaseFolder::default());
+/// assert_eq!(query.find_at("Die Straße", 0), Some((4, 11)));
+/// assert!(!FoldedQuery::new("s", CaseFolder::default()).is_match("ß"));
+/// ```
+#[derive(Debug, Clone)]
+pub struct FoldedQuery {
+    chars: Vec<char>,
+    folder: CaseFolder,
+}
+
+impl FoldedQuery {
+    pub fn new(query: &str, folder: CaseFolder) -> FoldedQuery {
+        FoldedQuery {
+            chars: query.chars().flat_map(|c| folder.fold_char(c)).collect(),
+            folder,
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.chars.is_empty()
+    }
+
+    /// Returns true if `line` contains the query
+    pub fn is_match(&self, line: &str) -> bool {
+        self.find_at(line, 0).is_some()
+    }
+
+    /// Returns true if the query matches all of `line`
+    pub fn is_full_match(&self, line: &str) -> bool {
+        if self.chars.is_empty() {
+            return line.is_empty();
+        }
+        self.match_len(line) == Some(line.len())
+    }
+
+    /// The byte span of the first match starting at or after `start`
+    pub fn find_at(&self, line: &str, start: usize) -> Option<(usize, usize)> {
+        if self.chars.is_empty() {
+            return Some((start, start));
+        }
+
+        line[start..]
+            .char_indices()
+            .find_map(|(offset, _)| self.match_len(&line[start + offset..]).map(|len| (start + offset, start + offset + len)))
+    }
+
+    /// How many bytes at the start of `text` match the query, if they do
+    fn match_len(&self, text: &str) -> Option<usize> {
+        let mut expected = self.chars.iter();
+        for (offset, c) in text.char_indices() {
+            for folded in self.folder.fold_char(c) {
+                // The query ran out partway through this character's folding
+                if expected.next() != Some(&folded) {
+                    return None;
+                }
+            }
+            if expected.len() == 0 {
+                return Some(offset + c.len_utf8());
+            }
+        }
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// The greeting names from the Chapter 8.2 strings example, and friends
+    const NAMES: &[&str] = &["Alice", "Bob", "世界", "مرحبا", "José", "François", "🚀🦀"];
+
+    #[test]
+    fn international_names_fold_like_lowercase() {
+        let folder = CaseFolder::default();
+        for name in NAMES {
+            assert_eq!(folder.fold(name), name.to_lowercase(), "{}", name);
+            assert!(folder.eq(&name.to_uppercase(), name), "{}", name);
+        }
+        assert!(folder.eq("JOSÉ", "josé"));
+        assert!(folder.eq("FRANÇOIS", "françois"));
+    }
+
+    #[test]
+    fn german_sharp_s() {
+        let folder = CaseFolder::default();
+        assert_eq!(folder.fold("Straße"), "strasse");
+        assert!(folder.eq("MASSE", "Maße"));
+        assert!(folder.eq("ẞ", "ss"));
+        // Lowercasing alone can't see these as equal
+        assert_ne!("STRASSE".to_lowercase(), "straße");
+
+        // Simple folding keeps one character per character
+        assert_eq!(CaseFolder::simple().fold("Straße"), "straße");
+        assert!(CaseFolder::simple().eq("ẞ", "ß"));
+    }
+
+    #[test]
+    fn greek_sigma() {
+        let folder = CaseFolder::default();
+        assert_eq!(folder.fold("ΟΔΥΣΣΕΥΣ"), "οδυσσευσ");
+        assert!(folder.eq("ΟΔΥΣΣΕΥΣ", "Οδυσσευς"));
+        assert_eq!(simple_fold('ς'), 'σ');
+        assert!(folder.eq("ᾼ", "ΑΙ"));
+        assert!(folder.eq("\u{1FAF}", "\u{1F67}ι"));
+    }
+
+    #[test]
+    fn turkish_i() {
+        let default = CaseFolder::default();
+        let turkic = default.turkic();
+
+        // Without the Turkic rules, I pairs with i and ı stands alone
+        assert!(default.eq("ISPARTA", "isparta"));
+        assert!(!default.eq("KIRMIZI", "kırmızı"));
+        assert_eq!(default.fold("İ"), "i\u{307}");
+
+        assert!(turkic.eq("KIRMIZI", "kırmızı"));
+        assert!(turkic.eq("İSTANBUL", "istanbul"));
+        assert!(!turkic.eq("ISPARTA", "isparta"));
+    }
+
+    #[test]
+    fn offsets_map_back_to_the_original() {
+        let folded = CaseFolder::default().fold_with_offsets("ﬁne Maße");
+        assert_eq!(folded.as_str(), "fine masse");
+
+        // "i" is the second half of the ﬁ ligature, so the span grows to cover it
+        assert_eq!(folded.original_span(1, 2), (0, 3));
+        let start = folded.as_str().find("masse").unwrap();
+        assert_eq!(folded.original_span(start, folded.as_str().len()), (6, 11));
+        assert_eq!(folded.original_span(10, 10), (11, 11));
+    }
+
+    #[test]
+    fn folded_query_finds_original_spans() {
+        let query = FoldedQuery::new("maße", CaseFolder::default());
+        let line = "Gleiche MASSE, gleiche Maße";
+        assert_eq!(query.find_at(line, 0), Some((8, 13)));
+        assert_eq!(query.find_at(line, 9), Some((23, 28)));
+
+        let query = FoldedQuery::new("FRANÇOIS", CaseFolder::default());
+        assert_eq!(query.find_at("Hello, François!", 0), Some((7, 16)));
+        assert_eq!(query.find_at("Hello, Francois!", 0), None);
+    }
+
+    #[test]
+    fn cherokee_folds_to_uppercase() {
+        assert_eq!(simple_fold('ꭰ'), 'Ꭰ');
+        assert_eq!(simple_fold('Ꭰ'), 'Ꭰ');
+        assert_eq!(simple_fold('ᏸ'), 'Ᏸ');
+    }
+}
@@ -0,0 +1,712 @@
+//! # Command Line Flags
+//!
+//! The one flag surface shared by the minigrep examples.
+//!
+//! Every flag is described once in [`FLAGS`], and [`MINIGREP`] hands that
+//! table to the shared [`args`](crate::args) parser, which also generates
+//! `--help` from it. This module turns the parsed flags into a [`Config`]:
+//! which flags override which, and which can't be combined. `-` on its own
+//! is a path meaning standard input.
+//!
+//! Once `-e` or `-f` has supplied the patterns, every positional argument is
+//! a path; otherwise the first one is the query.
+//!
+//! `minigrep index [DIR]...` builds or refreshes the trigram index of each
+//! directory (the current one by default) instead of searching. To search
+//! for the word "index", put anything before it: `minigrep -- index PATH`.
+//!
+//! ## Exit status
+//!
+//! Like grep, [`Status`] is 0 when a line was selected, 1 when none was, and
+//! 2 when something went wrong, even if other files matched.
+//!
+//! ## Case sensitivity precedence
+//!
+//! 1. `-i`/`--ignore-case` or `--no-ignore-case`, the last one given wins
+//! 2. the `IGNORE_CASE` environment variable
+//! 3. the `CASE_INSENSITIVE` environment variable
+//! 4. case-sensitive
+//!
+//! A variable that is set counts as "on" unless its value is `0`, `false`,
+//! `no` or `off`.
+//!
+//! Ignoring case compares fixed strings by full Unicode case folding, so
+//! `STRASSE` matches `Straße`; `--turkic` adds the Turkish rules for `I` and `İ`.
+//! Regular expressions fold one character to one character.
+
+use super::color::ColorChoice;
+use super::replace::Template;
+use super::MatchOptions;
+use crate::args::{self, Positional};
+use std::fs;
+use std::io::{self, IsTerminal};
+use std::str::FromStr;
+
+/// Environment variables that set the default for `--ignore-case`, highest priority first
+pub const IGNORE_CASE_VARS: [&str; 2] = ["IGNORE_CASE", "CASE_INSENSITIVE"];
+
+pub use crate::args::{ArgError, Flag};
+
+const fn switch(short: Option<char>, long: &'static str, help: &'static str) -> Flag {
+    Flag::switch(short, long, help)
+}
+
+const fn option(short: Option<char>, long: &'static str, value: &'static str, help: &'static str) -> Flag {
+    Flag::option(short, long, value, help)
+}
+
+/// Every flag minigrep understands, in the order `--help` lists them
+pub const FLAGS: &[Flag] = &[
+    switch(Some('i'), "ignore-case", "Match letters regardless of case").env(&IGNORE_CASE_VARS),
+    switch(None, "no-ignore-case", "Match case exactly, overriding -i and the environment"),
+    switch(None, "turkic", "With -i, fold I to dotless ı and İ to i, as in Turkish"),
+    switch(Some('E'), "regex", "Treat the query as a regular expression"),
+    option(Some('e'), "regexp", "PATTERN", "Search for PATTERN; repeat to match any of several"),
+    option(Some('f'), "file", "FILE", "Read patterns from FILE, one per line (repeatable)"),
+    switch(Some('w'), "word-regexp", "Only match whole words"),
+    switch(Some('x'), "line-regexp", "Only match whole lines"),
+    switch(Some('v'), "invert-match", "Select lines that don't match"),
+    switch(Some('n'), "line-number", "Prefix each line with its line number"),
+    switch(Some('c'), "count", "Print only a count of selected lines per file"),
+    switch(Some('l'), "files-with-matches", "Print only the names of files with selected lines"),
+    switch(Some('L'), "files-without-match", "Print only the names of files without selected lines"),
+    option(Some('m'), "max-count", "NUM", "Stop reading a file after NUM selected lines"),
+    switch(Some('o'), "only-matching", "Print only the matched parts of lines"),
+    switch(Some('q'), "quiet", "Print nothing; stop at the first selected line and exit 0"),
+    option(Some('A'), "after-context", "NUM", "Print NUM lines after each match"),
+    option(Some('B'), "before-context", "NUM", "Print NUM lines before each match"),
+    option(Some('C'), "context", "NUM", "Print NUM lines before and after each match"),
+    option(Some('r'), "replace", "TEMPLATE", "Print lines with each match replaced; $1, ${2} insert -E groups, $$ a '$'"),
+    switch(None, "in-place", "With --replace, rewrite the files instead of printing"),
+    option(None, "backup", "SUFFIX", "With --in-place, keep the original as FILE+SUFFIX (default .bak, empty for none)"),
+    switch(None, "dry-run", "With --replace, print a diff of the changes without writing them"),
+    option(None, "color", "WHEN", "Highlight matches: auto, always or never"),
+    switch(None, "json", "Print results as JSON Lines: begin, match, context, end and summary records"),
+    switch(Some('Z'), "null", "Follow file names with a NUL byte instead of ':' or a newline (for xargs -0)"),
+    option(None, "include", "GLOB", "Only search files matching GLOB (repeatable)"),
+    option(None, "exclude", "GLOB", "Skip files and directories matching GLOB (repeatable)"),
+    switch(None, "hidden", "Search hidden files and directories"),
+    switch(None, "no-ignore", "Don't skip files listed in .gitignore"),
+    switch(None, "follow", "Follow symbolic links found inside directories"),
+    switch(None, "no-index", "Search every file, even in directories with a `minigrep index`"),
+    option(Some('j'), "threads", "NUM", "Search with NUM threads (default: one per CPU, 1 disables)"),
+    option(None, "sort", "BY", "Order files by: none (walk order, the default) or path"),
+    switch(Some('s'), "no-messages", "Don't report unreadable files on stderr"),
+    switch(None, "demo", "Run the built-in demo on sample text instead of searching files"),
+];
+
+/// The query comes first, unless `-e` or `-f` supplied the patterns
+const QUERY: Positional = Positional::optional("QUERY", "Text to search for");
+const PATH: Positional = Positional::optional("PATH", "Files or directories to search; - reads standard input").multiple();
+const DIR: Positional = Positional::optional("DIR", "Directories to index (default: the current one)").multiple();
+
+/// The `minigrep index` subcommand
+pub const INDEX: args::Command = args::Command::new("index", "Build or refresh the trigram index of each DIR")
+    .positionals(&[DIR]);
+
+/// The whole minigrep command line, for the [`args`](crate::args) parser
+pub const MINIGREP: args::Command = args::Command::new(
+    "minigrep",
+    "Search each PATH for lines containing QUERY, or any of the PATTERNs.\n\
+     Directories are searched recursively, and - reads standard input.",
+)
+.flags(FLAGS)
+.positionals(&[QUERY, PATH])
+.subcommands(&[INDEX])
+.usage(&[
+    "[OPTIONS] <QUERY> <PATH>...",
+    "[OPTIONS] -e PATTERN... | -f FILE... <PATH>...",
+])
+.after_help(
+    "The first variable set wins, and 0/false/no/off turn it off.\n\n\
+     Exit status is 0 if a line is selected, 1 if none is, and 2 on errors.",
+);
+
+/// What minigrep was asked to do
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Command {
+    /// Search the paths, the usual case
+    #[default]
+    Search,
+    /// `minigrep index`: build or refresh the index of each path
+    Index,
+}
+
+/// Everything the command line asked for
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct Config {
+    pub command: Command,
+    /// The first positional argument; empty when `-e` or `-f` was given
+    pub query: String,
+    /// Patterns given with `-e`
+    pub patterns: Vec<String>,
+    /// Pattern files given with `-f`
+    pub pattern_files: Vec<String>,
+    pub paths: Vec<String>,
+    pub ignore_case: bool,
+    pub turkic: bool,
+    pub use_regex: bool,
+    pub word: bool,
+    pub whole_line: bool,
+    pub invert: bool,
+    pub line_number: bool,
+    pub count: bool,
+    pub files_with_matches: bool,
+    pub files_without_match: bool,
+    pub max_count: Option<usize>,
+    pub only_matching: bool,
+    /// Print nothing, only set the exit status (`-q`)
+    pub quiet: bool,
+    pub before_context: usize,
+    pub after_context: usize,
+    pub color: ColorChoice,
+    /// Print JSON Lines instead of text (`--json`)
+    pub json: bool,
+    /// End file names with NUL (`-Z`)
+    pub null: bool,
+    /// The `--replace` template
+    pub replace: Option<String>,
+    pub in_place: bool,
+    /// The `--backup` suffix as given; see [`Config::backup_suffix`]
+    pub backup: Option<String>,
+    pub dry_run: bool,
+    pub include: Vec<String>,
+    pub exclude: Vec<String>,
+    pub hidden: bool,
+    pub no_ignore: bool,
+    /// Follow symbolic links while walking (`--follow`)
+    pub follow: bool,
+    /// Don't prune files with a directory's trigram index (`--no-index`)
+    pub no_index: bool,
+    pub no_messages: bool,
+    /// Worker threads; `None` means one per CPU
+    pub threads: Option<usize>,
+    pub sort: SortBy,
+    /// `--demo` was given; the query and paths may be missing
+    pub demo: bool,
+    /// `--help` was given; the query and paths may be missing
+    pub help: bool,
+}
+
+impl Config {
+    /// Parse `args` (including the program name), reading defaults from the environment
+    ///
+    /// # Example
+    /// ```
+    /// use rust_book_examples::minigrep::cli::Config;
+    ///
+    /// let args = ["minigrep", "-inC2", "--max-count=5", "nobody", "poem.txt"];
+    /// let config = Config::build(args.iter().map(|s| s.to_string())).unwrap();
+    /// assert!(config.ignore_case && config.line_number);
+    /// assert_eq!((config.before_context, config.after_context), (2, 2));
+    /// assert_eq!(config.max_count, Some(5));
+    /// assert_eq!(config.paths, vec!["poem.txt"]);
+    /// ```
+    pub fn build(args: impl Iterator<Item = String>) -> Result<Config, ArgError> {
+        Config::build_with_env(args, |name| std::env::var(name).ok())
+    }
+
+    /// Like [`Config::build`], but looks environment variables up with `env`
+    pub fn build_with_env<F>(args: impl Iterator<Item = String>, env: F) -> Result<Config, ArgError>
+    where
+        F: Fn(&str) -> Option<String>,
+    {
+        let matches = MINIGREP.parse_with_env(args, env)?;
+        let (command, matches) = match matches.subcommand() {
+            Some((_, index)) => (Command::Index, index),
+            None => (Command::Search, &matches),
+        };
+
+        let mut parser = Parser {
+            config: Config {
+                command,
+                // Flags given on the command line override this below
+                ignore_case: matches.flag("ignore-case"),
+                ..Config::default()
+            },
+            before: None,
+            after: None,
+            context: None,
+        };
+        for (flag, value) in matches.given() {
+            parser.apply(flag, value)?;
+        }
+        let mut config = parser.finish()?;
+
+        if command == Command::Index {
+            config.paths = matches.positionals(DIR.name).to_vec();
+            if config.paths.is_empty() {
+                config.paths.push(".".to_string());
+            }
+            return Ok(config);
+        }
+        if config.help || config.demo {
+            return Ok(config);
+        }
+
+        let mut positional = matches
+            .positionals(QUERY.name)
+            .iter()
+            .chain(matches.positionals(PATH.name))
+            .cloned();
+        if config.patterns.is_empty() && config.pattern_files.is_empty() {
+            config.query = positional.next().ok_or(ArgError::MissingArgument(QUERY))?;
+        }
+        config.paths = positional.collect();
+        if config.paths.is_empty() {
+            return Err(ArgError::MissingArgument(PATH));
+        }
+
+        Ok(config)
+    }
+
+    /// Every pattern to search for: the `-e` patterns, then each line of the
+    /// `-f` files, or just the query when neither was given
+    ///
+    /// # Errors
+    ///
+    /// Fails if a pattern file can't be read; the error names the file.
+    pub fn load_patterns(&self) -> io::Result<Vec<String>> {
+        if self.patterns.is_empty() && self.pattern_files.is_empty() {
+            return Ok(vec![self.query.clone()]);
+        }
+
+        let mut patterns = self.patterns.clone();
+        for path in &self.pattern_files {
+            let contents = fs::read_to_string(path)
+                .map_err(|e| io::Error::new(e.kind(), format!("{}: {}", path, e)))?;
+            patterns.extend(contents.lines().map(String::from));
+        }
+        Ok(patterns)
+    }
+
+    /// The suffix `--in-place` backs files up with, or `None` for no backup
+    pub fn backup_suffix(&self) -> Option<&str> {
+        match self.backup.as_deref() {
+            None => Some(".bak"),
+            Some("") => None,
+            Some(suffix) => Some(suffix),
+        }
+    }
+
+    /// Whether to add a banner, numbering and totals around the results
+    ///
+    /// Only when a person is reading: never for the modes whose output other
+    /// programs parse (`-c`, `-l`, `-L`, `-o`, `-q`, `-Z`, `--json` and the
+    /// `--dry-run` diff), and not when standard output is piped or redirected.
+    pub fn decorate(&self) -> bool {
+        self.for_people() && io::stdout().is_terminal()
+    }
+
+    /// `decorate` without the terminal check
+    fn for_people(&self) -> bool {
+        let machine_readable = self.json
+            || self.null
+            || self.count
+            || self.files_with_matches
+            || self.files_without_match
+            || self.only_matching
+            || self.quiet
+            || self.dry_run;
+        !machine_readable
+    }
+
+    /// The matcher settings these flags ask for
+    pub fn match_options(&self) -> MatchOptions {
+        MatchOptions {
+            ignore_case: self.ignore_case,
+            regex: self.use_regex,
+            word: self.word,
+            whole_line: self.whole_line,
+            turkic: self.turkic,
+        }
+    }
+}
+
+/// The order files are searched and printed in
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum SortBy {
+    /// The order the paths were given, each directory walked in sorted order
+    #[default]
+    None,
+    /// By path across all inputs
+    Path,
+}
+
+impl FromStr for SortBy {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<SortBy, String> {
+        match s {
+            "none" => Ok(SortBy::None),
+            "path" => Ok(SortBy::Path),
+            other => Err(format!("invalid sort order '{}' (expected none or path)", other)),
+        }
+    }
+}
+
+/// How a search ended, reported as grep's exit status
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Status {
+    /// At least one line was selected
+    Matched,
+    /// Every input was searched and nothing was selected
+    NoMatch,
+    /// Bad arguments, or an input that couldn't be read
+    Error,
+}
+
+impl Status {
+    /// Errors win over matches, so scripts notice files that were skipped
+    pub fn from_search(matched: bool, had_errors: bool) -> Status {
+        if had_errors {
+            Status::Error
+        } else if matched {
+            Status::Matched
+        } else {
+            Status::NoMatch
+        }
+    }
+
+    /// The process exit code: 0, 1 or 2
+    pub fn code(self) -> i32 {
+        match self {
+            Status::Matched => 0,
+            Status::NoMatch => 1,
+            Status::Error => 2,
+        }
+    }
+}
+
+/// Collects flags, holding back `-A`/`-B`/`-C` until all have been seen
+struct Parser {
+    config: Config,
+    before: Option<usize>,
+    after: Option<usize>,
+    context: Option<usize>,
+}
+
+impl Parser {
+    fn apply(&mut self, flag: Flag, value: Option<&str>) -> Result<(), ArgError> {
+        let config = &mut self.config;
+        let value = value.unwrap_or_default().to_string();
+
+        match flag.long {
+            "ignore-case" => config.ignore_case = true,
+            "no-ignore-case" => config.ignore_case = false,
+            "turkic" => config.turkic = true,
+            "regex" => config.use_regex = true,
+            "regexp" => config.patterns.push(value),
+            "file" => config.pattern_files.push(value),
+            "word-regexp" => config.word = true,
+            "line-regexp" => config.whole_line = true,
+            "invert-match" => config.invert = true,
+            "line-number" => config.line_number = true,
+            "count" => config.count = true,
+            // -l and -L are opposites; the last one given wins
+            "files-with-matches" => {
+                config.files_with_matches = true;
+                config.files_without_match = false;
+            }
+            "files-without-match" => {
+                config.files_without_match = true;
+                config.files_with_matches = false;
+            }
+            "max-count" => config.max_count = Some(parse_number(flag, value)?),
+            "only-matching" => config.only_matching = true,
+            "quiet" => config.quiet = true,
+            "after-context" => self.after = Some(parse_number(flag, value)?),
+            "before-context" => self.before = Some(parse_number(flag, value)?),
+            "context" => self.context = Some(parse_number(flag, value)?),
+            "color" => {
+                config.color = value
+                    .parse()
+                    .map_err(|_| ArgError::InvalidValue { flag, value })?;
+            }
+            "json" => config.json = true,
+            "null" => config.null = true,
+            "replace" => {
+                Template::parse(&value).map_err(|_| ArgError::InvalidValue { flag, value: value.clone() })?;
+                config.replace = Some(value);
+            }
+            "in-place" => config.in_place = true,
+            "backup" => config.backup = Some(value),
+            "dry-run" => config.dry_run = true,
+            "include" => config.include.push(value),
+            "exclude" => config.exclude.push(value),
+            "hidden" => config.hidden = true,
+            "no-ignore" => config.no_ignore = true,
+            "follow" => config.follow = true,
+            "no-index" => config.no_index = true,
+            "no-messages" => config.no_messages = true,
+            "threads" => config.threads = Some(parse_number(flag, value)?).filter(|&n| n > 0),
+            "sort" => {
+                config.sort = value
+                    .parse()
+                    .map_err(|_| ArgError::InvalidValue { flag, value })?;
+            }
+            "demo" => config.demo = true,
+            "help" => config.help = true,
+            other => unreachable!("flag --{} is in FLAGS but not handled", other),
+        }
+        Ok(())
+    }
+
+    fn finish(mut self) -> Result<Config, ArgError> {
+        // Like grep, -A and -B override the matching half of -C
+        self.config.before_context = self.before.or(self.context).unwrap_or(0);
+        self.config.after_context = self.after.or(self.context).unwrap_or(0);
+
+        // JSON describes matching lines; per-file summaries and -q have no records
+        let config = &self.config;
+        let summaries = [
+            ("count", config.count),
+            ("files-with-matches", config.files_with_matches),
+            ("files-without-match", config.files_without_match),
+            ("quiet", config.quiet),
+        ];
+        if config.json
+            && let Some((name, _)) = summaries.iter().find(|(_, given)| *given)
+        {
+            return Err(ArgError::Conflict("json", name));
+        }
+
+        // Editing files needs something to write, and has no JSON form
+        let editing = [("in-place", config.in_place), ("dry-run", config.dry_run)];
+        if let Some((name, _)) = editing.iter().find(|(_, given)| *given) {
+            if config.replace.is_none() {
+                return Err(ArgError::Requires(name, "replace"));
+            }
+            if config.json || config.invert {
+                let other = if config.json { "json" } else { "invert-match" };
+                return Err(ArgError::Conflict(other, name));
+            }
+        }
+        if config.backup.is_some() && !config.in_place {
+            return Err(ArgError::Requires("backup", "in-place"));
+        }
+        Ok(self.config)
+    }
+}
+
+fn parse_number(flag: Flag, value: String) -> Result<usize, ArgError> {
+    value.parse().map_err(|_| ArgError::InvalidValue { flag, value })
+}
+
+/// Usage and flag descriptions generated from [`MINIGREP`]
+pub fn help(program: &str) -> String {
+    MINIGREP.help(program)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fs_util::TempPath;
+
+    fn parse(args: &[&str]) -> Result<Config, ArgError> {
+        parse_with_env(args, &[])
+    }
+
+    fn parse_with_env(args: &[&str], vars: &[(&str, &str)]) -> Result<Config, ArgError> {
+        let args = std::iter::once("minigrep").chain(args.iter().copied()).map(String::from);
+        Config::build_with_env(args, |name| {
+            vars.iter()
+                .find(|(var, _)| *var == name)
+                .map(|(_, value)| value.to_string())
+        })
+    }
+
+    #[test]
+    fn switches_and_positionals() {
+        let config = parse(&["-v", "--count", "nobody", "a.txt", "-w", "b.txt"]).unwrap();
+        assert!(config.invert && config.count && config.word);
+        assert!(!config.ignore_case && !config.line_number);
+        assert_eq!(config.query, "nobody");
+        assert_eq!(config.paths, vec!["a.txt", "b.txt"]);
+    }
+
+    #[test]
+    fn bundled_short_flags_and_values() {
+        let config = parse(&["-nom3", "-B", "1", "query", "-"]).unwrap();
+        assert!(config.line_number && config.only_matching);
+        assert_eq!(config.max_count, Some(3));
+        assert_eq!((config.before_context, config.after_context), (1, 0));
+        assert_eq!(config.paths, vec!["-"]);
+
+        let config = parse(&["-A1", "-C", "4", "q", "f"]).unwrap();
+        assert_eq!((config.before_context, config.after_context), (4, 1));
+    }
+
+    #[test]
+    fn long_flags_with_values() {
+        let config = parse(&["--include=*.md", "--include", "*.txt", "--color", "never", "q", "notes"]).unwrap();
+        assert_eq!(config.include, vec!["*.md", "*.txt"]);
+        assert_eq!(config.color, ColorChoice::Never);
+    }
+
+    #[test]
+    fn patterns_from_flags_and_files() {
+        let config = parse(&["-e", "fast", "--regexp=three", "poem.txt", "notes"]).unwrap();
+        assert!(config.query.is_empty());
+        assert_eq!(config.patterns, vec!["fast", "three"]);
+        assert_eq!(config.paths, vec!["poem.txt", "notes"]);
+        assert_eq!(parse(&["-e", "fast"]), Err(ArgError::MissingArgument(PATH)));
+
+        let path = TempPath::new("minigrep_patterns.txt");
+        fs::write(&path, "borrow\nlifetime\n").unwrap();
+        let file = path.to_str().unwrap();
+        let config = parse(&["-e", "own", "-f", file, "src"]).unwrap();
+        assert_eq!(config.load_patterns().unwrap(), vec!["own", "borrow", "lifetime"]);
+        fs::remove_file(&path).unwrap();
+
+        let error = config.load_patterns().unwrap_err();
+        assert!(error.to_string().starts_with(file));
+
+        let config = parse(&["q", "f"]).unwrap();
+        assert_eq!(config.load_patterns().unwrap(), vec!["q"]);
+    }
+
+    #[test]
+    fn double_dash_ends_flags() {
+        let config = parse(&["--", "-v", "file"]).unwrap();
+        assert_eq!(config.query, "-v");
+        assert!(!config.invert);
+    }
+
+    #[test]
+    fn files_with_and_without_matches_override_each_other() {
+        let config = parse(&["-l", "-L", "q", "f"]).unwrap();
+        assert!(!config.files_with_matches && config.files_without_match);
+    }
+
+    #[test]
+    fn errors() {
+        assert_eq!(parse(&["-y", "x", "f"]), Err(ArgError::UnknownFlag("-y".to_string())));
+        assert_eq!(parse(&["--verbose", "x", "f"]), Err(ArgError::UnknownFlag("--verbose".to_string())));
+        let include = MINIGREP.find_long("include").unwrap();
+        assert_eq!(parse(&["x", "f", "--include"]), Err(ArgError::MissingValue(include)));
+        assert_eq!(parse(&["x"]), Err(ArgError::MissingArgument(PATH)));
+        assert_eq!(parse(&[]), Err(ArgError::MissingArgument(QUERY)));
+        assert!(matches!(parse(&["-m", "lots", "x", "f"]), Err(ArgError::InvalidValue { .. })));
+        assert!(matches!(parse(&["--color=sometimes", "x", "f"]), Err(ArgError::InvalidValue { .. })));
+        assert!(matches!(parse(&["--hidden=yes", "x", "f"]), Err(ArgError::UnexpectedValue(_))));
+
+        let message = parse(&["x", "f", "--max-count"]).unwrap_err().to_string();
+        assert_eq!(message, "missing NUM for --max-count");
+    }
+
+    #[test]
+    fn help_skips_required_arguments() {
+        let config = parse(&["--help"]).unwrap();
+        assert!(config.help);
+
+        let text = help("minigrep");
+        for flag in FLAGS {
+            assert!(text.contains(&format!("--{}", flag.long)), "help is missing --{}", flag.long);
+        }
+        assert!(text.contains("IGNORE_CASE, CASE_INSENSITIVE"));
+    }
+
+    #[test]
+    fn demo_and_no_messages() {
+        let config = parse(&["--demo"]).unwrap();
+        assert!(config.demo && config.query.is_empty());
+
+        let config = parse(&["-s", "q", "f"]).unwrap();
+        assert!(config.no_messages);
+    }
+
+    #[test]
+    fn json_and_null() {
+        let config = parse(&["--json", "-Z", "q", "f"]).unwrap();
+        assert!(config.json && config.null);
+
+        let error = parse(&["--json", "-c", "q", "f"]).unwrap_err();
+        assert_eq!(error, ArgError::Conflict("json", "count"));
+        assert_eq!(error.to_string(), "--json can't be used with --count");
+        assert!(parse(&["-l", "--json", "q", "f"]).is_err());
+    }
+
+    #[test]
+    fn machine_readable_modes_are_not_decorated() {
+        assert!(parse(&["-n", "-C", "2", "q", "f"]).unwrap().for_people());
+        for flag in ["--json", "-Z", "-c", "-l", "-L", "-o", "-q"] {
+            let config = parse(&[flag, "q", "f"]).unwrap();
+            assert!(!config.for_people() && !config.decorate(), "{} is decorated", flag);
+        }
+        let config = parse(&["-r", "x", "--dry-run", "q", "f"]).unwrap();
+        assert!(!config.decorate());
+    }
+
+    #[test]
+    fn replace_and_in_place() {
+        let config = parse(&["-E", "-r", "$2 $1", "--in-place", "(a)(b)", "f"]).unwrap();
+        assert_eq!(config.replace.as_deref(), Some("$2 $1"));
+        assert!(config.in_place && !config.dry_run);
+        assert_eq!(config.backup_suffix(), Some(".bak"));
+        assert_eq!(parse(&["-r", "x", "--in-place", "--backup=", "q", "f"]).unwrap().backup_suffix(), None);
+        assert_eq!(parse(&["-r", "x", "--dry-run", "q", "f"]).map(|c| c.dry_run), Ok(true));
+
+        let error = parse(&["--in-place", "q", "f"]).unwrap_err();
+        assert_eq!(error.to_string(), "--in-place requires --replace");
+        assert_eq!(parse(&["-r", "x", "--backup", "~", "q", "f"]), Err(ArgError::Requires("backup", "in-place")));
+        assert_eq!(parse(&["--json", "-r", "x", "--dry-run", "q", "f"]), Err(ArgError::Conflict("json", "dry-run")));
+        assert!(parse(&["-v", "-r", "x", "--in-place", "q", "f"]).is_err());
+        assert!(matches!(parse(&["-r", "${1", "q", "f"]), Err(ArgError::InvalidValue { .. })));
+    }
+
+    #[test]
+    fn index_subcommand() {
+        let config = parse(&["index", "notes", "src"]).unwrap();
+        assert_eq!(config.command, Command::Index);
+        assert_eq!(config.paths, vec!["notes", "src"]);
+        assert_eq!(parse(&["index"]).unwrap().paths, vec!["."]);
+
+        // Anywhere but first, "index" is just an argument
+        let config = parse(&["--", "index", "notes"]).unwrap();
+        assert_eq!((config.command, config.query.as_str()), (Command::Search, "index"));
+        let config = parse(&["-i", "index", "notes", "--no-index"]).unwrap();
+        assert_eq!((config.command, config.query.as_str()), (Command::Search, "index"));
+        assert!(config.no_index);
+    }
+
+    #[test]
+    fn threads_and_sort() {
+        let config = parse(&["-j4", "--sort=path", "q", "f"]).unwrap();
+        assert_eq!(config.threads, Some(4));
+        assert_eq!(config.sort, SortBy::Path);
+
+        // 0 asks for the default, one per CPU
+        assert_eq!(parse(&["-j", "0", "q", "f"]).unwrap().threads, None);
+        assert!(matches!(parse(&["--sort", "size", "q", "f"]), Err(ArgError::InvalidValue { .. })));
+    }
+
+    #[test]
+    fn exit_status() {
+        assert_eq!(Status::from_search(true, false).code(), 0);
+        assert_eq!(Status::from_search(false, false).code(), 1);
+        assert_eq!(Status::from_search(true, true).code(), 2);
+        assert_eq!(Status::from_search(false, true), Status::Error);
+    }
+
+    #[test]
+    fn ignore_case_precedence() {
+        let on = |vars: &[(&str, &str)]| parse_with_env(&["q", "f"], vars).unwrap().ignore_case;
+
+        assert!(!on(&[]));
+        assert!(on(&[("CASE_INSENSITIVE", "")]));
+        assert!(on(&[("IGNORE_CASE", "1")]));
+        // IGNORE_CASE is checked before CASE_INSENSITIVE
+        assert!(!on(&[("IGNORE_CASE", "false"), ("CASE_INSENSITIVE", "1")]));
+        assert!(on(&[("IGNORE_CASE", "yes"), ("CASE_INSENSITIVE", "0")]));
+
+        // Flags beat the environment, and the last flag wins
+        let config = parse_with_env(&["--no-ignore-case", "q", "f"], &[("IGNORE_CASE", "1")]).unwrap();
+        assert!(!config.ignore_case);
+        let config = parse_with_env(&["--no-ignore-case", "-i", "q", "f"], &[]).unwrap();
+        assert!(config.ignore_case);
+
+        let options = parse(&["-i", "--turkic", "q", "f"]).unwrap().match_options();
+        assert!(options.ignore_case && options.turkic);
+    }
+}
@@ -0,0 +1,118 @@
+//! # Match Highlighting
+//!
+//! `--color=auto|always|never` handling and ANSI highlighting of matches.
+
+use std::env;
+use std::fmt;
+use std::io::{self, IsTerminal};
+use std::str::FromStr;
+
+/// Bold red, the same default grep uses for matches
+const MATCH_STYLE: &str = "\x1b[1;31m";
+const RESET: &str = "\x1b[0m";
+
+/// When to colorize output
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ColorChoice {
+    /// Only when stdout is a terminal and `NO_COLOR` isn't set
+    #[default]
+    Auto,
+    Always,
+    Never,
+}
+
+impl ColorChoice {
+    /// Resolve the choice for the current process
+    pub fn enabled(self) -> bool {
+        match self {
+            ColorChoice::Always => true,
+            ColorChoice::Never => false,
+            ColorChoice::Auto => io::stdout().is_terminal() && env::var_os("NO_COLOR").is_none(),
+        }
+    }
+}
+
+impl FromStr for ColorChoice {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<ColorChoice, String> {
+        match s {
+            "auto" => Ok(ColorChoice::Auto),
+            "always" => Ok(ColorChoice::Always),
+            "never" => Ok(ColorChoice::Never),
+            other => Err(format!("invalid color choice '{}' (expected auto, always or never)", other)),
+        }
+    }
+}
+
+impl fmt::Display for ColorChoice {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let name = match self {
+            ColorChoice::Auto => "auto",
+            ColorChoice::Always => "always",
+            ColorChoice::Never => "never",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+/// Wrap each `(start, end)` byte span of `line` in highlight escape codes
+///
+/// Spans must be sorted and non-overlapping; empty spans are skipped.
+///
+/// # Example
+/// ```
+/// use rust_book_examples::minigrep::color::highlight;
+///
+/// let line = highlight("Trust me.", &[(1, 5)]);
+/// assert_eq!(line, "T\x1b[1;31mrust\x1b[0m me.");
+/// ```
+pub fn highlight(line: &str, spans: &[(usize, usize)]) -> String {
+    let mut result = String::with_capacity(line.len() + spans.len() * 12);
+    let mut last = 0;
+
+    for &(start, end) in spans {
+        if start == end {
+            continue;
+        }
+        result.push_str(&line[last..start]);
+        result.push_str(MATCH_STYLE);
+        result.push_str(&line[start..end]);
+        result.push_str(RESET);
+        last = end;
+    }
+
+    result.push_str(&line[last..]);
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_color_choice() {
+        assert_eq!("always".parse(), Ok(ColorChoice::Always));
+        assert_eq!("never".parse(), Ok(ColorChoice::Never));
+        assert_eq!("auto".parse(), Ok(ColorChoice::Auto));
+        assert!("sometimes".parse::<ColorChoice>().is_err());
+    }
+
+    #[test]
+    fn explicit_choices_ignore_the_terminal() {
+        assert!(ColorChoice::Always.enabled());
+        assert!(!ColorChoice::Never.enabled());
+    }
+
+    #[test]
+    fn highlight_multiple_spans() {
+        let line = highlight("rust and Rust", &[(0, 4), (9, 13)]);
+        assert_eq!(line, "\x1b[1;31mrust\x1b[0m and \x1b[1;31mRust\x1b[0m");
+    }
+
+    #[test]
+    fn highlight_without_spans_is_unchanged() {
+        assert_eq!(highlight("plain", &[]), "plain");
+        assert_eq!(highlight("plain", &[(2, 2)]), "plain");
+    }
+}
@@ -0,0 +1,197 @@
+//! # Context Lines
+//!
+//! grep-style `-B`/`-A`/`-C` context around matching lines.
+//!
+//! [`ContextWindow`] is fed one line at a time, so it works the same whether
+//! the caller holds the whole file in memory or reads it line by line.
+//! Lines that fall in overlapping windows are printed once, and a
+//! [`ContextItem::Separator`] is emitted between groups that aren't adjacent.
+
+use std::collections::VecDeque;
+
+/// Something the caller should print
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ContextItem<'a> {
+    /// A line with its 1-based number; `is_match` is false for context lines
+    Line {
+        number: usize,
+        text: &'a str,
+        is_match: bool,
+    },
+    /// The `--` printed between non-adjacent groups
+    Separator,
+}
+
+/// Tracks which lines around the matches need printing
+///
+/// # Example
+/// ```
+/// use rust_book_examples::minigrep::context::{ContextItem, ContextWindow};
+///
+/// let lines = ["a", "match", "b", "c", "d", "match"];
+/// let mut window = ContextWindow::new(1, 0);
+/// let mut printed = Vec::new();
+///
+/// for (i, line) in lines.iter().enumerate() {
+///     window.push(i + 1, line, *line == "match", |item| {
+///         printed.push(match item {
+///             ContextItem::Line { text, .. } => text.to_string(),
+///             ContextItem::Separator => "--".to_string(),
+///         })
+///     });
+/// }
+///
+/// assert_eq!(printed, vec!["a", "match", "--", "d", "match"]);
+/// ```
+#[derive(Debug, Clone)]
+pub struct ContextWindow {
+    before: usize,
+    after: usize,
+    /// Recent lines that weren't printed, kept in case a match follows
+    buffer: VecDeque<(usize, String)>,
+    after_remaining: usize,
+    last_printed: Option<usize>,
+    /// Set when switching files after something was printed
+    separator_pending: bool,
+}
+
+impl ContextWindow {
+    /// Create a window showing `before` lines before and `after` lines after each match
+    pub fn new(before: usize, after: usize) -> ContextWindow {
+        ContextWindow {
+            before,
+            after,
+            buffer: VecDeque::with_capacity(before),
+            after_remaining: 0,
+            last_printed: None,
+            separator_pending: false,
+        }
+    }
+
+    /// Whether any context lines were requested
+    pub fn has_context(&self) -> bool {
+        self.before > 0 || self.after > 0
+    }
+
+    /// Feed the next line and emit whatever should be printed because of it
+    pub fn push<F>(&mut self, number: usize, text: &str, is_match: bool, mut emit: F)
+    where
+        F: FnMut(ContextItem<'_>),
+    {
+        if is_match {
+            let buffered: Vec<(usize, String)> = self.buffer.drain(..).collect();
+            for (buffered_number, buffered_text) in &buffered {
+                self.emit_line(*buffered_number, buffered_text, false, &mut emit);
+            }
+            self.emit_line(number, text, true, &mut emit);
+            self.after_remaining = self.after;
+        } else if self.after_remaining > 0 {
+            self.emit_line(number, text, false, &mut emit);
+            self.after_remaining -= 1;
+        } else if self.before > 0 {
+            if self.buffer.len() == self.before {
+                self.buffer.pop_front();
+            }
+            self.buffer.push_back((number, text.to_string()));
+        }
+    }
+
+    /// Prepare for the next file; its first group is separated from this one
+    pub fn next_file(&mut self) {
+        self.separator_pending = self.last_printed.is_some() || self.separator_pending;
+        self.buffer.clear();
+        self.after_remaining = 0;
+        self.last_printed = None;
+    }
+
+    fn emit_line<F>(&mut self, number: usize, text: &str, is_match: bool, emit: &mut F)
+    where
+        F: FnMut(ContextItem<'_>),
+    {
+        if self.has_context() {
+            let gap = self.last_printed.is_some_and(|last| number > last + 1);
+            if gap || (self.last_printed.is_none() && self.separator_pending) {
+                emit(ContextItem::Separator);
+                self.separator_pending = false;
+            }
+        }
+        self.last_printed = Some(number);
+        emit(ContextItem::Line { number, text, is_match });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Render the window's output like grep: `N:match`, `N-context`, `--`
+    fn render(window: &mut ContextWindow, lines: &[&str], query: &str) -> Vec<String> {
+        let mut out = Vec::new();
+        for (i, line) in lines.iter().enumerate() {
+            window.push(i + 1, line, line.contains(query), |item| {
+                out.push(match item {
+                    ContextItem::Line { number, text, is_match: true } => format!("{}:{}", number, text),
+                    ContextItem::Line { number, text, .. } => format!("{}-{}", number, text),
+                    ContextItem::Separator => "--".to_string(),
+                })
+            });
+        }
+        out
+    }
+
+    const LINES: [&str; 9] = ["one", "two", "x three", "four", "five", "six", "x seven", "eight", "nine"];
+
+    #[test]
+    fn no_context_prints_only_matches_without_separators() {
+        let mut window = ContextWindow::new(0, 0);
+        assert_eq!(render(&mut window, &LINES, "x "), vec!["3:x three", "7:x seven"]);
+    }
+
+    #[test]
+    fn after_context() {
+        let mut window = ContextWindow::new(0, 1);
+        assert_eq!(
+            render(&mut window, &LINES, "x "),
+            vec!["3:x three", "4-four", "--", "7:x seven", "8-eight"]
+        );
+    }
+
+    #[test]
+    fn before_context_is_limited_to_available_lines() {
+        let mut window = ContextWindow::new(3, 0);
+        assert_eq!(
+            render(&mut window, &LINES, "x "),
+            vec!["1-one", "2-two", "3:x three", "4-four", "5-five", "6-six", "7:x seven"]
+        );
+    }
+
+    #[test]
+    fn overlapping_windows_merge() {
+        let mut window = ContextWindow::new(2, 2);
+        assert_eq!(
+            render(&mut window, &LINES, "x "),
+            vec![
+                "1-one", "2-two", "3:x three", "4-four", "5-five", "6-six", "7:x seven", "8-eight", "9-nine"
+            ]
+        );
+    }
+
+    #[test]
+    fn adjacent_matches_share_context() {
+        let lines = ["a", "x1", "x2", "b", "c", "d", "x3"];
+        let mut window = ContextWindow::new(1, 1);
+        assert_eq!(
+            render(&mut window, &lines, "x"),
+            vec!["1-a", "2:x1", "3:x2", "4-b", "--", "6-d", "7:x3"]
+        );
+    }
+
+    #[test]
+    fn separator_between_files() {
+        let mut window = ContextWindow::new(0, 1);
+        let mut out = render(&mut window, &["x", "y"], "x");
+        window.next_file();
+        out.extend(render(&mut window, &["z", "x"], "x"));
+        assert_eq!(out, vec!["1:x", "2-y", "--", "2:x"]);
+    }
+}
@@ -0,0 +1,315 @@
+//! # Fuzzy Search
+//!
+//! `--fuzzy` finds lines that only roughly contain the query and ranks them,
+//! best first. A line can match in two ways:
+//!
+//! - **Subsequence**: the query's characters appear in order, perhaps with
+//!   gaps, as in `brwchk` for "borrow checker". Runs of consecutive
+//!   characters and characters at the start of words score extra; gaps cost,
+//!   opening one more than widening it.
+//! - **Edit distance**: failing that, some stretch of the line is within a
+//!   few typos (insertions, deletions or substitutions) of the query, as in
+//!   `lifetmie` for "lifetime". Each typo costs more than a gap.
+//!
+//! Every match records which characters of the line it used, so they can be
+//! highlighted.
+
+use super::casefold::simple_fold;
+
+/// How a line matched a fuzzy query
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FuzzyMatch {
+    /// Higher is better
+    pub score: i32,
+    /// Byte offsets of the line's characters that matched the query
+    pub positions: Vec<usize>,
+    /// Typos needed; 0 for a subsequence match
+    pub typos: usize,
+}
+
+impl FuzzyMatch {
+    /// The matched characters as byte spans, with neighbours merged, ready for
+    /// [`highlight`](super::color::highlight)
+    pub fn spans(&self, line: &str) -> Vec<(usize, usize)> {
+        let mut spans: Vec<(usize, usize)> = Vec::new();
+        for &start in &self.positions {
+            let end = start + line[start..].chars().next().map_or(0, char::len_utf8);
+            match spans.last_mut() {
+                Some(last) if last.1 == start => last.1 = end,
+                _ => spans.push((start, end)),
+            }
+        }
+        spans
+    }
+}
+
+/// A line that matched, as ranked by [`rank`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Ranked {
+    pub line_number: usize,
+    pub line: String,
+    pub fuzzy: FuzzyMatch,
+}
+
+const MATCH: i32 = 16;
+const CONSECUTIVE: i32 = 8;
+const WORD_START: i32 = 10;
+const GAP_START: i32 = 3;
+const GAP_EXTEND: i32 = 1;
+const TYPO: i32 = 24;
+
+/// Score `line` against `query`, or `None` if it doesn't match even roughly
+///
+/// # Example
+/// ```
+/// use rust_book_examples::minigrep::fuzzy::fuzzy_match;
+///
+/// let exact = fuzzy_match("borrow", "The borrow checker", false).unwrap();
+/// let scattered = fuzzy_match("brwchk", "The borrow checker", false).unwrap();
+/// let typo = fuzzy_match("lifetmie", "Validating lifetimes", false).unwrap();
+/// assert!(exact.score > scattered.score && typo.typos > 0);
+/// assert_eq!(exact.positions, vec![4, 5, 6, 7, 8, 9]);
+///
+/// assert!(fuzzy_match("ownership", "Smart pointers", false).is_none());
+/// ```
+pub fn fuzzy_match(query: &str, line: &str, ignore_case: bool) -> Option<FuzzyMatch> {
+    let fold = |c: char| if ignore_case { simple_fold(c) } else { c };
+    let query: Vec<char> = query.chars().map(fold).collect();
+    let chars: Vec<(usize, char)> = line.char_indices().collect();
+    if query.is_empty() {
+        return None;
+    }
+    let folded: Vec<char> = chars.iter().map(|&(_, c)| fold(c)).collect();
+
+    subsequence(&query, &folded)
+        .map(|matched| FuzzyMatch {
+            score: score(&matched, &chars),
+            positions: matched.iter().map(|&i| chars[i].0).collect(),
+            typos: 0,
+        })
+        .or_else(|| {
+            let (typos, matched) = closest_substring(&query, &folded, query.len() / 4)?;
+            Some(FuzzyMatch {
+                score: MATCH * matched.len() as i32 - TYPO * typos as i32,
+                positions: matched.iter().map(|&i| chars[i].0).collect(),
+                typos,
+            })
+        })
+}
+
+/// Score every line, keeping the `top` best, best first
+///
+/// Takes the `(line number, line)` pairs the minigrep examples' `search`
+/// functions return. Equal scores keep line order.
+///
+/// # Example
+/// ```
+/// use rust_book_examples::minigrep::fuzzy::rank;
+///
+/// let lines = vec![(1, "Pick three.".to_string()), (2, "Pick one: three".to_string()), (3, "Duct tape.".to_string())];
+/// let ranked = rank("pick three", lines, true, 10);
+/// assert_eq!(ranked.iter().map(|r| r.line_number).collect::<Vec<_>>(), vec![1, 2]);
+/// ```
+pub fn rank<I>(query: &str, lines: I, ignore_case: bool, top: usize) -> Vec<Ranked>
+where
+    I: IntoIterator<Item = (usize, String)>,
+{
+    let mut ranked: Vec<Ranked> = lines
+        .into_iter()
+        .filter_map(|(line_number, line)| {
+            let fuzzy = fuzzy_match(query, &line, ignore_case)?;
+            Some(Ranked { line_number, line, fuzzy })
+        })
+        .collect();
+    ranked.sort_by(|a, b| b.fuzzy.score.cmp(&a.fuzzy.score).then(a.line_number.cmp(&b.line_number)));
+    ranked.truncate(top);
+    ranked
+}
+
+/// Indexes of the shortest window of `text` holding `query` in order
+fn subsequence(query: &[char], text: &[char]) -> Option<Vec<usize>> {
+    // Find where the first complete match ends...
+    let mut next = 0;
+    let mut end = None;
+    for (i, &c) in text.iter().enumerate() {
+        if c == query[next] {
+            next += 1;
+            if next == query.len() {
+                end = Some(i);
+                break;
+            }
+        }
+    }
+    let end = end?;
+
+    // ...walk back from there to the latest start...
+    let mut start = end;
+    let mut remaining = query.len();
+    for i in (0..=end).rev() {
+        if text[i] == query[remaining - 1] {
+            remaining -= 1;
+            if remaining == 0 {
+                start = i;
+                break;
+            }
+        }
+    }
+
+    // ...then match forward again inside that tighter window
+    let mut matched = Vec::with_capacity(query.len());
+    for (i, &c) in text.iter().enumerate().take(end + 1).skip(start) {
+        if matched.len() < query.len() && c == query[matched.len()] {
+            matched.push(i);
+        }
+    }
+    Some(matched)
+}
+
+fn score(matched: &[usize], chars: &[(usize, char)]) -> i32 {
+    let mut score = 0;
+    for (n, &i) in matched.iter().enumerate() {
+        score += MATCH;
+        match n.checked_sub(1).map(|p| i - matched[p] - 1) {
+            Some(0) => score += CONSECUTIVE,
+            // Opening a gap costs more than widening it, so one long gap beats many short ones
+            Some(gap) => score -= GAP_START + GAP_EXTEND * gap as i32,
+            None => {}
+        }
+        if is_word_start(chars, i) {
+            score += WORD_START;
+        }
+    }
+    score
+}
+
+/// The start of a word, or a capital inside camelCase
+fn is_word_start(chars: &[(usize, char)], i: usize) -> bool {
+    let c = chars[i].1;
+    match i.checked_sub(1).map(|p| chars[p].1) {
+        None => true,
+        Some(prev) => !prev.is_alphanumeric() && c.is_alphanumeric() || prev.is_lowercase() && c.is_uppercase(),
+    }
+}
+
+/// The fewest typos turning `query` into some stretch of `text`, up to
+/// `max_typos`, with the indexes of the characters that matched exactly
+///
+/// Sellers' variant of edit distance: a match may start anywhere in `text`.
+/// Two rows of the table find the cheapest end; only the stretch that a
+/// match ending there can cover gets a full table to trace back through, so
+/// memory stays proportional to the line plus the query squared.
+fn closest_substring(query: &[char], text: &[char], max_typos: usize) -> Option<(usize, Vec<usize>)> {
+    if max_typos == 0 || text.is_empty() {
+        return None;
+    }
+    let mut previous = vec![0usize; text.len() + 1];
+    let mut current = vec![0usize; text.len() + 1];
+    for i in 1..=query.len() {
+        current[0] = i;
+        for j in 1..=text.len() {
+            let substitute = previous[j - 1] + usize::from(query[i - 1] != text[j - 1]);
+            current[j] = substitute.min(previous[j] + 1).min(current[j - 1] + 1);
+        }
+        std::mem::swap(&mut previous, &mut current);
+    }
+
+    let (end, typos) = (1..=text.len()).map(|j| (j, previous[j])).min_by_key(|&(_, typos)| typos)?;
+    if typos > max_typos {
+        return None;
+    }
+
+    // With `typos` typos the match covers at most `query.len() + typos` characters
+    let start = end.saturating_sub(query.len() + typos);
+    let window = &text[start..end];
+    let (rows, columns) = (query.len() + 1, window.len() + 1);
+    let mut cost = vec![0usize; rows * columns];
+    for i in 1..rows {
+        cost[i * columns] = i;
+        for j in 1..columns {
+            let substitute = cost[(i - 1) * columns + j - 1] + usize::from(query[i - 1] != window[j - 1]);
+            let skip_query = cost[(i - 1) * columns + j] + 1;
+            let skip_text = cost[i * columns + j - 1] + 1;
+            cost[i * columns + j] = substitute.min(skip_query).min(skip_text);
+        }
+    }
+
+    // Trace the cheapest path back, keeping the characters that matched
+    let mut matched = Vec::new();
+    let (mut i, mut j) = (query.len(), window.len());
+    while i > 0 && j > 0 {
+        let here = cost[i * columns + j];
+        let same = query[i - 1] == window[j - 1];
+        if here == cost[(i - 1) * columns + j - 1] + usize::from(!same) {
+            if same {
+                matched.push(start + j - 1);
+            }
+            i -= 1;
+            j -= 1;
+        } else if here == cost[(i - 1) * columns + j] + 1 {
+            i -= 1;
+        } else {
+            j -= 1;
+        }
+    }
+    matched.reverse();
+    Some((typos, matched))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn subsequence_takes_the_tightest_window() {
+        let m = fuzzy_match("ab", "a...a b", false).unwrap();
+        assert_eq!(m.positions, vec![4, 6]);
+        assert_eq!(m.spans("a...a b"), vec![(4, 5), (6, 7)]);
+
+        let m = fuzzy_match("RUST", "Trust me", true).unwrap();
+        assert_eq!(m.spans("Trust me"), vec![(1, 5)]);
+        assert!(fuzzy_match("RUST", "Trust me", false).is_none());
+    }
+
+    #[test]
+    fn word_starts_and_runs_score_higher() {
+        let score = |query, line| fuzzy_match(query, line, true).unwrap().score;
+        assert!(score("bc", "borrow checker") > score("bc", "abacus"));
+        assert!(score("sp", "SmartPointer") > score("sp", "transport"));
+        assert!(score("three", "three") > score("three", "the rest of the line"));
+    }
+
+    #[test]
+    fn typos_fall_back_to_edit_distance() {
+        let line = "Rust’s ownership: borrowing";
+        let m = fuzzy_match("ownreship", line, false).unwrap();
+        assert_eq!(m.typos, 2);
+        assert!(m.positions.iter().all(|&p| line[p..].starts_with(|c| "ownership".contains(c))));
+
+        // Short queries must match exactly as a subsequence
+        assert!(fuzzy_match("fnx", "fn main", false).is_none());
+        assert!(fuzzy_match("lifetmie", "smart pointers", false).is_none());
+        let multibyte = fuzzy_match("cafe", "Un café à Paris", false).unwrap();
+        assert_eq!(multibyte.spans("Un café à Paris"), vec![(3, 6)]);
+    }
+
+    #[test]
+    fn typos_are_found_far_into_a_long_line() {
+        let query: Vec<char> = "ownreship".chars().collect();
+        let text: Vec<char> = format!("{}ownership", "x".repeat(100_000)).chars().collect();
+        let (typos, matched) = closest_substring(&query, &text, 2).unwrap();
+        assert_eq!(typos, 2);
+        assert_eq!(matched.first(), Some(&100_000));
+        assert_eq!(matched.last(), Some(&100_008));
+    }
+
+    #[test]
+    fn ranking_is_stable_and_truncated() {
+        let lines: Vec<(usize, String)> = ["rust", "Rust", "trust", "crate"]
+            .iter()
+            .enumerate()
+            .map(|(i, line)| (i + 1, line.to_string()))
+            .collect();
+        let ranked = rank("rust", lines, true, 2);
+        assert_eq!(ranked.iter().map(|r| r.line_number).collect::<Vec<_>>(), vec![1, 2]);
+    }
+}
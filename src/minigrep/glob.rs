@@ -0,0 +1,222 @@
+//! # Glob Patterns
+//!
+//! Shell-style wildcards for `--include`/`--exclude` filters and `.gitignore` rules.
+//!
+//! - `*` matches any run of characters except `/`
+//! - `?` matches one character except `/`
+//! - `**` matches across directories, as in `src/**/*.rs`
+//! - `[abc]`, `[a-z]`, `[!0-9]` match one character from a set
+//! - `\` escapes the next character
+//!
+//! A glob without a `/` is matched against the file name only, so `*.md`
+//! finds markdown files at any depth. A glob containing `/` is matched
+//! against the whole relative path.
+//!
+//! Globs are translated into a [`Regex`] and matched with the minigrep
+//! regex engine.
+
+use super::regex::{escape, Regex, RegexError};
+use std::fmt;
+
+/// Errors produced while compiling a glob
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum GlobError {
+    /// A `[` without a matching `]`
+    UnclosedClass(String),
+    /// The translated pattern was rejected by the regex engine
+    Invalid(String, RegexError),
+}
+
+impl fmt::Display for GlobError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            GlobError::UnclosedClass(glob) => write!(f, "unclosed character class in glob '{}'", glob),
+            GlobError::Invalid(glob, err) => write!(f, "invalid glob '{}': {}", glob, err),
+        }
+    }
+}
+
+impl std::error::Error for GlobError {}
+
+/// A compiled glob pattern
+///
+/// # Example
+/// ```
+/// use rust_book_examples::minigrep::glob::Glob;
+///
+/// let glob = Glob::new("*.md").unwrap();
+/// assert!(glob.is_match("notes/chapter-12.md"));
+/// assert!(!glob.is_match("src/lib.rs"));
+///
+/// let glob = Glob::new("src/**/*.rs").unwrap();
+/// assert!(glob.is_match("src/minigrep/glob.rs"));
+/// ```
+#[derive(Debug, Clone)]
+pub struct Glob {
+    pattern: String,
+    regex: Regex,
+    match_path: bool,
+}
+
+impl Glob {
+    /// Compile a glob pattern
+    pub fn new(pattern: &str) -> Result<Glob, GlobError> {
+        let source = translate(pattern)?;
+        let regex = Regex::new(&source).map_err(|e| GlobError::Invalid(pattern.to_string(), e))?;
+
+        Ok(Glob {
+            pattern: pattern.to_string(),
+            regex,
+            match_path: pattern.contains('/'),
+        })
+    }
+
+    /// The source pattern
+    pub fn as_str(&self) -> &str {
+        &self.pattern
+    }
+
+    /// Whether this glob is matched against whole paths instead of file names
+    pub fn matches_whole_path(&self) -> bool {
+        self.match_path
+    }
+
+    /// Match a `/`-separated relative path
+    ///
+    /// Uses the whole path or just the file name, depending on the pattern.
+    pub fn is_match(&self, relative_path: &str) -> bool {
+        if self.match_path {
+            self.regex.is_match(relative_path)
+        } else {
+            let name = relative_path.rsplit('/').next().unwrap_or(relative_path);
+            self.regex.is_match(name)
+        }
+    }
+
+    /// Match against the whole relative path, even if the pattern has no `/`
+    pub fn is_match_path(&self, relative_path: &str) -> bool {
+        self.regex.is_match(relative_path)
+    }
+}
+
+impl fmt::Display for Glob {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.pattern)
+    }
+}
+
+/// Translate a glob into an anchored regular expression
+fn translate(glob: &str) -> Result<String, GlobError> {
+    let chars: Vec<char> = glob.chars().collect();
+    let mut regex = String::from("^");
+    let mut i = 0;
+
+    while i < chars.len() {
+        match chars[i] {
+            '*' if chars.get(i + 1) == Some(&'*') => {
+                let at_segment_start = i == 0 || chars[i - 1] == '/';
+                let before_slash = chars.get(i + 2) == Some(&'/');
+                if at_segment_start && before_slash {
+                    // `**/` matches zero or more whole directories
+                    regex.push_str("(?:.*/)?");
+                    i += 3;
+                } else {
+                    regex.push_str(".*");
+                    i += 2;
+                }
+                continue;
+            }
+            '*' => regex.push_str("[^/]*"),
+            '?' => regex.push_str("[^/]"),
+            '[' => {
+                let end = chars[i + 1..]
+                    .iter()
+                    .skip(1)
+                    .position(|&c| c == ']')
+                    .map(|offset| i + 2 + offset)
+                    .ok_or_else(|| GlobError::UnclosedClass(glob.to_string()))?;
+
+                regex.push('[');
+                let mut body = &chars[i + 1..end];
+                if let Some('!') | Some('^') = body.first() {
+                    regex.push('^');
+                    body = &body[1..];
+                }
+                for &c in body {
+                    if c == '\\' || c == '[' {
+                        regex.push('\\');
+                    }
+                    regex.push(c);
+                }
+                regex.push(']');
+                i = end;
+            }
+            '\\' if i + 1 < chars.len() => {
+                i += 1;
+                regex.push_str(&escape(&chars[i].to_string()));
+            }
+            c => regex.push_str(&escape(&c.to_string())),
+        }
+        i += 1;
+    }
+
+    regex.push('$');
+    Ok(regex)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn star_and_question_mark() {
+        let glob = Glob::new("chapter-1?.md").unwrap();
+        assert!(glob.is_match("chapter-12.md"));
+        assert!(!glob.is_match("chapter-1.md"));
+
+        let glob = Glob::new("*.rs").unwrap();
+        assert!(glob.is_match("examples/ch12_01_accepting_cli_args.rs"));
+        assert!(!glob.is_match("Cargo.toml"));
+    }
+
+    #[test]
+    fn path_globs_do_not_cross_directories_with_single_star() {
+        let glob = Glob::new("src/*.rs").unwrap();
+        assert!(glob.is_match("src/lib.rs"));
+        assert!(!glob.is_match("src/minigrep/regex.rs"));
+    }
+
+    #[test]
+    fn double_star_spans_directories() {
+        let glob = Glob::new("**/target/**").unwrap();
+        assert!(glob.is_match("target/debug/app"));
+        assert!(glob.is_match("a/b/target/x"));
+
+        let glob = Glob::new("notes/**/*.md").unwrap();
+        assert!(glob.is_match("notes/INDEX.md"));
+        assert!(glob.is_match("notes/old/chapter-01.md"));
+    }
+
+    #[test]
+    fn character_classes() {
+        let glob = Glob::new("ch[0-9][0-9]_*.rs").unwrap();
+        assert!(glob.is_match("ch08_01_vectors.rs"));
+        assert!(!glob.is_match("chXY_vectors.rs"));
+
+        let glob = Glob::new("*.[!o]").unwrap();
+        assert!(glob.is_match("main.c"));
+        assert!(!glob.is_match("main.o"));
+
+        assert_eq!(
+            Glob::new("[abc").unwrap_err(),
+            GlobError::UnclosedClass("[abc".to_string())
+        );
+    }
+
+    #[test]
+    fn regex_metacharacters_are_literal() {
+        let glob = Glob::new("a+b(1).txt").unwrap();
+        assert!(glob.is_match("a+b(1).txt"));
+        assert!(!glob.is_match("aab1xtxt"));
+    }
+}
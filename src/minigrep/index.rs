@@ -0,0 +1,449 @@
+//! # Trigram Index
+//!
+//! `minigrep index DIR` records which three-character sequences (trigrams)
+//! appear in each file under `DIR`, in a `.minigrep-index` file at its root.
+//! A later search of `DIR` for a literal query only has to read the files
+//! that contain every trigram of the query; the rest can't match.
+//!
+//! - Text is case folded before indexing, so one index serves `-i` too
+//! - Running `minigrep index` again only re-reads files whose modification
+//!   time or size changed, and drops files that are gone
+//! - A file changed since it was indexed is always searched, so a stale
+//!   index makes a search slower, never wrong
+//! - Regular expressions, `--turkic`, and queries shorter than three
+//!   characters can't be pruned and search every file
+//!
+//! ## File format
+//!
+//! A line-based text file: a `minigrep-index 1` header, one `file` line per
+//! indexed file (`file SECS NANOS SIZE PATH`, with `PATH` relative to the
+//! root), then one `trigram` line per trigram listing the numbers of the
+//! files that contain it (`trigram HEX ID...`).
+
+use super::casefold::CaseFolder;
+use super::regex;
+use super::walk::{walk, WalkOptions};
+use crate::fs_util;
+use super::MatchOptions;
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::time::UNIX_EPOCH;
+
+/// Name of the index file kept at the root of an indexed directory
+pub const INDEX_FILE: &str = ".minigrep-index";
+
+const HEADER: &str = "minigrep-index 1";
+
+/// Three characters packed into one number, 21 bits each
+pub type Trigram = u64;
+
+/// Errors from loading an index
+#[derive(Debug)]
+pub enum IndexError {
+    Io(io::Error),
+    /// The index file is damaged; rebuild it with `minigrep index`
+    Corrupt { line: usize, message: String },
+}
+
+impl fmt::Display for IndexError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            IndexError::Io(e) => write!(f, "{}", e),
+            IndexError::Corrupt { line, message } => write!(f, "corrupt index at line {}: {}", line, message),
+        }
+    }
+}
+
+impl std::error::Error for IndexError {}
+
+impl From<io::Error> for IndexError {
+    fn from(e: io::Error) -> IndexError {
+        IndexError::Io(e)
+    }
+}
+
+/// How many files an [`Index::update`] touched
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct UpdateStats {
+    pub added: usize,
+    pub updated: usize,
+    pub removed: usize,
+    pub unchanged: usize,
+}
+
+/// When a file was indexed: its modification time and size
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct Stamp {
+    secs: u64,
+    nanos: u32,
+    size: u64,
+}
+
+impl Stamp {
+    fn of(path: &Path) -> io::Result<Stamp> {
+        let metadata = fs::metadata(path)?;
+        let modified = metadata.modified()?.duration_since(UNIX_EPOCH).unwrap_or_default();
+        Ok(Stamp {
+            secs: modified.as_secs(),
+            nanos: modified.subsec_nanos(),
+            size: metadata.len(),
+        })
+    }
+}
+
+#[derive(Debug, Clone)]
+struct Entry {
+    stamp: Stamp,
+    trigrams: BTreeSet<Trigram>,
+}
+
+/// The trigram index of one directory
+///
+/// # Example
+/// ```no_run
+/// use rust_book_examples::minigrep::index::{Index, IndexQuery};
+/// use rust_book_examples::minigrep::walk::WalkOptions;
+/// use rust_book_examples::minigrep::MatchOptions;
+///
+/// let mut index = Index::open("notes").unwrap();
+/// index.update(&WalkOptions::default());
+/// index.save().unwrap();
+///
+/// let query = IndexQuery::new(&["ownership"], &MatchOptions::default()).unwrap();
+/// println!("{} files to search", index.candidates(&query).len());
+/// ```
+#[derive(Debug, Clone)]
+pub struct Index {
+    root: PathBuf,
+    /// Keyed by path relative to the root, so output stays sorted
+    files: BTreeMap<PathBuf, Entry>,
+}
+
+impl Index {
+    /// An empty index of `root`, ready for [`Index::update`]
+    pub fn new(root: impl AsRef<Path>) -> Index {
+        Index {
+            root: root.as_ref().to_path_buf(),
+            files: BTreeMap::new(),
+        }
+    }
+
+    /// Load the index of `root`, or start an empty one if it has none
+    pub fn open(root: impl AsRef<Path>) -> Result<Index, IndexError> {
+        Ok(Index::load(root.as_ref())?.unwrap_or_else(|| Index::new(root)))
+    }
+
+    /// Load the index of `root`, or `None` if it has none
+    pub fn load(root: impl AsRef<Path>) -> Result<Option<Index>, IndexError> {
+        let root = root.as_ref();
+        let text = match fs::read_to_string(root.join(INDEX_FILE)) {
+            Ok(text) => text,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(None),
+            Err(e) => return Err(e.into()),
+        };
+        let files = parse(&text)?;
+        Ok(Some(Index {
+            root: root.to_path_buf(),
+            files,
+        }))
+    }
+
+    /// The directory this index covers
+    pub fn root(&self) -> &Path {
+        &self.root
+    }
+
+    /// Number of indexed files
+    pub fn len(&self) -> usize {
+        self.files.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.files.is_empty()
+    }
+
+    /// Bring the index up to date with the files under the root
+    ///
+    /// Only new files and files whose modification time or size changed are
+    /// read. Files that can't be read are left out, so searches still read them.
+    pub fn update(&mut self, options: &WalkOptions) -> UpdateStats {
+        let mut stats = UpdateStats::default();
+        let mut seen = BTreeSet::new();
+
+        for path in walk(&[&self.root], options).into_iter().filter_map(Result::ok) {
+            let Ok(relative) = path.strip_prefix(&self.root).map(Path::to_path_buf) else {
+                continue;
+            };
+            // Paths are stored one per line, so these can't be
+            if relative.to_str().is_none_or(|p| p.contains('\n')) {
+                continue;
+            }
+            let Ok(stamp) = Stamp::of(&path) else {
+                continue;
+            };
+            seen.insert(relative.clone());
+
+            match self.files.get(&relative) {
+                Some(entry) if entry.stamp == stamp => {
+                    stats.unchanged += 1;
+                    continue;
+                }
+                Some(_) => stats.updated += 1,
+                None => stats.added += 1,
+            }
+            match fs::read(&path) {
+                Ok(bytes) => {
+                    let trigrams = trigrams(&String::from_utf8_lossy(&bytes));
+                    self.files.insert(relative, Entry { stamp, trigrams });
+                }
+                Err(_) => {
+                    self.files.remove(&relative);
+                }
+            }
+        }
+
+        let before = self.files.len();
+        self.files.retain(|path, _| seen.contains(path));
+        stats.removed = before - self.files.len();
+        stats
+    }
+
+    /// Write the index to `.minigrep-index` in the root, atomically
+    pub fn save(&self) -> io::Result<()> {
+        let mut text = format!("{}\n", HEADER);
+        let mut postings: BTreeMap<Trigram, Vec<usize>> = BTreeMap::new();
+        for (id, (path, entry)) in self.files.iter().enumerate() {
+            let Stamp { secs, nanos, size } = entry.stamp;
+            text.push_str(&format!("file {} {} {} {}\n", secs, nanos, size, path.display()));
+            for &trigram in &entry.trigrams {
+                postings.entry(trigram).or_default().push(id);
+            }
+        }
+        for (trigram, ids) in postings {
+            let ids: Vec<String> = ids.iter().map(usize::to_string).collect();
+            text.push_str(&format!("trigram {:x} {}\n", trigram, ids.join(" ")));
+        }
+        fs_util::write_atomically(&self.root.join(INDEX_FILE), text.as_bytes(), None)
+    }
+
+    /// Whether `path` has to be searched for `query`
+    ///
+    /// True unless `path` is under the root, was indexed, hasn't changed
+    /// since, and lacks one of the query's trigrams.
+    pub fn may_match(&self, path: &Path, query: &IndexQuery) -> bool {
+        let Some(entry) = path.strip_prefix(&self.root).ok().and_then(|relative| self.files.get(relative)) else {
+            return true;
+        };
+        if Stamp::of(path).ok() != Some(entry.stamp) {
+            return true;
+        }
+        query.matches(&entry.trigrams)
+    }
+
+    /// The indexed files that may match `query`, as paths under the root
+    pub fn candidates(&self, query: &IndexQuery) -> Vec<PathBuf> {
+        self.files
+            .iter()
+            .filter(|(_, entry)| query.matches(&entry.trigrams))
+            .map(|(path, _)| self.root.join(path))
+            .collect()
+    }
+}
+
+/// The trigrams each of a search's patterns needs; a file may match if it
+/// has all the trigrams of any one pattern
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct IndexQuery {
+    alternatives: Vec<BTreeSet<Trigram>>,
+}
+
+impl IndexQuery {
+    /// The query for `patterns`, or `None` if the index can't narrow it down
+    ///
+    /// # Example
+    /// ```
+    /// use rust_book_examples::minigrep::index::IndexQuery;
+    /// use rust_book_examples::minigrep::MatchOptions;
+    ///
+    /// assert!(IndexQuery::new(&["borrow"], &MatchOptions::default()).is_some());
+    /// // Too short to have a trigram
+    /// assert!(IndexQuery::new(&["fn"], &MatchOptions::default()).is_none());
+    /// ```
+    pub fn new<S: AsRef<str>>(patterns: &[S], options: &MatchOptions) -> Option<IndexQuery> {
+        if options.turkic || patterns.is_empty() {
+            return None;
+        }
+        let mut alternatives = Vec::new();
+        for pattern in patterns {
+            let pattern = pattern.as_ref();
+            // A regex without metacharacters is still a literal
+            if options.regex && regex::escape(pattern) != pattern {
+                return None;
+            }
+            let needed = trigrams(pattern);
+            if needed.is_empty() {
+                return None;
+            }
+            alternatives.push(needed);
+        }
+        Some(IndexQuery { alternatives })
+    }
+
+    fn matches(&self, trigrams: &BTreeSet<Trigram>) -> bool {
+        self.alternatives.iter().any(|needed| needed.is_subset(trigrams))
+    }
+}
+
+/// The trigrams of `text` after case folding
+fn trigrams(text: &str) -> BTreeSet<Trigram> {
+    let folded: Vec<char> = CaseFolder::default().fold(text).chars().collect();
+    folded
+        .windows(3)
+        .map(|w| (u64::from(w[0]) << 42) | (u64::from(w[1]) << 21) | u64::from(w[2]))
+        .collect()
+}
+
+fn parse(text: &str) -> Result<BTreeMap<PathBuf, Entry>, IndexError> {
+    let mut lines = text.lines().enumerate().map(|(i, line)| (i + 1, line));
+    match lines.next() {
+        Some((_, HEADER)) => {}
+        _ => return Err(corrupt(1, "missing header")),
+    }
+
+    let mut paths = Vec::new();
+    let mut entries: HashMap<usize, Entry> = HashMap::new();
+    for (number, line) in lines {
+        let bad = |message: &str| corrupt(number, message);
+        if let Some(rest) = line.strip_prefix("file ") {
+            let mut fields = rest.splitn(4, ' ');
+            let mut number_field = || fields.next().and_then(|f| f.parse::<u64>().ok());
+            let (Some(secs), Some(nanos), Some(size)) = (number_field(), number_field(), number_field()) else {
+                return Err(bad("bad file stamp"));
+            };
+            let path = fields.next().filter(|p| !p.is_empty()).ok_or_else(|| bad("missing path"))?;
+            let nanos = u32::try_from(nanos).map_err(|_| bad("bad file stamp"))?;
+            entries.insert(
+                paths.len(),
+                Entry {
+                    stamp: Stamp { secs, nanos, size },
+                    trigrams: BTreeSet::new(),
+                },
+            );
+            paths.push(PathBuf::from(path));
+        } else if let Some(rest) = line.strip_prefix("trigram ") {
+            let mut fields = rest.split(' ');
+            let trigram = fields
+                .next()
+                .and_then(|hex| Trigram::from_str_radix(hex, 16).ok())
+                .ok_or_else(|| bad("bad trigram"))?;
+            for id in fields {
+                let entry = id.parse().ok().and_then(|id: usize| entries.get_mut(&id));
+                entry.ok_or_else(|| bad("unknown file number"))?.trigrams.insert(trigram);
+            }
+        } else {
+            return Err(bad("unrecognised line"));
+        }
+    }
+
+    Ok(paths
+        .into_iter()
+        .enumerate()
+        .map(|(id, path)| (path, entries.remove(&id).expect("every file has an entry")))
+        .collect())
+}
+
+fn corrupt(line: usize, message: &str) -> IndexError {
+    IndexError::Corrupt {
+        line,
+        message: message.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fs_util::TempPath;
+    use std::fs::File;
+    use std::time::{Duration, SystemTime};
+
+    fn options(ignore_case: bool, regex: bool) -> MatchOptions {
+        MatchOptions {
+            ignore_case,
+            regex,
+            ..MatchOptions::default()
+        }
+    }
+
+    #[test]
+    fn queries() {
+        let query = IndexQuery::new(&["Straße"], &options(false, false)).unwrap();
+        // Folding makes the index ignore case, so one file set serves both
+        assert!(query.matches(&trigrams("STRASSE and more")));
+        assert!(!query.matches(&trigrams("Strasbourg")));
+
+        let any = IndexQuery::new(&["borrow", "lifetime"], &options(true, false)).unwrap();
+        assert!(any.matches(&trigrams("Lifetimes")));
+        assert!(IndexQuery::new(&["borrow", "fn"], &options(false, false)).is_none());
+
+        assert!(IndexQuery::new(&["own.*ship"], &options(false, true)).is_none());
+        assert!(IndexQuery::new(&["ownership"], &options(false, true)).is_some());
+        let turkic = MatchOptions { turkic: true, ..options(true, false) };
+        assert!(IndexQuery::new(&["ISTANBUL"], &turkic).is_none());
+    }
+
+    #[test]
+    fn build_update_and_reload() {
+        let root = TempPath::dir("minigrep_index");
+        fs::create_dir_all(root.join("ch04")).unwrap();
+        fs::write(root.join("ch04/ownership.md"), "Each value has an owner.").unwrap();
+        fs::write(root.join("ch10.md"), "Lifetimes ensure references are valid.").unwrap();
+        fs::write(root.join("ch15.md"), "Smart pointers own data.").unwrap();
+
+        let mut index = Index::open(&root).unwrap();
+        let stats = index.update(&WalkOptions::default());
+        assert_eq!(stats.added, 3);
+        index.save().unwrap();
+
+        let owner = IndexQuery::new(&["OWNER"], &options(true, false)).unwrap();
+        assert_eq!(index.candidates(&owner), vec![root.join("ch04/ownership.md")]);
+        assert!(!index.may_match(&root.join("ch10.md"), &owner));
+        // Files the index doesn't know about are always searched
+        assert!(index.may_match(Path::new("elsewhere.md"), &owner));
+
+        // Reloading gives the same answers
+        let mut index = Index::load(&root).unwrap().unwrap();
+        assert_eq!(index.len(), 3);
+        assert_eq!(index.candidates(&owner), vec![root.join("ch04/ownership.md")]);
+
+        // A changed file is searched until the index catches up
+        fs::write(root.join("ch10.md"), "The borrow checker and the owner.").unwrap();
+        let later = SystemTime::now() + Duration::from_secs(5);
+        File::options().write(true).open(root.join("ch10.md")).unwrap().set_modified(later).unwrap();
+        assert!(index.may_match(&root.join("ch10.md"), &owner));
+
+        fs::remove_file(root.join("ch15.md")).unwrap();
+        let stats = index.update(&WalkOptions::default());
+        assert_eq!(
+            stats,
+            UpdateStats {
+                added: 0,
+                updated: 1,
+                removed: 1,
+                unchanged: 1
+            }
+        );
+        assert_eq!(index.candidates(&owner).len(), 2);
+    }
+
+    #[test]
+    fn corrupt_files_are_reported() {
+        let error = parse("minigrep-index 1\nfile 1 2 3 a.md\ntrigram zz 0\n").unwrap_err();
+        assert_eq!(error.to_string(), "corrupt index at line 3: bad trigram");
+        assert!(matches!(parse("not an index"), Err(IndexError::Corrupt { line: 1, .. })));
+        assert!(matches!(parse("minigrep-index 1\ntrigram 1 4\n"), Err(IndexError::Corrupt { line: 2, .. })));
+        assert!(parse("minigrep-index 1\n").unwrap().is_empty());
+    }
+}
@@ -0,0 +1,298 @@
+//! # JSON Lines Output
+//!
+//! `--json` prints one JSON object per line for tools to consume, in the
+//! shape ripgrep uses:
+//!
+//! - `begin` when a file with matches starts
+//! - `match` for each selected line, with its byte offset and the spans
+//!   ("submatches") that matched
+//! - `context` for each `-A`/`-B`/`-C` line
+//! - `end` when the file is done, with its statistics
+//! - `summary` once at the very end
+//!
+//! Every object has a `type` and a `data` field. Paths and lines are
+//! wrapped as `{"text": ...}`; unlike ripgrep, lines don't include their
+//! line terminator.
+//!
+//! Submatch `start` and `end` are byte offsets into the line's `text`. For
+//! a line that is valid UTF-8 they are also offsets into the file, counted
+//! from `absolute_offset`. A line with invalid UTF-8 is printed with each bad
+//! sequence replaced by U+FFFD, which takes 3 bytes, so from the first one
+//! on its offsets no longer line up with the file's bytes.
+
+use std::fmt::{self, Write};
+use std::time::Duration;
+
+/// A JSON value
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Json {
+    Null,
+    Bool(bool),
+    Number(u64),
+    String(String),
+    Array(Vec<Json>),
+    /// Fields in the order they are printed
+    Object(Vec<(&'static str, Json)>),
+}
+
+impl Json {
+    /// An object from `(name, value)` pairs
+    pub fn object<const N: usize>(fields: [(&'static str, Json); N]) -> Json {
+        Json::Object(fields.into())
+    }
+
+    /// `{"text": text}`, how paths and lines are wrapped
+    pub fn text(text: &str) -> Json {
+        Json::object([("text", text.into())])
+    }
+}
+
+impl From<&str> for Json {
+    fn from(s: &str) -> Json {
+        Json::String(s.to_string())
+    }
+}
+
+impl From<bool> for Json {
+    fn from(b: bool) -> Json {
+        Json::Bool(b)
+    }
+}
+
+impl From<u64> for Json {
+    fn from(n: u64) -> Json {
+        Json::Number(n)
+    }
+}
+
+impl From<usize> for Json {
+    fn from(n: usize) -> Json {
+        Json::Number(n as u64)
+    }
+}
+
+/// Compact JSON on one line
+///
+/// # Example
+/// ```
+/// use rust_book_examples::minigrep::json::Json;
+///
+/// let value = Json::object([("path", Json::text("poem.txt")), ("line", 2usize.into())]);
+/// assert_eq!(value.to_string(), r#"{"path":{"text":"poem.txt"},"line":2}"#);
+/// ```
+impl fmt::Display for Json {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Json::Null => f.write_str("null"),
+            Json::Bool(b) => write!(f, "{}", b),
+            Json::Number(n) => write!(f, "{}", n),
+            Json::String(s) => write_string(f, s),
+            Json::Array(items) => {
+                f.write_char('[')?;
+                for (i, item) in items.iter().enumerate() {
+                    if i > 0 {
+                        f.write_char(',')?;
+                    }
+                    write!(f, "{}", item)?;
+                }
+                f.write_char(']')
+            }
+            Json::Object(fields) => {
+                f.write_char('{')?;
+                for (i, (name, value)) in fields.iter().enumerate() {
+                    if i > 0 {
+                        f.write_char(',')?;
+                    }
+                    write_string(f, name)?;
+                    write!(f, ":{}", value)?;
+                }
+                f.write_char('}')
+            }
+        }
+    }
+}
+
+fn write_string(f: &mut fmt::Formatter, s: &str) -> fmt::Result {
+    f.write_char('"')?;
+    for c in s.chars() {
+        match c {
+            '"' => f.write_str("\\\"")?,
+            '\\' => f.write_str("\\\\")?,
+            '\n' => f.write_str("\\n")?,
+            '\r' => f.write_str("\\r")?,
+            '\t' => f.write_str("\\t")?,
+            c if (c as u32) < 0x20 => write!(f, "\\u{:04x}", c as u32)?,
+            c => f.write_char(c)?,
+        }
+    }
+    f.write_char('"')
+}
+
+/// Counts reported in `end` and `summary` messages
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct Stats {
+    /// Files searched
+    pub searches: usize,
+    /// Files with at least one selected line
+    pub searches_with_match: usize,
+    /// Selected lines
+    pub matched_lines: usize,
+    /// Matched spans within the selected lines
+    pub matches: usize,
+}
+
+impl Stats {
+    /// Add another file's counts to these
+    pub fn add(&mut self, other: &Stats) {
+        self.searches += other.searches;
+        self.searches_with_match += other.searches_with_match;
+        self.matched_lines += other.matched_lines;
+        self.matches += other.matches;
+    }
+
+    fn to_json(self, elapsed: Option<Duration>) -> Json {
+        let mut fields = Vec::new();
+        if let Some(elapsed) = elapsed {
+            fields.push(("elapsed", duration(elapsed)));
+        }
+        fields.extend([
+            ("searches", self.searches.into()),
+            ("searches_with_match", self.searches_with_match.into()),
+            ("matched_lines", self.matched_lines.into()),
+            ("matches", self.matches.into()),
+        ]);
+        Json::Object(fields)
+    }
+}
+
+fn duration(elapsed: Duration) -> Json {
+    Json::object([
+        ("secs", elapsed.as_secs().into()),
+        ("nanos", u64::from(elapsed.subsec_nanos()).into()),
+        ("human", format!("{:.6}s", elapsed.as_secs_f64()).as_str().into()),
+    ])
+}
+
+fn message(kind: &str, data: Json) -> Json {
+    Json::object([("type", kind.into()), ("data", data)])
+}
+
+/// A file with matches is starting
+pub fn begin(path: &str) -> Json {
+    message("begin", Json::object([("path", Json::text(path))]))
+}
+
+/// A selected line, with the byte spans of its matches
+///
+/// The spans index `line` as given, which is the file's bytes only if the
+/// line was valid UTF-8 (see the [module docs](self)).
+///
+/// # Example
+/// ```
+/// use rust_book_examples::minigrep::json;
+///
+/// let line = json::matched("poem.txt", 2, 25, "Then there's a pair of us!", &[(23, 25)]);
+/// assert_eq!(
+///     line.to_string(),
+///     concat!(
+///         r#"{"type":"match","data":{"path":{"text":"poem.txt"},"#,
+///         r#""lines":{"text":"Then there's a pair of us!"},"line_number":2,"absolute_offset":25,"#,
+///         r#""submatches":[{"match":{"text":"us"},"start":23,"end":25}]}}"#,
+///     )
+/// );
+/// ```
+pub fn matched(path: &str, line_number: usize, absolute_offset: u64, line: &str, spans: &[(usize, usize)]) -> Json {
+    let submatches = spans
+        .iter()
+        .map(|&(start, end)| {
+            Json::object([
+                ("match", Json::text(&line[start..end])),
+                ("start", start.into()),
+                ("end", end.into()),
+            ])
+        })
+        .collect();
+    message("match", line_data(path, line_number, absolute_offset, line, submatches))
+}
+
+/// A context line around a match
+pub fn context(path: &str, line_number: usize, absolute_offset: u64, line: &str) -> Json {
+    message("context", line_data(path, line_number, absolute_offset, line, Vec::new()))
+}
+
+fn line_data(path: &str, line_number: usize, absolute_offset: u64, line: &str, submatches: Vec<Json>) -> Json {
+    Json::object([
+        ("path", Json::text(path)),
+        ("lines", Json::text(line)),
+        ("line_number", line_number.into()),
+        ("absolute_offset", absolute_offset.into()),
+        ("submatches", Json::Array(submatches)),
+    ])
+}
+
+/// A file is finished; `binary` says whether it held binary data
+pub fn end(path: &str, binary: bool, stats: &Stats) -> Json {
+    message(
+        "end",
+        Json::object([
+            ("path", Json::text(path)),
+            ("binary", binary.into()),
+            ("stats", stats.to_json(None)),
+        ]),
+    )
+}
+
+/// The whole search is finished
+pub fn summary(elapsed: Duration, stats: &Stats) -> Json {
+    message(
+        "summary",
+        Json::object([
+            ("elapsed_total", duration(elapsed)),
+            ("stats", stats.to_json(Some(elapsed))),
+        ]),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn strings_are_escaped() {
+        let value = Json::from("tab\there \"quoted\" back\\slash\n\u{1}é");
+        assert_eq!(value.to_string(), r#""tab\there \"quoted\" back\\slash\n\u0001é""#);
+    }
+
+    #[test]
+    fn values() {
+        let value = Json::Array(vec![Json::Null, true.into(), 3usize.into(), "x".into()]);
+        assert_eq!(value.to_string(), r#"[null,true,3,"x"]"#);
+        assert_eq!(Json::object([]).to_string(), "{}");
+    }
+
+    #[test]
+    fn messages() {
+        let stats = Stats {
+            searches: 1,
+            searches_with_match: 1,
+            matched_lines: 2,
+            matches: 3,
+        };
+        assert_eq!(begin("a.txt").to_string(), r#"{"type":"begin","data":{"path":{"text":"a.txt"}}}"#);
+        assert_eq!(
+            context("a.txt", 1, 0, "ctx").to_string(),
+            r#"{"type":"context","data":{"path":{"text":"a.txt"},"lines":{"text":"ctx"},"line_number":1,"absolute_offset":0,"submatches":[]}}"#
+        );
+        assert_eq!(
+            end("a.txt", false, &stats).to_string(),
+            r#"{"type":"end","data":{"path":{"text":"a.txt"},"binary":false,"stats":{"searches":1,"searches_with_match":1,"matched_lines":2,"matches":3}}}"#
+        );
+
+        let mut total = Stats::default();
+        total.add(&stats);
+        total.add(&stats);
+        let summary = summary(Duration::from_millis(1500), &total).to_string();
+        assert!(summary.starts_with(r#"{"type":"summary","data":{"elapsed_total":{"secs":1,"nanos":500000000,"human":"1.500000s"}"#));
+        assert!(summary.ends_with(r#""searches":2,"searches_with_match":2,"matched_lines":4,"matches":6}}}"#));
+    }
+}
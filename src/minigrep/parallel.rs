@@ -0,0 +1,210 @@
+//! # Parallel Search
+//!
+//! Searches many files across a [`ThreadPool`] while keeping output
+//! deterministic.
+//!
+//! Each file is searched into a [`FileReport`] holding everything that
+//! should be printed for it. Reports come back in the order the files were
+//! given, so output is grouped per file and identical to a single-threaded
+//! run, whatever order the workers finish in. Only a few files are searched
+//! ahead of the one being printed, so memory stays bounded however many
+//! files there are.
+//!
+//! Without a pool, [`FileSearch::search_path_with`] hands each line over as
+//! soon as it's found and keeps nothing.
+
+use super::context::{ContextItem, ContextWindow};
+use super::stream::{open_input, SearchSummary, Searcher};
+use super::Matcher;
+use crate::thread_pool::ThreadPool;
+use std::collections::VecDeque;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+/// One line of a file's output
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ReportLine {
+    /// A selected line, or a context line around one
+    Line {
+        number: usize,
+        /// Byte offset of the start of the line in the file
+        offset: u64,
+        text: String,
+        selected: bool,
+    },
+    /// The `--` between non-adjacent groups of context
+    Separator,
+}
+
+/// Everything found in one file
+#[derive(Debug)]
+pub struct FileReport {
+    pub path: PathBuf,
+    pub lines: Vec<ReportLine>,
+    pub summary: SearchSummary,
+    /// Set if the file couldn't be opened or read
+    pub error: Option<io::Error>,
+}
+
+/// How to search each file
+#[derive(Debug, Clone)]
+pub struct FileSearch {
+    pub matcher: Matcher,
+    pub searcher: Searcher,
+    pub before_context: usize,
+    pub after_context: usize,
+    /// Keep the lines themselves; `-c`, `-l` and `-L` only need the summary
+    pub collect_lines: bool,
+}
+
+impl FileSearch {
+    /// Search one file (or stdin for `-`), keeping its lines in the report
+    pub fn search_path(&self, path: &Path) -> FileReport {
+        let mut lines = Vec::new();
+        let mut report = self.search_path_with(path, |line| lines.push(line));
+        report.lines = lines;
+        report
+    }
+
+    /// Search one file, passing each line to `emit` as soon as it's known
+    ///
+    /// The returned report's `lines` is empty. With `collect_lines` off,
+    /// `emit` is never called.
+    pub fn search_path_with(&self, path: &Path, mut emit: impl FnMut(ReportLine)) -> FileReport {
+        let mut report = FileReport {
+            path: path.to_path_buf(),
+            lines: Vec::new(),
+            summary: SearchSummary::default(),
+            error: None,
+        };
+
+        let reader = match open_input(path) {
+            Ok(reader) => reader,
+            Err(error) => {
+                report.error = Some(error);
+                return report;
+            }
+        };
+
+        let mut window = ContextWindow::new(self.before_context, self.after_context);
+        // Offsets of the lines the window may still be holding back as context
+        let mut offsets: VecDeque<(usize, u64)> = VecDeque::with_capacity(self.before_context + 1);
+        let searched = self.searcher.search_lines(reader, &self.matcher, |line| {
+            if !self.collect_lines {
+                return;
+            }
+            if offsets.len() > self.before_context {
+                offsets.pop_front();
+            }
+            offsets.push_back((line.number, line.offset));
+
+            window.push(line.number, line.text, line.selected, |item| {
+                emit(match item {
+                    ContextItem::Separator => ReportLine::Separator,
+                    ContextItem::Line { number, text, is_match } => ReportLine::Line {
+                        number,
+                        offset: offsets
+                            .iter()
+                            .find(|(n, _)| *n == number)
+                            .map_or(0, |&(_, offset)| offset),
+                        text: text.to_string(),
+                        selected: is_match,
+                    },
+                })
+            });
+        });
+
+        match searched {
+            Ok(summary) => report.summary = summary,
+            Err(error) => report.error = Some(error),
+        }
+        report
+    }
+}
+
+/// Search `files`, on `pool` if one is given, yielding reports in order
+///
+/// # Example
+/// ```
+/// use rust_book_examples::minigrep::parallel::{search_files, FileSearch};
+/// use rust_book_examples::minigrep::stream::Searcher;
+/// use rust_book_examples::minigrep::Matcher;
+/// use rust_book_examples::thread_pool::ThreadPool;
+/// use std::path::PathBuf;
+///
+/// let search = FileSearch {
+///     matcher: Matcher::new("pub mod", false, false).unwrap(),
+///     searcher: Searcher::default(),
+///     before_context: 0,
+///     after_context: 0,
+///     collect_lines: true,
+/// };
+/// let files = vec![PathBuf::from("src/lib.rs"), PathBuf::from("src/minigrep.rs")];
+///
+/// let pool = ThreadPool::new(2);
+/// let reports: Vec<_> = search_files(search, files, Some(&pool)).collect();
+/// assert!(reports[0].path.ends_with("lib.rs"));
+/// assert!(reports.iter().all(|report| report.summary.matches > 0));
+/// ```
+pub fn search_files<'a>(
+    search: FileSearch,
+    files: Vec<PathBuf>,
+    pool: Option<&'a ThreadPool>,
+) -> Box<dyn Iterator<Item = FileReport> + 'a> {
+    match pool {
+        Some(pool) => {
+            let search = Arc::new(search);
+            Box::new(pool.map(files, move |path: PathBuf| search.search_path(&path)))
+        }
+        None => Box::new(files.into_iter().map(move |path| search.search_path(&path))),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fs_util::TempPath;
+    use std::fs;
+
+    #[test]
+    fn parallel_reports_match_serial_reports() {
+        let dir = TempPath::dir("minigrep_parallel");
+
+        let mut files = Vec::new();
+        for i in 0..12 {
+            let path = dir.join(format!("file{:02}.txt", i));
+            let contents: String = (0..50).map(|n| format!("line {} of file {}\n", n, i)).collect();
+            fs::write(&path, contents).unwrap();
+            files.push(path);
+        }
+        files.push(dir.join("missing.txt"));
+
+        let search = FileSearch {
+            matcher: Matcher::new("line 1", false, false).unwrap(),
+            searcher: Searcher::default(),
+            before_context: 1,
+            after_context: 0,
+            collect_lines: true,
+        };
+        let describe = |reports: Box<dyn Iterator<Item = FileReport> + '_>| -> Vec<_> {
+            reports
+                .map(|report| (report.path, report.lines, report.summary, report.error.is_some()))
+                .collect()
+        };
+
+        let serial = describe(search_files(search.clone(), files.clone(), None));
+        let mut streamed = Vec::new();
+        let report = search.search_path_with(&files[0], |line| streamed.push(line));
+        let pool = ThreadPool::new(4);
+        let parallel = describe(search_files(search, files, Some(&pool)));
+
+        assert_eq!(serial, parallel);
+        assert!(report.lines.is_empty());
+        assert_eq!(streamed, serial[0].1);
+        // "line 1" and "line 10".."line 19" match in every file
+        assert_eq!(serial[0].2.matches, 11);
+        assert!(matches!(serial[0].1[1], ReportLine::Line { number: 2, offset: 17, selected: true, .. }));
+        assert!(serial[12].3);
+    }
+}
@@ -0,0 +1,1439 @@
+//! # A Small Regular-Expression Engine
+//!
+//! Powers minigrep's `-E` mode without pulling in an external crate.
+//!
+//! Supported syntax:
+//! - Literals and `.` (any character except `\n`)
+//! - Character classes: `[abc]`, `[a-z]`, `[^0-9]`, `\d \w \s \D \W \S`
+//! - Anchors and boundaries: `^`, `$`, `\b`, `\B`
+//! - Alternation `a|b`, capturing groups `(...)`, non-capturing groups `(?:...)`
+//! - Quantifiers `*`, `+`, `?`, `{m}`, `{m,}`, `{m,n}`, with a trailing `?` for lazy
+//!
+//! Patterns are compiled into a Thompson NFA. `find` and `captures` run it with a
+//! Pike VM, which tracks every NFA state at once, and `is_match` uses a lazily
+//! built DFA whose states are cached between calls. Both take time linear in the
+//! length of the input, so there is no catastrophic backtracking.
+//!
+//! Case-insensitive patterns compare characters by their simple case folding,
+//! so `Σ` matches `ς`. One character always matches one character here;
+//! `ß` matching `ss` needs the full folding of plain `-i` searches.
+
+use super::casefold::simple_fold;
+use std::collections::HashMap;
+use std::fmt;
+use std::sync::Mutex;
+
+/// Largest number of instructions a compiled program may contain
+const MAX_PROGRAM_SIZE: usize = 100_000;
+
+/// Largest count allowed in a `{m,n}` quantifier
+const MAX_REPEAT: u32 = 1_000;
+
+/// Number of DFA states cached before the cache is flushed
+const MAX_DFA_STATES: usize = 4_096;
+
+/// Deepest nesting of groups and quantifiers a pattern may have; parsing
+/// and compiling recurse once per level, so this keeps them off the end of
+/// the stack
+const MAX_NESTING: usize = 250;
+
+/// Errors produced while parsing a pattern
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum RegexError {
+    /// A `(` without a matching `)`
+    UnclosedGroup(usize),
+    /// A `)` without a matching `(`
+    UnopenedGroup(usize),
+    /// A `[` without a matching `]`
+    UnclosedClass(usize),
+    /// A class range whose start is greater than its end, like `[z-a]`
+    InvalidRange(char, char),
+    /// A quantifier with nothing to repeat, like `*a`
+    MissingRepeatOperand(usize),
+    /// A `{m,n}` quantifier with `m > n` or a count above the limit
+    InvalidRepeat(usize),
+    /// An escape sequence the engine doesn't know, like `\q`
+    InvalidEscape(char),
+    /// A pattern ending in a lone `\`
+    TrailingBackslash,
+    /// The compiled program would exceed the size limit
+    TooLarge,
+    /// Groups or quantifiers nested more than the limit deep, at the offset
+    /// where the limit was crossed
+    TooDeep(usize),
+}
+
+impl fmt::Display for RegexError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            RegexError::UnclosedGroup(pos) => write!(f, "unclosed group opened at offset {}", pos),
+            RegexError::UnopenedGroup(pos) => write!(f, "unopened group closed at offset {}", pos),
+            RegexError::UnclosedClass(pos) => write!(f, "unclosed character class opened at offset {}", pos),
+            RegexError::InvalidRange(start, end) => write!(f, "invalid class range {}-{}", start, end),
+            RegexError::MissingRepeatOperand(pos) => write!(f, "quantifier at offset {} has nothing to repeat", pos),
+            RegexError::InvalidRepeat(pos) => write!(f, "invalid repetition count at offset {}", pos),
+            RegexError::InvalidEscape(c) => write!(f, "unknown escape sequence \\{}", c),
+            RegexError::TrailingBackslash => write!(f, "pattern ends with a trailing backslash"),
+            RegexError::TooLarge => write!(f, "compiled pattern exceeds the size limit"),
+            RegexError::TooDeep(pos) => write!(
+                f,
+                "pattern nested too deeply at offset {}; at most {} levels of groups and quantifiers",
+                pos, MAX_NESTING
+            ),
+        }
+    }
+}
+
+impl std::error::Error for RegexError {}
+
+/// Escape every metacharacter in `text` so it matches literally
+///
+/// # Example
+/// ```
+/// use rust_book_examples::minigrep::regex::{escape, Regex};
+///
+/// let re = Regex::new(&escape("1+1=2?")).unwrap();
+/// assert!(re.is_match("is 1+1=2?"));
+/// ```
+pub fn escape(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        if "\\.+*?()|[]{}^$".contains(c) {
+            escaped.push('\\');
+        }
+        escaped.push(c);
+    }
+    escaped
+}
+
+// === PARSING ===
+
+/// Zero-width assertions
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Assertion {
+    StartText,
+    EndText,
+    WordBoundary,
+    NotWordBoundary,
+}
+
+/// A set of character ranges, possibly negated
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct CharClass {
+    ranges: Vec<(char, char)>,
+    negated: bool,
+}
+
+impl CharClass {
+    fn new(ranges: Vec<(char, char)>, negated: bool) -> CharClass {
+        CharClass { ranges, negated }
+    }
+
+    fn in_ranges(&self, c: char) -> bool {
+        self.ranges.iter().any(|&(lo, hi)| lo <= c && c <= hi)
+    }
+
+    fn contains(&self, c: char) -> bool {
+        self.in_ranges(c) != self.negated
+    }
+
+    /// Like `contains`, but also accepts the other case of `c`
+    fn contains_ignoring_case(&self, c: char) -> bool {
+        let found = self.in_ranges(c) || self.in_ranges(simple_fold(c)) || self.in_ranges(simple_upper(c));
+        found != self.negated
+    }
+}
+
+/// Every character not covered by `ranges`
+fn complement(ranges: &[(char, char)]) -> Vec<(char, char)> {
+    let mut sorted: Vec<(u32, u32)> = ranges.iter().map(|&(a, b)| (a as u32, b as u32)).collect();
+    sorted.sort();
+
+    let mut result = Vec::new();
+    let mut next = 0u32;
+    for (lo, hi) in sorted {
+        if lo > next {
+            push_char_range(&mut result, next, lo - 1);
+        }
+        next = next.max(hi + 1);
+    }
+    if next <= char::MAX as u32 {
+        push_char_range(&mut result, next, char::MAX as u32);
+    }
+    result
+}
+
+/// Push `lo..=hi` as char ranges, splitting around the surrogate gap
+fn push_char_range(out: &mut Vec<(char, char)>, lo: u32, hi: u32) {
+    const SURROGATE_START: u32 = 0xD800;
+    const SURROGATE_END: u32 = 0xDFFF;
+
+    let mut pieces = Vec::new();
+    if lo < SURROGATE_START {
+        pieces.push((lo, hi.min(SURROGATE_START - 1)));
+    }
+    if hi > SURROGATE_END {
+        pieces.push((lo.max(SURROGATE_END + 1), hi));
+    }
+    for (a, b) in pieces {
+        if let (Some(a), Some(b)) = (char::from_u32(a), char::from_u32(b))
+            && a <= b
+        {
+            out.push((a, b));
+        }
+    }
+}
+
+fn digit_ranges() -> Vec<(char, char)> {
+    vec![('0', '9')]
+}
+
+fn word_ranges() -> Vec<(char, char)> {
+    vec![('0', '9'), ('A', 'Z'), ('_', '_'), ('a', 'z')]
+}
+
+fn space_ranges() -> Vec<(char, char)> {
+    vec![('\t', '\r'), (' ', ' ')]
+}
+
+/// Word characters for `\w` and `\b` (ASCII letters, digits and `_`)
+fn is_word_char(c: char) -> bool {
+    c.is_ascii_alphanumeric() || c == '_'
+}
+
+/// Abstract syntax tree produced by the parser
+#[derive(Debug, Clone)]
+enum Ast {
+    Empty,
+    Literal(char),
+    Any,
+    Class(CharClass),
+    Assert(Assertion),
+    Group(Box<Ast>, Option<usize>),
+    Concat(Vec<Ast>),
+    Alternate(Vec<Ast>),
+    Repeat {
+        ast: Box<Ast>,
+        min: u32,
+        max: Option<u32>,
+        greedy: bool,
+    },
+}
+
+/// Recursive-descent parser over the pattern's characters
+///
+/// Each `parse_*` method returns the tree it parsed with its nesting
+/// height, which counts the groups and quantifiers wrapped around its
+/// deepest atom.
+struct Parser {
+    chars: Vec<char>,
+    pos: usize,
+    groups: usize,
+    /// Groups open around the current position
+    depth: usize,
+}
+
+impl Parser {
+    fn parse(pattern: &str) -> Result<(Ast, usize), RegexError> {
+        let mut parser = Parser {
+            chars: pattern.chars().collect(),
+            pos: 0,
+            groups: 0,
+            depth: 0,
+        };
+
+        let (ast, _) = parser.parse_alternation()?;
+        if parser.pos < parser.chars.len() {
+            // The only way to stop early is an unmatched `)`
+            return Err(RegexError::UnopenedGroup(parser.pos));
+        }
+        Ok((ast, parser.groups))
+    }
+
+    fn peek(&self) -> Option<char> {
+        self.chars.get(self.pos).copied()
+    }
+
+    fn eat(&mut self, expected: char) -> bool {
+        if self.peek() == Some(expected) {
+            self.pos += 1;
+            true
+        } else {
+            false
+        }
+    }
+
+    fn parse_alternation(&mut self) -> Result<(Ast, usize), RegexError> {
+        let (first, mut height) = self.parse_concat()?;
+        let mut branches = vec![first];
+        while self.eat('|') {
+            let (branch, branch_height) = self.parse_concat()?;
+            branches.push(branch);
+            height = height.max(branch_height);
+        }
+
+        let ast = if branches.len() == 1 {
+            branches.pop().unwrap()
+        } else {
+            Ast::Alternate(branches)
+        };
+        Ok((ast, height))
+    }
+
+    fn parse_concat(&mut self) -> Result<(Ast, usize), RegexError> {
+        let mut items = Vec::new();
+        let mut height = 0;
+        while let Some(c) = self.peek() {
+            if c == '|' || c == ')' {
+                break;
+            }
+            let atom = self.parse_atom()?;
+            let (item, item_height) = self.parse_quantifiers(atom)?;
+            items.push(item);
+            height = height.max(item_height);
+        }
+
+        let ast = match items.len() {
+            0 => Ast::Empty,
+            1 => items.pop().unwrap(),
+            _ => Ast::Concat(items),
+        };
+        Ok((ast, height))
+    }
+
+    fn parse_quantifiers(&mut self, (mut ast, mut height): (Ast, usize)) -> Result<(Ast, usize), RegexError> {
+        loop {
+            let start = self.pos;
+            let (min, max) = match self.peek() {
+                Some('*') => {
+                    self.pos += 1;
+                    (0, None)
+                }
+                Some('+') => {
+                    self.pos += 1;
+                    (1, None)
+                }
+                Some('?') => {
+                    self.pos += 1;
+                    (0, Some(1))
+                }
+                Some('{') => match self.parse_counted()? {
+                    Some(bounds) => bounds,
+                    None => return Ok((ast, height)),
+                },
+                _ => return Ok((ast, height)),
+            };
+
+            if matches!(ast, Ast::Assert(_)) {
+                return Err(RegexError::MissingRepeatOperand(start));
+            }
+            if height >= MAX_NESTING {
+                return Err(RegexError::TooDeep(start));
+            }
+            height += 1;
+
+            let greedy = !self.eat('?');
+            ast = Ast::Repeat {
+                ast: Box::new(ast),
+                min,
+                max,
+                greedy,
+            };
+        }
+    }
+
+    /// Parse `{m}`, `{m,}` or `{m,n}`; returns `None` if `{` is a literal
+    fn parse_counted(&mut self) -> Result<Option<(u32, Option<u32>)>, RegexError> {
+        let start = self.pos;
+        self.pos += 1;
+
+        let min = match self.parse_number() {
+            Some(n) => n,
+            None => {
+                self.pos = start;
+                return Ok(None);
+            }
+        };
+
+        let max = if self.eat(',') {
+            if self.peek() == Some('}') {
+                None
+            } else {
+                match self.parse_number() {
+                    Some(n) => Some(n),
+                    None => {
+                        self.pos = start;
+                        return Ok(None);
+                    }
+                }
+            }
+        } else {
+            Some(min)
+        };
+
+        if !self.eat('}') {
+            self.pos = start;
+            return Ok(None);
+        }
+
+        let too_big = min > MAX_REPEAT || max.is_some_and(|m| m > MAX_REPEAT);
+        if too_big || max.is_some_and(|m| m < min) {
+            return Err(RegexError::InvalidRepeat(start));
+        }
+        Ok(Some((min, max)))
+    }
+
+    fn parse_number(&mut self) -> Option<u32> {
+        let start = self.pos;
+        while self.peek().is_some_and(|c| c.is_ascii_digit()) {
+            self.pos += 1;
+        }
+        if start == self.pos {
+            return None;
+        }
+        let digits: String = self.chars[start..self.pos].iter().collect();
+        // Overflowing counts are reported as invalid repeats by the caller
+        Some(digits.parse().unwrap_or(u32::MAX))
+    }
+
+    fn parse_atom(&mut self) -> Result<(Ast, usize), RegexError> {
+        let start = self.pos;
+        let c = self.peek().expect("parse_atom called at end of pattern");
+        self.pos += 1;
+
+        let ast = match c {
+            '(' => return self.parse_group(start),
+            '[' => self.parse_class(start).map(Ast::Class),
+            '.' => Ok(Ast::Any),
+            '^' => Ok(Ast::Assert(Assertion::StartText)),
+            '$' => Ok(Ast::Assert(Assertion::EndText)),
+            '\\' => self.parse_escape(),
+            '*' | '+' | '?' => Err(RegexError::MissingRepeatOperand(start)),
+            '{' => {
+                self.pos = start;
+                if self.parse_counted()?.is_some() {
+                    Err(RegexError::MissingRepeatOperand(start))
+                } else {
+                    self.pos = start + 1;
+                    Ok(Ast::Literal('{'))
+                }
+            }
+            c => Ok(Ast::Literal(c)),
+        }?;
+        Ok((ast, 0))
+    }
+
+    fn parse_group(&mut self, start: usize) -> Result<(Ast, usize), RegexError> {
+        // Checked on the way in too, so the recursion itself stays shallow
+        if self.depth >= MAX_NESTING {
+            return Err(RegexError::TooDeep(start));
+        }
+        let index = if self.chars[self.pos..].starts_with(&['?', ':']) {
+            self.pos += 2;
+            None
+        } else {
+            self.groups += 1;
+            Some(self.groups)
+        };
+
+        self.depth += 1;
+        let (inner, height) = self.parse_alternation()?;
+        self.depth -= 1;
+        if !self.eat(')') {
+            return Err(RegexError::UnclosedGroup(start));
+        }
+        if height >= MAX_NESTING {
+            return Err(RegexError::TooDeep(start));
+        }
+        Ok((Ast::Group(Box::new(inner), index), height + 1))
+    }
+
+    fn parse_escape(&mut self) -> Result<Ast, RegexError> {
+        let c = self.peek().ok_or(RegexError::TrailingBackslash)?;
+        self.pos += 1;
+
+        Ok(match c {
+            'b' => Ast::Assert(Assertion::WordBoundary),
+            'B' => Ast::Assert(Assertion::NotWordBoundary),
+            'd' => Ast::Class(CharClass::new(digit_ranges(), false)),
+            'D' => Ast::Class(CharClass::new(digit_ranges(), true)),
+            'w' => Ast::Class(CharClass::new(word_ranges(), false)),
+            'W' => Ast::Class(CharClass::new(word_ranges(), true)),
+            's' => Ast::Class(CharClass::new(space_ranges(), false)),
+            'S' => Ast::Class(CharClass::new(space_ranges(), true)),
+            other => Ast::Literal(escaped_literal(other)?),
+        })
+    }
+
+    fn parse_class(&mut self, start: usize) -> Result<CharClass, RegexError> {
+        let negated = self.eat('^');
+        let mut ranges = Vec::new();
+        let mut first = true;
+
+        loop {
+            let c = self.peek().ok_or(RegexError::UnclosedClass(start))?;
+            self.pos += 1;
+
+            if c == ']' && !first {
+                break;
+            }
+            first = false;
+
+            let lo = if c == '\\' {
+                let escaped = self.peek().ok_or(RegexError::UnclosedClass(start))?;
+                self.pos += 1;
+                match escaped {
+                    'd' | 'w' | 's' => {
+                        ranges.extend(class_escape(escaped));
+                        continue;
+                    }
+                    'D' | 'W' | 'S' => {
+                        ranges.extend(complement(&class_escape(escaped.to_ascii_lowercase())));
+                        continue;
+                    }
+                    other => escaped_literal(other)?,
+                }
+            } else {
+                c
+            };
+
+            // A `-` forms a range unless it is the last character in the class
+            let is_range = self.peek() == Some('-') && self.chars.get(self.pos + 1).is_some_and(|&n| n != ']');
+            if !is_range {
+                ranges.push((lo, lo));
+                continue;
+            }
+
+            self.pos += 1;
+            let hi = match self.peek().ok_or(RegexError::UnclosedClass(start))? {
+                '\\' => {
+                    self.pos += 1;
+                    let escaped = self.peek().ok_or(RegexError::UnclosedClass(start))?;
+                    self.pos += 1;
+                    escaped_literal(escaped)?
+                }
+                other => {
+                    self.pos += 1;
+                    other
+                }
+            };
+
+            if lo > hi {
+                return Err(RegexError::InvalidRange(lo, hi));
+            }
+            ranges.push((lo, hi));
+        }
+
+        Ok(CharClass::new(ranges, negated))
+    }
+}
+
+fn class_escape(c: char) -> Vec<(char, char)> {
+    match c {
+        'd' => digit_ranges(),
+        'w' => word_ranges(),
+        _ => space_ranges(),
+    }
+}
+
+/// Resolve an escape that stands for a single literal character
+fn escaped_literal(c: char) -> Result<char, RegexError> {
+    match c {
+        'n' => Ok('\n'),
+        't' => Ok('\t'),
+        'r' => Ok('\r'),
+        c if c.is_ascii_alphanumeric() => Err(RegexError::InvalidEscape(c)),
+        c => Ok(c),
+    }
+}
+
+// === COMPILATION ===
+
+/// One instruction of the Thompson NFA program
+#[derive(Debug, Clone)]
+enum Inst {
+    Char(char),
+    Any,
+    Class(CharClass),
+    Assert(Assertion),
+    /// Try both targets, preferring the first
+    Split(usize, usize),
+    Jmp(usize),
+    /// Record the current position in a capture slot
+    Save(usize),
+    Match,
+}
+
+struct Compiler {
+    program: Vec<Inst>,
+}
+
+impl Compiler {
+    fn compile(ast: &Ast) -> Result<Vec<Inst>, RegexError> {
+        let mut compiler = Compiler { program: Vec::new() };
+        compiler.push(Inst::Save(0))?;
+        compiler.emit(ast)?;
+        compiler.push(Inst::Save(1))?;
+        compiler.push(Inst::Match)?;
+        Ok(compiler.program)
+    }
+
+    fn push(&mut self, inst: Inst) -> Result<usize, RegexError> {
+        if self.program.len() >= MAX_PROGRAM_SIZE {
+            return Err(RegexError::TooLarge);
+        }
+        self.program.push(inst);
+        Ok(self.program.len() - 1)
+    }
+
+    fn patch(&mut self, at: usize, target: usize) {
+        match &mut self.program[at] {
+            Inst::Jmp(t) => *t = target,
+            Inst::Split(_, t) => *t = target,
+            other => unreachable!("cannot patch {:?}", other),
+        }
+    }
+
+    fn emit(&mut self, ast: &Ast) -> Result<(), RegexError> {
+        match ast {
+            Ast::Empty => {}
+            Ast::Literal(c) => {
+                self.push(Inst::Char(*c))?;
+            }
+            Ast::Any => {
+                self.push(Inst::Any)?;
+            }
+            Ast::Class(class) => {
+                self.push(Inst::Class(class.clone()))?;
+            }
+            Ast::Assert(assertion) => {
+                self.push(Inst::Assert(*assertion))?;
+            }
+            Ast::Group(inner, Some(index)) => {
+                self.push(Inst::Save(index * 2))?;
+                self.emit(inner)?;
+                self.push(Inst::Save(index * 2 + 1))?;
+            }
+            Ast::Group(inner, None) => self.emit(inner)?,
+            Ast::Concat(items) => {
+                for item in items {
+                    self.emit(item)?;
+                }
+            }
+            Ast::Alternate(branches) => {
+                let mut jumps_to_end = Vec::new();
+                for (i, branch) in branches.iter().enumerate() {
+                    if i + 1 == branches.len() {
+                        self.emit(branch)?;
+                    } else {
+                        let split = self.push(Inst::Split(0, 0))?;
+                        self.program[split] = Inst::Split(split + 1, 0);
+                        self.emit(branch)?;
+                        jumps_to_end.push(self.push(Inst::Jmp(0))?);
+                        let next = self.program.len();
+                        self.patch(split, next);
+                    }
+                }
+                let end = self.program.len();
+                for jump in jumps_to_end {
+                    self.patch(jump, end);
+                }
+            }
+            Ast::Repeat { ast, min, max, greedy } => {
+                for _ in 0..*min {
+                    self.emit(ast)?;
+                }
+                match max {
+                    None => self.emit_star(ast, *greedy)?,
+                    Some(max) => {
+                        let mut splits = Vec::new();
+                        for _ in *min..*max {
+                            splits.push(self.emit_split_to_later(*greedy)?);
+                            self.emit(ast)?;
+                        }
+                        let end = self.program.len();
+                        for split in splits {
+                            self.patch_split(split, end, *greedy);
+                        }
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// `e*`: loop back over `e` until the split chooses to leave
+    fn emit_star(&mut self, ast: &Ast, greedy: bool) -> Result<(), RegexError> {
+        let split = self.emit_split_to_later(greedy)?;
+        self.emit(ast)?;
+        self.push(Inst::Jmp(split))?;
+        let end = self.program.len();
+        self.patch_split(split, end, greedy);
+        Ok(())
+    }
+
+    /// A split whose "skip" target is patched in later
+    fn emit_split_to_later(&mut self, greedy: bool) -> Result<usize, RegexError> {
+        let at = self.program.len();
+        if greedy {
+            self.push(Inst::Split(at + 1, 0))
+        } else {
+            self.push(Inst::Split(0, at + 1))
+        }
+    }
+
+    fn patch_split(&mut self, at: usize, skip: usize, greedy: bool) {
+        if let Inst::Split(first, second) = &mut self.program[at] {
+            if greedy {
+                *second = skip;
+            } else {
+                *first = skip;
+            }
+        }
+    }
+}
+
+// === MATCHING ===
+
+/// A single match: byte offsets into the searched text
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Match<'t> {
+    text: &'t str,
+    start: usize,
+    end: usize,
+}
+
+impl<'t> Match<'t> {
+    /// Byte offset where the match starts
+    pub fn start(&self) -> usize {
+        self.start
+    }
+
+    /// Byte offset just past the end of the match
+    pub fn end(&self) -> usize {
+        self.end
+    }
+
+    /// The matched text
+    pub fn as_str(&self) -> &'t str {
+        &self.text[self.start..self.end]
+    }
+}
+
+/// Capture groups from a single match; group 0 is the whole match
+#[derive(Debug, Clone)]
+pub struct Captures<'t> {
+    text: &'t str,
+    slots: Vec<Option<usize>>,
+}
+
+impl<'t> Captures<'t> {
+    /// The match for group `index`, if that group took part in the match
+    pub fn get(&self, index: usize) -> Option<Match<'t>> {
+        let start = (*self.slots.get(index * 2)?)?;
+        let end = (*self.slots.get(index * 2 + 1)?)?;
+        Some(Match {
+            text: self.text,
+            start,
+            end,
+        })
+    }
+
+    /// Number of groups, including group 0
+    pub fn len(&self) -> usize {
+        self.slots.len() / 2
+    }
+
+    /// Always false: group 0 is always present
+    pub fn is_empty(&self) -> bool {
+        self.slots.is_empty()
+    }
+}
+
+/// A compiled regular expression
+///
+/// # Example
+/// ```
+/// use rust_book_examples::minigrep::regex::Regex;
+///
+/// let re = Regex::new(r"^(\w+)@(\w+)\.com$").unwrap();
+/// assert!(re.is_match("ferris@rust.com"));
+///
+/// let caps = re.captures("ferris@rust.com").unwrap();
+/// assert_eq!(caps.get(2).unwrap().as_str(), "rust");
+/// ```
+pub struct Regex {
+    pattern: String,
+    program: Vec<Inst>,
+    groups: usize,
+    case_insensitive: bool,
+    has_word_boundary: bool,
+    /// Spare DFA caches; each search borrows one so threads never wait on each other
+    dfa: Mutex<Vec<DfaCache>>,
+}
+
+impl Regex {
+    /// Compile a case-sensitive pattern
+    pub fn new(pattern: &str) -> Result<Regex, RegexError> {
+        Regex::build(pattern, false)
+    }
+
+    /// Compile a pattern that ignores letter case
+    pub fn case_insensitive(pattern: &str) -> Result<Regex, RegexError> {
+        Regex::build(pattern, true)
+    }
+
+    fn build(pattern: &str, case_insensitive: bool) -> Result<Regex, RegexError> {
+        let (ast, groups) = Parser::parse(pattern)?;
+        let program = Compiler::compile(&ast)?;
+        let has_word_boundary = program.iter().any(|inst| {
+            matches!(
+                inst,
+                Inst::Assert(Assertion::WordBoundary) | Inst::Assert(Assertion::NotWordBoundary)
+            )
+        });
+
+        Ok(Regex {
+            pattern: pattern.to_string(),
+            program,
+            groups,
+            case_insensitive,
+            has_word_boundary,
+            dfa: Mutex::new(Vec::new()),
+        })
+    }
+
+    /// The source pattern
+    pub fn as_str(&self) -> &str {
+        &self.pattern
+    }
+
+    /// Number of capture groups, including the implicit group 0
+    pub fn captures_len(&self) -> usize {
+        self.groups + 1
+    }
+
+    /// Returns true if the pattern matches anywhere in `text`
+    pub fn is_match(&self, text: &str) -> bool {
+        // The DFA can't see word boundaries; the Pike VM is linear-time too
+        if self.has_word_boundary {
+            return self.pike(text, 0, true).is_some();
+        }
+
+        // Threads sharing this regex each take a cache of their own, and
+        // give it back warm for the next search
+        let spare = self.dfa.lock().ok().and_then(|mut caches| caches.pop());
+        let mut cache = spare.unwrap_or_default();
+        let matched = self.dfa_is_match(&mut cache, text);
+        if let Ok(mut caches) = self.dfa.lock() {
+            caches.push(cache);
+        }
+        matched
+    }
+
+    /// Leftmost-first match in `text`
+    pub fn find<'t>(&self, text: &'t str) -> Option<Match<'t>> {
+        self.find_at(text, 0)
+    }
+
+    /// Leftmost-first match starting at or after byte offset `start`
+    pub fn find_at<'t>(&self, text: &'t str, start: usize) -> Option<Match<'t>> {
+        let slots = self.pike(text, start, false)?;
+        Some(Match {
+            text,
+            start: slots[0]?,
+            end: slots[1]?,
+        })
+    }
+
+    /// Capture groups of the leftmost-first match in `text`
+    pub fn captures<'t>(&self, text: &'t str) -> Option<Captures<'t>> {
+        self.captures_at(text, 0)
+    }
+
+    /// Capture groups of the first match starting at or after `start`
+    pub fn captures_at<'t>(&self, text: &'t str, start: usize) -> Option<Captures<'t>> {
+        let slots = self.pike(text, start, false)?;
+        Some(Captures { text, slots })
+    }
+
+    /// Iterate over successive non-overlapping matches
+    pub fn find_iter<'r, 't>(&'r self, text: &'t str) -> FindIter<'r, 't> {
+        FindIter {
+            regex: self,
+            text,
+            next_start: Some(0),
+        }
+    }
+
+    /// Iterate over the captures of successive non-overlapping matches
+    pub fn captures_iter<'r, 't>(&'r self, text: &'t str) -> CapturesIter<'r, 't> {
+        CapturesIter {
+            regex: self,
+            text,
+            next_start: Some(0),
+        }
+    }
+
+    fn char_matches(&self, inst: &Inst, c: char) -> bool {
+        match inst {
+            Inst::Char(expected) => {
+                *expected == c || (self.case_insensitive && simple_fold(*expected) == simple_fold(c))
+            }
+            Inst::Any => c != '\n',
+            Inst::Class(class) if self.case_insensitive => class.contains_ignoring_case(c),
+            Inst::Class(class) => class.contains(c),
+            _ => false,
+        }
+    }
+
+    // --- Pike VM ---
+
+    /// Run the NFA from `start`, returning the capture slots of the first match
+    fn pike(&self, text: &str, start: usize, stop_early: bool) -> Option<Vec<Option<usize>>> {
+        let slot_count = self.captures_len() * 2;
+        let mut current = Threads::new(self.program.len(), slot_count);
+        let mut next = Threads::new(self.program.len(), slot_count);
+        let mut scratch = vec![None; slot_count];
+        let mut matched = None;
+        let mut pos = start;
+
+        loop {
+            if matched.is_none() {
+                scratch.iter_mut().for_each(|slot| *slot = None);
+                self.add_thread(&mut current, 0, pos, &mut scratch, text);
+            }
+            if current.set.is_empty() {
+                break;
+            }
+
+            let c = text[pos..].chars().next();
+            for i in 0..current.set.len() {
+                let pc = current.set.dense[i];
+                match &self.program[pc] {
+                    Inst::Match => {
+                        matched = Some(current.slots_of(pc).to_vec());
+                        if stop_early {
+                            return matched;
+                        }
+                        // Lower-priority threads can't win anymore
+                        break;
+                    }
+                    inst => {
+                        if let Some(c) = c
+                            && self.char_matches(inst, c)
+                        {
+                            scratch.copy_from_slice(current.slots_of(pc));
+                            self.add_thread(&mut next, pc + 1, pos + c.len_utf8(), &mut scratch, text);
+                        }
+                    }
+                }
+            }
+
+            match c {
+                Some(c) => pos += c.len_utf8(),
+                None => break,
+            }
+            std::mem::swap(&mut current, &mut next);
+            next.set.clear();
+        }
+
+        matched
+    }
+
+    /// Follow epsilon transitions from `pc`, adding consuming states to `list`
+    fn add_thread(&self, list: &mut Threads, pc: usize, pos: usize, slots: &mut [Option<usize>], text: &str) {
+        enum Frame {
+            Explore(usize),
+            Restore(usize, Option<usize>),
+        }
+
+        let mut stack = vec![Frame::Explore(pc)];
+        while let Some(frame) = stack.pop() {
+            let pc = match frame {
+                Frame::Restore(slot, value) => {
+                    slots[slot] = value;
+                    continue;
+                }
+                Frame::Explore(pc) => pc,
+            };
+            if !list.set.insert(pc) {
+                continue;
+            }
+
+            match &self.program[pc] {
+                Inst::Jmp(target) => stack.push(Frame::Explore(*target)),
+                Inst::Split(first, second) => {
+                    stack.push(Frame::Explore(*second));
+                    stack.push(Frame::Explore(*first));
+                }
+                Inst::Save(slot) => {
+                    stack.push(Frame::Restore(*slot, slots[*slot]));
+                    slots[*slot] = Some(pos);
+                    stack.push(Frame::Explore(pc + 1));
+                }
+                Inst::Assert(assertion) => {
+                    if assertion_holds(*assertion, text, pos) {
+                        stack.push(Frame::Explore(pc + 1));
+                    }
+                }
+                _ => list.slots_of_mut(pc).copy_from_slice(slots),
+            }
+        }
+    }
+
+    // --- Lazy DFA ---
+
+    fn dfa_is_match(&self, cache: &mut DfaCache, text: &str) -> bool {
+        if cache.states.is_empty() {
+            cache.restart = self.dfa_closure([0], false, false);
+        }
+
+        let initial = self.dfa_closure([0], true, text.is_empty());
+        let mut state = self.dfa_intern(cache, initial);
+
+        for c in text.chars() {
+            if cache.states[state].is_match {
+                return true;
+            }
+            state = match cache.states[state].transitions.get(&c) {
+                Some(&next) => next,
+                None => {
+                    let seeds: Vec<usize> = cache.states[state]
+                        .pcs
+                        .iter()
+                        .filter(|&&pc| self.char_matches(&self.program[pc], c))
+                        .map(|&pc| pc + 1)
+                        .chain(cache.restart.iter().copied())
+                        .collect();
+                    let pcs = self.dfa_closure(seeds, false, false);
+
+                    let flushed = cache.states.len() >= MAX_DFA_STATES;
+                    if flushed {
+                        cache.flush();
+                    }
+                    let next = self.dfa_intern(cache, pcs);
+                    // After a flush `state` no longer exists, so there's nothing to link
+                    if !flushed {
+                        cache.states[state].transitions.insert(c, next);
+                    }
+                    next
+                }
+            };
+        }
+
+        let state = &cache.states[state];
+        state.is_match
+            || self
+                .dfa_closure(state.pcs.iter().copied(), text.is_empty(), true)
+                .iter()
+                .any(|&pc| matches!(self.program[pc], Inst::Match))
+    }
+
+    fn dfa_intern(&self, cache: &mut DfaCache, pcs: Vec<usize>) -> usize {
+        let is_match = pcs.iter().any(|&pc| matches!(self.program[pc], Inst::Match));
+        cache.intern(pcs, is_match)
+    }
+
+    /// Epsilon closure for the DFA, keeping only states that matter for matching
+    ///
+    /// An unsatisfied `$` is kept in the set so it can be resolved at the end
+    /// of the input.
+    fn dfa_closure(&self, seeds: impl IntoIterator<Item = usize>, at_start: bool, at_end: bool) -> Vec<usize> {
+        let mut seen = SparseSet::new(self.program.len());
+        let mut kept = Vec::new();
+        let mut stack: Vec<usize> = seeds.into_iter().collect();
+
+        while let Some(pc) = stack.pop() {
+            if !seen.insert(pc) {
+                continue;
+            }
+            match &self.program[pc] {
+                Inst::Jmp(target) => stack.push(*target),
+                Inst::Split(first, second) => {
+                    stack.push(*second);
+                    stack.push(*first);
+                }
+                Inst::Save(_) => stack.push(pc + 1),
+                Inst::Assert(Assertion::StartText) => {
+                    if at_start {
+                        stack.push(pc + 1);
+                    }
+                }
+                Inst::Assert(Assertion::EndText) => {
+                    if at_end {
+                        stack.push(pc + 1);
+                    } else {
+                        kept.push(pc);
+                    }
+                }
+                Inst::Assert(_) => {}
+                _ => kept.push(pc),
+            }
+        }
+
+        kept.sort_unstable();
+        kept
+    }
+}
+
+impl Clone for Regex {
+    fn clone(&self) -> Regex {
+        Regex {
+            pattern: self.pattern.clone(),
+            program: self.program.clone(),
+            groups: self.groups,
+            case_insensitive: self.case_insensitive,
+            has_word_boundary: self.has_word_boundary,
+            dfa: Mutex::new(Vec::new()),
+        }
+    }
+}
+
+impl fmt::Debug for Regex {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("Regex")
+            .field("pattern", &self.pattern)
+            .field("case_insensitive", &self.case_insensitive)
+            .finish()
+    }
+}
+
+impl fmt::Display for Regex {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.pattern)
+    }
+}
+
+/// Iterator returned by [`Regex::find_iter`]
+pub struct FindIter<'r, 't> {
+    regex: &'r Regex,
+    text: &'t str,
+    next_start: Option<usize>,
+}
+
+impl<'t> Iterator for FindIter<'_, 't> {
+    type Item = Match<'t>;
+
+    fn next(&mut self) -> Option<Match<'t>> {
+        let start = self.next_start?;
+        let found = self.regex.find_at(self.text, start)?;
+        self.next_start = advance_after(self.text, found.start, found.end);
+        Some(found)
+    }
+}
+
+/// Iterator returned by [`Regex::captures_iter`]
+pub struct CapturesIter<'r, 't> {
+    regex: &'r Regex,
+    text: &'t str,
+    next_start: Option<usize>,
+}
+
+impl<'t> Iterator for CapturesIter<'_, 't> {
+    type Item = Captures<'t>;
+
+    fn next(&mut self) -> Option<Captures<'t>> {
+        let start = self.next_start?;
+        let caps = self.regex.captures_at(self.text, start)?;
+        let whole = caps.get(0)?;
+        self.next_start = advance_after(self.text, whole.start, whole.end);
+        Some(caps)
+    }
+}
+
+/// Where to resume after a match; empty matches step over one character
+fn advance_after(text: &str, start: usize, end: usize) -> Option<usize> {
+    if end > start {
+        Some(end)
+    } else {
+        text[end..].chars().next().map(|c| end + c.len_utf8())
+    }
+}
+
+fn assertion_holds(assertion: Assertion, text: &str, pos: usize) -> bool {
+    match assertion {
+        Assertion::StartText => pos == 0,
+        Assertion::EndText => pos == text.len(),
+        Assertion::WordBoundary | Assertion::NotWordBoundary => {
+            let before = text[..pos].chars().next_back().is_some_and(is_word_char);
+            let after = text[pos..].chars().next().is_some_and(is_word_char);
+            (before != after) == (assertion == Assertion::WordBoundary)
+        }
+    }
+}
+
+/// Uppercase `c` when it maps to exactly one character
+fn simple_upper(c: char) -> char {
+    let mut upper = c.to_uppercase();
+    match (upper.next(), upper.next()) {
+        (Some(u), None) => u,
+        _ => c,
+    }
+}
+
+/// Set of small integers with O(1) insert, membership and clear
+struct SparseSet {
+    dense: Vec<usize>,
+    sparse: Vec<usize>,
+}
+
+impl SparseSet {
+    fn new(capacity: usize) -> SparseSet {
+        SparseSet {
+            dense: Vec::with_capacity(capacity),
+            sparse: vec![0; capacity],
+        }
+    }
+
+    fn len(&self) -> usize {
+        self.dense.len()
+    }
+
+    fn is_empty(&self) -> bool {
+        self.dense.is_empty()
+    }
+
+    fn contains(&self, value: usize) -> bool {
+        let index = self.sparse[value];
+        index < self.dense.len() && self.dense[index] == value
+    }
+
+    /// Insert `value`, returning false if it was already present
+    fn insert(&mut self, value: usize) -> bool {
+        if self.contains(value) {
+            return false;
+        }
+        self.sparse[value] = self.dense.len();
+        self.dense.push(value);
+        true
+    }
+
+    fn clear(&mut self) {
+        self.dense.clear();
+    }
+}
+
+/// Pike VM thread list: active program counters plus their capture slots
+struct Threads {
+    set: SparseSet,
+    slots: Vec<Option<usize>>,
+    slot_count: usize,
+}
+
+impl Threads {
+    fn new(program_len: usize, slot_count: usize) -> Threads {
+        Threads {
+            set: SparseSet::new(program_len),
+            slots: vec![None; program_len * slot_count],
+            slot_count,
+        }
+    }
+
+    fn slots_of(&self, pc: usize) -> &[Option<usize>] {
+        &self.slots[pc * self.slot_count..(pc + 1) * self.slot_count]
+    }
+
+    fn slots_of_mut(&mut self, pc: usize) -> &mut [Option<usize>] {
+        &mut self.slots[pc * self.slot_count..(pc + 1) * self.slot_count]
+    }
+}
+
+/// A DFA state: the set of NFA states it stands for and its known transitions
+#[derive(Debug, Default)]
+struct DfaState {
+    pcs: Vec<usize>,
+    is_match: bool,
+    transitions: HashMap<char, usize>,
+}
+
+/// DFA states built so far, shared between calls to `is_match`
+#[derive(Debug, Default)]
+struct DfaCache {
+    states: Vec<DfaState>,
+    index: HashMap<Vec<usize>, usize>,
+    /// Closure of the start state away from the beginning of the text
+    restart: Vec<usize>,
+}
+
+impl DfaCache {
+    fn intern(&mut self, pcs: Vec<usize>, is_match: bool) -> usize {
+        if let Some(&id) = self.index.get(&pcs) {
+            return id;
+        }
+        let id = self.states.len();
+        self.states.push(DfaState {
+            is_match,
+            pcs: pcs.clone(),
+            transitions: HashMap::new(),
+        });
+        self.index.insert(pcs, id);
+        id
+    }
+
+    /// Forget every state; the restart set is kept since it never changes
+    fn flush(&mut self) {
+        self.states.clear();
+        self.index.clear();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn find(pattern: &str, text: &str) -> Option<(usize, usize)> {
+        Regex::new(pattern)
+            .unwrap()
+            .find(text)
+            .map(|m| (m.start(), m.end()))
+    }
+
+    #[test]
+    fn literals_and_dot() {
+        assert_eq!(find("duct", "safe, productive."), Some((9, 13)));
+        assert_eq!(find("a.c", "xxabcxx"), Some((2, 5)));
+        assert_eq!(find("a.c", "a\nc"), None);
+    }
+
+    #[test]
+    fn anchors() {
+        let re = Regex::new("^Rust").unwrap();
+        assert!(re.is_match("Rust:"));
+        assert!(!re.is_match("Trust me."));
+
+        let re = Regex::new("me\\.$").unwrap();
+        assert!(re.is_match("Trust me."));
+        assert!(!re.is_match("me. too"));
+
+        assert!(Regex::new("^$").unwrap().is_match(""));
+        assert!(!Regex::new("^$").unwrap().is_match("x"));
+    }
+
+    #[test]
+    fn classes_and_escapes() {
+        assert_eq!(find("[0-9]+", "abc 2024 def"), Some((4, 8)));
+        assert_eq!(find("[^a-z ]", "abc def!"), Some((7, 8)));
+        assert_eq!(find(r"\d\d:\d\d", "at 12:30 today"), Some((3, 8)));
+        assert_eq!(find(r"\s\w+", "hello world"), Some((5, 11)));
+        assert_eq!(find(r"[\d-]+", "tel 555-1234"), Some((4, 12)));
+        assert_eq!(find(r"[]]", "a]b"), Some((1, 2)));
+        assert_eq!(find(r"\.", "a.b"), Some((1, 2)));
+    }
+
+    #[test]
+    fn alternation_and_groups() {
+        let re = Regex::new("(cat|dog)s?").unwrap();
+        let caps = re.captures("hot dogs").unwrap();
+        assert_eq!(caps.get(0).unwrap().as_str(), "dogs");
+        assert_eq!(caps.get(1).unwrap().as_str(), "dog");
+
+        // Leftmost-first: the earlier alternative wins
+        assert_eq!(find("a|ab", "ab"), Some((0, 1)));
+        assert_eq!(find("(?:ab)+", "xababab"), Some((1, 7)));
+    }
+
+    #[test]
+    fn quantifiers() {
+        assert_eq!(find("ab*c", "ac abbbc"), Some((0, 2)));
+        assert_eq!(find("ab+c", "ac abbbc"), Some((3, 8)));
+        assert_eq!(find("colou?r", "color"), Some((0, 5)));
+        assert_eq!(find("a{3}", "aa aaaa"), Some((3, 6)));
+        assert_eq!(find("a{2,}", "a aaaaa"), Some((2, 7)));
+        assert_eq!(find("a{1,2}", "aaaa"), Some((0, 2)));
+        assert_eq!(find("<.+?>", "<b>bold</b>"), Some((0, 3)));
+        assert_eq!(find("<.+>", "<b>bold</b>"), Some((0, 11)));
+        // `{` that isn't a valid quantifier is a literal
+        assert_eq!(find("a{x", "a{x"), Some((0, 3)));
+    }
+
+    #[test]
+    fn word_boundaries() {
+        let re = Regex::new(r"\bthe\b").unwrap();
+        assert!(re.is_match("over the lazy dog"));
+        assert!(!re.is_match("other theme"));
+        assert!(Regex::new(r"\Bus").unwrap().is_match("Trust"));
+    }
+
+    #[test]
+    fn case_insensitive_matching() {
+        assert!(Regex::case_insensitive("ΟΔΥΣΣΕΥΣ").unwrap().is_match("Οδυσσευς"));
+        let re = Regex::case_insensitive("rust").unwrap();
+        assert!(re.is_match("RUST has zero-cost abstractions"));
+        assert!(re.is_match("Trust me."));
+        assert!(Regex::case_insensitive("[a-c]+").unwrap().is_match("ABC"));
+    }
+
+    #[test]
+    fn find_iter_handles_empty_matches() {
+        let re = Regex::new("a*").unwrap();
+        let spans: Vec<(usize, usize)> = re.find_iter("baa").map(|m| (m.start(), m.end())).collect();
+        assert_eq!(spans, vec![(0, 0), (1, 3), (3, 3)]);
+
+        let words: Vec<&str> = Regex::new(r"\w+").unwrap().find_iter("one two  three").map(|m| m.as_str()).collect();
+        assert_eq!(words, vec!["one", "two", "three"]);
+    }
+
+    #[test]
+    fn unicode_text() {
+        assert_eq!(find("é.", "café!"), Some((3, 6)));
+        assert!(Regex::new("^.{4}$").unwrap().is_match("Дом!"));
+    }
+
+    #[test]
+    fn dfa_and_pike_agree() {
+        let patterns = ["a(b|c)*d", "^x?y+$", "[a-f]{2,3}z", "(foo|bar)$", "q*", "^$"];
+        let inputs = ["", "abd", "acbcd", "ad", "yyy", "xyy", "xxy", "abcz", "fz", "foobar", "barfoo", "q"];
+
+        for pattern in patterns {
+            let re = Regex::new(pattern).unwrap();
+            for input in inputs {
+                assert_eq!(
+                    re.is_match(input),
+                    re.pike(input, 0, true).is_some(),
+                    "pattern {:?} on {:?}",
+                    pattern,
+                    input
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn pathological_pattern_is_linear() {
+        // (a?){n}a{n} takes exponential time with a backtracking engine
+        let n = 30;
+        let pattern = format!("{}{}", "a?".repeat(n), "a".repeat(n));
+        let text = "a".repeat(n);
+        assert!(Regex::new(&pattern).unwrap().is_match(&text));
+        assert!(Regex::new("(x+x+)+y").unwrap().find(&"x".repeat(5_000)).is_none());
+    }
+
+    #[test]
+    fn parse_errors() {
+        assert_eq!(Regex::new("(ab").unwrap_err(), RegexError::UnclosedGroup(0));
+        assert_eq!(Regex::new("ab)").unwrap_err(), RegexError::UnopenedGroup(2));
+        assert_eq!(Regex::new("[ab").unwrap_err(), RegexError::UnclosedClass(0));
+        assert_eq!(Regex::new("[z-a]").unwrap_err(), RegexError::InvalidRange('z', 'a'));
+        assert_eq!(Regex::new("*a").unwrap_err(), RegexError::MissingRepeatOperand(0));
+        assert_eq!(Regex::new("a{3,1}").unwrap_err(), RegexError::InvalidRepeat(1));
+        assert_eq!(Regex::new(r"\q").unwrap_err(), RegexError::InvalidEscape('q'));
+        assert_eq!(Regex::new("a\\").unwrap_err(), RegexError::TrailingBackslash);
+    }
+
+    #[test]
+    fn deep_nesting_is_an_error_not_a_stack_overflow() {
+        let nested = |n: usize| format!("{}a{}", "(".repeat(n), ")".repeat(n));
+        assert!(Regex::new(&nested(MAX_NESTING)).unwrap().is_match("a"));
+        assert_eq!(Regex::new(&nested(MAX_NESTING + 1)).unwrap_err(), RegexError::TooDeep(MAX_NESTING));
+        assert_eq!(Regex::new(&nested(5_000)).unwrap_err(), RegexError::TooDeep(MAX_NESTING));
+        assert!(Regex::new(&format!("{})", "(".repeat(100_000))).is_err());
+
+        // Stacked quantifiers nest too, alone and around groups
+        assert_eq!(Regex::new(&format!("a{}", "*".repeat(50_000))).unwrap_err(), RegexError::TooDeep(251));
+        let mixed = format!("{}a{}", "(".repeat(200), ")*".repeat(200));
+        assert!(matches!(Regex::new(&mixed), Err(RegexError::TooDeep(_))));
+        let err = Regex::new(&nested(300)).unwrap_err().to_string();
+        assert_eq!(err, "pattern nested too deeply at offset 250; at most 250 levels of groups and quantifiers");
+    }
+}
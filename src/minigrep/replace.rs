@@ -0,0 +1,360 @@
+//! # Search and Replace
+//!
+//! `--replace` rewrites each match using a template. In the template, `$0`
+//! is the whole match, `$1`, `$2`, ... are capture groups in `-E` mode,
+//! `${1}` separates a group number from digits that follow it, and `$$` is
+//! a literal `$`.
+//!
+//! `--in-place` writes the result back to each file atomically: the new
+//! contents go to a temporary file in the same directory, which is then
+//! renamed over the original, so readers see either the old file or the new
+//! one and never half of each. `--dry-run` prints a unified diff instead.
+
+use super::Matcher;
+use crate::fs_util;
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+/// A parsed `--replace` template
+///
+/// # Example
+/// ```
+/// use rust_book_examples::minigrep::replace::Template;
+///
+/// let template = Template::parse("$2, $1 (${1}st) costs $$5").unwrap();
+/// let groups = ["Ferris Crab", "Ferris", "Crab"];
+///
+/// let mut out = String::new();
+/// template.expand(|i| groups.get(i).copied(), &mut out);
+/// assert_eq!(out, "Crab, Ferris (Ferrisst) costs $5");
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Template {
+    parts: Vec<Part>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Part {
+    Literal(String),
+    Group(usize),
+}
+
+/// Errors from parsing a template
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum TemplateError {
+    /// A `${` with no closing `}`, at this byte offset
+    UnclosedBrace(usize),
+    /// Something other than a group number inside `${...}`
+    InvalidGroup(String),
+}
+
+impl fmt::Display for TemplateError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            TemplateError::UnclosedBrace(offset) => write!(f, "unclosed '${{' at offset {} in replacement", offset),
+            TemplateError::InvalidGroup(name) => write!(f, "invalid group '{}' in replacement", name),
+        }
+    }
+}
+
+impl std::error::Error for TemplateError {}
+
+impl Template {
+    pub fn parse(text: &str) -> Result<Template, TemplateError> {
+        let mut parts = Vec::new();
+        let mut literal = String::new();
+        let mut rest = text;
+
+        while let Some(dollar) = rest.find('$') {
+            literal.push_str(&rest[..dollar]);
+            let after = &rest[dollar + 1..];
+
+            let (group, consumed) = if let Some(braced) = after.strip_prefix('{') {
+                let close = braced
+                    .find('}')
+                    .ok_or(TemplateError::UnclosedBrace(text.len() - rest.len() + dollar))?;
+                let name = &braced[..close];
+                let group = name.parse().map_err(|_| TemplateError::InvalidGroup(name.to_string()))?;
+                (Some(group), close + 2)
+            } else if after.starts_with('$') {
+                literal.push('$');
+                (None, 1)
+            } else {
+                let digits = after.len() - after.trim_start_matches(|c: char| c.is_ascii_digit()).len();
+                match after[..digits].parse() {
+                    Ok(group) => (Some(group), digits),
+                    // A `$` not followed by a group is just a `$`
+                    Err(_) => {
+                        literal.push('$');
+                        (None, 0)
+                    }
+                }
+            };
+
+            if let Some(group) = group {
+                if !literal.is_empty() {
+                    parts.push(Part::Literal(std::mem::take(&mut literal)));
+                }
+                parts.push(Part::Group(group));
+            }
+            rest = &after[consumed..];
+        }
+
+        literal.push_str(rest);
+        if !literal.is_empty() {
+            parts.push(Part::Literal(literal));
+        }
+        Ok(Template { parts })
+    }
+
+    /// Append the template to `out`, looking groups up with `group`
+    ///
+    /// Groups that don't exist or didn't take part in the match expand to
+    /// nothing.
+    pub fn expand<'t, F>(&self, group: F, out: &mut String)
+    where
+        F: Fn(usize) -> Option<&'t str>,
+    {
+        for part in &self.parts {
+            match part {
+                Part::Literal(text) => out.push_str(text),
+                Part::Group(index) => out.push_str(group(*index).unwrap_or("")),
+            }
+        }
+    }
+}
+
+/// One line that a replacement changed
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LineChange {
+    pub number: usize,
+    pub before: String,
+    pub after: String,
+}
+
+/// Replace the matches on every line of `contents`
+///
+/// Line endings, `\n` or `\r\n`, are kept as they were. Returns the new
+/// contents and the lines that changed.
+///
+/// # Example
+/// ```
+/// use rust_book_examples::minigrep::replace::{replace_text, Template};
+/// use rust_book_examples::minigrep::Matcher;
+///
+/// let matcher = Matcher::new(r"(\w+)@example\.com", false, true).unwrap();
+/// let template = Template::parse("<$1 at example.com>").unwrap();
+///
+/// let (text, changes) = replace_text("Mail ferris@example.com\r\nthanks\r\n", &matcher, &template);
+/// assert_eq!(text, "Mail <ferris at example.com>\r\nthanks\r\n");
+/// assert_eq!(changes[0].number, 1);
+/// ```
+pub fn replace_text(contents: &str, matcher: &Matcher, template: &Template) -> (String, Vec<LineChange>) {
+    let mut output = String::with_capacity(contents.len());
+    let mut changes = Vec::new();
+
+    for (index, raw) in contents.split_inclusive('\n').enumerate() {
+        let line = raw.strip_suffix('\n').unwrap_or(raw);
+        let line = line.strip_suffix('\r').unwrap_or(line);
+        let ending = &raw[line.len()..];
+
+        let replaced = matcher.replace_all(line, template);
+        if replaced != line {
+            changes.push(LineChange {
+                number: index + 1,
+                before: line.to_string(),
+                after: replaced.clone(),
+            });
+        }
+        output.push_str(&replaced);
+        output.push_str(ending);
+    }
+
+    (output, changes)
+}
+
+/// Read `path`, run the replacement over it, and return the edit to make
+///
+/// # Errors
+///
+/// Fails if the file can't be read, or holds binary data or invalid UTF-8,
+/// which a text replacement could corrupt.
+pub fn edit_file(path: &Path, matcher: &Matcher, template: &Template) -> io::Result<FileEdit> {
+    let bytes = fs::read(path)?;
+    if bytes.contains(&0) {
+        return Err(io::Error::new(io::ErrorKind::InvalidData, "binary file, not editing"));
+    }
+    let contents = String::from_utf8(bytes)
+        .map_err(|_| io::Error::new(io::ErrorKind::InvalidData, "not valid UTF-8, not editing"))?;
+
+    let (contents, changes) = replace_text(&contents, matcher, template);
+    Ok(FileEdit {
+        path: path.to_path_buf(),
+        contents,
+        changes,
+    })
+}
+
+/// The new contents for one file
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FileEdit {
+    pub path: PathBuf,
+    pub contents: String,
+    pub changes: Vec<LineChange>,
+}
+
+impl FileEdit {
+    /// Write the new contents over the file, keeping the old one as
+    /// `path` + `backup_suffix` if a suffix is given
+    ///
+    /// Files without changes are left alone.
+    pub fn apply(&self, backup_suffix: Option<&str>) -> io::Result<()> {
+        if self.changes.is_empty() {
+            return Ok(());
+        }
+        fs_util::write_atomically(&self.path, self.contents.as_bytes(), backup_suffix)
+    }
+
+    /// The changes as a unified diff
+    pub fn diff(&self) -> String {
+        unified_diff(&self.path.to_string_lossy(), &self.changes)
+    }
+}
+
+/// A unified diff of `changes`, labelled with `path`
+///
+/// Each run of adjacent changed lines becomes one hunk, without context.
+///
+/// # Example
+/// ```
+/// use rust_book_examples::minigrep::replace::{unified_diff, LineChange};
+///
+/// let changes = [LineChange { number: 3, before: "Pick three.".into(), after: "Pick four.".into() }];
+/// assert_eq!(
+///     unified_diff("poem.txt", &changes),
+///     "--- a/poem.txt\n+++ b/poem.txt\n@@ -3 +3 @@\n-Pick three.\n+Pick four.\n"
+/// );
+/// ```
+pub fn unified_diff(path: &str, changes: &[LineChange]) -> String {
+    let mut diff = String::new();
+    if changes.is_empty() {
+        return diff;
+    }
+    diff.push_str(&format!("--- a/{}\n+++ b/{}\n", path, path));
+
+    // Replacements can add lines, shifting where later hunks land in the new file
+    let mut shift: isize = 0;
+    let mut start = 0;
+    while start < changes.len() {
+        let mut end = start + 1;
+        while end < changes.len() && changes[end].number == changes[end - 1].number + 1 {
+            end += 1;
+        }
+        let hunk = &changes[start..end];
+
+        let old_lines: Vec<&str> = hunk.iter().map(|c| c.before.as_str()).collect();
+        let new_lines: Vec<&str> = hunk.iter().flat_map(|c| c.after.split('\n')).collect();
+        let old_start = hunk[0].number;
+        let new_start = old_start as isize + shift;
+
+        diff.push_str(&format!(
+            "@@ -{} +{} @@\n",
+            range(old_start as isize, old_lines.len()),
+            range(new_start, new_lines.len())
+        ));
+        for line in &old_lines {
+            diff.push_str(&format!("-{}\n", line));
+        }
+        for line in &new_lines {
+            diff.push_str(&format!("+{}\n", line));
+        }
+
+        shift += new_lines.len() as isize - old_lines.len() as isize;
+        start = end;
+    }
+    diff
+}
+
+/// A hunk range: the count is left out when it's 1
+fn range(start: isize, len: usize) -> String {
+    if len == 1 {
+        start.to_string()
+    } else {
+        format!("{},{}", start, len)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fs_util::TempPath;
+    use crate::minigrep::MatchOptions;
+
+    fn expand(template: &str, groups: &[&str]) -> String {
+        let mut out = String::new();
+        Template::parse(template).unwrap().expand(|i| groups.get(i).copied(), &mut out);
+        out
+    }
+
+    #[test]
+    fn templates() {
+        assert_eq!(expand("[$0]", &["x"]), "[x]");
+        assert_eq!(expand("$1$2$3", &["ab", "a", "b"]), "ab");
+        assert_eq!(expand("${1}0 $10", &["", "one"]), "one0 ");
+        assert_eq!(expand("$$1 costs $ and $x", &["m", "g"]), "$1 costs $ and $x");
+
+        assert_eq!(Template::parse("a ${1"), Err(TemplateError::UnclosedBrace(2)));
+        assert_eq!(Template::parse("${name}"), Err(TemplateError::InvalidGroup("name".to_string())));
+    }
+
+    #[test]
+    fn replaces_literals_and_regex_groups() {
+        let matcher = Matcher::new("rust", true, false).unwrap();
+        let template = Template::parse("[$0]").unwrap();
+        assert_eq!(matcher.replace_all("Rust and rust", &template), "[Rust] and [rust]");
+
+        let options = MatchOptions {
+            regex: true,
+            word: true,
+            ..MatchOptions::default()
+        };
+        let matcher = Matcher::with_options(r"(\w+)=(\d+)", &options).unwrap();
+        let template = Template::parse("$2=$1").unwrap();
+        assert_eq!(matcher.replace_all("a=1, xb=2c, c=3", &template), "1=a, xb=2c, 3=c");
+    }
+
+    #[test]
+    fn diff_hunks_track_added_lines() {
+        let matcher = Matcher::new("x", false, false).unwrap();
+        let template = Template::parse("y\ny").unwrap();
+        let (text, changes) = replace_text("x\nx\nkeep\nx\n", &matcher, &template);
+        assert_eq!(text, "y\ny\ny\ny\nkeep\ny\ny\n");
+        assert_eq!(
+            unified_diff("f", &changes),
+            "--- a/f\n+++ b/f\n@@ -1,2 +1,4 @@\n-x\n-x\n+y\n+y\n+y\n+y\n@@ -4 +6,2 @@\n-x\n+y\n+y\n"
+        );
+    }
+
+    #[test]
+    fn in_place_edit_with_backup() {
+        let dir = TempPath::dir("minigrep_replace");
+        let path = dir.join("poem.txt");
+        fs::write(&path, "How dreary to be somebody!\r\nHow public, like a frog\r\n").unwrap();
+
+        let matcher = Matcher::new("frog", false, false).unwrap();
+        let edit = edit_file(&path, &matcher, &Template::parse("toad").unwrap()).unwrap();
+        assert_eq!(edit.changes.len(), 1);
+        assert!(edit.diff().contains("-How public, like a frog\n+How public, like a toad\n"));
+
+        edit.apply(Some(".bak")).unwrap();
+        assert_eq!(fs::read_to_string(&path).unwrap(), "How dreary to be somebody!\r\nHow public, like a toad\r\n");
+        assert!(fs::read_to_string(dir.join("poem.txt.bak")).unwrap().contains("frog"));
+        // Nothing but the file and its backup is left behind
+        assert_eq!(fs::read_dir(&dir).unwrap().count(), 2);
+
+        fs::write(&path, b"bin\0ary").unwrap();
+        assert_eq!(edit_file(&path, &matcher, &Template::parse("").unwrap()).unwrap_err().kind(), io::ErrorKind::InvalidData);
+    }
+}
@@ -0,0 +1,417 @@
+//! # Streaming Search
+//!
+//! Searches any [`BufRead`] one line at a time, so memory use depends on the
+//! longest line rather than the size of the file.
+//!
+//! - The path `-` reads standard input
+//! - Files containing NUL bytes are treated as binary: their lines aren't
+//!   printed, only whether they matched ("binary file matches")
+//! - A line longer than [`MAX_LINE_LEN`] also counts as binary; only its
+//!   first `MAX_LINE_LEN` bytes are kept and searched, so a file without
+//!   newlines can't fill memory
+//! - Invalid UTF-8 is decoded lossily, each bad sequence becoming `U+FFFD`,
+//!   so the valid text around it can still match
+
+use super::Matcher;
+use std::borrow::Cow;
+use std::fs::File;
+use std::io::{self, BufRead, BufReader};
+use std::path::Path;
+
+/// The path that means "read standard input"
+pub const STDIN_PATH: &str = "-";
+
+/// Read buffer size for files; large enough to make binary detection reliable
+const BUFFER_SIZE: usize = 64 * 1024;
+
+/// The most bytes of one line that are kept; longer lines are treated as binary
+pub const MAX_LINE_LEN: usize = 1024 * 1024;
+
+/// Open `path` for streaming, or standard input if it is `-`
+pub fn open_input(path: &Path) -> io::Result<Box<dyn BufRead>> {
+    if is_stdin(path) {
+        Ok(Box::new(io::stdin().lock()))
+    } else {
+        let file = File::open(path)?;
+        Ok(Box::new(BufReader::with_capacity(BUFFER_SIZE, file)))
+    }
+}
+
+/// Whether `path` is the `-` placeholder for standard input
+pub fn is_stdin(path: &Path) -> bool {
+    path.as_os_str() == STDIN_PATH
+}
+
+/// The name to show for `path` in output, like grep's "(standard input)"
+pub fn display_name(path: &Path) -> Cow<'_, str> {
+    if is_stdin(path) {
+        Cow::Borrowed("(standard input)")
+    } else {
+        path.to_string_lossy()
+    }
+}
+
+/// Reads numbered lines from a [`BufRead`], reusing one buffer
+#[derive(Debug)]
+pub struct LineReader<R> {
+    reader: R,
+    buffer: Vec<u8>,
+    line_number: usize,
+    /// Bytes read before the current line
+    offset: u64,
+    /// Bytes the current line took up in the input, including any not kept
+    line_len: u64,
+    max_line_len: usize,
+}
+
+impl<R: BufRead> LineReader<R> {
+    pub fn new(reader: R) -> LineReader<R> {
+        LineReader::with_max_line_len(reader, MAX_LINE_LEN)
+    }
+
+    /// Like [`LineReader::new`], but keeping at most `max` bytes of a line
+    pub fn with_max_line_len(reader: R, max: usize) -> LineReader<R> {
+        LineReader {
+            reader,
+            buffer: Vec::new(),
+            line_number: 0,
+            offset: 0,
+            line_len: 0,
+            max_line_len: max,
+        }
+    }
+
+    /// Peek at the start of the input and report whether it looks binary
+    ///
+    /// Nothing is consumed, so call this before reading any lines.
+    pub fn starts_binary(&mut self) -> io::Result<bool> {
+        Ok(self.reader.fill_buf()?.contains(&0))
+    }
+
+    /// The next line without its `\n` or `\r\n`, decoded lossily
+    ///
+    /// Returns the 1-based line number, the text, and whether the raw line
+    /// looks binary: it contained a NUL byte, or was too long to keep whole,
+    /// in which case the text is only its start.
+    pub fn next_line(&mut self) -> io::Result<Option<(usize, Cow<'_, str>, bool)>> {
+        self.offset += self.line_len;
+        self.line_len = 0;
+        self.buffer.clear();
+        let mut truncated = false;
+
+        // Like read_until, but past max_line_len the rest of the line is skipped
+        loop {
+            let available = self.reader.fill_buf()?;
+            if available.is_empty() {
+                break;
+            }
+            let (chunk, done) = match available.iter().position(|&b| b == b'\n') {
+                Some(newline) => (&available[..=newline], true),
+                None => (available, false),
+            };
+            let room = self.max_line_len.saturating_sub(self.buffer.len());
+            truncated |= chunk.len() > room;
+            self.buffer.extend_from_slice(&chunk[..chunk.len().min(room)]);
+            let used = chunk.len();
+            self.reader.consume(used);
+            self.line_len += used as u64;
+            if done {
+                break;
+            }
+        }
+        if self.line_len == 0 {
+            return Ok(None);
+        }
+        self.line_number += 1;
+
+        let mut line = self.buffer.as_slice();
+        if let Some(rest) = line.strip_suffix(b"\n") {
+            line = rest.strip_suffix(b"\r").unwrap_or(rest);
+        }
+        let binary = truncated || line.contains(&0);
+
+        Ok(Some((self.line_number, String::from_utf8_lossy(line), binary)))
+    }
+
+    /// Byte offset in the input where the next line starts
+    pub fn next_offset(&self) -> u64 {
+        self.offset + self.line_len
+    }
+}
+
+/// A line passed on by [`Searcher::search_lines`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SearchLine<'a> {
+    /// 1-based line number
+    pub number: usize,
+    /// Byte offset of the start of the line in the input
+    pub offset: u64,
+    /// The line without its terminator
+    pub text: &'a str,
+    /// Whether the line was selected, rather than passed on as context
+    pub selected: bool,
+}
+
+/// What searching one input found
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct SearchSummary {
+    /// Number of selected lines (binary inputs stop counting at the first)
+    pub matches: usize,
+    /// Whether binary data was found, which stops lines being reported
+    pub binary: bool,
+}
+
+/// Which lines a search selects and when it stops
+///
+/// The default selects matching lines and reads to the end of the input.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct Searcher {
+    /// Select the lines that don't match (`-v`)
+    pub invert: bool,
+    /// Stop after this many selected lines (`-m`)
+    pub max_count: Option<usize>,
+    /// Lines still passed on after the last selected line once `max_count`
+    /// is reached, so `-A` context isn't cut short
+    pub after_context: usize,
+}
+
+impl Searcher {
+    /// Search `reader` line by line
+    ///
+    /// `each_line` receives every line of a text input as
+    /// `(line_number, text, is_selected)`, so callers can print matches and
+    /// the context around them. Once binary data is seen no more lines are
+    /// passed on, and the search stops at the next selected line.
+    pub fn search<R, F>(&self, reader: R, matcher: &Matcher, mut each_line: F) -> io::Result<SearchSummary>
+    where
+        R: BufRead,
+        F: FnMut(usize, &str, bool),
+    {
+        self.search_lines(reader, matcher, |line| each_line(line.number, line.text, line.selected))
+    }
+
+    /// Like [`Searcher::search`], but each line also comes with its byte offset
+    pub fn search_lines<R, F>(&self, reader: R, matcher: &Matcher, mut each_line: F) -> io::Result<SearchSummary>
+    where
+        R: BufRead,
+        F: FnMut(SearchLine<'_>),
+    {
+        let mut summary = SearchSummary::default();
+        if self.max_count == Some(0) {
+            return Ok(summary);
+        }
+
+        let mut lines = LineReader::new(reader);
+        summary.binary = lines.starts_binary()?;
+        // Counts down the trailing context once max_count has been reached
+        let mut trailing: Option<usize> = None;
+
+        loop {
+            let offset = lines.next_offset();
+            let Some((number, text, has_nul)) = lines.next_line()? else {
+                break;
+            };
+            let line = |selected| SearchLine {
+                number,
+                offset,
+                text: &text,
+                selected,
+            };
+
+            if let Some(remaining) = trailing.as_mut() {
+                if *remaining == 0 || has_nul {
+                    break;
+                }
+                each_line(line(false));
+                *remaining -= 1;
+                continue;
+            }
+
+            summary.binary |= has_nul;
+            let selected = matcher.is_match(&text) != self.invert;
+            if selected {
+                summary.matches += 1;
+            }
+
+            if summary.binary {
+                if selected {
+                    break;
+                }
+                continue;
+            }
+
+            each_line(line(selected));
+            if selected && Some(summary.matches) == self.max_count {
+                trailing = Some(self.after_context);
+            }
+        }
+
+        Ok(summary)
+    }
+}
+
+/// Search `reader` line by line with the default [`Searcher`]
+///
+/// # Example
+/// ```
+/// use rust_book_examples::minigrep::stream::search_reader;
+/// use rust_book_examples::minigrep::Matcher;
+///
+/// let matcher = Matcher::new("us", false, false).unwrap();
+/// let input = "I'm nobody! Who are you?\nThen there's a pair of us!\n".as_bytes();
+///
+/// let mut matched = Vec::new();
+/// let summary = search_reader(input, &matcher, |number, text, is_match| {
+///     if is_match {
+///         matched.push(format!("{}:{}", number, text));
+///     }
+/// })
+/// .unwrap();
+///
+/// assert_eq!(matched, vec!["2:Then there's a pair of us!"]);
+/// assert_eq!(summary.matches, 1);
+/// assert!(!summary.binary);
+/// ```
+pub fn search_reader<R, F>(reader: R, matcher: &Matcher, each_line: F) -> io::Result<SearchSummary>
+where
+    R: BufRead,
+    F: FnMut(usize, &str, bool),
+{
+    Searcher::default().search(reader, matcher, each_line)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn collect(input: &[u8], query: &str) -> (Vec<(usize, String, bool)>, SearchSummary) {
+        let matcher = Matcher::new(query, false, false).unwrap();
+        let mut lines = Vec::new();
+        let summary = search_reader(input, &matcher, |number, text, is_match| {
+            lines.push((number, text.to_string(), is_match))
+        })
+        .unwrap();
+        (lines, summary)
+    }
+
+    #[test]
+    fn reports_every_line_with_numbers() {
+        let (lines, summary) = collect(b"safe\r\nfast\nproductive", "fast");
+        assert_eq!(
+            lines,
+            vec![
+                (1, "safe".to_string(), false),
+                (2, "fast".to_string(), true),
+                (3, "productive".to_string(), false),
+            ]
+        );
+        assert_eq!(summary, SearchSummary { matches: 1, binary: false });
+    }
+
+    #[test]
+    fn binary_input_reports_no_lines() {
+        let (lines, summary) = collect(b"ELF\0\x01\x02\nsymbol main\nsymbol main\n", "main");
+        assert!(lines.is_empty());
+        assert_eq!(summary, SearchSummary { matches: 1, binary: true });
+
+        let (_, summary) = collect(b"\0\0\0", "main");
+        assert_eq!(summary, SearchSummary { matches: 0, binary: true });
+    }
+
+    #[test]
+    fn nul_bytes_later_in_the_file_switch_to_binary() {
+        let mut input = b"text main\n".repeat(BUFFER_SIZE / 8);
+        input.extend_from_slice(b"\0 main\nmain\n");
+
+        let matcher = Matcher::new("main", false, false).unwrap();
+        let reader = BufReader::with_capacity(16, input.as_slice());
+        let mut printed = 0;
+        let summary = search_reader(reader, &matcher, |_, _, _| printed += 1).unwrap();
+
+        assert_eq!(printed, BUFFER_SIZE / 8);
+        assert!(summary.binary);
+    }
+
+    #[test]
+    fn overlong_lines_are_binary_and_not_kept() {
+        let mut input = b"short main\n".to_vec();
+        input.extend(std::iter::repeat_n(b'x', 100));
+        input.extend_from_slice(b" main\nafter\n");
+
+        let mut lines = LineReader::with_max_line_len(BufReader::with_capacity(8, input.as_slice()), 16);
+        assert_eq!(lines.next_line().unwrap(), Some((1, "short main".into(), false)));
+        let (number, text, binary) = lines.next_line().unwrap().unwrap();
+        assert_eq!((number, text.len(), binary), (2, 16, true));
+        assert_eq!(lines.next_offset(), 11 + 106);
+        assert_eq!(lines.next_line().unwrap(), Some((3, "after".into(), false)));
+        assert_eq!(lines.next_line().unwrap(), None);
+
+        // A file with no newlines at all is searched without being read whole
+        let huge = vec![b'a'; MAX_LINE_LEN * 3];
+        let (lines, summary) = collect(&huge, "a");
+        assert!(lines.is_empty());
+        assert_eq!(summary, SearchSummary { matches: 1, binary: true });
+    }
+
+    #[test]
+    fn invert_and_max_count() {
+        let matcher = Matcher::new("a", false, false).unwrap();
+        let input = b"a1\nb2\na3\nb4\na5\nb6\n";
+        let run = |searcher: Searcher| {
+            let mut lines = Vec::new();
+            let summary = searcher
+                .search(&input[..], &matcher, |number, _, selected| lines.push((number, selected)))
+                .unwrap();
+            (lines, summary.matches)
+        };
+
+        let inverted = Searcher {
+            invert: true,
+            ..Searcher::default()
+        };
+        let (lines, matches) = run(inverted);
+        assert_eq!(matches, 3);
+        assert_eq!(lines.iter().filter(|(_, selected)| *selected).count(), 3);
+        assert!(lines[1] == (2, true) && lines[0] == (1, false));
+
+        let limited = Searcher {
+            max_count: Some(2),
+            after_context: 1,
+            ..Searcher::default()
+        };
+        let (lines, matches) = run(limited);
+        assert_eq!(matches, 2);
+        assert_eq!(lines, vec![(1, true), (2, false), (3, true), (4, false)]);
+
+        let none = Searcher {
+            max_count: Some(0),
+            ..Searcher::default()
+        };
+        assert_eq!(run(none), (vec![], 0));
+    }
+
+    #[test]
+    fn lines_carry_byte_offsets() {
+        let matcher = Matcher::new("a", false, false).unwrap();
+        let mut offsets = Vec::new();
+        Searcher::default()
+            .search_lines(&b"a1\r\nbb2\n\na3"[..], &matcher, |line| offsets.push((line.number, line.offset)))
+            .unwrap();
+        assert_eq!(offsets, vec![(1, 0), (2, 4), (3, 8), (4, 9)]);
+    }
+
+    #[test]
+    fn invalid_utf8_is_decoded_lossily() {
+        let (lines, summary) = collect(b"caf\xe9 ownership\nplain\n", "ownership");
+        assert_eq!(lines[0], (1, "caf\u{FFFD} ownership".to_string(), true));
+        assert_eq!(summary.matches, 1);
+    }
+
+    #[test]
+    fn stdin_placeholder() {
+        assert!(is_stdin(Path::new("-")));
+        assert!(!is_stdin(Path::new("./-")));
+        assert_eq!(display_name(Path::new("-")), "(standard input)");
+        assert_eq!(display_name(Path::new("poem.txt")), "poem.txt");
+    }
+}
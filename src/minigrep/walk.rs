@@ -0,0 +1,600 @@
+//! # Recursive File Walking
+//!
+//! Expands the paths given to minigrep into the list of files to search.
+//!
+//! - Directories are walked recursively in sorted order, so output is stable
+//! - `--include`/`--exclude` globs filter what the walk finds
+//! - Hidden entries (names starting with `.`) are skipped unless asked for
+//! - Paths listed in `.gitignore` files are skipped unless asked for
+//! - Symbolic links found inside directories are skipped unless asked for;
+//!   when followed, a link back to a directory being walked is not
+//! - [`walk_parallel`] reads directories on a [`ThreadPool`] with the same result
+//!
+//! Files named explicitly on the command line are always searched, and `-`
+//! is passed through untouched so the caller can read standard input.
+
+use super::glob::Glob;
+use super::stream::is_stdin;
+use crate::thread_pool::ThreadPool;
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+/// Settings that control which files a walk yields
+#[derive(Debug, Clone)]
+pub struct WalkOptions {
+    /// Only yield files matching at least one of these (if any are given)
+    pub include: Vec<Glob>,
+    /// Never yield files or enter directories matching any of these
+    pub exclude: Vec<Glob>,
+    /// Also walk hidden files and directories
+    pub hidden: bool,
+    /// Honor `.gitignore` files found along the way
+    pub respect_gitignore: bool,
+    /// Follow symbolic links found while walking (`--follow`)
+    pub follow_links: bool,
+}
+
+impl Default for WalkOptions {
+    fn default() -> WalkOptions {
+        WalkOptions {
+            include: Vec::new(),
+            exclude: Vec::new(),
+            hidden: false,
+            respect_gitignore: true,
+            follow_links: false,
+        }
+    }
+}
+
+/// A path that couldn't be read during the walk
+#[derive(Debug)]
+pub struct WalkError {
+    pub path: PathBuf,
+    pub error: io::Error,
+}
+
+impl fmt::Display for WalkError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}: {}", self.path.display(), self.error)
+    }
+}
+
+impl std::error::Error for WalkError {}
+
+/// Expand `paths` into the files to search
+///
+/// Each entry is either a file path or the error met while reading a path.
+/// Like `grep -r`, a path in `paths` is followed even if it's a symbolic link;
+/// links found inside directories are only followed with `follow_links`.
+///
+/// # Example
+/// ```
+/// use rust_book_examples::minigrep::walk::{walk, WalkOptions};
+///
+/// let files: Vec<_> = walk(&["src"], &WalkOptions::default())
+///     .into_iter()
+///     .filter_map(Result::ok)
+///     .collect();
+/// assert!(files.iter().any(|path| path.ends_with("lib.rs")));
+/// ```
+pub fn walk<P: AsRef<Path>>(paths: &[P], options: &WalkOptions) -> Vec<Result<PathBuf, WalkError>> {
+    let mut results = Vec::new();
+
+    for path in paths {
+        let path = path.as_ref();
+        match start(path) {
+            Start::Dir => {
+                walk_dir(path, path, options, &[], &mut results);
+            }
+            Start::File(result) => results.push(result),
+        }
+    }
+
+    results
+}
+
+/// Like [`walk`], but reads directories on the pool's threads
+///
+/// Each level of the tree is read in parallel; the results are then sorted
+/// so they come out in exactly the order [`walk`] gives.
+///
+/// # Example
+/// ```
+/// use rust_book_examples::minigrep::walk::{walk, walk_parallel, WalkOptions};
+/// use rust_book_examples::thread_pool::ThreadPool;
+///
+/// let pool = ThreadPool::new(4);
+/// let options = WalkOptions::default();
+/// let serial: Vec<_> = walk(&["src"], &options).into_iter().map(Result::unwrap).collect();
+/// let parallel: Vec<_> = walk_parallel(&["src"], &options, &pool).into_iter().map(Result::unwrap).collect();
+/// assert_eq!(serial, parallel);
+/// ```
+pub fn walk_parallel<P: AsRef<Path>>(
+    paths: &[P],
+    options: &WalkOptions,
+    pool: &ThreadPool,
+) -> Vec<Result<PathBuf, WalkError>> {
+    let options = Arc::new(options.clone());
+    // (argument index, path) reproduces walk's depth-first, sorted order
+    let mut found: Vec<(usize, PathBuf, Result<PathBuf, WalkError>)> = Vec::new();
+    let mut frontier: Vec<PendingDir> = Vec::new();
+
+    for (index, path) in paths.iter().enumerate() {
+        let path = path.as_ref();
+        match start(path) {
+            Start::Dir => frontier.push(PendingDir {
+                index,
+                root: path.to_path_buf(),
+                dir: path.to_path_buf(),
+                ignores: Vec::new(),
+            }),
+            Start::File(result) => found.push((index, path.to_path_buf(), result)),
+        }
+    }
+
+    while !frontier.is_empty() {
+        let options = Arc::clone(&options);
+        let levels: Vec<_> = pool
+            .map(frontier, move |pending: PendingDir| {
+                let listing = read_dir(&pending.root, &pending.dir, &options, &pending.ignores);
+                (pending, listing)
+            })
+            .collect();
+
+        frontier = Vec::new();
+        for (pending, listing) in levels {
+            let (entries, ignores) = match listing {
+                Ok(listing) => listing,
+                Err(error) => {
+                    found.push((pending.index, pending.dir, Err(error)));
+                    continue;
+                }
+            };
+
+            for entry in entries {
+                match entry {
+                    Entry::File(path) => found.push((pending.index, path.clone(), Ok(path))),
+                    Entry::Dir(dir) => frontier.push(PendingDir {
+                        index: pending.index,
+                        root: pending.root.clone(),
+                        dir,
+                        ignores: ignores.clone(),
+                    }),
+                }
+            }
+        }
+    }
+
+    found.sort_by(|a, b| (a.0, &a.1).cmp(&(b.0, &b.1)));
+    found.into_iter().map(|(_, _, result)| result).collect()
+}
+
+/// How a path named on the command line is handled
+enum Start {
+    Dir,
+    /// A file (or stdin, or an error) that goes straight into the results
+    File(Result<PathBuf, WalkError>),
+}
+
+fn start(path: &Path) -> Start {
+    if is_stdin(path) {
+        return Start::File(Ok(path.to_path_buf()));
+    }
+
+    match fs::metadata(path) {
+        Ok(metadata) if metadata.is_dir() => Start::Dir,
+        Ok(_) => Start::File(Ok(path.to_path_buf())),
+        Err(error) => Start::File(Err(WalkError {
+            path: path.to_path_buf(),
+            error,
+        })),
+    }
+}
+
+/// A directory waiting to be read by [`walk_parallel`]
+struct PendingDir {
+    index: usize,
+    root: PathBuf,
+    dir: PathBuf,
+    ignores: Vec<Arc<Gitignore>>,
+}
+
+/// Something in a directory that survived the filters
+enum Entry {
+    File(PathBuf),
+    Dir(PathBuf),
+}
+
+/// Read one directory, returning its surviving entries in sorted order and
+/// the `.gitignore` stack that applies inside it
+fn read_dir(
+    root: &Path,
+    dir: &Path,
+    options: &WalkOptions,
+    ignores: &[Arc<Gitignore>],
+) -> Result<(Vec<Entry>, Vec<Arc<Gitignore>>), WalkError> {
+    let entries = fs::read_dir(dir).map_err(|error| WalkError {
+        path: dir.to_path_buf(),
+        error,
+    })?;
+
+    let mut ignores = ignores.to_vec();
+    if options.respect_gitignore
+        && let Some(gitignore) = load_gitignore(dir)
+    {
+        ignores.push(Arc::new(gitignore));
+    }
+
+    let mut paths: Vec<PathBuf> = entries.filter_map(|entry| entry.ok().map(|e| e.path())).collect();
+    paths.sort();
+
+    let mut kept = Vec::new();
+    for path in paths {
+        let name = path.file_name().map(|n| n.to_string_lossy()).unwrap_or_default();
+        if !options.hidden && name.starts_with('.') {
+            continue;
+        }
+
+        // Links are looked at, not through, unless following was asked for
+        let Ok(metadata) = fs::symlink_metadata(&path) else {
+            continue;
+        };
+        let is_dir = if metadata.file_type().is_symlink() {
+            match options.follow_links.then(|| fs::metadata(&path)) {
+                Some(Ok(target)) if target.is_dir() => {
+                    if links_to_ancestor(root, dir, &path) {
+                        continue;
+                    }
+                    true
+                }
+                Some(Ok(_)) => false,
+                // Not following, or a dangling link
+                _ => continue,
+            }
+        } else {
+            metadata.is_dir()
+        };
+        if options.respect_gitignore && is_ignored(&ignores, &path, is_dir) {
+            continue;
+        }
+
+        let relative = relative_path(root, &path);
+        if options.exclude.iter().any(|glob| glob.is_match(&relative)) {
+            continue;
+        }
+
+        if is_dir {
+            kept.push(Entry::Dir(path));
+        } else if options.include.is_empty() || options.include.iter().any(|glob| glob.is_match(&relative)) {
+            kept.push(Entry::File(path));
+        }
+    }
+
+    Ok((kept, ignores))
+}
+
+/// Whether the directory `link` points at holds `dir` or any directory the
+/// walk passed through from `root` to reach it, so following it would walk
+/// the same files again, forever
+fn links_to_ancestor(root: &Path, dir: &Path, link: &Path) -> bool {
+    let Ok(target) = fs::canonicalize(link) else {
+        return true;
+    };
+    dir.ancestors()
+        .take_while(|ancestor| ancestor.starts_with(root))
+        .any(|ancestor| fs::canonicalize(ancestor).map_or(true, |ancestor| ancestor.starts_with(&target)))
+}
+
+fn walk_dir(
+    root: &Path,
+    dir: &Path,
+    options: &WalkOptions,
+    ignores: &[Arc<Gitignore>],
+    results: &mut Vec<Result<PathBuf, WalkError>>,
+) {
+    let (entries, inner_ignores) = match read_dir(root, dir, options, ignores) {
+        Ok(listing) => listing,
+        Err(error) => {
+            results.push(Err(error));
+            return;
+        }
+    };
+
+    for entry in entries {
+        match entry {
+            Entry::File(path) => results.push(Ok(path)),
+            Entry::Dir(path) => walk_dir(root, &path, options, &inner_ignores, results),
+        }
+    }
+}
+
+/// `path` relative to `base`, with `/` separators
+fn relative_path(base: &Path, path: &Path) -> String {
+    let relative = path.strip_prefix(base).unwrap_or(path);
+    relative
+        .components()
+        .map(|c| c.as_os_str().to_string_lossy())
+        .collect::<Vec<_>>()
+        .join("/")
+}
+
+// === .gitignore SUPPORT ===
+
+/// One line of a `.gitignore` file
+#[derive(Debug)]
+struct IgnoreRule {
+    glob: Glob,
+    negated: bool,
+    dir_only: bool,
+    anchored: bool,
+}
+
+/// The rules from one `.gitignore`, relative to the directory holding it
+#[derive(Debug)]
+struct Gitignore {
+    base: PathBuf,
+    rules: Vec<IgnoreRule>,
+}
+
+impl Gitignore {
+    fn parse(base: &Path, contents: &str) -> Gitignore {
+        let rules = contents
+            .lines()
+            .filter_map(|line| {
+                let line = line.trim_end();
+                if line.is_empty() || line.starts_with('#') {
+                    return None;
+                }
+
+                let (negated, line) = match line.strip_prefix('!') {
+                    Some(rest) => (true, rest),
+                    None => (false, line),
+                };
+                let (dir_only, line) = match line.strip_suffix('/') {
+                    Some(rest) => (true, rest),
+                    None => (false, line),
+                };
+                // A leading or inner `/` ties the rule to this directory;
+                // otherwise it matches a name at any depth
+                let (anchored, pattern) = match line.strip_prefix('/') {
+                    Some(rest) => (true, rest),
+                    None => (line.contains('/'), line),
+                };
+                let glob = Glob::new(pattern).ok()?;
+
+                Some(IgnoreRule {
+                    glob,
+                    negated,
+                    dir_only,
+                    anchored,
+                })
+            })
+            .collect();
+
+        Gitignore {
+            base: base.to_path_buf(),
+            rules,
+        }
+    }
+
+    /// `Some(true)` if ignored, `Some(false)` if re-included with `!`,
+    /// `None` if no rule applies. The last matching rule wins.
+    fn matched(&self, path: &Path, is_dir: bool) -> Option<bool> {
+        let relative = relative_path(&self.base, path);
+
+        self.rules
+            .iter()
+            .rev()
+            .find(|rule| {
+                if rule.dir_only && !is_dir {
+                    return false;
+                }
+                if rule.anchored {
+                    rule.glob.is_match_path(&relative)
+                } else {
+                    rule.glob.is_match(&relative)
+                }
+            })
+            .map(|rule| !rule.negated)
+    }
+}
+
+/// Read `dir/.gitignore` if it exists
+fn load_gitignore(dir: &Path) -> Option<Gitignore> {
+    let contents = fs::read_to_string(dir.join(".gitignore")).ok()?;
+    Some(Gitignore::parse(dir, &contents))
+}
+
+/// Deeper `.gitignore` files take priority over ones closer to the root
+fn is_ignored(ignores: &[Arc<Gitignore>], path: &Path, is_dir: bool) -> bool {
+    ignores
+        .iter()
+        .rev()
+        .find_map(|ignore| ignore.matched(path, is_dir))
+        .unwrap_or(false)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fs_util::TempPath;
+
+    fn temp_tree(name: &str, files: &[(&str, &str)]) -> TempPath {
+        let dir = TempPath::dir(&format!("minigrep_walk_{}", name));
+        for (path, contents) in files {
+            let path = dir.join(path);
+            fs::create_dir_all(path.parent().unwrap()).unwrap();
+            fs::write(path, contents).unwrap();
+        }
+        dir
+    }
+
+    fn walked(root: &Path, options: &WalkOptions) -> Vec<String> {
+        walk(&[root], options)
+            .into_iter()
+            .map(|result| relative_path(root, &result.unwrap()))
+            .collect()
+    }
+
+    #[test]
+    fn walks_recursively_in_sorted_order() {
+        let root = temp_tree("sorted", &[("b.txt", ""), ("a/z.txt", ""), ("a/y/x.txt", "")]);
+        assert_eq!(walked(&root, &WalkOptions::default()), vec!["a/y/x.txt", "a/z.txt", "b.txt"]);
+    }
+
+    #[test]
+    fn include_and_exclude_globs() {
+        let root = temp_tree(
+            "globs",
+            &[("notes/a.md", ""), ("notes/b.txt", ""), ("target/c.md", ""), ("d.md", "")],
+        );
+
+        let options = WalkOptions {
+            include: vec![Glob::new("*.md").unwrap()],
+            exclude: vec![Glob::new("target").unwrap()],
+            ..WalkOptions::default()
+        };
+        assert_eq!(walked(&root, &options), vec!["d.md", "notes/a.md"]);
+    }
+
+    #[test]
+    fn hidden_files_are_opt_in() {
+        let root = temp_tree("hidden", &[(".secret", ""), (".git/config", ""), ("visible", "")]);
+        assert_eq!(walked(&root, &WalkOptions::default()), vec!["visible"]);
+
+        let options = WalkOptions {
+            hidden: true,
+            ..WalkOptions::default()
+        };
+        assert_eq!(walked(&root, &options), vec![".git/config", ".secret", "visible"]);
+    }
+
+    #[test]
+    fn gitignore_rules() {
+        let root = temp_tree(
+            "gitignore",
+            &[
+                (".gitignore", "# build output\n/build\n*.log\n!keep.log\ncache/\n"),
+                ("build/out.txt", ""),
+                ("src/build/mod.txt", ""),
+                ("debug.log", ""),
+                ("keep.log", ""),
+                ("cache/data", ""),
+                ("src/cache", ""),
+                ("src/.gitignore", "*.tmp\n"),
+                ("src/a.tmp", ""),
+                ("a.tmp", ""),
+            ],
+        );
+
+        assert_eq!(
+            walked(&root, &WalkOptions::default()),
+            vec!["a.tmp", "keep.log", "src/build/mod.txt", "src/cache"]
+        );
+
+        let options = WalkOptions {
+            respect_gitignore: false,
+            ..WalkOptions::default()
+        };
+        assert_eq!(walked(&root, &options).len(), 8);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn symlinks_are_followed_only_when_asked() {
+        use std::os::unix::fs::symlink;
+
+        let root = temp_tree("symlinks", &[("a/file.txt", ""), ("outside/secret.txt", "")]);
+        let tree = root.join("tree");
+        fs::create_dir_all(&tree).unwrap();
+        symlink(root.join("a"), tree.join("a")).unwrap();
+        symlink(root.join("outside/secret.txt"), tree.join("secret.txt")).unwrap();
+        symlink(root.join("missing"), tree.join("dangling")).unwrap();
+        // A link back up the tree would walk forever if followed blindly
+        symlink(&tree, root.join("a/loop")).unwrap();
+
+        assert!(walked(&tree, &WalkOptions::default()).is_empty());
+
+        let follow = WalkOptions {
+            follow_links: true,
+            ..WalkOptions::default()
+        };
+        assert_eq!(walked(&tree, &follow), vec!["a/file.txt", "secret.txt"]);
+        assert_eq!(walked(&tree, &follow), {
+            let pool = ThreadPool::new(2);
+            walk_parallel(&[&tree], &follow, &pool)
+                .into_iter()
+                .map(|result| relative_path(&tree, &result.unwrap()))
+                .collect::<Vec<_>>()
+        });
+    }
+
+    #[test]
+    fn explicit_files_bypass_filters() {
+        let root = temp_tree("explicit", &[(".hidden.log", "")]);
+        let file = root.join(".hidden.log");
+        let options = WalkOptions {
+            include: vec![Glob::new("*.md").unwrap()],
+            ..WalkOptions::default()
+        };
+
+        let results = walk(&[&file], &options);
+        assert_eq!(results.len(), 1);
+        assert_eq!(results[0].as_ref().unwrap(), &file);
+    }
+
+    #[test]
+    fn parallel_walk_matches_serial_order() {
+        let root = temp_tree(
+            "parallel",
+            &[
+                (".gitignore", "*.log\n"),
+                ("b/z.txt", ""),
+                ("b/a/deep/x.txt", ""),
+                ("b/a.txt", ""),
+                ("a.txt", ""),
+                ("c.log", ""),
+                ("d/.gitignore", "!keep.log\n"),
+                ("d/keep.log", ""),
+            ],
+        );
+        let file = root.join("a.txt");
+        let inputs = [&*root, Path::new("/definitely/not/here"), file.as_path()];
+        let options = WalkOptions::default();
+        let pool = ThreadPool::new(3);
+
+        let describe = |results: Vec<Result<PathBuf, WalkError>>| -> Vec<String> {
+            results
+                .into_iter()
+                .map(|result| match result {
+                    Ok(path) => relative_path(&root, &path),
+                    Err(error) => format!("error: {}", error.path.display()),
+                })
+                .collect()
+        };
+
+        let serial = describe(walk(&inputs, &options));
+        assert_eq!(describe(walk_parallel(&inputs, &options, &pool)), serial);
+        assert_eq!(
+            serial,
+            vec!["a.txt", "b/a/deep/x.txt", "b/a.txt", "b/z.txt", "d/keep.log", "error: /definitely/not/here", "a.txt"]
+        );
+    }
+
+    #[test]
+    fn stdin_placeholder_passes_through() {
+        let results = walk(&["-"], &WalkOptions::default());
+        assert_eq!(results[0].as_ref().unwrap(), &PathBuf::from("-"));
+    }
+
+    #[test]
+    fn missing_paths_are_reported() {
+        let results = walk(&["/definitely/not/here"], &WalkOptions::default());
+        let error = results[0].as_ref().unwrap_err();
+        assert_eq!(error.path, PathBuf::from("/definitely/not/here"));
+        assert_eq!(error.error.kind(), io::ErrorKind::NotFound);
+    }
+}
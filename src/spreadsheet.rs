@@ -0,0 +1,649 @@
+//! # Spreadsheet
+//!
+//! Chapter 8.1 stores a row of `SpreadsheetCell`s in a vector to show how an
+//! enum lets one `Vec` hold integers, floats and text. This module grows that
+//! enum into a small spreadsheet: cells addressed as `A1`, a
+//! [`SpreadsheetCell::Formula`] variant (`=SUM(A1:A3)*2`, `IF`, references
+//! and ranges; see the **formula** submodule), and a [`Sheet`] that keeps
+//! every formula's value up to date.
+//!
+//! The sheet records which formulas read each cell. When a cell changes, only
+//! the cells downstream of it are recalculated, in dependency order; a
+//! formula that ends up reading itself, directly or through other cells,
+//! evaluates to `#CYCLE!` instead of looping forever.
+//!
+//! The **csv** submodule loads and saves sheets as RFC 4180 CSV.
+//!
+//! This module demonstrates:
+//! - An enum with data in every variant, extended without breaking matches
+//!   on the original three
+//! - `HashMap` and `BTreeMap` working together: values by cell, and cells in
+//!   row order for rendering
+//! - A dependency graph with a topological sort (Kahn's algorithm)
+//! - A recursive descent parser with errors that point at a column
+
+pub mod csv;
+pub mod formula;
+
+pub use formula::{Formula, FormulaError};
+
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::fmt;
+use std::str::FromStr;
+
+/// Rows a sheet can have, as in most spreadsheet programs
+pub const MAX_ROWS: u32 = 1_048_576;
+
+/// Columns a sheet can have, `A` to `ZZZ`
+pub const MAX_COLS: u32 = 26 + 26 * 26 + 26 * 26 * 26;
+
+/// The most cells [`Sheet::render`] and [`csv::write`] lay out, empty ones
+/// included; a sheet spread wider is refused rather than filling memory
+pub const MAX_LAYOUT_CELLS: u64 = 1_000_000;
+
+/// The address of a cell, such as `B3`
+///
+/// Ordered row by row, so sorted cells read like the sheet.
+///
+/// # Example
+/// ```
+/// use rust_book_examples::spreadsheet::CellRef;
+///
+/// let cell: CellRef = "ab12".parse().unwrap();
+/// assert_eq!((cell.col(), cell.row()), (27, 11));
+/// assert_eq!(cell.to_string(), "AB12");
+/// assert!("A0".parse::<CellRef>().is_err());
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct CellRef {
+    // Row first, for the derived ordering
+    row: u32,
+    col: u32,
+}
+
+impl CellRef {
+    /// A cell from 0-based column and row numbers, so `A1` is `(0, 0)`
+    ///
+    /// # Panics
+    ///
+    /// Panics if the cell is outside [`MAX_COLS`] and [`MAX_ROWS`].
+    pub fn new(col: u32, row: u32) -> CellRef {
+        assert!(col < MAX_COLS && row < MAX_ROWS, "cell ({}, {}) is off the sheet", col, row);
+        CellRef { row, col }
+    }
+
+    pub fn col(&self) -> u32 {
+        self.col
+    }
+
+    pub fn row(&self) -> u32 {
+        self.row
+    }
+
+    /// The letters of a 0-based column: `A`, ..., `Z`, `AA`, ...
+    pub fn column_name(col: u32) -> String {
+        let mut letters = Vec::new();
+        let mut n = col + 1;
+        while n > 0 {
+            n -= 1;
+            letters.push(char::from(b'A' + (n % 26) as u8));
+            n /= 26;
+        }
+        letters.iter().rev().collect()
+    }
+}
+
+impl fmt::Display for CellRef {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}{}", CellRef::column_name(self.col), self.row + 1)
+    }
+}
+
+impl FromStr for CellRef {
+    type Err = SheetError;
+
+    fn from_str(s: &str) -> Result<CellRef, SheetError> {
+        let bad = || SheetError::Address(s.to_string());
+        let digits = s.find(|c: char| c.is_ascii_digit()).ok_or_else(bad)?;
+        let (letters, number) = s.split_at(digits);
+        if letters.is_empty() || !letters.chars().all(|c| c.is_ascii_alphabetic()) {
+            return Err(bad());
+        }
+        let col = letters
+            .bytes()
+            .try_fold(0u32, |col, b| col.checked_mul(26)?.checked_add(u32::from(b.to_ascii_uppercase() - b'A') + 1))
+            .ok_or_else(bad)?;
+        let row: u32 = number.parse().map_err(|_| bad())?;
+        if col > MAX_COLS || row == 0 || row > MAX_ROWS {
+            return Err(bad());
+        }
+        Ok(CellRef { row: row - 1, col: col - 1 })
+    }
+}
+
+/// What a cell holds, as typed
+///
+/// The chapter's three variants, plus formulas.
+#[derive(Debug, Clone, PartialEq)]
+pub enum SpreadsheetCell {
+    Int(i32),
+    Float(f64),
+    Text(String),
+    Formula(Formula),
+}
+
+impl SpreadsheetCell {
+    /// The value of a cell that isn't a formula
+    fn literal(&self) -> Option<Value> {
+        match self {
+            SpreadsheetCell::Int(i) => Some(Value::Number(f64::from(*i))),
+            SpreadsheetCell::Float(f) => Some(Value::Number(*f)),
+            SpreadsheetCell::Text(s) => Some(Value::Text(s.clone())),
+            SpreadsheetCell::Formula(_) => None,
+        }
+    }
+}
+
+/// Read a cell the way a spreadsheet reads typing: `=` starts a formula,
+/// whole numbers that fit an `i32` are `Int`, other numbers are `Float`, and
+/// anything else is `Text`
+///
+/// # Example
+/// ```
+/// use rust_book_examples::spreadsheet::SpreadsheetCell;
+///
+/// assert_eq!("42".parse(), Ok(SpreadsheetCell::Int(42)));
+/// assert_eq!(" 2.5 ".parse(), Ok(SpreadsheetCell::Float(2.5)));
+/// assert_eq!("inf".parse(), Ok(SpreadsheetCell::Text("inf".to_string())));
+/// assert!(matches!("=A1*2".parse(), Ok(SpreadsheetCell::Formula(_))));
+/// assert!("=A1*".parse::<SpreadsheetCell>().is_err());
+/// ```
+impl FromStr for SpreadsheetCell {
+    type Err = FormulaError;
+
+    fn from_str(input: &str) -> Result<SpreadsheetCell, FormulaError> {
+        if input.starts_with('=') {
+            return input.parse().map(SpreadsheetCell::Formula);
+        }
+        let trimmed = input.trim();
+        if let Ok(i) = trimmed.parse() {
+            return Ok(SpreadsheetCell::Int(i));
+        }
+        // Rust also reads "inf" and "NaN" as floats; a sheet shouldn't
+        match trimmed.parse::<f64>() {
+            Ok(f) if f.is_finite() => Ok(SpreadsheetCell::Float(f)),
+            _ => Ok(SpreadsheetCell::Text(input.to_string())),
+        }
+    }
+}
+
+/// A cell as typed: formulas show their source
+impl fmt::Display for SpreadsheetCell {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            SpreadsheetCell::Int(i) => write!(f, "{}", i),
+            SpreadsheetCell::Float(x) => write!(f, "{}", x),
+            SpreadsheetCell::Text(s) => f.write_str(s),
+            SpreadsheetCell::Formula(formula) => write!(f, "{}", formula),
+        }
+    }
+}
+
+/// Why a formula has no value
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CellError {
+    /// The formula depends on itself
+    Cycle,
+    DivByZero,
+    /// Text where a number or condition was needed
+    Value,
+    /// A result too large to represent
+    Num,
+}
+
+impl fmt::Display for CellError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(match self {
+            CellError::Cycle => "#CYCLE!",
+            CellError::DivByZero => "#DIV/0!",
+            CellError::Value => "#VALUE!",
+            CellError::Num => "#NUM!",
+        })
+    }
+}
+
+/// What a cell shows
+#[derive(Debug, Clone, PartialEq)]
+pub enum Value {
+    Empty,
+    Number(f64),
+    Text(String),
+    Bool(bool),
+    Error(CellError),
+}
+
+impl fmt::Display for Value {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Value::Empty => Ok(()),
+            // Whole numbers without a trailing ".0"
+            Value::Number(n) if n.fract() == 0.0 && n.abs() < 1e15 => write!(f, "{}", *n as i64),
+            Value::Number(n) => write!(f, "{}", n),
+            Value::Text(s) => f.write_str(s),
+            Value::Bool(b) => f.write_str(if *b { "TRUE" } else { "FALSE" }),
+            Value::Error(e) => write!(f, "{}", e),
+        }
+    }
+}
+
+/// A problem with something typed into a sheet, or with laying it out
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SheetError {
+    /// Not a cell address like `A1`
+    Address(String),
+    Formula(FormulaError),
+    /// The cells to lay out span more than [`MAX_LAYOUT_CELLS`]
+    TooLarge { cols: u32, rows: u32 },
+}
+
+impl fmt::Display for SheetError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            SheetError::Address(s) => write!(f, "'{}' is not a cell address like A1", s),
+            SheetError::Formula(e) => write!(f, "bad formula at {}", e),
+            SheetError::TooLarge { cols, rows } => write!(
+                f,
+                "{} columns by {} rows is more than {} cells to lay out",
+                cols, rows, MAX_LAYOUT_CELLS
+            ),
+        }
+    }
+}
+
+impl SheetError {
+    /// An error if `cols` by `rows` cells is too many to lay out
+    pub(crate) fn check_layout(cols: u32, rows: u32) -> Result<(), SheetError> {
+        if cols as u64 * rows as u64 > MAX_LAYOUT_CELLS {
+            return Err(SheetError::TooLarge { cols, rows });
+        }
+        Ok(())
+    }
+}
+
+impl std::error::Error for SheetError {}
+
+impl From<FormulaError> for SheetError {
+    fn from(e: FormulaError) -> SheetError {
+        SheetError::Formula(e)
+    }
+}
+
+/// Cells, their current values, and which formulas read which cells
+///
+/// # Example
+/// ```
+/// use rust_book_examples::spreadsheet::{Sheet, Value};
+///
+/// let mut sheet = Sheet::new();
+/// for (at, input) in [("A1", "3"), ("A2", "4.5"), ("A3", "=SUM(A1:A2)*2"), ("B1", "=A3 > 10")] {
+///     sheet.enter(at, input).unwrap();
+/// }
+/// assert_eq!(sheet.value(&"A3".parse().unwrap()), Value::Number(15.0));
+///
+/// // Changing A1 recalculates just the cells that read it
+/// let changed: Vec<String> = sheet.enter("A1", "1").unwrap().iter().map(|c| c.to_string()).collect();
+/// assert_eq!(changed, ["A1", "A3", "B1"]);
+/// assert_eq!(sheet.value(&"B1".parse().unwrap()), Value::Bool(true));
+/// ```
+#[derive(Debug, Default)]
+pub struct Sheet {
+    cells: BTreeMap<CellRef, SpreadsheetCell>,
+    values: HashMap<CellRef, Value>,
+    /// For each cell, the formulas that read it
+    dependents: HashMap<CellRef, BTreeSet<CellRef>>,
+}
+
+impl Sheet {
+    pub fn new() -> Sheet {
+        Sheet::default()
+    }
+
+    /// Put `cell` at `at`, returning every cell whose value was recalculated,
+    /// in the order they were calculated
+    pub fn set(&mut self, at: CellRef, cell: SpreadsheetCell) -> Vec<CellRef> {
+        self.replace(at, Some(cell))
+    }
+
+    /// Empty the cell at `at`; formulas reading it now see an empty cell
+    pub fn clear(&mut self, at: CellRef) -> Vec<CellRef> {
+        self.replace(at, None)
+    }
+
+    /// Type `input` into the cell named `at`, as [`SpreadsheetCell`]'s
+    /// `FromStr` reads it; blank input clears the cell
+    pub fn enter(&mut self, at: &str, input: &str) -> Result<Vec<CellRef>, SheetError> {
+        let at = at.parse()?;
+        if input.trim().is_empty() {
+            return Ok(self.clear(at));
+        }
+        Ok(self.set(at, input.parse()?))
+    }
+
+    pub fn get(&self, at: &CellRef) -> Option<&SpreadsheetCell> {
+        self.cells.get(at)
+    }
+
+    /// What the cell shows; [`Value::Empty`] for a cell never set
+    pub fn value(&self, at: &CellRef) -> Value {
+        self.values.get(at).cloned().unwrap_or(Value::Empty)
+    }
+
+    /// Every non-empty cell, row by row
+    pub fn cells(&self) -> impl Iterator<Item = (CellRef, &SpreadsheetCell)> {
+        self.cells.iter().map(|(at, cell)| (*at, cell))
+    }
+
+    /// The number of columns and rows in use, counting from `A1`
+    pub fn extent(&self) -> (u32, u32) {
+        self.cells
+            .keys()
+            .fold((0, 0), |(cols, rows), at| (cols.max(at.col + 1), rows.max(at.row + 1)))
+    }
+
+    /// The top left and bottom right corners of the cells in use; `None` for
+    /// an empty sheet
+    pub fn bounds(&self) -> Option<(CellRef, CellRef)> {
+        // Cells are in row order, so the first and last have the outer rows
+        let (first, _) = self.cells.first_key_value()?;
+        let (last, _) = self.cells.last_key_value()?;
+        let (left, right) = self
+            .cells
+            .keys()
+            .fold((u32::MAX, 0), |(left, right), at| (left.min(at.col), right.max(at.col)));
+        Some((CellRef::new(left, first.row), CellRef::new(right, last.row)))
+    }
+
+    /// The cells a formula reads; nothing for other cells
+    fn precedents(&self, at: &CellRef) -> &[CellRef] {
+        match self.cells.get(at) {
+            Some(SpreadsheetCell::Formula(formula)) => formula.references(),
+            _ => &[],
+        }
+    }
+
+    fn replace(&mut self, at: CellRef, cell: Option<SpreadsheetCell>) -> Vec<CellRef> {
+        for precedent in self.precedents(&at).to_vec() {
+            if let Some(dependents) = self.dependents.get_mut(&precedent) {
+                dependents.remove(&at);
+                if dependents.is_empty() {
+                    self.dependents.remove(&precedent);
+                }
+            }
+        }
+        match cell {
+            Some(cell) => {
+                self.cells.insert(at, cell);
+            }
+            None => {
+                self.cells.remove(&at);
+            }
+        }
+        for precedent in self.precedents(&at).to_vec() {
+            self.dependents.entry(precedent).or_default().insert(at);
+        }
+        self.recalculate(at)
+    }
+
+    /// Recalculate `changed` and everything downstream of it
+    fn recalculate(&mut self, changed: CellRef) -> Vec<CellRef> {
+        // Every cell that reads `changed`, directly or not
+        let mut affected = BTreeSet::new();
+        let mut stack = vec![changed];
+        while let Some(at) = stack.pop() {
+            if affected.insert(at)
+                && let Some(dependents) = self.dependents.get(&at)
+            {
+                stack.extend(dependents);
+            }
+        }
+
+        // Kahn's algorithm, over just the affected cells: a cell is ready once
+        // none of the affected cells it reads are still waiting
+        let mut waiting_on: HashMap<CellRef, usize> = affected
+            .iter()
+            .map(|at| (*at, self.precedents(at).iter().filter(|p| affected.contains(p)).count()))
+            .collect();
+        // Ready cells are taken in row order, so the order is predictable
+        let mut ready: BTreeSet<CellRef> = affected.iter().copied().filter(|at| waiting_on[at] == 0).collect();
+        let mut order = Vec::with_capacity(affected.len());
+        while let Some(at) = ready.pop_first() {
+            self.evaluate(at);
+            order.push(at);
+            for dependent in self.dependents.get(&at).into_iter().flatten() {
+                if let Some(count) = waiting_on.get_mut(dependent) {
+                    *count -= 1;
+                    if *count == 0 {
+                        ready.insert(*dependent);
+                    }
+                }
+            }
+        }
+
+        // Whatever never became ready is on a cycle or downstream of one
+        for at in affected {
+            if waiting_on[&at] > 0 {
+                self.values.insert(at, Value::Error(CellError::Cycle));
+                order.push(at);
+            }
+        }
+        order
+    }
+
+    fn evaluate(&mut self, at: CellRef) {
+        let value = match self.cells.get(&at) {
+            None => {
+                self.values.remove(&at);
+                return;
+            }
+            Some(SpreadsheetCell::Formula(formula)) => formula.evaluate(|cell| self.value(&cell)),
+            Some(cell) => cell.literal().expect("only formulas have no literal value"),
+        };
+        self.values.insert(at, value);
+    }
+
+    /// The cells in use as an aligned text table, with column letters across
+    /// the top and row numbers down the side; numbers are right-aligned
+    ///
+    /// The table runs from the first row and column in use to the last, so
+    /// a sheet that starts at `C5` doesn't render four empty rows first. An
+    /// empty sheet renders as an empty string.
+    ///
+    /// # Errors
+    ///
+    /// [`SheetError::TooLarge`] if the table would have more than
+    /// [`MAX_LAYOUT_CELLS`] cells, as for a sheet with values in both `A1`
+    /// and `ZZZ1048576`.
+    pub fn render(&self) -> Result<String, SheetError> {
+        let Some((first, last)) = self.bounds() else {
+            return Ok(String::new());
+        };
+        SheetError::check_layout(last.col - first.col + 1, last.row - first.row + 1)?;
+
+        let cols = first.col..=last.col;
+        let values: Vec<Vec<Value>> = (first.row..=last.row)
+            .map(|row| cols.clone().map(|col| self.value(&CellRef { row, col })).collect())
+            .collect();
+        let label_width = (last.row + 1).to_string().len();
+        let widths: Vec<usize> = cols
+            .clone()
+            .enumerate()
+            .map(|(i, col)| {
+                values
+                    .iter()
+                    .map(|row| row[i].to_string().chars().count())
+                    .chain([CellRef::column_name(col).len()])
+                    .max()
+                    .unwrap_or(0)
+            })
+            .collect();
+
+        let mut out = String::new();
+        let header: Vec<String> = cols
+            .zip(&widths)
+            .map(|(col, width)| format!("{:<width$}", CellRef::column_name(col)))
+            .collect();
+        push_line(&mut out, &format!("{:label_width$} | {}", "", header.join(" | ")));
+        let rule: Vec<String> = widths.iter().map(|width| "-".repeat(*width)).collect();
+        push_line(&mut out, &format!("{}-+-{}", "-".repeat(label_width), rule.join("-+-")));
+        for (row, row_values) in (first.row..).zip(&values) {
+            let cells: Vec<String> = row_values
+                .iter()
+                .zip(&widths)
+                .map(|(value, width)| match value {
+                    Value::Number(_) | Value::Error(_) => format!("{:>width$}", value.to_string()),
+                    _ => format!("{:<width$}", value.to_string()),
+                })
+                .collect();
+            push_line(&mut out, &format!("{:>label_width$} | {}", row + 1, cells.join(" | ")));
+        }
+        Ok(out)
+    }
+}
+
+/// Append `line` without the padding an empty last column leaves
+fn push_line(out: &mut String, line: &str) {
+    out.push_str(line.trim_end());
+    out.push('\n');
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn cell(at: &str) -> CellRef {
+        at.parse().unwrap()
+    }
+
+    fn names(cells: &[CellRef]) -> Vec<String> {
+        cells.iter().map(|at| at.to_string()).collect()
+    }
+
+    fn sheet(entries: &[(&str, &str)]) -> Sheet {
+        let mut sheet = Sheet::new();
+        for (at, input) in entries {
+            sheet.enter(at, input).unwrap();
+        }
+        sheet
+    }
+
+    #[test]
+    fn addresses_round_trip() {
+        for (name, col, row) in [("A1", 0, 0), ("Z9", 25, 8), ("AA1", 26, 0), ("AZ2", 51, 1), ("ZZZ1048576", MAX_COLS - 1, MAX_ROWS - 1)] {
+            let at = cell(name);
+            assert_eq!((at.col(), at.row()), (col, row));
+            assert_eq!(at.to_string(), name);
+        }
+        for bad in ["", "A", "1", "A0", "1A", "A-1", "AAAA1", "A1048577", "A1B"] {
+            assert_eq!(bad.parse::<CellRef>(), Err(SheetError::Address(bad.to_string())));
+        }
+        assert!(cell("A2") > cell("Z1"));
+    }
+
+    #[test]
+    fn only_downstream_cells_are_recalculated() {
+        let mut sheet = sheet(&[
+            ("A1", "1"),
+            ("A2", "2"),
+            ("A3", "=A1+A2"),
+            ("B1", "=A3*10"),
+            ("B2", "=A2*10"),
+            ("C1", "=B1+A3"),
+        ]);
+        assert_eq!(sheet.value(&cell("C1")), Value::Number(33.0));
+
+        // A3 before B1, both before C1; B2 doesn't read A1 at all
+        assert_eq!(names(&sheet.enter("A1", "2").unwrap()), ["A1", "A3", "B1", "C1"]);
+        assert_eq!(sheet.value(&cell("C1")), Value::Number(44.0));
+        assert_eq!(sheet.value(&cell("B2")), Value::Number(20.0));
+
+        // Rewriting a formula drops its old dependencies
+        sheet.enter("A3", "=A2").unwrap();
+        assert_eq!(names(&sheet.enter("A1", "5").unwrap()), ["A1"]);
+        assert_eq!(sheet.value(&cell("C1")), Value::Number(22.0));
+
+        // Clearing a cell recalculates its readers with the cell empty
+        assert_eq!(names(&sheet.clear(cell("A2"))), ["A2", "B2", "A3", "B1", "C1"]);
+        assert_eq!(sheet.value(&cell("C1")), Value::Number(0.0));
+        assert_eq!(sheet.get(&cell("A2")), None);
+    }
+
+    #[test]
+    fn cycles_are_reported_and_recover() {
+        let mut sheet = sheet(&[("A1", "=A3+1"), ("A2", "=A1+1"), ("A3", "=A2+1"), ("B1", "=A1*2"), ("C1", "7")]);
+        for at in ["A1", "A2", "A3", "B1"] {
+            assert_eq!(sheet.value(&cell(at)), Value::Error(CellError::Cycle), "{}", at);
+        }
+        assert_eq!(sheet.value(&cell("C1")), Value::Number(7.0));
+
+        // Breaking the cycle anywhere fixes every cell on it
+        sheet.enter("A3", "10").unwrap();
+        assert_eq!(sheet.value(&cell("A2")), Value::Number(12.0));
+        assert_eq!(sheet.value(&cell("B1")), Value::Number(22.0));
+
+        sheet.enter("D1", "=D1").unwrap();
+        assert_eq!(sheet.value(&cell("D1")), Value::Error(CellError::Cycle));
+    }
+
+    #[test]
+    fn entering_bad_input_changes_nothing() {
+        let mut sheet = sheet(&[("A1", "1")]);
+        assert_eq!(sheet.enter("A0", "2"), Err(SheetError::Address("A0".to_string())));
+        let err = sheet.enter("A1", "=SUM(A2:A3").unwrap_err();
+        assert_eq!(err.to_string(), "bad formula at column 11: expected ')'");
+        assert_eq!(sheet.get(&cell("A1")), Some(&SpreadsheetCell::Int(1)));
+    }
+
+    #[test]
+    fn renders_an_aligned_table() {
+        let sheet = sheet(&[
+            ("A1", "Item"),
+            ("B1", "Price"),
+            ("A2", "Apple"),
+            ("B2", "0.5"),
+            ("A3", "Cheese"),
+            ("B3", "4.25"),
+            ("A4", "Total"),
+            ("B4", "=SUM(B2:B3)"),
+            ("C4", "=B4/0"),
+            ("D2", "=B2<1"),
+        ]);
+        let expected = [
+            "  | A      | B     | C       | D",
+            "--+--------+-------+---------+-----",
+            "1 | Item   | Price |         |",
+            "2 | Apple  |   0.5 |         | TRUE",
+            "3 | Cheese |  4.25 |         |",
+            "4 | Total  |  4.75 | #DIV/0! |",
+        ];
+        assert_eq!(sheet.render().unwrap(), expected.join("\n") + "\n");
+        assert_eq!(Sheet::new().render().unwrap(), "");
+    }
+
+    #[test]
+    fn renders_only_the_cells_in_use() {
+        let mut sheet = sheet(&[("C5", "1"), ("D7", "x")]);
+        let expected = ["  | C | D", "--+---+--", "5 | 1 |", "6 |   |", "7 |   | x"];
+        assert_eq!(sheet.render().unwrap(), expected.join("\n") + "\n");
+
+        // The far corner on its own is one small cell...
+        let mut corner = Sheet::new();
+        corner.enter("ZZZ1048576", "1").unwrap();
+        assert_eq!(corner.render().unwrap(), "        | ZZZ\n--------+----\n1048576 |   1\n");
+        assert_eq!(corner.bounds(), Some((cell("ZZZ1048576"), cell("ZZZ1048576"))));
+
+        // ...but with A1 too it spans the whole sheet
+        sheet.enter("A1", "0").unwrap();
+        sheet.enter("ZZZ1048576", "1").unwrap();
+        assert_eq!(sheet.render(), Err(SheetError::TooLarge { cols: MAX_COLS, rows: MAX_ROWS }));
+    }
+}
@@ -0,0 +1,455 @@
+//! # CSV
+//!
+//! Loading and saving a [`Sheet`] as CSV, following RFC 4180: fields that
+//! contain the delimiter, a quote or a line break are wrapped in quotes, and
+//! a quote inside a quoted field is doubled. Records end with CRLF when
+//! written; either CRLF or a bare LF is accepted when reading.
+//!
+//! Reading is strict about quotes, so a damaged file is reported with the
+//! line and column of the problem rather than silently shifting cells:
+//! a quote inside an unquoted field, anything but a delimiter or line end
+//! after a closing quote, and a quoted field that never closes are errors.
+//!
+//! On import each field is read as [`SpreadsheetCell`]'s `FromStr` reads
+//! typing, so `42` becomes an `Int`, `2.5` a `Float` and `=A1*2` a
+//! `Formula`. On export, formulas are written as their source or as their
+//! current value, as [`FormulaExport`] says.
+
+use crate::fs_util;
+use super::{CellRef, Sheet, SheetError, SpreadsheetCell, MAX_COLS, MAX_ROWS};
+use std::fmt;
+use std::fs;
+use std::io;
+use std::iter::Peekable;
+use std::path::Path;
+use std::str::Chars;
+
+/// How formula cells are written
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum FormulaExport {
+    /// The formula as typed, `=SUM(A1:A3)`, so reading the file back
+    /// restores the formulas
+    #[default]
+    Text,
+    /// What the formula currently shows, for programs that don't know our
+    /// formulas
+    Values,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CsvOptions {
+    /// Separates fields; `,` by default, `;` and `\t` are common too
+    pub delimiter: char,
+    /// Read numbers and formulas as such; when off, every field is text
+    pub infer_types: bool,
+    pub formulas: FormulaExport,
+}
+
+impl Default for CsvOptions {
+    fn default() -> CsvOptions {
+        CsvOptions {
+            delimiter: ',',
+            infer_types: true,
+            formulas: FormulaExport::default(),
+        }
+    }
+}
+
+#[derive(Debug)]
+pub enum CsvError {
+    Io(io::Error),
+    /// `line` and `column` are 1-based and count characters
+    Parse { line: usize, column: usize, message: String },
+    /// The sheet is too large to write out
+    Sheet(SheetError),
+}
+
+impl fmt::Display for CsvError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            CsvError::Io(e) => write!(f, "{}", e),
+            CsvError::Parse { line, column, message } => write!(f, "line {}, column {}: {}", line, column, message),
+            CsvError::Sheet(e) => write!(f, "{}", e),
+        }
+    }
+}
+
+impl std::error::Error for CsvError {}
+
+impl From<io::Error> for CsvError {
+    fn from(e: io::Error) -> CsvError {
+        CsvError::Io(e)
+    }
+}
+
+impl From<SheetError> for CsvError {
+    fn from(e: SheetError) -> CsvError {
+        CsvError::Sheet(e)
+    }
+}
+
+/// Split CSV text into records of fields, quotes removed
+///
+/// # Example
+/// ```
+/// use rust_book_examples::spreadsheet::csv;
+///
+/// let records = csv::parse_records("name;note\r\nAda;\"said \"\"hi\"\"\nthen left\"\n", ';').unwrap();
+/// assert_eq!(records, [vec!["name", "note"], vec!["Ada", "said \"hi\"\nthen left"]]);
+///
+/// let err = csv::parse_records("a,b\nc,d\"e\n", ',').unwrap_err();
+/// assert_eq!(err.to_string(), "line 2, column 4: quote in an unquoted field; quote the whole field and double the quote");
+/// ```
+///
+/// # Panics
+///
+/// Panics if `delimiter` is a quote or a line break.
+pub fn parse_records(input: &str, delimiter: char) -> Result<Vec<Vec<String>>, CsvError> {
+    let records = fields(input, delimiter)?;
+    Ok(records
+        .into_iter()
+        .map(|record| record.into_iter().map(|field| field.text).collect())
+        .collect())
+}
+
+/// Build a sheet from CSV text: record `n` becomes row `n`, field `m` column `m`
+///
+/// Empty fields leave their cells empty. A formula that doesn't parse is
+/// reported at its position in the file.
+///
+/// # Example
+/// ```
+/// use rust_book_examples::spreadsheet::csv::{self, CsvOptions};
+/// use rust_book_examples::spreadsheet::{SpreadsheetCell, Value};
+///
+/// let sheet = csv::read("3,2.5,apples\n=A1*B1\n", &CsvOptions::default()).unwrap();
+/// assert_eq!(sheet.get(&"A1".parse().unwrap()), Some(&SpreadsheetCell::Int(3)));
+/// assert_eq!(sheet.value(&"A2".parse().unwrap()), Value::Number(7.5));
+///
+/// let err = csv::read("1,2\n3,=A1+\n", &CsvOptions::default()).unwrap_err();
+/// assert_eq!(err.to_string(), "line 2, column 7: bad formula: unexpected end of formula");
+/// ```
+///
+/// # Panics
+///
+/// Panics if the delimiter is a quote or a line break.
+pub fn read(input: &str, options: &CsvOptions) -> Result<Sheet, CsvError> {
+    let mut sheet = Sheet::new();
+    for (row, record) in fields(input, options.delimiter)?.into_iter().enumerate() {
+        for (col, field) in record.into_iter().enumerate() {
+            if field.text.is_empty() {
+                continue;
+            }
+            let error = |column, message: String| CsvError::Parse { line: field.line, column, message };
+            if row >= MAX_ROWS as usize || col >= MAX_COLS as usize {
+                return Err(error(field.column, "beyond the last row or column of a sheet".to_string()));
+            }
+
+            let cell = if options.infer_types {
+                field.text.parse::<SpreadsheetCell>().map_err(|e| {
+                    // Where the formula starts in the file, plus where in the formula it went wrong
+                    let start = field.column + usize::from(field.quoted);
+                    error(start + e.column - 1, format!("bad formula: {}", e.message))
+                })?
+            } else {
+                SpreadsheetCell::Text(field.text)
+            };
+            sheet.set(CellRef::new(col as u32, row as u32), cell);
+        }
+    }
+    Ok(sheet)
+}
+
+/// The sheet as CSV text, one record per row from row 1 to the last row in
+/// use, each with a field for every column up to the last one in use
+///
+/// # Example
+/// ```
+/// use rust_book_examples::spreadsheet::csv::{self, CsvOptions, FormulaExport};
+/// use rust_book_examples::spreadsheet::Sheet;
+///
+/// let mut sheet = Sheet::new();
+/// sheet.enter("A1", "Tom, Dick").unwrap();
+/// sheet.enter("B2", "=6*7").unwrap();
+/// assert_eq!(csv::write(&sheet, &CsvOptions::default()).unwrap(), "\"Tom, Dick\",\r\n,=6*7\r\n");
+///
+/// let values = CsvOptions { delimiter: ';', formulas: FormulaExport::Values, ..CsvOptions::default() };
+/// assert_eq!(csv::write(&sheet, &values).unwrap(), "Tom, Dick;\r\n;42\r\n");
+/// ```
+///
+/// # Errors
+///
+/// [`CsvError::Sheet`] if that's more than
+/// [`MAX_LAYOUT_CELLS`](super::MAX_LAYOUT_CELLS) fields, since CSV has no way
+/// to skip the empty ones.
+///
+/// # Panics
+///
+/// Panics if the delimiter is a quote or a line break.
+pub fn write(sheet: &Sheet, options: &CsvOptions) -> Result<String, CsvError> {
+    check_delimiter(options.delimiter);
+    let (cols, rows) = sheet.extent();
+    SheetError::check_layout(cols, rows)?;
+    let mut out = String::new();
+    let mut delimiter = [0; 4];
+    let delimiter = options.delimiter.encode_utf8(&mut delimiter);
+    for row in 0..rows {
+        let fields: Vec<String> = (0..cols)
+            .map(|col| {
+                let at = CellRef::new(col, row);
+                let text = match sheet.get(&at) {
+                    None => String::new(),
+                    Some(SpreadsheetCell::Formula(_)) if options.formulas == FormulaExport::Values => {
+                        sheet.value(&at).to_string()
+                    }
+                    Some(cell) => cell.to_string(),
+                };
+                quote(text, options.delimiter)
+            })
+            .collect();
+        out.push_str(&fields.join(delimiter));
+        out.push_str("\r\n");
+    }
+    Ok(out)
+}
+
+/// Read a CSV file into a sheet
+pub fn load(path: impl AsRef<Path>, options: &CsvOptions) -> Result<Sheet, CsvError> {
+    read(&fs::read_to_string(path)?, options)
+}
+
+/// Write the sheet to a CSV file, replacing it only once it's fully written
+pub fn save(sheet: &Sheet, path: impl AsRef<Path>, options: &CsvOptions) -> Result<(), CsvError> {
+    fs_util::write_atomically(path.as_ref(), write(sheet, options)?.as_bytes(), None)?;
+    Ok(())
+}
+
+/// A field as read, with where it started
+struct Field {
+    text: String,
+    line: usize,
+    column: usize,
+    quoted: bool,
+}
+
+/// Characters with the line and column of the next one
+struct Cursor<'a> {
+    chars: Peekable<Chars<'a>>,
+    line: usize,
+    column: usize,
+}
+
+impl Cursor<'_> {
+    fn peek(&mut self) -> Option<char> {
+        self.chars.peek().copied()
+    }
+
+    fn next(&mut self) -> Option<char> {
+        let c = self.chars.next()?;
+        if c == '\n' {
+            self.line += 1;
+            self.column = 1;
+        } else {
+            self.column += 1;
+        }
+        Some(c)
+    }
+
+    fn error(&self, message: String) -> CsvError {
+        CsvError::Parse { line: self.line, column: self.column, message }
+    }
+}
+
+fn check_delimiter(delimiter: char) {
+    assert!(
+        !matches!(delimiter, '"' | '\r' | '\n'),
+        "a CSV delimiter can't be a quote or a line break, got {:?}",
+        delimiter
+    );
+}
+
+fn fields(input: &str, delimiter: char) -> Result<Vec<Vec<Field>>, CsvError> {
+    check_delimiter(delimiter);
+    // Spreadsheet programs often start UTF-8 files with a byte order mark
+    let input = input.strip_prefix('\u{feff}').unwrap_or(input);
+    let mut cursor = Cursor { chars: input.chars().peekable(), line: 1, column: 1 };
+    let mut records = Vec::new();
+    let mut record = Vec::new();
+
+    loop {
+        let (line, column) = (cursor.line, cursor.column);
+        let quoted = cursor.peek() == Some('"');
+        let mut text = String::new();
+        if quoted {
+            cursor.next();
+            loop {
+                match cursor.next() {
+                    None => {
+                        return Err(CsvError::Parse { line, column, message: "quoted field never closes".to_string() });
+                    }
+                    Some('"') if cursor.peek() == Some('"') => {
+                        cursor.next();
+                        text.push('"');
+                    }
+                    Some('"') => break,
+                    Some(c) => text.push(c),
+                }
+            }
+            match cursor.peek() {
+                None | Some('\r' | '\n') => {}
+                Some(c) if c == delimiter => {}
+                Some(c) => return Err(cursor.error(format!("expected a delimiter or line end after a closing quote, found {:?}", c))),
+            }
+        } else {
+            while let Some(c) = cursor.peek() {
+                if c == delimiter || c == '\r' || c == '\n' {
+                    break;
+                }
+                if c == '"' {
+                    return Err(cursor.error(
+                        "quote in an unquoted field; quote the whole field and double the quote".to_string(),
+                    ));
+                }
+                text.push(c);
+                cursor.next();
+            }
+        }
+        record.push(Field { text, line, column, quoted });
+
+        match cursor.next() {
+            Some(c) if c == delimiter => {}
+            Some('\r') => {
+                if cursor.peek() == Some('\n') {
+                    cursor.next();
+                }
+                records.push(std::mem::take(&mut record));
+            }
+            // The only other way a field ends is a line feed
+            Some(_) => records.push(std::mem::take(&mut record)),
+            None => {
+                // A final line break doesn't start another record
+                let trailing = matches!(&record[..], [Field { text, quoted: false, .. }] if text.is_empty());
+                if !trailing {
+                    records.push(record);
+                }
+                return Ok(records);
+            }
+        }
+    }
+}
+
+/// Quote a field only if it needs it
+fn quote(field: String, delimiter: char) -> String {
+    if field.contains([delimiter, '"', '\r', '\n']) {
+        format!("\"{}\"", field.replace('"', "\"\""))
+    } else {
+        field
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fs_util::TempPath;
+    use crate::spreadsheet::Value;
+
+    fn parse_error(input: &str) -> (usize, usize) {
+        match parse_records(input, ',') {
+            Err(CsvError::Parse { line, column, .. }) => (line, column),
+            other => panic!("expected a parse error, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn parses_rfc_4180() {
+        let input = "a,\"b,c\",\"say \"\"hi\"\"\"\r\n\"multi\nline\",,\r\n\n\"\",x\n";
+        let records = parse_records(input, ',').unwrap();
+        assert_eq!(
+            records,
+            [
+                vec!["a", "b,c", "say \"hi\""],
+                vec!["multi\nline", "", ""],
+                vec![""],
+                vec!["", "x"],
+            ]
+        );
+        assert_eq!(parse_records("", ',').unwrap(), Vec::<Vec<String>>::new());
+        assert_eq!(parse_records("\u{feff}a\tb", '\t').unwrap(), [["a", "b"]]);
+        // A quoted empty field at the end is still a record
+        assert_eq!(parse_records("\"\"", ',').unwrap(), [[""]]);
+    }
+
+    #[test]
+    fn errors_have_positions() {
+        assert_eq!(parse_error("ab\"c"), (1, 3));
+        // The line of the opening quote, however far the field runs
+        assert_eq!(parse_error("a\n\"open,\nstill open"), (2, 1));
+        assert_eq!(parse_error("\"closed\"x,y"), (1, 9));
+        // Columns count characters, not bytes
+        assert_eq!(parse_error("é,\"ü\"!"), (1, 6));
+    }
+
+    #[test]
+    fn infers_types_unless_told_not_to() {
+        let input = "7,-2.5,hello,=A1*B1\n\"12\",\" padded \",,\n";
+        let sheet = read(input, &CsvOptions::default()).unwrap();
+        let get = |at: &str| sheet.get(&at.parse().unwrap()).cloned();
+        assert_eq!(get("A1"), Some(SpreadsheetCell::Int(7)));
+        assert_eq!(get("B1"), Some(SpreadsheetCell::Float(-2.5)));
+        assert_eq!(get("C1"), Some(SpreadsheetCell::Text("hello".to_string())));
+        assert_eq!(sheet.value(&"D1".parse().unwrap()), Value::Number(-17.5));
+        assert_eq!(get("A2"), Some(SpreadsheetCell::Int(12)));
+        assert_eq!(get("B2"), Some(SpreadsheetCell::Text(" padded ".to_string())));
+        assert_eq!(get("C2"), None);
+
+        let text = CsvOptions { infer_types: false, ..CsvOptions::default() };
+        let sheet = read(input, &text).unwrap();
+        assert_eq!(sheet.get(&"D1".parse().unwrap()), Some(&SpreadsheetCell::Text("=A1*B1".to_string())));
+
+        // The formula error points into the quoted field
+        let err = read("1,\"=SUM(A1\"", &CsvOptions::default()).unwrap_err();
+        assert_eq!(err.to_string(), "line 1, column 11: bad formula: expected ')'");
+    }
+
+    #[test]
+    fn deeply_nested_formulas_are_errors() {
+        let input = format!("1\n2,={}1{}\n", "(".repeat(200_000), ")".repeat(200_000));
+        let err = read(&input, &CsvOptions::default()).unwrap_err();
+        assert!(matches!(err, CsvError::Parse { line: 2, column: 68, .. }));
+        assert!(err.to_string().ends_with("bad formula: more than 64 parentheses and calls inside each other"));
+    }
+
+    #[test]
+    fn round_trips_through_a_file() {
+        let mut sheet = Sheet::new();
+        for (at, input) in [("A1", "name"), ("B1", "note"), ("A2", "Ann"), ("B2", "likes \"quotes\",\ncommas"), ("C3", "=1/0"), ("C4", "=SUM(A1:C3)+4.5")] {
+            sheet.enter(at, input).unwrap();
+        }
+        let path = TempPath::new("spreadsheet_csv.csv");
+        let options = CsvOptions { delimiter: ';', ..CsvOptions::default() };
+        save(&sheet, &path, &options).unwrap();
+        let loaded = load(&path, &options).unwrap();
+
+        let cells: Vec<_> = sheet.cells().collect();
+        assert_eq!(loaded.cells().collect::<Vec<_>>(), cells);
+        assert_eq!(loaded.render().unwrap(), sheet.render().unwrap());
+
+        let values = CsvOptions { formulas: FormulaExport::Values, ..CsvOptions::default() };
+        assert_eq!(
+            write(&sheet, &values).unwrap(),
+            "name,note,\r\nAnn,\"likes \"\"quotes\"\",\ncommas\",\r\n,,#DIV/0!\r\n,,#DIV/0!\r\n"
+        );
+    }
+
+    #[test]
+    fn refuses_to_write_a_sheet_too_large_to_lay_out() {
+        let mut sheet = Sheet::new();
+        sheet.enter("ZZZ1048576", "1").unwrap();
+        let err = write(&sheet, &CsvOptions::default()).unwrap_err();
+        assert!(matches!(err, CsvError::Sheet(SheetError::TooLarge { cols: MAX_COLS, rows: MAX_ROWS })));
+
+        let path = TempPath::new("spreadsheet_csv_too_large.csv");
+        assert!(matches!(save(&sheet, &path, &CsvOptions::default()), Err(CsvError::Sheet(_))));
+        assert!(!path.exists());
+    }
+}
@@ -0,0 +1,779 @@
+//! # Formulas
+//!
+//! Parsing and evaluating the `=` cells of a [`Sheet`](super::Sheet).
+//!
+//! ## Grammar
+//!
+//! ```text
+//! formula    = "=" comparison
+//! comparison = sum [ ("=" | "<>" | "<" | ">" | "<=" | ">=") sum ]
+//! sum        = product { ("+" | "-") product }
+//! product    = unary { ("*" | "/") unary }
+//! unary      = "-" unary | "+" unary | primary
+//! primary    = NUMBER | "TEXT" | TRUE | FALSE | CELL | NAME "(" [ args ] ")" | "(" comparison ")"
+//! args       = arg { "," arg }
+//! arg        = CELL ":" CELL | comparison
+//! ```
+//!
+//! Names, cell addresses and `TRUE`/`FALSE` are case-insensitive. Ranges
+//! like `A1:B3` may only appear as function arguments, and a quote inside a
+//! text literal is written twice: `"say ""hi"""`.
+//!
+//! ## Functions
+//!
+//! - `SUM`, `AVERAGE`, `MIN`, `MAX` and `COUNT` take any number of cells,
+//!   ranges and values; they skip anything that isn't a number
+//! - `IF(condition, then[, otherwise])` only evaluates the branch it takes
+
+use super::{CellError, CellRef, Value};
+use std::collections::BTreeSet;
+use std::fmt;
+use std::str::FromStr;
+
+/// Cells a single range may cover, so a typo like `A1:XFD1048576` can't
+/// make every edit recalculate millions of cells
+pub const MAX_RANGE_CELLS: u64 = 100_000;
+
+/// Parentheses and function calls a formula may have open inside each
+/// other, as in most spreadsheet programs
+pub const MAX_NESTING: usize = 64;
+
+/// Levels of operators and calls a formula may stack on one value, so
+/// `=1+1+...` can't run on forever. Parsing and evaluating recurse once per
+/// level, so deeper formulas are refused rather than overflowing the stack
+pub const MAX_DEPTH: usize = 256;
+
+/// Why a formula couldn't be parsed
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FormulaError {
+    /// 1-based character position in the formula, counting the `=`
+    pub column: usize,
+    pub message: String,
+}
+
+impl fmt::Display for FormulaError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "column {}: {}", self.column, self.message)
+    }
+}
+
+impl std::error::Error for FormulaError {}
+
+/// A parsed formula, with the cells it reads
+///
+/// # Example
+/// ```
+/// use rust_book_examples::spreadsheet::{CellRef, Formula, Value};
+///
+/// let formula: Formula = "=SUM(A1:A3)*2".parse().unwrap();
+/// assert_eq!(formula.references().len(), 3);
+/// let value = formula.evaluate(|cell| Value::Number(cell.row() as f64 + 1.0));
+/// assert_eq!(value, Value::Number(12.0));
+///
+/// let err = "=SUM(A1:A3".parse::<Formula>().unwrap_err();
+/// assert_eq!(err.to_string(), "column 11: expected ')'");
+/// ```
+#[derive(Debug, Clone, PartialEq)]
+pub struct Formula {
+    source: String,
+    expr: Expr,
+    references: Vec<CellRef>,
+}
+
+impl Formula {
+    /// The formula as typed, starting with `=`
+    pub fn source(&self) -> &str {
+        &self.source
+    }
+
+    /// Every cell the formula reads, ranges expanded, in row-major order
+    pub fn references(&self) -> &[CellRef] {
+        &self.references
+    }
+
+    /// Evaluate with `lookup` supplying the current value of each cell
+    ///
+    /// An error in a cell the formula needs becomes the formula's value, and
+    /// a bare reference to an empty cell counts as 0.
+    pub fn evaluate(&self, lookup: impl Fn(CellRef) -> Value) -> Value {
+        match eval(&self.expr, &lookup) {
+            Ok(Value::Empty) => Value::Number(0.0),
+            Ok(value) => value,
+            Err(e) => Value::Error(e),
+        }
+    }
+}
+
+impl FromStr for Formula {
+    type Err = FormulaError;
+
+    fn from_str(source: &str) -> Result<Formula, FormulaError> {
+        let Some(body) = source.strip_prefix('=') else {
+            return Err(FormulaError { column: 1, message: "a formula starts with '='".to_string() });
+        };
+        let tokens = lex(body)?;
+        let mut parser = Parser { tokens, pos: 0, end: source.chars().count() + 1, depth: 0 };
+        let (expr, _) = parser.comparison()?;
+        if let Some(token) = parser.tokens.get(parser.pos) {
+            return Err(parser.error_at(token.column, format!("unexpected {}", token.kind)));
+        }
+
+        let mut references = BTreeSet::new();
+        expr.collect_references(&mut references);
+        Ok(Formula {
+            source: source.to_string(),
+            expr,
+            references: references.into_iter().collect(),
+        })
+    }
+}
+
+impl fmt::Display for Formula {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(&self.source)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Function {
+    Sum,
+    Average,
+    Min,
+    Max,
+    Count,
+    If,
+}
+
+impl Function {
+    fn named(name: &str) -> Option<Function> {
+        Some(match name.to_ascii_uppercase().as_str() {
+            "SUM" => Function::Sum,
+            "AVERAGE" => Function::Average,
+            "MIN" => Function::Min,
+            "MAX" => Function::Max,
+            "COUNT" => Function::Count,
+            "IF" => Function::If,
+            _ => return None,
+        })
+    }
+
+    /// Fewest and most arguments accepted
+    fn arity(self) -> (usize, usize) {
+        match self {
+            Function::If => (2, 3),
+            _ => (1, usize::MAX),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum BinaryOp {
+    Add,
+    Sub,
+    Mul,
+    Div,
+    Eq,
+    Ne,
+    Lt,
+    Gt,
+    Le,
+    Ge,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Expr {
+    Number(f64),
+    Text(String),
+    Bool(bool),
+    Ref(CellRef),
+    /// Top-left and bottom-right corners
+    Range(CellRef, CellRef),
+    Neg(Box<Expr>),
+    Binary(BinaryOp, Box<Expr>, Box<Expr>),
+    Call(Function, Vec<Expr>),
+}
+
+impl Expr {
+    fn collect_references(&self, references: &mut BTreeSet<CellRef>) {
+        match self {
+            Expr::Ref(cell) => {
+                references.insert(*cell);
+            }
+            Expr::Range(start, end) => references.extend(cells(*start, *end)),
+            Expr::Neg(expr) => expr.collect_references(references),
+            Expr::Binary(_, left, right) => {
+                left.collect_references(references);
+                right.collect_references(references);
+            }
+            Expr::Call(_, args) => args.iter().for_each(|arg| arg.collect_references(references)),
+            Expr::Number(_) | Expr::Text(_) | Expr::Bool(_) => {}
+        }
+    }
+}
+
+/// The cells of a range, row by row
+fn cells(start: CellRef, end: CellRef) -> impl Iterator<Item = CellRef> {
+    (start.row()..=end.row()).flat_map(move |row| (start.col()..=end.col()).map(move |col| CellRef::new(col, row)))
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum TokenKind {
+    Number(f64),
+    Text(String),
+    Name(String),
+    Op(&'static str),
+    LParen,
+    RParen,
+    Comma,
+    Colon,
+}
+
+impl fmt::Display for TokenKind {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            TokenKind::Number(n) => write!(f, "number {}", n),
+            TokenKind::Text(_) => write!(f, "text"),
+            TokenKind::Name(name) => write!(f, "'{}'", name),
+            TokenKind::Op(op) => write!(f, "'{}'", op),
+            TokenKind::LParen => write!(f, "'('"),
+            TokenKind::RParen => write!(f, "')'"),
+            TokenKind::Comma => write!(f, "','"),
+            TokenKind::Colon => write!(f, "':'"),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+struct Token {
+    kind: TokenKind,
+    column: usize,
+}
+
+/// Split a formula body (after the `=`) into tokens
+fn lex(body: &str) -> Result<Vec<Token>, FormulaError> {
+    let chars: Vec<char> = body.chars().collect();
+    let mut tokens = Vec::new();
+    let mut i = 0;
+    while i < chars.len() {
+        let c = chars[i];
+        // The `=` is column 1
+        let column = i + 2;
+        let start = i;
+        i += 1;
+        let kind = match c {
+            c if c.is_whitespace() => continue,
+            '(' => TokenKind::LParen,
+            ')' => TokenKind::RParen,
+            ',' => TokenKind::Comma,
+            ':' => TokenKind::Colon,
+            '+' => TokenKind::Op("+"),
+            '-' => TokenKind::Op("-"),
+            '*' => TokenKind::Op("*"),
+            '/' => TokenKind::Op("/"),
+            '=' => TokenKind::Op("="),
+            '<' | '>' => {
+                let op = match (c, chars.get(i)) {
+                    ('<', Some('=')) => "<=",
+                    ('<', Some('>')) => "<>",
+                    ('>', Some('=')) => ">=",
+                    ('<', _) => "<",
+                    _ => ">",
+                };
+                i += op.len() - 1;
+                TokenKind::Op(op)
+            }
+            '"' => {
+                let mut text = String::new();
+                loop {
+                    match chars.get(i) {
+                        None => return Err(FormulaError { column, message: "unterminated text".to_string() }),
+                        Some('"') if chars.get(i + 1) == Some(&'"') => {
+                            text.push('"');
+                            i += 2;
+                        }
+                        Some('"') => {
+                            i += 1;
+                            break;
+                        }
+                        Some(&c) => {
+                            text.push(c);
+                            i += 1;
+                        }
+                    }
+                }
+                TokenKind::Text(text)
+            }
+            c if c.is_ascii_digit() || c == '.' => {
+                while i < chars.len() && (chars[i].is_ascii_digit() || chars[i] == '.') {
+                    i += 1;
+                }
+                let number: String = chars[start..i].iter().collect();
+                match number.parse() {
+                    Ok(n) => TokenKind::Number(n),
+                    Err(_) => return Err(FormulaError { column, message: format!("bad number {}", number) }),
+                }
+            }
+            c if c.is_alphabetic() => {
+                while i < chars.len() && (chars[i].is_alphanumeric() || chars[i] == '_') {
+                    i += 1;
+                }
+                TokenKind::Name(chars[start..i].iter().collect())
+            }
+            c => return Err(FormulaError { column, message: format!("unexpected '{}'", c) }),
+        };
+        tokens.push(Token { kind, column });
+    }
+    Ok(tokens)
+}
+
+/// A recursive descent parser, one method per grammar rule
+///
+/// The rules return the expression they parsed with its height, the number
+/// of nodes from it down to its deepest leaf, which is kept under
+/// [`MAX_DEPTH`].
+struct Parser {
+    tokens: Vec<Token>,
+    pos: usize,
+    /// Column just past the end of the formula, for errors at the end
+    end: usize,
+    /// Parentheses and calls open around the current token
+    depth: usize,
+}
+
+impl Parser {
+    fn peek(&self) -> Option<&TokenKind> {
+        self.tokens.get(self.pos).map(|token| &token.kind)
+    }
+
+    fn column(&self) -> usize {
+        self.tokens.get(self.pos).map_or(self.end, |token| token.column)
+    }
+
+    fn error_at(&self, column: usize, message: String) -> FormulaError {
+        FormulaError { column, message }
+    }
+
+    fn next(&mut self) -> Option<TokenKind> {
+        let kind = self.peek().cloned();
+        self.pos += 1;
+        kind
+    }
+
+    fn expect(&mut self, kind: TokenKind) -> Result<(), FormulaError> {
+        if self.peek() == Some(&kind) {
+            self.pos += 1;
+            Ok(())
+        } else {
+            Err(self.error_at(self.column(), format!("expected {}", kind)))
+        }
+    }
+
+    /// The height of a node over children as high as `height`, if that's
+    /// allowed; `column` is where the node is
+    fn nest(&self, height: usize, column: usize) -> Result<usize, FormulaError> {
+        if height >= MAX_DEPTH {
+            return Err(self.error_at(column, format!("formula nested too deeply; the limit is {} levels", MAX_DEPTH)));
+        }
+        Ok(height + 1)
+    }
+
+    /// Note a parenthesis or call opening at `column`, before parsing into it
+    fn open(&mut self, column: usize) -> Result<(), FormulaError> {
+        if self.depth >= MAX_NESTING {
+            let message = format!("more than {} parentheses and calls inside each other", MAX_NESTING);
+            return Err(self.error_at(column, message));
+        }
+        self.depth += 1;
+        Ok(())
+    }
+
+    /// Consume the next token if it's one of `ops`
+    fn op(&mut self, ops: &[&'static str]) -> Option<&'static str> {
+        match self.peek() {
+            Some(TokenKind::Op(op)) if ops.contains(op) => {
+                let op = *op;
+                self.pos += 1;
+                Some(op)
+            }
+            _ => None,
+        }
+    }
+
+    fn comparison(&mut self) -> Result<(Expr, usize), FormulaError> {
+        let (left, left_height) = self.sum()?;
+        let column = self.column();
+        let op = match self.op(&["=", "<>", "<", ">", "<=", ">="]) {
+            Some("=") => BinaryOp::Eq,
+            Some("<>") => BinaryOp::Ne,
+            Some("<") => BinaryOp::Lt,
+            Some(">") => BinaryOp::Gt,
+            Some("<=") => BinaryOp::Le,
+            Some(_) => BinaryOp::Ge,
+            None => return Ok((left, left_height)),
+        };
+        let (right, right_height) = self.sum()?;
+        let height = self.nest(left_height.max(right_height), column)?;
+        Ok((Expr::Binary(op, Box::new(left), Box::new(right)), height))
+    }
+
+    fn sum(&mut self) -> Result<(Expr, usize), FormulaError> {
+        let (mut expr, mut height) = self.product()?;
+        loop {
+            let column = self.column();
+            let Some(op) = self.op(&["+", "-"]) else { break };
+            let op = if op == "+" { BinaryOp::Add } else { BinaryOp::Sub };
+            let (right, right_height) = self.product()?;
+            height = self.nest(height.max(right_height), column)?;
+            expr = Expr::Binary(op, Box::new(expr), Box::new(right));
+        }
+        Ok((expr, height))
+    }
+
+    fn product(&mut self) -> Result<(Expr, usize), FormulaError> {
+        let (mut expr, mut height) = self.unary()?;
+        loop {
+            let column = self.column();
+            let Some(op) = self.op(&["*", "/"]) else { break };
+            let op = if op == "*" { BinaryOp::Mul } else { BinaryOp::Div };
+            let (right, right_height) = self.unary()?;
+            height = self.nest(height.max(right_height), column)?;
+            expr = Expr::Binary(op, Box::new(expr), Box::new(right));
+        }
+        Ok((expr, height))
+    }
+
+    fn unary(&mut self) -> Result<(Expr, usize), FormulaError> {
+        // A loop rather than recursion, so a long run of signs can't overflow
+        let mut negations = Vec::new();
+        loop {
+            let column = self.column();
+            match self.op(&["-", "+"]) {
+                Some("-") => negations.push(column),
+                Some(_) => {}
+                None => break,
+            }
+        }
+        let (mut expr, mut height) = self.primary()?;
+        for column in negations.into_iter().rev() {
+            height = self.nest(height, column)?;
+            expr = Expr::Neg(Box::new(expr));
+        }
+        Ok((expr, height))
+    }
+
+    fn primary(&mut self) -> Result<(Expr, usize), FormulaError> {
+        let column = self.column();
+        let expr = match self.next() {
+            Some(TokenKind::Number(n)) => Expr::Number(n),
+            Some(TokenKind::Text(text)) => Expr::Text(text),
+            Some(TokenKind::LParen) => {
+                self.open(column)?;
+                let inner = self.comparison()?;
+                self.depth -= 1;
+                self.expect(TokenKind::RParen)?;
+                return Ok(inner);
+            }
+            Some(TokenKind::Name(name)) if self.peek() == Some(&TokenKind::LParen) => {
+                let function = Function::named(&name)
+                    .ok_or_else(|| self.error_at(column, format!("unknown function {}", name)))?;
+                self.pos += 1;
+                return self.call(function, &name, column);
+            }
+            Some(TokenKind::Name(name)) => match name.to_ascii_uppercase().as_str() {
+                "TRUE" => Expr::Bool(true),
+                "FALSE" => Expr::Bool(false),
+                _ => Expr::Ref(self.cell(&name, column)?),
+            },
+            Some(kind) => return Err(self.error_at(column, format!("unexpected {}", kind))),
+            None => return Err(self.error_at(column, "unexpected end of formula".to_string())),
+        };
+        Ok((expr, 0))
+    }
+
+    /// The arguments of a call, after its `(`
+    fn call(&mut self, function: Function, name: &str, column: usize) -> Result<(Expr, usize), FormulaError> {
+        self.open(column)?;
+        let mut args = Vec::new();
+        let mut height = 0;
+        if self.peek() != Some(&TokenKind::RParen) {
+            loop {
+                let (arg, arg_height) = self.argument()?;
+                args.push(arg);
+                height = height.max(arg_height);
+                if self.peek() != Some(&TokenKind::Comma) {
+                    break;
+                }
+                self.pos += 1;
+            }
+        }
+        self.expect(TokenKind::RParen)?;
+        self.depth -= 1;
+
+        let (min, max) = function.arity();
+        if args.len() < min || args.len() > max {
+            let expected = if max == usize::MAX {
+                format!("at least {}", min)
+            } else {
+                format!("{} to {}", min, max)
+            };
+            return Err(self.error_at(
+                column,
+                format!("{} takes {} argument(s), got {}", name.to_ascii_uppercase(), expected, args.len()),
+            ));
+        }
+        if function == Function::If && matches!(args[0], Expr::Range(..)) {
+            return Err(self.error_at(column, "IF needs a single condition, not a range".to_string()));
+        }
+        Ok((Expr::Call(function, args), self.nest(height, column)?))
+    }
+
+    fn argument(&mut self) -> Result<(Expr, usize), FormulaError> {
+        let is_range = matches!(
+            (self.tokens.get(self.pos), self.tokens.get(self.pos + 1)),
+            (Some(Token { kind: TokenKind::Name(_), .. }), Some(Token { kind: TokenKind::Colon, .. }))
+        );
+        if !is_range {
+            return self.comparison();
+        }
+
+        let column = self.column();
+        let Some(TokenKind::Name(first)) = self.next() else { unreachable!("checked above") };
+        let first = self.cell(&first, column)?;
+        self.pos += 1;
+        let column = self.column();
+        let Some(TokenKind::Name(second)) = self.next() else {
+            return Err(self.error_at(column, "expected a cell after ':'".to_string()));
+        };
+        let second = self.cell(&second, column)?;
+
+        // Either corner may come first
+        let start = CellRef::new(first.col().min(second.col()), first.row().min(second.row()));
+        let end = CellRef::new(first.col().max(second.col()), first.row().max(second.row()));
+        let size = u64::from(end.col() - start.col() + 1) * u64::from(end.row() - start.row() + 1);
+        if size > MAX_RANGE_CELLS {
+            return Err(self.error_at(
+                column,
+                format!("{}:{} covers {} cells; the limit is {}", start, end, size, MAX_RANGE_CELLS),
+            ));
+        }
+        Ok((Expr::Range(start, end), 0))
+    }
+
+    fn cell(&self, name: &str, column: usize) -> Result<CellRef, FormulaError> {
+        name.parse()
+            .map_err(|_| self.error_at(column, format!("'{}' is not a cell like A1 or a function", name)))
+    }
+}
+
+fn eval(expr: &Expr, lookup: &impl Fn(CellRef) -> Value) -> Result<Value, CellError> {
+    let value = match expr {
+        Expr::Number(n) => Value::Number(*n),
+        Expr::Text(text) => Value::Text(text.clone()),
+        Expr::Bool(b) => Value::Bool(*b),
+        Expr::Ref(cell) => match lookup(*cell) {
+            Value::Error(e) => return Err(e),
+            value => value,
+        },
+        // The parser only allows ranges as function arguments
+        Expr::Range(..) => return Err(CellError::Value),
+        Expr::Neg(expr) => Value::Number(-number(&eval(expr, lookup)?)?),
+        Expr::Binary(op, left, right) => binary(*op, eval(left, lookup)?, eval(right, lookup)?)?,
+        Expr::Call(Function::If, args) => {
+            if truthy(&eval(&args[0], lookup)?)? {
+                eval(&args[1], lookup)?
+            } else {
+                match args.get(2) {
+                    Some(otherwise) => eval(otherwise, lookup)?,
+                    None => Value::Bool(false),
+                }
+            }
+        }
+        Expr::Call(function, args) => aggregate(*function, &numbers(args, lookup)?)?,
+    };
+    Ok(value)
+}
+
+fn binary(op: BinaryOp, left: Value, right: Value) -> Result<Value, CellError> {
+    let result = match op {
+        BinaryOp::Add => number(&left)? + number(&right)?,
+        BinaryOp::Sub => number(&left)? - number(&right)?,
+        BinaryOp::Mul => number(&left)? * number(&right)?,
+        BinaryOp::Div => {
+            let divisor = number(&right)?;
+            if divisor == 0.0 {
+                return Err(CellError::DivByZero);
+            }
+            number(&left)? / divisor
+        }
+        _ => {
+            let ordering = compare(&left, &right)?;
+            return Ok(Value::Bool(match op {
+                BinaryOp::Eq => ordering.is_eq(),
+                BinaryOp::Ne => ordering.is_ne(),
+                BinaryOp::Lt => ordering.is_lt(),
+                BinaryOp::Gt => ordering.is_gt(),
+                BinaryOp::Le => ordering.is_le(),
+                _ => ordering.is_ge(),
+            }));
+        }
+    };
+    if result.is_finite() { Ok(Value::Number(result)) } else { Err(CellError::Num) }
+}
+
+/// Text compares with text, ignoring case, and numbers with numbers; an
+/// empty cell is whichever of `""` and 0 the other side needs
+fn compare(left: &Value, right: &Value) -> Result<std::cmp::Ordering, CellError> {
+    match (left, right) {
+        (Value::Text(a), Value::Text(b)) => Ok(a.to_lowercase().cmp(&b.to_lowercase())),
+        (Value::Text(a), Value::Empty) => Ok(a.as_str().cmp("")),
+        (Value::Empty, Value::Text(b)) => Ok("".cmp(b.as_str())),
+        (Value::Text(_), _) | (_, Value::Text(_)) => Err(CellError::Value),
+        _ => number(left)?.partial_cmp(&number(right)?).ok_or(CellError::Num),
+    }
+}
+
+/// A value used as a number: empty is 0 and `TRUE` is 1
+fn number(value: &Value) -> Result<f64, CellError> {
+    match value {
+        Value::Number(n) => Ok(*n),
+        Value::Bool(b) => Ok(if *b { 1.0 } else { 0.0 }),
+        Value::Empty => Ok(0.0),
+        Value::Text(_) => Err(CellError::Value),
+        Value::Error(e) => Err(*e),
+    }
+}
+
+fn truthy(value: &Value) -> Result<bool, CellError> {
+    match value {
+        Value::Text(_) => Err(CellError::Value),
+        value => Ok(number(value)? != 0.0),
+    }
+}
+
+/// The numbers among a function's arguments, ranges expanded
+fn numbers(args: &[Expr], lookup: &impl Fn(CellRef) -> Value) -> Result<Vec<f64>, CellError> {
+    let mut numbers = Vec::new();
+    let mut take = |value: Value| match value {
+        Value::Number(n) => {
+            numbers.push(n);
+            Ok(())
+        }
+        Value::Error(e) => Err(e),
+        _ => Ok(()),
+    };
+    for arg in args {
+        match arg {
+            Expr::Range(start, end) => cells(*start, *end).try_for_each(|cell| take(lookup(cell)))?,
+            arg => take(eval(arg, lookup)?)?,
+        }
+    }
+    Ok(numbers)
+}
+
+fn aggregate(function: Function, numbers: &[f64]) -> Result<Value, CellError> {
+    let sum: f64 = numbers.iter().sum();
+    let result = match function {
+        Function::Sum => sum,
+        Function::Average if numbers.is_empty() => return Err(CellError::DivByZero),
+        Function::Average => sum / numbers.len() as f64,
+        // Like other spreadsheets, MIN and MAX of nothing are 0
+        Function::Min => numbers.iter().copied().reduce(f64::min).unwrap_or(0.0),
+        Function::Max => numbers.iter().copied().reduce(f64::max).unwrap_or(0.0),
+        Function::Count => numbers.len() as f64,
+        Function::If => unreachable!("IF is evaluated lazily"),
+    };
+    if result.is_finite() { Ok(Value::Number(result)) } else { Err(CellError::Num) }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+
+    fn eval_with(source: &str, cells: &[(&str, Value)]) -> Value {
+        let cells: HashMap<CellRef, Value> =
+            cells.iter().map(|(at, value)| (at.parse().unwrap(), value.clone())).collect();
+        let formula: Formula = source.parse().unwrap();
+        formula.evaluate(|cell| cells.get(&cell).cloned().unwrap_or(Value::Empty))
+    }
+
+    #[test]
+    fn precedence_and_functions() {
+        let cells = [
+            ("A1", Value::Number(1.0)),
+            ("A2", Value::Number(2.0)),
+            ("A3", Value::Number(3.0)),
+            ("B1", Value::Text("n/a".to_string())),
+        ];
+        assert_eq!(eval_with("=1+2*3-4/2", &cells), Value::Number(5.0));
+        assert_eq!(eval_with("=-(1+2)*-2", &cells), Value::Number(6.0));
+        assert_eq!(eval_with("=SUM(A1:A3)*2", &cells), Value::Number(12.0));
+        // Text and empty cells are skipped, so B1 doesn't count
+        assert_eq!(eval_with("=sum(a3:b1, 10)", &cells), Value::Number(16.0));
+        assert_eq!(eval_with("=AVERAGE(A1:B3)", &cells), Value::Number(2.0));
+        assert_eq!(eval_with("=COUNT(A1:B3)", &cells), Value::Number(3.0));
+        assert_eq!(eval_with("=MAX(A1:A3, -1) - MIN(A1:A3)", &cells), Value::Number(2.0));
+        assert_eq!(eval_with("=IF(A1 < A2, \"yes\", \"no\")", &cells), Value::Text("yes".to_string()));
+        assert_eq!(eval_with("=IF(B1 = \"N/A\", 0, B1 * 2)", &cells), Value::Number(0.0));
+        assert_eq!(eval_with("=IF(FALSE, 1)", &cells), Value::Bool(false));
+        assert_eq!(eval_with("=C9", &cells), Value::Number(0.0));
+        assert_eq!(eval_with("=\"say \"\"hi\"\"\"", &cells), Value::Text("say \"hi\"".to_string()));
+    }
+
+    #[test]
+    fn errors_propagate() {
+        let cells = [("A1", Value::Text("x".to_string())), ("A2", Value::Error(CellError::Cycle))];
+        assert_eq!(eval_with("=1/0", &cells), Value::Error(CellError::DivByZero));
+        assert_eq!(eval_with("=A1+1", &cells), Value::Error(CellError::Value));
+        assert_eq!(eval_with("=SUM(A1:A2)", &cells), Value::Error(CellError::Cycle));
+        assert_eq!(eval_with("=AVERAGE(B1:B2)", &cells), Value::Error(CellError::DivByZero));
+        // Only the branch taken is evaluated
+        assert_eq!(eval_with("=IF(TRUE, 1, A2)", &cells), Value::Number(1.0));
+    }
+
+    #[test]
+    fn parse_errors_point_at_the_problem() {
+        let error = |source: &str| source.parse::<Formula>().unwrap_err().to_string();
+        assert_eq!(error("SUM(A1)"), "column 1: a formula starts with '='");
+        assert_eq!(error("=1 +"), "column 5: unexpected end of formula");
+        assert_eq!(error("=(1 + 2"), "column 8: expected ')'");
+        assert_eq!(error("=FOO(1)"), "column 2: unknown function FOO");
+        assert_eq!(error("=A1 B1"), "column 5: unexpected 'B1'");
+        assert_eq!(error("=1 + apple"), "column 6: 'apple' is not a cell like A1 or a function");
+        assert_eq!(error("=IF(1)"), "column 2: IF takes 2 to 3 argument(s), got 1");
+        assert_eq!(error("=A1:A3"), "column 4: unexpected ':'");
+        assert_eq!(error("=\"open"), "column 2: unterminated text");
+        assert_eq!(error("=SUM(A1:ZZ9999)"), "column 9: A1:ZZ9999 covers 7019298 cells; the limit is 100000");
+    }
+
+    #[test]
+    fn deep_nesting_is_an_error_not_a_stack_overflow() {
+        let error = |source: String| source.parse::<Formula>().unwrap_err().to_string();
+        let too_deep = format!("formula nested too deeply; the limit is {} levels", MAX_DEPTH);
+        let too_many_open = format!("more than {} parentheses and calls inside each other", MAX_NESTING);
+
+        let parens = |n: usize| format!("={}1{}", "(".repeat(n), ")".repeat(n));
+        assert_eq!(eval_with(&parens(MAX_NESTING), &[]), Value::Number(1.0));
+        assert_eq!(error(parens(MAX_NESTING + 1)), format!("column {}: {}", MAX_NESTING + 2, too_many_open));
+        assert_eq!(error(parens(200_000)), format!("column {}: {}", MAX_NESTING + 2, too_many_open));
+        let calls = |n: usize| format!("={}1{}", "SUM(".repeat(n), ")".repeat(n));
+        assert_eq!(eval_with(&calls(MAX_NESTING), &[]), Value::Number(1.0));
+        assert!(error(calls(MAX_NESTING + 1)).ends_with(&too_many_open));
+
+        // Signs and chains of operators build deep trees without parentheses
+        let negations = |n: usize| format!("={}1", "-".repeat(n));
+        assert_eq!(eval_with(&negations(MAX_DEPTH), &[]), Value::Number(1.0));
+        assert!(error(negations(200_000)).ends_with(&too_deep));
+        let sum = |n: usize| format!("=1{}", "+1".repeat(n));
+        assert_eq!(eval_with(&sum(MAX_DEPTH), &[]), Value::Number(MAX_DEPTH as f64 + 1.0));
+        assert_eq!(error(sum(100_000)), format!("column {}: {}", 2 * MAX_DEPTH + 3, too_deep));
+    }
+
+    #[test]
+    fn references_expand_ranges_once() {
+        let formula: Formula = "=SUM(B2:A1) + A1 + IF(C3, 1, 2)".parse().unwrap();
+        let references: Vec<String> = formula.references().iter().map(|cell| cell.to_string()).collect();
+        assert_eq!(references, ["A1", "B1", "A2", "B2", "C3"]);
+        assert_eq!(formula.to_string(), "=SUM(B2:A1) + A1 + IF(C3, 1, 2)");
+    }
+}
@@ -0,0 +1,546 @@
+//! # Student Records
+//!
+//! Chapter 8.3 keeps `Student { name, grade, age }` values in a
+//! `HashMap<u32, Student>` keyed by ID, then looks them up, updates a grade
+//! and groups them by age. This module turns that into a small records tool:
+//! a validated [`Roster`] with queries, group-by statistics and sorted
+//! reports, saved in a line-based text file. The **shell** submodule puts an
+//! interactive command line in front of it.
+//!
+//! This module demonstrates:
+//! - A `HashMap` as the primary store, with `entry` for grouping
+//! - Sorting with `sort_by` and `f64::total_cmp`
+//! - Validation at the edges: a [`Student`] can't hold a grade outside 0-100
+//! - Reading a file strictly, with the line number of the first bad line
+//!
+//! ## File format
+//!
+//! A `student-records 1` header, then one tab-separated line per student:
+//! `ID NAME GRADE AGE`. Blank lines and lines starting with `#` are skipped.
+
+pub mod shell;
+
+use crate::fs_util;
+use std::collections::HashMap;
+use std::fmt;
+use std::fs;
+use std::io;
+use std::ops::RangeInclusive;
+use std::path::Path;
+use std::str::FromStr;
+
+const HEADER: &str = "student-records 1";
+
+/// The ID given to the first student added to an empty roster
+pub const FIRST_ID: u32 = 1001;
+
+/// Ages a student can have
+pub const AGES: RangeInclusive<u32> = 1..=150;
+
+#[derive(Debug)]
+pub enum RecordError {
+    Io(io::Error),
+    /// A field that isn't allowed, with why
+    Invalid(String),
+    NoSuchStudent(u32),
+    DuplicateId(u32),
+    /// A records file line that couldn't be read
+    Corrupt { line: usize, message: String },
+}
+
+impl fmt::Display for RecordError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            RecordError::Io(e) => write!(f, "{}", e),
+            RecordError::Invalid(message) => f.write_str(message),
+            RecordError::NoSuchStudent(id) => write!(f, "no student with ID {}", id),
+            RecordError::DuplicateId(id) => write!(f, "ID {} is already taken", id),
+            RecordError::Corrupt { line, message } => write!(f, "line {}: {}", line, message),
+        }
+    }
+}
+
+impl std::error::Error for RecordError {}
+
+impl From<io::Error> for RecordError {
+    fn from(e: io::Error) -> RecordError {
+        RecordError::Io(e)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Student {
+    pub name: String,
+    pub grade: f64,
+    pub age: u32,
+}
+
+impl Student {
+    /// A student, if every field is allowed
+    ///
+    /// # Example
+    /// ```
+    /// use rust_book_examples::student_records::Student;
+    ///
+    /// assert!(Student::new("Alice", 85.5, 20).is_ok());
+    /// let err = Student::new("Bob", 120.0, 19).unwrap_err();
+    /// assert_eq!(err.to_string(), "grade must be between 0 and 100, got 120");
+    /// ```
+    pub fn new(name: &str, grade: f64, age: u32) -> Result<Student, RecordError> {
+        let student = Student { name: name.trim().to_string(), grade, age };
+        student.validate()?;
+        Ok(student)
+    }
+
+    pub fn validate(&self) -> Result<(), RecordError> {
+        let invalid = |message: String| Err(RecordError::Invalid(message));
+        if self.name.is_empty() {
+            return invalid("a name can't be empty".to_string());
+        }
+        // Tabs and line breaks would break the file format
+        if self.name.contains(|c: char| c.is_control()) {
+            return invalid(format!("a name can't contain tabs or line breaks: {:?}", self.name));
+        }
+        if !(0.0..=100.0).contains(&self.grade) {
+            return invalid(format!("grade must be between 0 and 100, got {}", self.grade));
+        }
+        if !AGES.contains(&self.age) {
+            return invalid(format!("age must be between {} and {}, got {}", AGES.start(), AGES.end(), self.age));
+        }
+        Ok(())
+    }
+
+    /// The letter for the grade: A from 90, B from 80, down to F below 60
+    pub fn band(&self) -> char {
+        match self.grade {
+            g if g >= 90.0 => 'A',
+            g if g >= 80.0 => 'B',
+            g if g >= 70.0 => 'C',
+            g if g >= 60.0 => 'D',
+            _ => 'F',
+        }
+    }
+}
+
+/// Changes to some of a student's fields
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct StudentUpdate {
+    pub name: Option<String>,
+    pub grade: Option<f64>,
+    pub age: Option<u32>,
+}
+
+/// How [`Roster::group_by`] splits students
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum GroupBy {
+    Age,
+    /// The letter grade, as given by [`Student::band`]
+    Band,
+}
+
+impl FromStr for GroupBy {
+    type Err = RecordError;
+
+    fn from_str(s: &str) -> Result<GroupBy, RecordError> {
+        match s {
+            "age" => Ok(GroupBy::Age),
+            "band" => Ok(GroupBy::Band),
+            _ => Err(RecordError::Invalid(format!("can't group by '{}'; try age or band", s))),
+        }
+    }
+}
+
+/// The order of a report
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum SortKey {
+    #[default]
+    Id,
+    Name,
+    Grade,
+    Age,
+}
+
+impl FromStr for SortKey {
+    type Err = RecordError;
+
+    fn from_str(s: &str) -> Result<SortKey, RecordError> {
+        match s {
+            "id" => Ok(SortKey::Id),
+            "name" => Ok(SortKey::Name),
+            "grade" => Ok(SortKey::Grade),
+            "age" => Ok(SortKey::Age),
+            _ => Err(RecordError::Invalid(format!("can't sort by '{}'; try id, name, grade or age", s))),
+        }
+    }
+}
+
+/// Mean, median and mode of some grades
+#[derive(Debug, Clone, PartialEq)]
+pub struct Summary {
+    pub count: usize,
+    pub mean: f64,
+    pub median: f64,
+    /// The most common grades, lowest first; empty when no grade repeats
+    pub mode: Vec<f64>,
+}
+
+impl Summary {
+    /// Statistics for `grades`, or `None` if there are none
+    ///
+    /// # Example
+    /// ```
+    /// use rust_book_examples::student_records::Summary;
+    ///
+    /// let summary = Summary::of(&[80.0, 92.5, 80.0, 70.0]).unwrap();
+    /// assert_eq!((summary.mean, summary.median), (80.625, 80.0));
+    /// assert_eq!(summary.mode, [80.0]);
+    /// assert!(Summary::of(&[1.0, 2.0]).unwrap().mode.is_empty());
+    /// ```
+    pub fn of(grades: &[f64]) -> Option<Summary> {
+        if grades.is_empty() {
+            return None;
+        }
+        let mut sorted = grades.to_vec();
+        sorted.sort_by(f64::total_cmp);
+        let middle = sorted.len() / 2;
+        let median = if sorted.len().is_multiple_of(2) { (sorted[middle - 1] + sorted[middle]) / 2.0 } else { sorted[middle] };
+
+        // f64 isn't Hash, but its bits are. Grades are never NaN, and adding
+        // 0.0 turns -0.0 into 0.0, so equal grades have equal bits
+        let mut counts: HashMap<u64, usize> = HashMap::new();
+        for grade in &sorted {
+            *counts.entry((grade + 0.0).to_bits()).or_insert(0) += 1;
+        }
+        let most = counts.values().copied().max().unwrap_or(0);
+        let mut mode: Vec<f64> = if most > 1 {
+            counts.iter().filter(|&(_, &n)| n == most).map(|(bits, _)| f64::from_bits(*bits)).collect()
+        } else {
+            Vec::new()
+        };
+        mode.sort_by(f64::total_cmp);
+
+        Some(Summary {
+            count: grades.len(),
+            mean: grades.iter().sum::<f64>() / grades.len() as f64,
+            median,
+            mode,
+        })
+    }
+}
+
+/// Students by ID
+///
+/// # Example
+/// ```
+/// use rust_book_examples::student_records::{Roster, SortKey, Student};
+///
+/// let mut roster = Roster::new();
+/// let alice = roster.add(Student::new("Alice", 85.5, 20).unwrap()).unwrap();
+/// roster.add(Student::new("Bob", 92.0, 19).unwrap()).unwrap();
+/// assert_eq!(alice, 1001);
+/// let names: Vec<&str> = roster.report(SortKey::Grade, true).iter().map(|(_, s)| s.name.as_str()).collect();
+/// assert_eq!(names, ["Bob", "Alice"]);
+/// ```
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Roster {
+    students: HashMap<u32, Student>,
+}
+
+impl Roster {
+    pub fn new() -> Roster {
+        Roster::default()
+    }
+
+    /// Add a student under the ID after the highest in use, returning it
+    ///
+    /// Fails if the student isn't valid, or if the highest ID in use is
+    /// `u32::MAX`, which a loaded file can hold.
+    pub fn add(&mut self, student: Student) -> Result<u32, RecordError> {
+        student.validate()?;
+        let id = match self.students.keys().max() {
+            None => FIRST_ID,
+            Some(max) => max
+                .checked_add(1)
+                .ok_or_else(|| RecordError::Invalid(format!("no IDs left after {}", max)))?,
+        };
+        self.students.insert(id, student);
+        Ok(id)
+    }
+
+    /// Add a student under a chosen ID
+    pub fn insert(&mut self, id: u32, student: Student) -> Result<(), RecordError> {
+        student.validate()?;
+        if self.students.contains_key(&id) {
+            return Err(RecordError::DuplicateId(id));
+        }
+        self.students.insert(id, student);
+        Ok(())
+    }
+
+    /// Change some of a student's fields; nothing changes unless every new
+    /// value is allowed
+    pub fn update(&mut self, id: u32, update: StudentUpdate) -> Result<&Student, RecordError> {
+        let student = self.students.get_mut(&id).ok_or(RecordError::NoSuchStudent(id))?;
+        let updated = Student {
+            name: update.name.map_or_else(|| student.name.clone(), |name| name.trim().to_string()),
+            grade: update.grade.unwrap_or(student.grade),
+            age: update.age.unwrap_or(student.age),
+        };
+        updated.validate()?;
+        *student = updated;
+        Ok(student)
+    }
+
+    pub fn remove(&mut self, id: u32) -> Result<Student, RecordError> {
+        self.students.remove(&id).ok_or(RecordError::NoSuchStudent(id))
+    }
+
+    pub fn get(&self, id: u32) -> Option<&Student> {
+        self.students.get(&id)
+    }
+
+    pub fn len(&self) -> usize {
+        self.students.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.students.is_empty()
+    }
+
+    /// Students with a grade in `grades`, best first
+    pub fn by_grade(&self, grades: RangeInclusive<f64>) -> Vec<(u32, &Student)> {
+        let mut found: Vec<_> = self.report(SortKey::Grade, true);
+        found.retain(|(_, student)| grades.contains(&student.grade));
+        found
+    }
+
+    /// Students of exactly `age`, by name
+    pub fn by_age(&self, age: u32) -> Vec<(u32, &Student)> {
+        let mut found = self.report(SortKey::Name, false);
+        found.retain(|(_, student)| student.age == age);
+        found
+    }
+
+    /// Every student, sorted by `key`, ties broken by ID
+    pub fn report(&self, key: SortKey, descending: bool) -> Vec<(u32, &Student)> {
+        let mut rows: Vec<(u32, &Student)> = self.students.iter().map(|(id, student)| (*id, student)).collect();
+        rows.sort_by_key(|(id, _)| *id);
+        // A stable sort keeps IDs in order within ties
+        rows.sort_by(|(_, a), (_, b)| {
+            let ordering = match key {
+                SortKey::Id => std::cmp::Ordering::Equal,
+                SortKey::Name => a.name.to_lowercase().cmp(&b.name.to_lowercase()),
+                SortKey::Grade => a.grade.total_cmp(&b.grade),
+                SortKey::Age => a.age.cmp(&b.age),
+            };
+            if descending { ordering.reverse() } else { ordering }
+        });
+        if descending && key == SortKey::Id {
+            rows.reverse();
+        }
+        rows
+    }
+
+    /// Grade statistics for each group, in group order, with the group's label
+    pub fn group_by(&self, by: GroupBy) -> Vec<(String, Summary)> {
+        let mut groups: HashMap<u32, Vec<f64>> = HashMap::new();
+        for student in self.students.values() {
+            let key = match by {
+                GroupBy::Age => student.age,
+                GroupBy::Band => u32::from(student.band()),
+            };
+            groups.entry(key).or_default().push(student.grade);
+        }
+        let mut groups: Vec<_> = groups.into_iter().collect();
+        groups.sort_by_key(|(key, _)| *key);
+        groups
+            .into_iter()
+            .map(|(key, grades)| {
+                let label = match by {
+                    GroupBy::Age => key.to_string(),
+                    GroupBy::Band => char::from_u32(key).expect("grouped by a char").to_string(),
+                };
+                (label, Summary::of(&grades).expect("groups are never empty"))
+            })
+            .collect()
+    }
+
+    /// Statistics for every student's grade
+    pub fn summary(&self) -> Option<Summary> {
+        let grades: Vec<f64> = self.students.values().map(|student| student.grade).collect();
+        Summary::of(&grades)
+    }
+
+    /// Read a records file; a missing file is an empty roster
+    ///
+    /// The whole file is checked: the first line that's malformed, holds a
+    /// field that isn't allowed, or repeats an ID is reported with its line
+    /// number, and nothing is loaded.
+    pub fn load(path: impl AsRef<Path>) -> Result<Roster, RecordError> {
+        match fs::read_to_string(path) {
+            Ok(text) => Roster::parse(&text),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(Roster::new()),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    /// Read the text of a records file
+    pub fn parse(text: &str) -> Result<Roster, RecordError> {
+        let mut lines = text.lines().enumerate().map(|(i, line)| (i + 1, line));
+        if lines.next().map(|(_, header)| header) != Some(HEADER) {
+            return Err(RecordError::Corrupt { line: 1, message: format!("expected '{}'", HEADER) });
+        }
+        let mut roster = Roster::new();
+        for (number, line) in lines.filter(|(_, line)| !line.trim().is_empty() && !line.starts_with('#')) {
+            let corrupt = |message: String| RecordError::Corrupt { line: number, message };
+            let (id, student) = parse_line(line).map_err(corrupt)?;
+            roster.insert(id, student).map_err(|e| corrupt(e.to_string()))?;
+        }
+        Ok(roster)
+    }
+
+    /// Write the roster to `path`, by ID, replacing it only once it's fully
+    /// written
+    pub fn save(&self, path: impl AsRef<Path>) -> io::Result<()> {
+        let mut text = format!("{}\n", HEADER);
+        for (id, student) in self.report(SortKey::Id, false) {
+            text.push_str(&format!("{}\t{}\t{}\t{}\n", id, student.name, student.grade, student.age));
+        }
+        fs_util::write_atomically(path.as_ref(), text.as_bytes(), None)
+    }
+}
+
+fn parse_line(line: &str) -> Result<(u32, Student), String> {
+    let fields: Vec<&str> = line.split('\t').collect();
+    let [id, name, grade, age] = fields[..] else {
+        return Err(format!("expected 4 tab-separated fields (ID, name, grade, age), found {}", fields.len()));
+    };
+    let id = id.parse().map_err(|_| format!("bad ID '{}'", id))?;
+    let grade = grade.parse().map_err(|_| format!("bad grade '{}'", grade))?;
+    let age = age.parse().map_err(|_| format!("bad age '{}'", age))?;
+    let student = Student::new(name, grade, age).map_err(|e| e.to_string())?;
+    Ok((id, student))
+}
+
+/// An aligned table of students, with an ID, name, grade, band and age column
+pub fn render(rows: &[(u32, &Student)]) -> String {
+    let width = rows.iter().map(|(_, student)| student.name.chars().count()).max().unwrap_or(0).max(4);
+    let mut text = format!("{:>6}  {:width$}  {:>6}  {:>4}  {:>3}\n", "ID", "Name", "Grade", "Band", "Age");
+    for (id, student) in rows {
+        text.push_str(&format!(
+            "{:>6}  {:width$}  {:>6.1}  {:>4}  {:>3}\n",
+            id,
+            student.name,
+            student.grade,
+            student.band(),
+            student.age
+        ));
+    }
+    text
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fs_util::TempPath;
+
+    fn roster() -> Roster {
+        let mut roster = Roster::new();
+        for (name, grade, age) in [("Alice", 85.5, 20), ("Bob", 92.0, 19), ("Charlie", 78.5, 21), ("Diana", 96.5, 20), ("Eve", 85.5, 19)] {
+            roster.add(Student::new(name, grade, age).unwrap()).unwrap();
+        }
+        roster
+    }
+
+    fn names(rows: &[(u32, &Student)]) -> Vec<String> {
+        rows.iter().map(|(_, student)| student.name.clone()).collect()
+    }
+
+    #[test]
+    fn queries_and_reports() {
+        let roster = roster();
+        assert_eq!(names(&roster.by_grade(80.0..=95.0)), ["Bob", "Alice", "Eve"]);
+        assert_eq!(names(&roster.by_age(20)), ["Alice", "Diana"]);
+        assert!(roster.by_age(30).is_empty());
+        assert_eq!(names(&roster.report(SortKey::Name, true)), ["Eve", "Diana", "Charlie", "Bob", "Alice"]);
+        // Ties keep ID order
+        assert_eq!(names(&roster.report(SortKey::Age, false)), ["Bob", "Eve", "Alice", "Diana", "Charlie"]);
+        let ids: Vec<u32> = roster.report(SortKey::Id, true).iter().map(|(id, _)| *id).collect();
+        assert_eq!(ids, [1005, 1004, 1003, 1002, 1001]);
+
+        let table = render(&roster.by_age(21));
+        assert_eq!(table, "    ID  Name      Grade  Band  Age\n  1003  Charlie    78.5     C   21\n");
+    }
+
+    #[test]
+    fn group_statistics() {
+        let roster = roster();
+        let by_age = roster.group_by(GroupBy::Age);
+        let labels: Vec<&str> = by_age.iter().map(|(label, _)| label.as_str()).collect();
+        assert_eq!(labels, ["19", "20", "21"]);
+        assert_eq!(by_age[0].1, Summary { count: 2, mean: 88.75, median: 88.75, mode: vec![] });
+
+        let by_band = roster.group_by(GroupBy::Band);
+        let labels: Vec<&str> = by_band.iter().map(|(label, _)| label.as_str()).collect();
+        assert_eq!(labels, ["A", "B", "C"]);
+        assert_eq!(by_band[1].1.mode, [85.5]);
+
+        let all = roster.summary().unwrap();
+        assert_eq!((all.count, all.median, all.mode.clone()), (5, 85.5, vec![85.5]));
+        assert_eq!(Roster::new().summary(), None);
+        // -0.0 is the same grade as 0.0
+        assert_eq!(Summary::of(&[0.0, -0.0, 50.0]).unwrap().mode, [0.0]);
+    }
+
+    #[test]
+    fn updates_are_validated() {
+        let mut roster = roster();
+        let update = StudentUpdate { grade: Some(88.0), ..StudentUpdate::default() };
+        assert_eq!(roster.update(1001, update).unwrap().grade, 88.0);
+
+        let bad = StudentUpdate { name: Some("Alicia".to_string()), age: Some(0), ..StudentUpdate::default() };
+        assert_eq!(roster.update(1001, bad).unwrap_err().to_string(), "age must be between 1 and 150, got 0");
+        assert_eq!(roster.get(1001).unwrap().name, "Alice");
+
+        assert!(matches!(roster.update(9999, StudentUpdate::default()), Err(RecordError::NoSuchStudent(9999))));
+        assert_eq!(roster.remove(1002).unwrap().name, "Bob");
+        assert!(matches!(roster.remove(1002), Err(RecordError::NoSuchStudent(1002))));
+        assert!(Student::new(" ", 50.0, 20).is_err());
+        assert!(Student::new("Tab\tby", 50.0, 20).is_err());
+        assert!(Student::new("Nan", f64::NAN, 20).is_err());
+        // IDs keep counting up from the highest
+        assert_eq!(roster.add(Student::new("Frank", 70.0, 22).unwrap()).unwrap(), 1006);
+
+        // A student built by hand is checked too
+        let bad = Student { name: "Grace".to_string(), grade: 101.0, age: 20 };
+        assert!(matches!(roster.add(bad), Err(RecordError::Invalid(_))));
+        roster.insert(u32::MAX, Student::new("Last", 50.0, 20).unwrap()).unwrap();
+        let err = roster.add(Student::new("Heidi", 60.0, 21).unwrap()).unwrap_err();
+        assert_eq!(err.to_string(), "no IDs left after 4294967295");
+        assert_eq!(roster.len(), 6);
+    }
+
+    #[test]
+    fn saves_loads_and_rejects_bad_files() {
+        let path = TempPath::new("student_records.txt");
+        let roster = roster();
+        roster.save(&path).unwrap();
+        assert_eq!(Roster::load(&path).unwrap(), roster);
+        fs::remove_file(&path).unwrap();
+        assert_eq!(Roster::load(&path).unwrap(), Roster::new());
+
+        let error = |text: &str| Roster::parse(text).unwrap_err().to_string();
+        assert_eq!(error("students\n"), "line 1: expected 'student-records 1'");
+        let text = "student-records 1\n# a comment\n\n1001\tAlice\t85.5\t20\n1002\tBob\t92\n";
+        assert_eq!(error(text), "line 5: expected 4 tab-separated fields (ID, name, grade, age), found 3");
+        assert_eq!(error("student-records 1\n1001\tAlice\tA+\t20\n"), "line 2: bad grade 'A+'");
+        assert_eq!(
+            error("student-records 1\n1001\tAlice\t85\t20\n1001\tBob\t90\t19\n"),
+            "line 3: ID 1001 is already taken"
+        );
+        assert_eq!(
+            error("student-records 1\n1001\tAlice\t185\t20\n"),
+            "line 2: grade must be between 0 and 100, got 185"
+        );
+    }
+}
@@ -0,0 +1,260 @@
+//! An interactive command line for a [`Roster`]
+//!
+//! Each line is one command; `help` lists them. A [`Session`] remembers the
+//! file it was loaded from, so `save` needs no argument, and `quit` warns
+//! once before throwing away unsaved changes.
+
+use std::fmt::Write as _;
+use std::io::{self, BufRead, Write};
+use std::path::PathBuf;
+
+use super::{RecordError, Roster, SortKey, Student, StudentUpdate, Summary, render};
+
+pub const HELP: &str = "\
+Commands:
+  add NAME GRADE AGE        Add a student; the name can have spaces
+  update ID FIELD VALUE     Change a student's name, grade or age
+  remove ID                 Remove a student
+  show ID                   Show one student
+  list [FIELD] [desc]       Every student, by id, name, grade or age
+  grades LOW HIGH           Students with a grade from LOW to HIGH
+  age N                     Students aged N
+  stats [age|band]          Mean, median and mode of the grades, optionally per group
+  save [FILE]               Save to FILE, or to the file last loaded or saved
+  load FILE                 Replace the roster with FILE's
+  help                      Show this help
+  quit                      Leave; asks again if there are unsaved changes
+";
+
+/// What a command asks the caller to do next
+#[derive(Debug, Clone, PartialEq)]
+pub enum Reply {
+    /// Show the text and read another command
+    Text(String),
+    Quit,
+}
+
+/// A roster being edited, with where it lives on disk
+#[derive(Debug)]
+pub struct Session {
+    roster: Roster,
+    path: Option<PathBuf>,
+    unsaved: bool,
+    warned: bool,
+}
+
+impl Session {
+    /// Edit `roster`, which `save` writes back to `path` if given
+    pub fn new(roster: Roster, path: Option<PathBuf>) -> Session {
+        Session { roster, path, unsaved: false, warned: false }
+    }
+
+    pub fn roster(&self) -> &Roster {
+        &self.roster
+    }
+
+    /// Whether the roster has changed since it was loaded or saved
+    pub fn unsaved(&self) -> bool {
+        self.unsaved
+    }
+
+    /// Run one command line
+    ///
+    /// # Example
+    /// ```
+    /// use rust_book_examples::student_records::Roster;
+    /// use rust_book_examples::student_records::shell::{Reply, Session};
+    ///
+    /// let mut session = Session::new(Roster::new(), None);
+    /// assert_eq!(session.execute("add Ada Lovelace 97.5 36").unwrap(), Reply::Text("Added Ada Lovelace as 1001".to_string()));
+    /// assert_eq!(session.execute("remove 7").unwrap_err().to_string(), "no student with ID 7");
+    /// ```
+    pub fn execute(&mut self, line: &str) -> Result<Reply, RecordError> {
+        let words: Vec<&str> = line.split_whitespace().collect();
+        let Some((&command, rest)) = words.split_first() else {
+            return Ok(Reply::Text(String::new()));
+        };
+        if command != "quit" {
+            self.warned = false;
+        }
+        let text = match (command, rest) {
+            ("add", [name @ .., grade, age]) if !name.is_empty() => {
+                let student = Student::new(&name.join(" "), number(grade)?, number(age)?)?;
+                let name = student.name.clone();
+                let id = self.roster.add(student)?;
+                self.unsaved = true;
+                format!("Added {} as {}", name, id)
+            }
+            ("update", [id, field, value @ ..]) if !value.is_empty() => {
+                let value = value.join(" ");
+                let update = match *field {
+                    "name" => StudentUpdate { name: Some(value), ..StudentUpdate::default() },
+                    "grade" => StudentUpdate { grade: Some(number(&value)?), ..StudentUpdate::default() },
+                    "age" => StudentUpdate { age: Some(number(&value)?), ..StudentUpdate::default() },
+                    _ => return Err(RecordError::Invalid(format!("no field '{}'; try name, grade or age", field))),
+                };
+                let id = number(id)?;
+                let student = self.roster.update(id, update)?;
+                let text = format!("Updated {}: {}, grade {}, age {}", id, student.name, student.grade, student.age);
+                self.unsaved = true;
+                text
+            }
+            ("remove", [id]) => {
+                let id = number(id)?;
+                let student = self.roster.remove(id)?;
+                self.unsaved = true;
+                format!("Removed {} ({})", id, student.name)
+            }
+            ("show", [id]) => {
+                let id = number(id)?;
+                let student = self.roster.get(id).ok_or(RecordError::NoSuchStudent(id))?;
+                render(&[(id, student)])
+            }
+            ("list", [key @ .., "desc"]) | ("list", key @ ([] | [_])) if key.len() <= 1 => {
+                let key = key.first().map_or(Ok(SortKey::default()), |key| key.parse())?;
+                let descending = rest.last() == Some(&"desc");
+                table(&self.roster.report(key, descending))
+            }
+            ("grades", [low, high]) => table(&self.roster.by_grade(number(low)?..=number(high)?)),
+            ("age", [age]) => table(&self.roster.by_age(number(age)?)),
+            ("stats", []) => match self.roster.summary() {
+                Some(summary) => format!("All {}", describe(&summary)),
+                None => "No students".to_string(),
+            },
+            ("stats", [by]) => {
+                let mut text = String::new();
+                for (group, summary) in self.roster.group_by(by.parse()?) {
+                    let label = if *by == "age" { "Age" } else { "Band" };
+                    writeln!(text, "{} {}: {}", label, group, describe(&summary)).unwrap();
+                }
+                text
+            }
+            ("save", [] | [_]) => {
+                let path = match rest.first() {
+                    Some(file) => PathBuf::from(file),
+                    None => self.path.clone().ok_or(RecordError::Invalid("save where? give a FILE".to_string()))?,
+                };
+                self.roster.save(&path)?;
+                let text = format!("Saved {} to {}", students(self.roster.len()), path.display());
+                self.path = Some(path);
+                self.unsaved = false;
+                text
+            }
+            ("load", [file]) => {
+                let path = PathBuf::from(file);
+                // Nothing is replaced if the file has a bad line
+                self.roster = Roster::load(&path)?;
+                let text = format!("Loaded {} from {}", students(self.roster.len()), path.display());
+                self.path = Some(path);
+                self.unsaved = false;
+                text
+            }
+            ("help", []) => HELP.to_string(),
+            ("quit", []) => {
+                if self.unsaved && !self.warned {
+                    self.warned = true;
+                    return Ok(Reply::Text("There are unsaved changes; save them, or quit again to lose them".to_string()));
+                }
+                return Ok(Reply::Quit);
+            }
+            ("add" | "update" | "remove" | "show" | "list" | "grades" | "age" | "stats" | "save" | "load", _) => {
+                let usage = HELP.lines().find(|usage| usage.trim_start().starts_with(command)).unwrap_or_default();
+                return Err(RecordError::Invalid(format!("usage: {}", usage.trim())));
+            }
+            _ => return Err(RecordError::Invalid(format!("unknown command '{}'; try help", command))),
+        };
+        Ok(Reply::Text(text))
+    }
+}
+
+fn number<T: std::str::FromStr>(text: &str) -> Result<T, RecordError> {
+    text.parse().map_err(|_| RecordError::Invalid(format!("'{}' isn't a number", text)))
+}
+
+fn students(count: usize) -> String {
+    if count == 1 { "1 student".to_string() } else { format!("{} students", count) }
+}
+
+fn table(rows: &[(u32, &Student)]) -> String {
+    if rows.is_empty() { "No students".to_string() } else { render(rows) }
+}
+
+fn describe(summary: &Summary) -> String {
+    let mode = if summary.mode.is_empty() {
+        "none".to_string()
+    } else {
+        summary.mode.iter().map(|grade| grade.to_string()).collect::<Vec<_>>().join(", ")
+    };
+    format!(
+        "{}, mean {:.2}, median {:.2}, mode {}",
+        students(summary.count),
+        summary.mean,
+        summary.median,
+        mode
+    )
+}
+
+/// Read commands from `input` until `quit` or the end of input, writing a
+/// prompt before each and the replies to `output`
+///
+/// Errors in commands are reported and the session carries on; only failing
+/// to read or write ends it early.
+pub fn run(session: &mut Session, input: impl BufRead, mut output: impl Write) -> io::Result<()> {
+    let mut lines = input.lines();
+    loop {
+        write!(output, "records> ")?;
+        output.flush()?;
+        let Some(line) = lines.next() else {
+            writeln!(output)?;
+            return Ok(());
+        };
+        match session.execute(&line?) {
+            Ok(Reply::Quit) => return Ok(()),
+            Ok(Reply::Text(text)) if text.is_empty() => {}
+            Ok(Reply::Text(text)) => writeln!(output, "{}", text.trim_end())?,
+            Err(e) => writeln!(output, "❌ {}", e)?,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fs_util::TempPath;
+
+    #[test]
+    fn scripted_session() {
+        let path = TempPath::new("student_records_shell.txt");
+        let script = format!(
+            "add Alice 85.5 20\nadd Bob Smith 92 19\nupdate 1001 grade 88\nupdate 1001 grade 101\n\
+             list grade desc\nstats\nremove 1009\nfrobnicate\ngrades 90\nquit\nsave {}\nquit\n",
+            path.display()
+        );
+        let mut session = Session::new(Roster::new(), None);
+        let mut output = Vec::new();
+        run(&mut session, script.as_bytes(), &mut output).unwrap();
+        let output = String::from_utf8(output).unwrap();
+        let expected = [
+            "records> Added Alice as 1001",
+            "records> Added Bob Smith as 1002",
+            "records> Updated 1001: Alice, grade 88, age 20",
+            "records> ❌ grade must be between 0 and 100, got 101",
+            "records>     ID  Name        Grade  Band  Age",
+            "  1002  Bob Smith    92.0     A   19",
+            "  1001  Alice        88.0     B   20",
+            "records> All 2 students, mean 90.00, median 90.00, mode none",
+            "records> ❌ no student with ID 1009",
+            "records> ❌ unknown command 'frobnicate'; try help",
+            "records> ❌ usage: grades LOW HIGH           Students with a grade from LOW to HIGH",
+            "records> There are unsaved changes; save them, or quit again to lose them",
+            &format!("records> Saved 2 students to {}", path.display()),
+            "records> ",
+        ];
+        assert_eq!(output, expected.join("\n"));
+        assert!(!session.unsaved());
+
+        let mut loaded = Session::new(Roster::new(), None);
+        loaded.execute(&format!("load {}", path.display())).unwrap();
+        assert_eq!(loaded.roster(), session.roster());
+    }
+}
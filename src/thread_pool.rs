@@ -0,0 +1,319 @@
+//! # Thread Pool
+//!
+//! The Chapter 20 `ThreadPool`/`Worker` design, grown for the library's own
+//! parallel code: the minigrep search and the guessing game server. The
+//! Chapter 20 examples keep their hand-written pools, since building one is
+//! what they teach.
+//!
+//! This module demonstrates:
+//! - Workers sharing one job queue through `Arc<Mutex<mpsc::Receiver>>`
+//! - Graceful shutdown with `Message::Terminate` and `Drop`
+//! - Keeping a worker alive when a job panics, with `catch_unwind`
+//! - Running a stream of jobs with a bounded number in flight, getting the
+//!   results back in order
+
+use std::collections::BTreeMap;
+use std::panic::{self, AssertUnwindSafe};
+use std::sync::{mpsc, Arc, Mutex};
+use std::thread;
+
+/// A fixed set of worker threads that run queued closures
+///
+/// # Example
+/// ```
+/// use rust_book_examples::thread_pool::ThreadPool;
+///
+/// let pool = ThreadPool::new(4);
+/// let squares: Vec<u64> = pool.map(vec![1, 2, 3, 4], |n: u64| n * n).collect();
+/// assert_eq!(squares, vec![1, 4, 9, 16]);
+/// ```
+pub struct ThreadPool {
+    workers: Vec<Worker>,
+    sender: mpsc::Sender<Message>,
+}
+
+/// One worker thread waiting on the shared queue
+///
+/// The thread is named `worker N`, so jobs can tell which worker runs them
+/// through `thread::current().name()`.
+struct Worker {
+    thread: Option<thread::JoinHandle<()>>,
+}
+
+/// Messages sent to workers
+enum Message {
+    NewJob(Job),
+    Terminate,
+}
+
+/// Type alias for jobs (closures) sent to workers
+type Job = Box<dyn FnOnce() + Send + 'static>;
+
+impl ThreadPool {
+    /// Create a pool with `size` worker threads
+    ///
+    /// # Panics
+    ///
+    /// Panics if `size` is zero.
+    pub fn new(size: usize) -> ThreadPool {
+        assert!(size > 0);
+
+        let (sender, receiver) = mpsc::channel();
+        let receiver = Arc::new(Mutex::new(receiver));
+
+        let workers = (0..size).map(|id| Worker::new(id, Arc::clone(&receiver))).collect();
+
+        ThreadPool { workers, sender }
+    }
+
+    /// A pool with one worker per available CPU
+    pub fn with_available_parallelism() -> ThreadPool {
+        ThreadPool::new(thread::available_parallelism().map_or(1, |n| n.get()))
+    }
+
+    /// Number of worker threads
+    pub fn size(&self) -> usize {
+        self.workers.len()
+    }
+
+    /// Execute a closure on one of the worker threads
+    pub fn execute<F>(&self, f: F)
+    where
+        F: FnOnce() + Send + 'static,
+    {
+        // Workers only stop when the pool is dropped, so the queue is always open here
+        self.sender
+            .send(Message::NewJob(Box::new(f)))
+            .expect("thread pool workers have shut down");
+    }
+
+    /// Run `f` on every item across the pool
+    ///
+    /// Results come back in the order of `items`, each one as soon as it and
+    /// everything before it has finished. Items are taken from `items` only
+    /// as results are consumed, with at most twice the pool's size in flight,
+    /// so a long input is never queued, or its results held, all at once.
+    ///
+    /// # Panics
+    ///
+    /// The returned iterator panics if `f` panicked for one of the items.
+    pub fn map<I, R, F>(&self, items: I, f: F) -> Ordered<'_, I::IntoIter, R>
+    where
+        I: IntoIterator,
+        I::Item: Send + 'static,
+        R: Send + 'static,
+        F: Fn(I::Item) -> R + Send + Sync + 'static,
+    {
+        let (sender, receiver) = mpsc::channel();
+
+        Ordered {
+            pool: self,
+            items: items.into_iter(),
+            f: Arc::new(f),
+            sender,
+            receiver,
+            pending: BTreeMap::new(),
+            next: 0,
+            submitted: 0,
+            window: 2 * self.size(),
+        }
+    }
+}
+
+/// The outcome of one [`ThreadPool::map`] job, with its panic if it had one
+type Outcome<R> = thread::Result<R>;
+
+/// Results of [`ThreadPool::map`], in input order
+pub struct Ordered<'a, I: Iterator, R> {
+    pool: &'a ThreadPool,
+    /// Items not handed to the pool yet
+    items: I,
+    f: Arc<dyn Fn(I::Item) -> R + Send + Sync>,
+    sender: mpsc::Sender<(usize, Outcome<R>)>,
+    receiver: mpsc::Receiver<(usize, Outcome<R>)>,
+    /// Results that finished before an earlier one
+    pending: BTreeMap<usize, Outcome<R>>,
+    next: usize,
+    submitted: usize,
+    /// Most items queued or running at once
+    window: usize,
+}
+
+impl<I, R> Ordered<'_, I, R>
+where
+    I: Iterator,
+    I::Item: Send + 'static,
+    R: Send + 'static,
+{
+    /// Queue items until the window is full or they run out
+    fn fill_window(&mut self) {
+        while self.submitted - self.next < self.window
+            && let Some(item) = self.items.next()
+        {
+            let index = self.submitted;
+            let sender = self.sender.clone();
+            let f = Arc::clone(&self.f);
+            self.pool.execute(move || {
+                // Caught here so the panic reaches whoever iterates, not just the worker
+                let outcome = panic::catch_unwind(AssertUnwindSafe(|| f(item)));
+                // The receiver only goes away if the caller stopped iterating
+                let _ = sender.send((index, outcome));
+            });
+            self.submitted += 1;
+        }
+    }
+}
+
+impl<I, R> Iterator for Ordered<'_, I, R>
+where
+    I: Iterator,
+    I::Item: Send + 'static,
+    R: Send + 'static,
+{
+    type Item = R;
+
+    fn next(&mut self) -> Option<R> {
+        self.fill_window();
+        if self.next == self.submitted {
+            return None;
+        }
+
+        while !self.pending.contains_key(&self.next) {
+            // Every queued job sends exactly once, and `self.sender` keeps the channel open
+            let (index, outcome) = self.receiver.recv().expect("the thread pool's workers have shut down");
+            self.pending.insert(index, outcome);
+        }
+
+        let outcome = self.pending.remove(&self.next)?;
+        self.next += 1;
+        match outcome {
+            Ok(result) => Some(result),
+            Err(payload) => panic::resume_unwind(payload),
+        }
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let in_flight = self.submitted - self.next;
+        let (lower, upper) = self.items.size_hint();
+        (in_flight + lower, upper.map(|upper| in_flight + upper))
+    }
+}
+
+impl Drop for ThreadPool {
+    fn drop(&mut self) {
+        for _ in &self.workers {
+            let _ = self.sender.send(Message::Terminate);
+        }
+
+        for worker in &mut self.workers {
+            if let Some(thread) = worker.thread.take() {
+                let _ = thread.join();
+            }
+        }
+    }
+}
+
+impl Worker {
+    fn new(id: usize, receiver: Arc<Mutex<mpsc::Receiver<Message>>>) -> Worker {
+        let builder = thread::Builder::new().name(format!("worker {}", id));
+        let thread = builder.spawn(move || {
+            loop {
+                // The lock is released as soon as a message has been taken
+                let message = match receiver.lock() {
+                    Ok(guard) => guard.recv(),
+                    Err(_) => break,
+                };
+
+                match message {
+                    Ok(Message::NewJob(job)) => {
+                        // A panicking job shouldn't take the worker down with it
+                        let _ = panic::catch_unwind(AssertUnwindSafe(job));
+                    }
+                    Ok(Message::Terminate) | Err(_) => break,
+                }
+            }
+        });
+
+        Worker {
+            thread: Some(thread.expect("failed to spawn a worker thread")),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::time::Duration;
+
+    #[test]
+    fn map_keeps_input_order() {
+        let pool = ThreadPool::new(4);
+        // Earlier items take longer, so they finish last
+        let results: Vec<usize> = pool
+            .map(0..8, |n: usize| {
+                thread::sleep(Duration::from_millis((8 - n as u64) * 5));
+                n * 10
+            })
+            .collect();
+        assert_eq!(results, vec![0, 10, 20, 30, 40, 50, 60, 70]);
+    }
+
+    #[test]
+    fn map_keeps_a_bounded_number_of_items_in_flight() {
+        let pool = ThreadPool::new(2);
+        let taken = Arc::new(AtomicUsize::new(0));
+        let counted = {
+            let taken = Arc::clone(&taken);
+            (0..1000).inspect(move |_| {
+                taken.fetch_add(1, Ordering::SeqCst);
+            })
+        };
+
+        let mut results = pool.map(counted, |n: usize| n);
+        assert_eq!(results.next(), Some(0));
+        assert!(taken.load(Ordering::SeqCst) <= 4);
+        assert_eq!(results.count(), 999);
+        assert_eq!(taken.load(Ordering::SeqCst), 1000);
+    }
+
+    #[test]
+    #[should_panic(expected = "item 3 failed")]
+    fn map_passes_on_a_panic() {
+        let pool = ThreadPool::new(2);
+        for _ in pool.map(0..8, |n: usize| assert_ne!(n, 3, "item {} failed", n)) {}
+    }
+
+    #[test]
+    fn workers_are_named() {
+        let pool = ThreadPool::new(1);
+        let names: Vec<Option<String>> = pool
+            .map(vec![()], |_: ()| thread::current().name().map(str::to_string))
+            .collect();
+        assert_eq!(names, vec![Some("worker 0".to_string())]);
+    }
+
+    #[test]
+    fn drop_waits_for_queued_jobs() {
+        let counter = Arc::new(AtomicUsize::new(0));
+        {
+            let pool = ThreadPool::new(2);
+            for _ in 0..10 {
+                let counter = Arc::clone(&counter);
+                pool.execute(move || {
+                    counter.fetch_add(1, Ordering::SeqCst);
+                });
+            }
+        }
+        assert_eq!(counter.load(Ordering::SeqCst), 10);
+    }
+
+    #[test]
+    fn workers_survive_panicking_jobs() {
+        let pool = ThreadPool::new(1);
+        pool.execute(|| panic!("job failed"));
+        let results: Vec<i32> = pool.map(vec![1, 2], |n: i32| n + 1).collect();
+        assert_eq!(results, vec![2, 3]);
+        assert_eq!(pool.size(), 1);
+    }
+}